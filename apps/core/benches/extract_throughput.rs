@@ -0,0 +1,112 @@
+//! Extraction throughput harness: lines/second per config, comparing the old
+//! hot path (substitute `<IP>` and compile the regex for every line) with the
+//! precompiled `IpExtractor` the detector now builds once per config.
+//!
+//! Run with `cargo bench --bench extract_throughput`. `BANALIZE_BENCH_SECS`
+//! sets the time budget per measurement (default 1 second).
+//!
+//! The crate has no lib target, so the extraction module is compiled in
+//! directly; this measures exactly the code the detector runs.
+
+#[allow(dead_code)]
+#[path = "../src/ip_extract.rs"]
+mod ip_extract;
+
+use fancy_regex::Regex;
+use ip_extract::{IpExtractor, IP_PATTERN};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/// Representative configs: (name, patterns, sample lines). Samples mix lines
+/// that match with the noise a real log carries between them.
+fn scenarios() -> Vec<(&'static str, Vec<&'static str>, Vec<String>)> {
+    let sshd_lines = (0..64)
+        .map(|i| match i % 4 {
+            0 => format!("Oct 18 12:00:{:02} host sshd[811]: Failed password for root from 10.0.{}.{} port 22 ssh2", i % 60, i / 8, i),
+            1 => format!("Oct 18 12:00:{:02} host sshd[811]: Invalid user admin from 2001:db8::{:x} port 22", i % 60, i),
+            2 => format!("Oct 18 12:00:{:02} host sshd[811]: Accepted publickey for deploy from 10.1.0.{} port 22", i % 60, i),
+            _ => format!("Oct 18 12:00:{:02} host CRON[812]: pam_unix(cron:session): session opened for user root", i % 60),
+        })
+        .collect();
+    let nginx_lines = (0..64)
+        .map(|i| {
+            let status = if i % 3 == 0 { 404 } else { 200 };
+            format!(
+                "10.2.{}.{} - - [18/Oct/2026:12:00:00 +0000] \"GET /wp-login.php HTTP/1.1\" {} 153 \"-\" \"curl/8.0\"",
+                i / 16,
+                i,
+                status
+            )
+        })
+        .collect();
+    let lookaround_lines = (0..64)
+        .map(|i| format!("auth failure; logname= uid=0 euid=0 tty=ssh ruser= rhost=10.3.0.{} user=root", i))
+        .collect();
+
+    vec![
+        (
+            "sshd (3 patterns)",
+            vec![
+                r"Failed password for .* from <IP> port",
+                r"Invalid user \S+ from <IP> port",
+                r"Connection closed by authenticating user \S+ <IP> port",
+            ],
+            sshd_lines,
+        ),
+        ("nginx 404", vec![r#"^<IP> .* "GET [^"]*" 404 "#], nginx_lines),
+        ("pam look-behind", vec![r"(?<=rhost=)<IP>(?= user=)"], lookaround_lines),
+    ]
+}
+
+/// Drive `extract` over the sample lines for the time budget; lines/second.
+fn measure(lines: &[String], budget: Duration, mut extract: impl FnMut(&str)) -> f64 {
+    let start = Instant::now();
+    let mut processed: u64 = 0;
+    while start.elapsed() < budget {
+        for line in lines {
+            extract(line);
+        }
+        processed += lines.len() as u64;
+    }
+    processed as f64 / start.elapsed().as_secs_f64()
+}
+
+/// The pre-change hot path, kept here as the baseline: every line paid for the
+/// placeholder substitution and a full regex compile per pattern.
+fn recompile_per_line(patterns: &[&str], line: &str) -> bool {
+    patterns.iter().any(|p| {
+        Regex::new(&p.replace("<IP>", IP_PATTERN))
+            .ok()
+            .and_then(|re| re.captures(line).ok().flatten())
+            .is_some()
+    })
+}
+
+fn main() {
+    let budget = std::env::var("BANALIZE_BENCH_SECS")
+        .ok()
+        .and_then(|v| v.parse::<f64>().ok())
+        .map(Duration::from_secs_f64)
+        .unwrap_or(Duration::from_secs(1));
+
+    println!(
+        "{:<20} {:>16} {:>16} {:>9}",
+        "config", "before (l/s)", "after (l/s)", "speedup"
+    );
+    for (name, patterns, lines) in scenarios() {
+        let before = measure(&lines, budget, |line| {
+            black_box(recompile_per_line(&patterns, line));
+        });
+        let extractor = IpExtractor::new(&patterns).expect("scenario patterns compile");
+        let after = measure(&lines, budget, |line| {
+            black_box(extractor.extract(line));
+        });
+        println!(
+            "{:<20} {:>16.0} {:>16.0} {:>8.1}x",
+            name,
+            before,
+            after,
+            after / before
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use crate::ban_target::BanTarget;
+use crate::database::AllowlistRecord;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use tracing::warn;
+use utoipa::ToSchema;
+
+/// One global allowlist entry: an address or network no config may ban.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AllowlistEntry {
+    /// Generated when empty on creation
+    #[serde(default)]
+    pub id: String,
+    /// IPv4/IPv6 address or CIDR range, e.g. `10.0.0.0/8`
+    pub ip: String,
+    /// Free-form note, e.g. "office VPN"
+    #[serde(default)]
+    pub comment: Option<String>,
+}
+
+impl AllowlistEntry {
+    /// Parse `ip`, rewriting it to canonical form (`10.0.0.7/24` becomes
+    /// `10.0.0.0/24`) so lookups and the API agree on one spelling.
+    pub fn normalize(&mut self) -> Result<BanTarget, String> {
+        let target: BanTarget = self.ip.parse()?;
+        self.ip = target.to_string();
+        Ok(target)
+    }
+}
+
+impl From<AllowlistRecord> for AllowlistEntry {
+    fn from(record: AllowlistRecord) -> Self {
+        Self {
+            id: record.id,
+            ip: record.ip,
+            comment: record.comment,
+        }
+    }
+}
+
+impl From<&AllowlistEntry> for AllowlistRecord {
+    fn from(entry: &AllowlistEntry) -> Self {
+        Self {
+            id: entry.id.clone(),
+            ip: entry.ip.clone(),
+            comment: entry.comment.clone(),
+        }
+    }
+}
+
+/// The global allowlist shared by every detector, consulted before a match is
+/// recorded. Entries are kept parsed so the per-line check never re-parses.
+#[derive(Default)]
+pub struct Allowlist {
+    entries: Vec<(AllowlistEntry, BanTarget)>,
+}
+
+impl Allowlist {
+    /// Build from stored rows, skipping any that no longer parse.
+    pub fn from_entries(entries: Vec<AllowlistEntry>) -> Self {
+        let mut list = Self::default();
+        for mut entry in entries {
+            match entry.normalize() {
+                Ok(target) => list.entries.push((entry, target)),
+                Err(e) => warn!("Skipping invalid allowlist entry {}: {}", entry.id, e),
+            }
+        }
+        list
+    }
+
+    pub fn entries(&self) -> Vec<AllowlistEntry> {
+        self.entries.iter().map(|(e, _)| e.clone()).collect()
+    }
+
+    pub fn get(&self, id: &str) -> Option<AllowlistEntry> {
+        self.entries.iter().find(|(e, _)| e.id == id).map(|(e, _)| e.clone())
+    }
+
+    /// Add an entry, or replace the one with the same id.
+    pub fn upsert(&mut self, entry: AllowlistEntry, target: BanTarget) {
+        match self.entries.iter_mut().find(|(e, _)| e.id == entry.id) {
+            Some(slot) => *slot = (entry, target),
+            None => self.entries.push((entry, target)),
+        }
+    }
+
+    /// Returns true if an entry was removed.
+    pub fn remove(&mut self, id: &str) -> bool {
+        let before = self.entries.len();
+        self.entries.retain(|(e, _)| e.id != id);
+        self.entries.len() != before
+    }
+
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        self.entries.iter().any(|(_, t)| t.contains(ip))
+    }
+
+    /// Whether banning `target` would deny any allowlisted address.
+    pub fn overlaps(&self, target: &BanTarget) -> bool {
+        self.entries.iter().any(|(_, t)| t.overlaps(target))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: &str, ip: &str) -> AllowlistEntry {
+        AllowlistEntry {
+            id: id.to_string(),
+            ip: ip.to_string(),
+            comment: None,
+        }
+    }
+
+    #[test]
+    fn contains_hosts_and_networks() {
+        let list = Allowlist::from_entries(vec![entry("a", "10.0.0.0/8"), entry("b", "2001:db8::1")]);
+        assert!(list.contains(&"10.1.2.3".parse().unwrap()));
+        assert!(list.contains(&"2001:db8::1".parse().unwrap()));
+        assert!(!list.contains(&"2001:db8::2".parse().unwrap()));
+        assert!(!list.contains(&"192.0.2.1".parse().unwrap()));
+        assert!(list.overlaps(&"0.0.0.0/0".parse().unwrap()));
+        assert!(list.overlaps(&"10.9.0.0/16".parse().unwrap()));
+        assert!(!list.overlaps(&"2001:db8::2".parse().unwrap()));
+    }
+
+    #[test]
+    fn invalid_rows_are_skipped_and_ips_normalized() {
+        let list = Allowlist::from_entries(vec![entry("bad", "nope"), entry("ok", "10.0.0.7/24")]);
+        let entries = list.entries();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].ip, "10.0.0.0/24");
+    }
+
+    #[test]
+    fn upsert_replaces_by_id() {
+        let mut list = Allowlist::default();
+        let mut e = entry("a", "10.0.0.1");
+        let t = e.normalize().unwrap();
+        list.upsert(e, t);
+        let mut e = entry("a", "10.0.0.2");
+        let t = e.normalize().unwrap();
+        list.upsert(e, t);
+        assert!(!list.contains(&"10.0.0.1".parse().unwrap()));
+        assert!(list.contains(&"10.0.0.2".parse().unwrap()));
+        assert!(list.remove("a"));
+        assert!(!list.remove("a"));
+    }
+}
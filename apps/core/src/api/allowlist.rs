@@ -0,0 +1,198 @@
+use super::AppState;
+use crate::allowlist::AllowlistEntry;
+use crate::ban_target::BanTarget;
+use crate::database::AllowlistRecord;
+use crate::events::{Event, FirewallCommand};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+};
+use serde::Deserialize;
+use tracing::info;
+use uuid::Uuid;
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub(crate) struct AllowlistWriteQuery {
+    /// Also lift every active ban, in any config, that the entry now covers.
+    #[serde(default)]
+    lift_bans: bool,
+}
+
+/// Unban everything `target` covers, the way the cleaner expires a ban.
+async fn lift_covered_bans(state: &AppState, target: &BanTarget) {
+    let lifted = state.store.take_bans_within(target);
+    if lifted.is_empty() {
+        return;
+    }
+    let timestamp = crate::detector::now_millis();
+    for (config_id, ip) in &lifted {
+        let _ = state
+            .firewall_tx
+            .send(FirewallCommand::Allow {
+                config_id: config_id.clone(),
+                ip: *ip,
+            })
+            .await;
+        state
+            .event_emitter
+            .emit(Event::Unban {
+                config_id: config_id.clone(),
+                ip: ip.to_string(),
+                timestamp,
+            })
+            .await;
+    }
+    info!("Allowlisting {} lifted {} active ban(s)", target, lifted.len());
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/allowlist",
+    tag = "allowlist",
+    responses(
+        (status = 200, description = "All global allowlist entries", body = Vec<AllowlistEntry>),
+    )
+)]
+pub(crate) async fn get_allowlist(State(state): State<AppState>) -> Json<Vec<AllowlistEntry>> {
+    Json(state.allowlist.read().await.entries())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/allowlist/{id}",
+    tag = "allowlist",
+    params(
+        ("id" = String, Path, description = "Allowlist entry ID"),
+    ),
+    responses(
+        (status = 200, description = "Allowlist entry", body = AllowlistEntry),
+        (status = 404, description = "Entry not found"),
+    )
+)]
+pub(crate) async fn get_allowlist_entry(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<AllowlistEntry>, StatusCode> {
+    state
+        .allowlist
+        .read()
+        .await
+        .get(&id)
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/allowlist",
+    tag = "allowlist",
+    params(AllowlistWriteQuery),
+    request_body = AllowlistEntry,
+    responses(
+        (status = 200, description = "Created entry", body = AllowlistEntry),
+        (status = 400, description = "Not an IP address or CIDR"),
+        (status = 409, description = "An entry with this id already exists"),
+        (status = 500, description = "Internal server error"),
+    )
+)]
+pub(crate) async fn create_allowlist_entry(
+    State(state): State<AppState>,
+    Query(query): Query<AllowlistWriteQuery>,
+    Json(mut payload): Json<AllowlistEntry>,
+) -> Result<Json<AllowlistEntry>, StatusCode> {
+    if payload.id.is_empty() {
+        payload.id = Uuid::new_v4().to_string();
+    }
+    let target = payload.normalize().map_err(|_| StatusCode::BAD_REQUEST)?;
+    if state.allowlist.read().await.get(&payload.id).is_some() {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    {
+        let db = state.sqlite_configs_db.lock().await;
+        db.insert_allowlist_entry(&AllowlistRecord::from(&payload))
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    }
+    state.allowlist.write().await.upsert(payload.clone(), target);
+
+    if query.lift_bans {
+        lift_covered_bans(&state, &target).await;
+    }
+
+    Ok(Json(payload))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/allowlist/{id}",
+    tag = "allowlist",
+    params(
+        ("id" = String, Path, description = "Allowlist entry ID"),
+        AllowlistWriteQuery,
+    ),
+    request_body = AllowlistEntry,
+    responses(
+        (status = 200, description = "Updated entry", body = AllowlistEntry),
+        (status = 400, description = "Not an IP address or CIDR, or ID mismatch"),
+        (status = 404, description = "Entry not found"),
+        (status = 500, description = "Internal server error"),
+    )
+)]
+pub(crate) async fn update_allowlist_entry(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<AllowlistWriteQuery>,
+    Json(mut payload): Json<AllowlistEntry>,
+) -> Result<Json<AllowlistEntry>, StatusCode> {
+    if id != payload.id {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let target = payload.normalize().map_err(|_| StatusCode::BAD_REQUEST)?;
+    if state.allowlist.read().await.get(&id).is_none() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    {
+        let db = state.sqlite_configs_db.lock().await;
+        db.insert_allowlist_entry(&AllowlistRecord::from(&payload))
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    }
+    state.allowlist.write().await.upsert(payload.clone(), target);
+
+    if query.lift_bans {
+        lift_covered_bans(&state, &target).await;
+    }
+
+    Ok(Json(payload))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/allowlist/{id}",
+    tag = "allowlist",
+    params(
+        ("id" = String, Path, description = "Allowlist entry ID"),
+    ),
+    responses(
+        (status = 204, description = "Entry deleted"),
+        (status = 404, description = "Entry not found"),
+        (status = 500, description = "Internal server error"),
+    )
+)]
+pub(crate) async fn delete_allowlist_entry(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    let deleted = {
+        let db = state.sqlite_configs_db.lock().await;
+        db.delete_allowlist_entry(&id)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    };
+    if !deleted {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    state.allowlist.write().await.remove(&id);
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,150 @@
+use super::models::{CreateTokenRequest, CreatedTokenResponse};
+use super::AppState;
+use crate::auth::{generate_secret, hash_secret, required_role, ApiToken, Role, TokenRegistry};
+use crate::database::ApiTokenRecord;
+use axum::{
+    extract::{Path, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use uuid::Uuid;
+
+/// Route layer enforcing bearer tokens: 401 without a known token, 403 when
+/// its role is too weak for the route. A no-op until a token exists.
+pub(crate) async fn require_token(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(needed) = required_role(request.method(), request.uri().path()) else {
+        return next.run(request).await;
+    };
+    let role = {
+        let tokens = state.api_tokens.read().await;
+        if !tokens.enabled() {
+            drop(tokens);
+            return next.run(request).await;
+        }
+        bearer_role(&tokens, request.headers())
+    };
+    match role {
+        Some(role) if role >= needed => next.run(request).await,
+        Some(_) => StatusCode::FORBIDDEN.into_response(),
+        None => (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+        )
+            .into_response(),
+    }
+}
+
+/// The role of the bearer token in `headers`, if it is a known one.
+pub(crate) fn bearer_role(tokens: &TokenRegistry, headers: &HeaderMap) -> Option<Role> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .and_then(|secret| tokens.authenticate(secret.trim()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/tokens",
+    tag = "tokens",
+    responses(
+        (status = 200, description = "All API tokens, without their secrets", body = Vec<ApiToken>),
+    )
+)]
+pub(crate) async fn get_tokens(State(state): State<AppState>) -> Json<Vec<ApiToken>> {
+    Json(state.api_tokens.read().await.tokens())
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/tokens",
+    tag = "tokens",
+    request_body = CreateTokenRequest,
+    responses(
+        (status = 200, description = "Created token, with its secret shown once", body = CreatedTokenResponse),
+        (status = 400, description = "Empty name"),
+        (status = 403, description = "Authentication is off; set BANALIZE_CORE_API_TOKEN to mint the first token"),
+        (status = 409, description = "No admin token is stored yet, so this one must be admin"),
+        (status = 500, description = "Internal server error"),
+    )
+)]
+pub(crate) async fn create_token(
+    State(state): State<AppState>,
+    Json(payload): Json<CreateTokenRequest>,
+) -> Result<Json<CreatedTokenResponse>, StatusCode> {
+    if payload.name.trim().is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    {
+        // An open API would hand the first admin secret to whoever asks, and
+        // a read-only first token would leave no way to mint an admin one.
+        let tokens = state.api_tokens.read().await;
+        if !tokens.enabled() {
+            return Err(StatusCode::FORBIDDEN);
+        }
+        if payload.role != Role::Admin && tokens.admin_count() == 0 {
+            return Err(StatusCode::CONFLICT);
+        }
+    }
+    let secret = generate_secret();
+    let hash = hash_secret(&secret);
+    let token = ApiToken {
+        id: Uuid::new_v4().to_string(),
+        name: payload.name,
+        role: payload.role,
+        created_at: crate::detector::now_millis(),
+    };
+
+    {
+        let db = state.sqlite_configs_db.lock().await;
+        db.insert_api_token(&ApiTokenRecord::from((&token, hash.as_str())))
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    }
+    state.api_tokens.write().await.insert(token.clone(), hash);
+
+    Ok(Json(CreatedTokenResponse { token, secret }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/tokens/{id}",
+    tag = "tokens",
+    params(
+        ("id" = String, Path, description = "Token ID"),
+    ),
+    responses(
+        (status = 204, description = "Token revoked"),
+        (status = 404, description = "Token not found"),
+        (status = 409, description = "Last stored admin token; revoking it would turn authentication off"),
+        (status = 500, description = "Internal server error"),
+    )
+)]
+pub(crate) async fn delete_token(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    // Held throughout so two revocations cannot both pass the last-admin check.
+    let mut tokens = state.api_tokens.write().await;
+    let role = tokens
+        .tokens()
+        .into_iter()
+        .find(|t| t.id == id)
+        .map(|t| t.role)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if role == Role::Admin && tokens.admin_count() == 1 {
+        return Err(StatusCode::CONFLICT);
+    }
+    {
+        let db = state.sqlite_configs_db.lock().await;
+        db.delete_api_token(&id)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    }
+    tokens.remove(&id);
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,40 +1,138 @@
-use super::models::{BanResponse, UnbanResponse};
-use super::AppState;
+use super::models::{
+    page_size, ActiveBanQuery, ActiveBanResponse, BanResponse, EventListQuery, ManualBanRequest,
+    UnbanResponse,
+};
+use super::{paged, AppState, Paged};
+use crate::ban_target::BanTarget;
+use crate::events::{Event, FirewallCommand};
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
     response::Json,
 };
 use uuid::Uuid;
 
+async fn list_bans(
+    state: &AppState,
+    query: EventListQuery,
+    config_id: Option<String>,
+) -> Result<Paged<BanResponse>, StatusCode> {
+    let query = query
+        .into_query(config_id)
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    let db = state.sqlite_events_db.lock().await;
+    let (events, total) = db
+        .query_ban_events(&query)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(paged(events.into_iter().map(BanResponse::from).collect(), total))
+}
+
 #[utoipa::path(
     get,
     path = "/api/bans",
     tag = "bans",
+    params(EventListQuery),
     responses(
-        (status = 200, description = "All ban events across all configs", body = Vec<BanResponse>),
+        (status = 200, description = "Ban events across all configs", body = Vec<BanResponse>,
+            headers(("x-total-count" = u64, description = "Matching events before paging"))),
+        (status = 400, description = "Invalid IP filter or time range"),
         (status = 500, description = "Internal server error"),
     )
 )]
 pub(crate) async fn get_bans(
     State(state): State<AppState>,
-) -> Result<Json<Vec<BanResponse>>, StatusCode> {
-    let db = state.sqlite_events_db.lock().await;
-    let events = db
-        .get_ban_events(None)
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Query(query): Query<EventListQuery>,
+) -> Result<Paged<BanResponse>, StatusCode> {
+    list_bans(&state, query, None).await
+}
 
-    let responses = events
-        .into_iter()
-        .map(|e| BanResponse {
-            id: e.id,
-            config_id: e.config_id,
-            ip: e.ip,
-            timestamp: e.timestamp,
+#[utoipa::path(
+    post,
+    path = "/api/bans",
+    tag = "bans",
+    request_body = ManualBanRequest,
+    responses(
+        (status = 200, description = "Ban placed", body = BanResponse),
+        (status = 400, description = "Invalid IP/CIDR, zero duration, or a duration on a permanent ban"),
+        (status = 404, description = "Config not found"),
+        (status = 409, description = "Already banned under this config (the same target, or a network ban covering it), or the target overlaps the allowlist"),
+    )
+)]
+pub(crate) async fn create_ban(
+    State(state): State<AppState>,
+    Json(payload): Json<ManualBanRequest>,
+) -> Result<Json<BanResponse>, StatusCode> {
+    let target: BanTarget = payload.ip.parse().map_err(|_| StatusCode::BAD_REQUEST)?;
+    let config_ban_time = state
+        .configs
+        .read()
+        .await
+        .get(&payload.config_id)
+        .map(|c| c.ban_time)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let ban_time = match (payload.permanent, payload.duration) {
+        (true, Some(_)) | (false, Some(0)) => return Err(StatusCode::BAD_REQUEST),
+        (true, None) => None,
+        (false, Some(ms)) => Some(ms),
+        (false, None) => Some(config_ban_time),
+    };
+    if state.allowlist.read().await.overlaps(&target) {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    let timestamp = crate::detector::now_millis();
+    let id = Uuid::new_v4().to_string();
+    let reason = payload.reason.filter(|r| !r.trim().is_empty());
+
+    // Same path as a detected ban, so restore, expiry and notifications treat
+    // it alike. A host ban also counts towards the IP's recidive history, as
+    // it will once restore replays the audit log.
+    // Checked and inserted in one step, so concurrent requests for the same
+    // (or a covered) target cannot both get through.
+    if !state
+        .store
+        .add_ban_if_absent(&payload.config_id, target, timestamp, ban_time)
+    {
+        return Err(StatusCode::CONFLICT);
+    }
+    let recidive_level = target
+        .host()
+        .map(|ip| state.store.next_recidive(&payload.config_id, ip) + 1);
+    let _ = state
+        .firewall_tx
+        .send(FirewallCommand::Deny {
+            config_id: payload.config_id.clone(),
+            ip: target,
+            timeout_ms: ban_time.unwrap_or(0),
         })
-        .collect();
+        .await;
+    state
+        .event_emitter
+        .emit(Event::Ban {
+            id: id.clone(),
+            config_id: payload.config_id.clone(),
+            ip: target.to_string(),
+            timestamp,
+            ban_time,
+            recidive_level,
+            manual: true,
+            reason: reason.clone(),
+            simulated: false,
+        })
+        .await;
 
-    Ok(Json(responses))
+    Ok(Json(BanResponse {
+        id,
+        config_id: payload.config_id,
+        ip: target.to_string(),
+        timestamp,
+        expires_at: ban_time.map(|t| timestamp.saturating_add(t)),
+        permanent: ban_time.is_none(),
+        manual: true,
+        reason,
+        simulated: false,
+    }))
 }
 
 #[utoipa::path(
@@ -43,32 +141,94 @@ pub(crate) async fn get_bans(
     tag = "bans",
     params(
         ("config_id" = String, Path, description = "Config ID to filter bans by"),
+        EventListQuery,
     ),
     responses(
-        (status = 200, description = "Ban events for the given config", body = Vec<BanResponse>),
+        (status = 200, description = "Ban events for the given config", body = Vec<BanResponse>,
+            headers(("x-total-count" = u64, description = "Matching events before paging"))),
+        (status = 400, description = "Invalid IP filter or time range"),
         (status = 500, description = "Internal server error"),
     )
 )]
 pub(crate) async fn get_bans_by_config(
     State(state): State<AppState>,
     Path(config_id): Path<String>,
-) -> Result<Json<Vec<BanResponse>>, StatusCode> {
-    let db = state.sqlite_events_db.lock().await;
-    let events = db
-        .get_ban_events(Some(&config_id))
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Query(query): Query<EventListQuery>,
+) -> Result<Paged<BanResponse>, StatusCode> {
+    list_bans(&state, query, Some(config_id)).await
+}
 
-    let responses = events
+fn list_active_bans(
+    state: &AppState,
+    query: ActiveBanQuery,
+    config_id: Option<String>,
+) -> Paged<ActiveBanResponse> {
+    let config_id = config_id.or(query.config_id);
+    let bans = state.store.active_bans(config_id.as_deref());
+    let total = bans.len() as u64;
+    let limit = page_size(query.limit) as usize;
+    let now = crate::detector::now_millis();
+    let page = bans
         .into_iter()
-        .map(|e| BanResponse {
-            id: e.id,
-            config_id: e.config_id,
-            ip: e.ip,
-            timestamp: e.timestamp,
+        .skip(usize::try_from(query.offset).unwrap_or(usize::MAX))
+        .take(limit)
+        .map(|ban| {
+            let expires_at = ban.ban_time.map(|t| ban.timestamp.saturating_add(t));
+            let info = ban.target.host().map(|ip| state.geoip.lookup(ip)).unwrap_or_default();
+            ActiveBanResponse {
+                config_id: ban.config_id,
+                ip: ban.target.to_string(),
+                timestamp: ban.timestamp,
+                expires_at,
+                remaining: expires_at.map(|at| at.saturating_sub(now)),
+                permanent: ban.ban_time.is_none(),
+                recidive_count: ban.recidive_count,
+                simulated: ban.simulated,
+                country_code: info.country_code,
+                country_name: info.country_name,
+                flag: info.flag,
+            }
         })
         .collect();
+    paged(page, total)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/bans/active",
+    tag = "bans",
+    params(ActiveBanQuery),
+    responses(
+        (status = 200, description = "Bans in force across all configs, newest first", body = Vec<ActiveBanResponse>,
+            headers(("x-total-count" = u64, description = "Active bans before paging"))),
+    )
+)]
+pub(crate) async fn get_active_bans(
+    State(state): State<AppState>,
+    Query(query): Query<ActiveBanQuery>,
+) -> Paged<ActiveBanResponse> {
+    list_active_bans(&state, query, None)
+}
 
-    Ok(Json(responses))
+#[utoipa::path(
+    get,
+    path = "/api/bans/active/{config_id}",
+    tag = "bans",
+    params(
+        ("config_id" = String, Path, description = "Config ID to filter bans by"),
+        ActiveBanQuery,
+    ),
+    responses(
+        (status = 200, description = "Bans in force for the given config, newest first", body = Vec<ActiveBanResponse>,
+            headers(("x-total-count" = u64, description = "Active bans before paging"))),
+    )
+)]
+pub(crate) async fn get_active_bans_by_config(
+    State(state): State<AppState>,
+    Path(config_id): Path<String>,
+    Query(query): Query<ActiveBanQuery>,
+) -> Paged<ActiveBanResponse> {
+    list_active_bans(&state, query, Some(config_id))
 }
 
 #[utoipa::path(
@@ -82,6 +242,7 @@ pub(crate) async fn get_bans_by_config(
         (status = 200, description = "Ban disabled, unban event created", body = UnbanResponse),
         (status = 400, description = "Invalid IP address in ban record"),
         (status = 404, description = "Ban event not found"),
+        (status = 409, description = "Simulated ban of a monitor-mode config; there is nothing to lift"),
         (status = 500, description = "Internal server error"),
     )
 )]
@@ -89,15 +250,16 @@ pub(crate) async fn disable_ban(
     State(state): State<AppState>,
     Path(id): Path<String>,
 ) -> Result<Json<UnbanResponse>, StatusCode> {
-    use std::net::IpAddr;
-
     let db = state.sqlite_events_db.lock().await;
     let ban_event = db
         .get_ban_event_by_id(&id)
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
         .ok_or(StatusCode::NOT_FOUND)?;
+    if ban_event.simulated {
+        return Err(StatusCode::CONFLICT);
+    }
 
-    let ip: IpAddr = ban_event.ip.parse().map_err(|_| StatusCode::BAD_REQUEST)?;
+    let ip: BanTarget = ban_event.ip.parse().map_err(|_| StatusCode::BAD_REQUEST)?;
     let config_id = ban_event.config_id.clone();
     drop(db);
 
@@ -112,7 +274,7 @@ pub(crate) async fn disable_ban(
     // Remove the firewall rule via the actor (lossless command channel).
     let _ = state
         .firewall_tx
-        .send(crate::events::FirewallCommand::Allow {
+        .send(FirewallCommand::Allow {
             config_id: config_id.clone(),
             ip,
         })
@@ -120,7 +282,7 @@ pub(crate) async fn disable_ban(
 
     state
         .event_emitter
-        .emit(crate::events::Event::Unban {
+        .emit(Event::Unban {
             config_id: config_id.clone(),
             ip: ban_event.ip.clone(),
             timestamp,
@@ -1,5 +1,13 @@
-use super::models::{ConfigResponse, RegexValidationResponse, TailLineResponse};
-use super::AppState;
+use super::models::{
+    BacktestRequest, ConfigResponse, FileStatusResponse, RegexValidationResponse, TailLineResponse,
+};
+use super::{skip_lagged, AppState};
+use crate::allowlist::Allowlist;
+use crate::backtest::{Backtest, BacktestReport};
+use crate::config::{Config, ConfigKind};
+use crate::database::ConfigRecord;
+use crate::file_tail::base_dir;
+use crate::metrics::Bus;
 use axum::{
     extract::{Path, Query, State},
     http::StatusCode,
@@ -10,12 +18,20 @@ use axum::{
 };
 use serde::Deserialize;
 use std::convert::Infallible;
+use std::path::PathBuf;
 use tokio_stream::{wrappers::BroadcastStream, StreamExt};
 
+/// Most of a host log an API backtest reads; the report says when it stopped
+/// short.
+const MAX_BACKTEST_BYTES: u64 = 256 * 1024 * 1024;
+
 #[derive(Deserialize, utoipa::IntoParams)]
 pub(crate) struct ValidateRegexQuery {
     /// The raw config regex to validate, including the `<IP>` placeholder.
     regex: String,
+    /// Validate as an ignore regex, for which `<IP>` is optional.
+    #[serde(default)]
+    ignore: bool,
 }
 
 #[utoipa::path(
@@ -30,7 +46,12 @@ pub(crate) struct ValidateRegexQuery {
 pub(crate) async fn validate_regex(
     Query(query): Query<ValidateRegexQuery>,
 ) -> Json<RegexValidationResponse> {
-    match crate::config::validate_regex_pattern(&query.regex) {
+    let result = if query.ignore {
+        crate::config::validate_ignore_regex_pattern(&query.regex)
+    } else {
+        crate::config::validate_regex_pattern(&query.regex)
+    };
+    match result {
         Ok(()) => Json(RegexValidationResponse {
             valid: true,
             error: None,
@@ -42,6 +63,90 @@ pub(crate) async fn validate_regex(
     }
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/configs/backtest",
+    tag = "configs",
+    request_body = BacktestRequest,
+    responses(
+        (status = 200, description = "IPs the config would have matched and banned, on the log's own timestamps", body = BacktestReport),
+        (status = 400, description = "Not exactly one of config_id/config and of path/sample, invalid patterns or thresholds, or an unreadable log file"),
+        (status = 403, description = "The log file is outside the directories file configs tail"),
+        (status = 404, description = "Config not found"),
+    )
+)]
+pub(crate) async fn backtest_config(
+    State(state): State<AppState>,
+    Json(payload): Json<BacktestRequest>,
+) -> Result<Json<BacktestReport>, StatusCode> {
+    let mut config = match (payload.config_id, payload.config) {
+        (Some(id), None) => state
+            .configs
+            .read()
+            .await
+            .get(&id)
+            .cloned()
+            .ok_or(StatusCode::NOT_FOUND)?,
+        (None, Some(candidate)) => Config::from(candidate),
+        _ => return Err(StatusCode::BAD_REQUEST),
+    };
+    if !payload.regexes.is_empty() {
+        config.regexes = payload.regexes;
+    }
+    if config.validate_detection().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    // The allowlist as it is now, like the detector would see it.
+    let allowlist = Allowlist::from_entries(state.allowlist.read().await.entries());
+    let backtest = Backtest::new(&config, allowlist, payload.year)
+        .map_err(|_| StatusCode::BAD_REQUEST)?
+        .with_max_bytes(MAX_BACKTEST_BYTES);
+    // Reading and matching a whole log is blocking work.
+    let report = match (payload.path, payload.sample) {
+        (Some(path), None) => {
+            let path = source_log(&state, &path).await?;
+            tokio::task::spawn_blocking(move || {
+                let file = std::fs::File::open(&path).map_err(|e| e.to_string())?;
+                backtest.run(std::io::BufReader::new(file))
+            })
+            .await
+        }
+        (None, Some(sample)) => tokio::task::spawn_blocking(move || backtest.run(sample.as_bytes())).await,
+        _ => return Err(StatusCode::BAD_REQUEST),
+    };
+    match report {
+        Ok(Ok(report)) => Ok(Json(report)),
+        Ok(Err(_)) => Err(StatusCode::BAD_REQUEST),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+/// `path` resolved, if it lies under the directory of a file config's path or
+/// glob: the API reads no other host files. 400 when it cannot be resolved,
+/// 403 when it is elsewhere.
+async fn source_log(state: &AppState, path: &str) -> Result<PathBuf, StatusCode> {
+    let path = tokio::fs::canonicalize(path)
+        .await
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    let dirs: Vec<PathBuf> = state
+        .configs
+        .read()
+        .await
+        .values()
+        .filter(|c| matches!(c.kind, ConfigKind::File))
+        .map(|c| base_dir(&c.param))
+        .collect();
+    for dir in dirs {
+        if let Ok(dir) = tokio::fs::canonicalize(&dir).await {
+            if path.starts_with(&dir) {
+                return Ok(path);
+            }
+        }
+    }
+    Err(StatusCode::FORBIDDEN)
+}
+
 #[utoipa::path(
     get,
     path = "/api/configs/{id}/tail",
@@ -64,7 +169,7 @@ pub(crate) async fn tail_config_log(
         .await
         .ok_or(StatusCode::NOT_FOUND)?;
 
-    let stream = BroadcastStream::new(rx).filter_map(|r| r.ok()).map(|line| {
+    let stream = BroadcastStream::new(rx).filter_map(skip_lagged(&state, Bus::Lines)).map(|line| {
         let timestamp = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .map(|d| d.as_millis() as u64)
@@ -76,6 +181,30 @@ pub(crate) async fn tail_config_log(
     Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/configs/{id}/files",
+    tag = "configs",
+    params(
+        ("id" = String, Path, description = "Config ID"),
+    ),
+    responses(
+        (status = 200, description = "Tailing status of each file the config follows (empty for non-file sources)", body = Vec<FileStatusResponse>),
+        (status = 404, description = "No running watcher for this config"),
+    )
+)]
+pub(crate) async fn get_config_files(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<FileStatusResponse>>, StatusCode> {
+    let files = state
+        .watcher_manager
+        .file_status(&id)
+        .await
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(files.into_iter().map(FileStatusResponse::from).collect()))
+}
+
 #[utoipa::path(
     get,
     path = "/api/configs",
@@ -95,21 +224,7 @@ pub(crate) async fn get_configs(
 
     let responses = configs
         .into_iter()
-        .map(|c| {
-            let ignore_ips: Vec<String> =
-                serde_json::from_str(&c.ignore_ips).unwrap_or_default();
-            ConfigResponse {
-                id: c.id,
-                name: c.name,
-                param: c.param,
-                regex: c.regex,
-                ban_time: c.ban_time,
-                find_time: c.find_time,
-                max_matches: c.max_matches,
-                ignore_ips,
-                recidive_multiplicator: c.recidive_multiplicator,
-            }
-        })
+        .map(|c| ConfigResponse::from(Config::from(c)))
         .collect();
 
     Ok(Json(responses))
@@ -138,19 +253,7 @@ pub(crate) async fn get_config(
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
         .ok_or(StatusCode::NOT_FOUND)?;
 
-    let ignore_ips: Vec<String> =
-        serde_json::from_str(&config.ignore_ips).unwrap_or_default();
-    Ok(Json(ConfigResponse {
-        id: config.id,
-        name: config.name,
-        param: config.param,
-        regex: config.regex,
-        ban_time: config.ban_time,
-        find_time: config.find_time,
-        max_matches: config.max_matches,
-        ignore_ips,
-        recidive_multiplicator: config.recidive_multiplicator,
-    }))
+    Ok(Json(ConfigResponse::from(Config::from(config))))
 }
 
 #[utoipa::path(
@@ -169,17 +272,7 @@ pub(crate) async fn create_config(
     State(state): State<AppState>,
     Json(payload): Json<ConfigResponse>,
 ) -> Result<Json<ConfigResponse>, StatusCode> {
-    let config = crate::config::Config {
-        id: payload.id.clone(),
-        name: payload.name.clone(),
-        param: payload.param.clone(),
-        regex: payload.regex.clone(),
-        ban_time: payload.ban_time,
-        find_time: payload.find_time,
-        max_matches: payload.max_matches,
-        ignore_ips: payload.ignore_ips.clone(),
-        recidive_multiplicator: payload.recidive_multiplicator,
-    };
+    let config = Config::from(payload);
 
     if config.validate().is_err() {
         return Err(StatusCode::BAD_REQUEST);
@@ -192,23 +285,11 @@ pub(crate) async fn create_config(
         return Err(StatusCode::CONFLICT);
     }
 
-    let db = state.sqlite_configs_db.lock().await;
-    let ignore_ips_json =
-        serde_json::to_string(&payload.ignore_ips).unwrap_or_default();
-    let record = crate::database::ConfigRecord {
-        id: config.id.clone(),
-        name: config.name.clone(),
-        param: config.param.clone(),
-        regex: config.regex.clone(),
-        ban_time: config.ban_time,
-        find_time: config.find_time,
-        max_matches: config.max_matches,
-        ignore_ips: ignore_ips_json,
-        recidive_multiplicator: config.recidive_multiplicator,
-    };
-
-    db.insert_config(&record)
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    {
+        let db = state.sqlite_configs_db.lock().await;
+        db.insert_config(&ConfigRecord::from(&config))
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    }
 
     state
         .configs
@@ -225,7 +306,7 @@ pub(crate) async fn create_config(
         return Err(StatusCode::INTERNAL_SERVER_ERROR);
     }
 
-    Ok(Json(payload))
+    Ok(Json(ConfigResponse::from(config)))
 }
 
 #[utoipa::path(
@@ -257,58 +338,34 @@ pub(crate) async fn update_config(
         return Err(StatusCode::NOT_FOUND);
     }
 
-    let config = crate::config::Config {
-        id: payload.id.clone(),
-        name: payload.name.clone(),
-        param: payload.param.clone(),
-        regex: payload.regex.clone(),
-        ban_time: payload.ban_time,
-        find_time: payload.find_time,
-        max_matches: payload.max_matches,
-        ignore_ips: payload.ignore_ips.clone(),
-        recidive_multiplicator: payload.recidive_multiplicator,
-    };
+    let config = Config::from(payload);
 
     if config.validate().is_err() {
         return Err(StatusCode::BAD_REQUEST);
     }
 
-    let ignore_ips_json =
-        serde_json::to_string(&payload.ignore_ips).unwrap_or_default();
     {
         let db = state.sqlite_configs_db.lock().await;
-        let record = crate::database::ConfigRecord {
-            id: config.id.clone(),
-            name: config.name.clone(),
-            param: config.param.clone(),
-            regex: config.regex.clone(),
-            ban_time: config.ban_time,
-            find_time: config.find_time,
-            max_matches: config.max_matches,
-            ignore_ips: ignore_ips_json,
-            recidive_multiplicator: config.recidive_multiplicator,
-        };
-        db.insert_config(&record)
+        db.insert_config(&ConfigRecord::from(&config))
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     }
 
-    let config_clone = config.clone();
     state
         .configs
         .write()
         .await
-        .insert(config.id.clone(), config);
+        .insert(config.id.clone(), config.clone());
 
     if state
         .watcher_manager
-        .restart_watcher(config_clone)
+        .restart_watcher(config.clone())
         .await
         .is_err()
     {
         return Err(StatusCode::INTERNAL_SERVER_ERROR);
     }
 
-    Ok(Json(payload))
+    Ok(Json(ConfigResponse::from(config)))
 }
 
 #[utoipa::path(
@@ -1,5 +1,6 @@
 use super::models::EventResponse;
-use super::AppState;
+use super::{skip_lagged, AppState};
+use crate::metrics::Bus;
 use axum::{
     extract::State,
     response::sse::{Event, KeepAlive, Sse},
@@ -19,7 +20,7 @@ pub(crate) async fn stream_events(
     State(state): State<AppState>,
 ) -> Sse<impl futures_core::Stream<Item = Result<Event, Infallible>>> {
     let rx = state.event_emitter.subscribe();
-    let stream = BroadcastStream::new(rx).filter_map(|r| r.ok()).map(|ev| {
+    let stream = BroadcastStream::new(rx).filter_map(skip_lagged(&state, Bus::Events)).map(|ev| {
         let dto = EventResponse::from(ev);
         Ok(Event::default().data(serde_json::to_string(&dto).unwrap_or_default()))
     });
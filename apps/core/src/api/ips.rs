@@ -97,6 +97,7 @@ pub(crate) async fn get_country_stats(
     }
 
     let mut result: Vec<CountryStatsResponse> = by_country.into_values().collect();
+    #[allow(clippy::unnecessary_sort_by)]
     result.sort_by(|a, b| b.ban_count.cmp(&a.ban_count));
     Ok(Json(result))
 }
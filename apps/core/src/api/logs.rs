@@ -1,5 +1,6 @@
-use super::AppState;
+use super::{skip_lagged, AppState};
 use crate::log_capture::LogEntry;
+use crate::metrics::Bus;
 use axum::{
     extract::State,
     response::{
@@ -36,7 +37,7 @@ pub(crate) async fn stream_logs(
 ) -> Sse<impl futures_core::Stream<Item = Result<Event, Infallible>>> {
     let rx = state.log_tx.subscribe();
     let stream = BroadcastStream::new(rx)
-        .filter_map(|r| r.ok())
+        .filter_map(skip_lagged(&state, Bus::Logs))
         .map(|entry| {
             Ok(Event::default().data(serde_json::to_string(&entry).unwrap_or_default()))
         });
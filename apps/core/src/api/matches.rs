@@ -1,26 +1,21 @@
-use super::models::MatchResponse;
-use super::AppState;
+use super::models::{EventListQuery, MatchResponse};
+use super::{paged, AppState, Paged};
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
-    response::Json,
 };
 
-#[utoipa::path(
-    get,
-    path = "/api/matches",
-    tag = "matches",
-    responses(
-        (status = 200, description = "All match events across all configs", body = Vec<MatchResponse>),
-        (status = 500, description = "Internal server error"),
-    )
-)]
-pub(crate) async fn get_matches(
-    State(state): State<AppState>,
-) -> Result<Json<Vec<MatchResponse>>, StatusCode> {
+async fn list_matches(
+    state: &AppState,
+    query: EventListQuery,
+    config_id: Option<String>,
+) -> Result<Paged<MatchResponse>, StatusCode> {
+    let query = query
+        .into_query(config_id)
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
     let db = state.sqlite_events_db.lock().await;
-    let events = db
-        .get_match_events(None)
+    let (events, total) = db
+        .query_match_events(&query)
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     let responses = events
@@ -34,7 +29,26 @@ pub(crate) async fn get_matches(
         })
         .collect();
 
-    Ok(Json(responses))
+    Ok(paged(responses, total))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/matches",
+    tag = "matches",
+    params(EventListQuery),
+    responses(
+        (status = 200, description = "Match events across all configs", body = Vec<MatchResponse>,
+            headers(("x-total-count" = u64, description = "Matching events before paging"))),
+        (status = 400, description = "Invalid IP filter or time range"),
+        (status = 500, description = "Internal server error"),
+    )
+)]
+pub(crate) async fn get_matches(
+    State(state): State<AppState>,
+    Query(query): Query<EventListQuery>,
+) -> Result<Paged<MatchResponse>, StatusCode> {
+    list_matches(&state, query, None).await
 }
 
 #[utoipa::path(
@@ -43,31 +57,19 @@ pub(crate) async fn get_matches(
     tag = "matches",
     params(
         ("config_id" = String, Path, description = "Config ID to filter matches by"),
+        EventListQuery,
     ),
     responses(
-        (status = 200, description = "Match events for the given config", body = Vec<MatchResponse>),
+        (status = 200, description = "Match events for the given config", body = Vec<MatchResponse>,
+            headers(("x-total-count" = u64, description = "Matching events before paging"))),
+        (status = 400, description = "Invalid IP filter or time range"),
         (status = 500, description = "Internal server error"),
     )
 )]
 pub(crate) async fn get_matches_by_config(
     State(state): State<AppState>,
     Path(config_id): Path<String>,
-) -> Result<Json<Vec<MatchResponse>>, StatusCode> {
-    let db = state.sqlite_events_db.lock().await;
-    let events = db
-        .get_match_events(Some(&config_id))
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    let responses = events
-        .into_iter()
-        .map(|e| MatchResponse {
-            id: e.id,
-            config_id: e.config_id,
-            ip: e.ip,
-            timestamp: e.timestamp,
-            line: e.line,
-        })
-        .collect();
-
-    Ok(Json(responses))
+    Query(query): Query<EventListQuery>,
+) -> Result<Paged<MatchResponse>, StatusCode> {
+    list_matches(&state, query, Some(config_id)).await
 }
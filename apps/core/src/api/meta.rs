@@ -1,4 +1,12 @@
-use axum::response::Json;
+use super::auth::bearer_role;
+use super::AppState;
+use crate::health::{Component, ComponentHealth, ComponentStatus};
+use crate::metrics::Gauges;
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Json},
+};
 use serde::Serialize;
 use utoipa::ToSchema;
 
@@ -10,8 +18,13 @@ pub(crate) struct VersionResponse {
 
 #[derive(Debug, Clone, Serialize, ToSchema)]
 pub(crate) struct HealthResponse {
-    /// Liveness marker; always `"ok"` when the core answers.
+    /// `"ok"` when every component is, `"degraded"` when only non-critical
+    /// ones are not, `"unhealthy"` (with a 503) when a critical one is not.
     pub status: String,
+    /// Left out unless the request carries a valid token (or no token is
+    /// configured), as it names configs, files and errors.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub components: Option<Vec<ComponentHealth>>,
 }
 
 #[utoipa::path(
@@ -33,11 +46,75 @@ pub(crate) async fn get_version() -> Json<VersionResponse> {
     path = "/api/health",
     tag = "meta",
     responses(
-        (status = 200, description = "Core is alive", body = HealthResponse),
+        (status = 200, description = "No critical component is degraded", body = HealthResponse),
+        (status = 503, description = "A critical component is degraded", body = HealthResponse),
     )
 )]
-pub(crate) async fn get_health() -> Json<HealthResponse> {
-    Json(HealthResponse {
-        status: "ok".to_string(),
-    })
+pub(crate) async fn get_health(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> (StatusCode, Json<HealthResponse>) {
+    let mut components = vec![
+        ComponentHealth::new(Component::Firewall, None, state.health.firewall.problem()),
+        ComponentHealth::new(Component::EventsDb, None, state.health.events_db.problem()),
+    ];
+    components.extend(state.watcher_manager.health().await);
+    components.push(state.geoip.health());
+    let failing = state.outbox.failing();
+    for notifier in state.notifiers.read().await.iter() {
+        let problem = failing.get(&notifier.id).cloned();
+        components.push(ComponentHealth::new(Component::Notifier, Some(&notifier.id), problem));
+    }
+
+    let healthy = state.health.assess(&mut components);
+    let (code, status) = if !healthy {
+        (StatusCode::SERVICE_UNAVAILABLE, "unhealthy")
+    } else if components.iter().any(|c| c.status == ComponentStatus::Degraded) {
+        (StatusCode::OK, "degraded")
+    } else {
+        (StatusCode::OK, "ok")
+    };
+    let authenticated = {
+        let tokens = state.api_tokens.read().await;
+        !tokens.enabled() || bearer_role(&tokens, &headers).is_some()
+    };
+    (
+        code,
+        Json(HealthResponse {
+            status: status.to_string(),
+            components: authenticated.then_some(components),
+        }),
+    )
+}
+
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "meta",
+    responses(
+        (status = 200, description = "Counters and gauges in the Prometheus text format", body = String, content_type = "text/plain"),
+    )
+)]
+pub(crate) async fn get_metrics(State(state): State<AppState>) -> impl IntoResponse {
+    let mut config_ids: Vec<String> = state.configs.read().await.keys().cloned().collect();
+    config_ids.sort();
+    let mut queues = state.event_emitter.queue_depths().to_vec();
+    let firewall = &state.firewall_tx;
+    queues.push(("firewall", firewall.max_capacity() - firewall.capacity()));
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let gauges = Gauges {
+        config_ids,
+        active_bans: state.store.active_ban_counts(),
+        queues,
+        line_queues: state.watcher_manager.line_queue_depths().await,
+        notifications_dropped: state.event_emitter.notifications_dropped(),
+        geoip_age: state.geoip.build_epoch().map(|built| now.saturating_sub(built)),
+    };
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")],
+        state.metrics.render(&gauges),
+    )
 }
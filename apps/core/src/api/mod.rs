@@ -1,4 +1,6 @@
 pub mod models;
+mod allowlist;
+mod auth;
 mod bans;
 mod configs;
 mod events;
@@ -13,16 +15,29 @@ mod unbans;
 use crate::config::ConfigMap;
 use crate::database::SqliteDatabase;
 use crate::events::{EventEmitter, FirewallCommand};
+use crate::metrics::{Bus, Metrics};
 use crate::store::MemoryStore;
 use crate::watcher_manager::WatcherManager;
-use axum::{response::Html, response::Json, routing::get, routing::post, Router};
+use axum::{
+    http::{header, HeaderName, HeaderValue, Method},
+    middleware,
+    response::Html,
+    response::Json,
+    routing::get,
+    routing::post,
+    Router,
+};
 use models::{
     BanResponse, ConfigResponse, CountryStatsResponse, IpStatsResponse, MatchResponse,
     UnbanResponse,
 };
 use std::sync::Arc;
 use tokio::sync::RwLock;
-use utoipa::OpenApi;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tracing::warn;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
 
 #[derive(Clone)]
 pub struct AppState {
@@ -37,6 +52,37 @@ pub struct AppState {
     pub log_tx: tokio::sync::broadcast::Sender<crate::log_capture::LogEntry>,
     pub geoip: Arc<crate::geoip::GeoIp>,
     pub notifiers: Arc<RwLock<Vec<crate::notifier::NotifierConfig>>>,
+    pub allowlist: Arc<RwLock<crate::allowlist::Allowlist>>,
+    pub api_tokens: Arc<RwLock<crate::auth::TokenRegistry>>,
+    pub metrics: Arc<Metrics>,
+    pub health: Arc<crate::health::Health>,
+    pub outbox: Arc<crate::outbox::Outbox>,
+}
+
+/// Header carrying how many rows an event listing matched before paging.
+const TOTAL_COUNT_HEADER: HeaderName = HeaderName::from_static("x-total-count");
+
+/// An event listing page: the rows as a plain array, the total in a header.
+pub(crate) type Paged<T> = ([(HeaderName, String); 1], Json<Vec<T>>);
+
+pub(crate) fn paged<T>(items: Vec<T>, total: u64) -> Paged<T> {
+    ([(TOTAL_COUNT_HEADER, total.to_string())], Json(items))
+}
+
+/// `filter_map` step of an SSE stream over a broadcast bus: drops what a
+/// lagging subscriber missed, counting it on the way.
+pub(crate) fn skip_lagged<T>(
+    state: &AppState,
+    bus: Bus,
+) -> impl FnMut(Result<T, BroadcastStreamRecvError>) -> Option<T> {
+    let metrics = state.metrics.clone();
+    move |item| match item {
+        Ok(item) => Some(item),
+        Err(BroadcastStreamRecvError::Lagged(missed)) => {
+            metrics.lagged(bus, missed);
+            None
+        }
+    }
 }
 
 #[derive(OpenApi)]
@@ -53,11 +99,16 @@ pub struct AppState {
         configs::update_config,
         configs::delete_config,
         configs::tail_config_log,
+        configs::get_config_files,
         configs::validate_regex,
+        configs::backtest_config,
         matches::get_matches,
         matches::get_matches_by_config,
         bans::get_bans,
+        bans::create_ban,
         bans::get_bans_by_config,
+        bans::get_active_bans,
+        bans::get_active_bans_by_config,
         bans::disable_ban,
         unbans::get_unbans,
         unbans::get_unbans_by_config,
@@ -70,31 +121,68 @@ pub struct AppState {
         notifiers::update_notifier,
         notifiers::delete_notifier,
         notifiers::test_notifier,
+        notifiers::get_deliveries,
+        allowlist::get_allowlist,
+        allowlist::get_allowlist_entry,
+        allowlist::create_allowlist_entry,
+        allowlist::update_allowlist_entry,
+        allowlist::delete_allowlist_entry,
+        auth::get_tokens,
+        auth::create_token,
+        auth::delete_token,
         ip_infos::get_ip_infos,
         ips::get_ip_stats,
         ips::get_country_stats,
         meta::get_version,
         meta::get_health,
+        meta::get_metrics,
     ),
     components(schemas(
         ConfigResponse,
+        crate::config::ConfigKind,
+        crate::config::ConfigMode,
+        crate::config::JournalFilter,
+        crate::config::DockerFilter,
+        crate::config::SyslogFilter,
         MatchResponse,
         BanResponse,
+        models::ActiveBanResponse,
         UnbanResponse,
         IpStatsResponse,
         CountryStatsResponse,
+        models::ManualBanRequest,
         models::TailLineResponse,
+        models::FileStatusResponse,
+        crate::file_tail::FileState,
         models::EventResponse,
         models::TestResultResponse,
+        models::DeliveryResponse,
+        crate::outbox::DeliveryStatus,
         models::RegexValidationResponse,
+        models::BacktestRequest,
+        crate::backtest::BacktestReport,
+        crate::backtest::BacktestIp,
+        crate::backtest::BacktestBan,
         crate::notifier::NotifierConfig,
         crate::notifier::EmailConfig,
         crate::notifier::SignalConfig,
+        crate::notifier::WebhookConfig,
+        crate::notifier::BatchConfig,
+        crate::notifier::DailySummaryConfig,
+        crate::notifier::NotifierFilters,
         crate::notifier::NotifyEventType,
+        crate::allowlist::AllowlistEntry,
+        crate::auth::Role,
+        crate::auth::ApiToken,
+        models::CreateTokenRequest,
+        models::CreatedTokenResponse,
         crate::log_capture::LogEntry,
         crate::geoip::IpInfo,
         meta::VersionResponse,
         meta::HealthResponse,
+        crate::health::ComponentHealth,
+        crate::health::Component,
+        crate::health::ComponentStatus,
     )),
     tags(
         (name = "configs", description = "Configuration management"),
@@ -104,13 +192,32 @@ pub struct AppState {
         (name = "logs",    description = "Core application logs"),
         (name = "events",  description = "Live domain event stream"),
         (name = "notifiers", description = "Notification channels"),
+        (name = "allowlist", description = "Global allowlist shared by all configs"),
+        (name = "tokens",  description = "API bearer tokens"),
         (name = "ip-infos", description = "GeoIP country lookup"),
         (name = "ips",     description = "Per-IP aggregates"),
-        (name = "meta",    description = "Service version and health"),
-    )
+        (name = "meta",    description = "Service version, health and metrics"),
+    ),
+    modifiers(&BearerAuth),
+    security(("bearer" = []))
 )]
 pub struct ApiDoc;
 
+/// Declares the bearer scheme every route but health, version and the docs
+/// expect once tokens are in use.
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "bearer",
+                SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+            );
+        }
+    }
+}
+
 async fn swagger_ui() -> Html<&'static str> {
     Html(
         r##"<!DOCTYPE html>
@@ -138,6 +245,30 @@ async fn swagger_ui() -> Html<&'static str> {
     )
 }
 
+/// CORS for the origins listed in `BANALIZE_CORE_CORS_ORIGINS` (comma
+/// separated, e.g. `https://dash.example.com`). The list is empty by default:
+/// the dashboard reaches the API through its own proxy, so only other origins
+/// need listing. `*` is refused, as it would hand the API to any page.
+pub fn cors_layer(origins: &str) -> CorsLayer {
+    let origins: Vec<HeaderValue> = origins
+        .split(',')
+        .map(str::trim)
+        .filter(|origin| !origin.is_empty())
+        .filter_map(|origin| match HeaderValue::from_str(origin) {
+            Ok(value) if origin != "*" => Some(value),
+            _ => {
+                warn!("Ignoring invalid CORS origin: {}", origin);
+                None
+            }
+        })
+        .collect();
+    CorsLayer::new()
+        .allow_origin(AllowOrigin::list(origins))
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
+        .allow_headers([header::AUTHORIZATION, header::CONTENT_TYPE])
+        .expose_headers([TOTAL_COUNT_HEADER])
+}
+
 pub fn create_router(state: AppState) -> Router {
     // Build the stateful API router first, then collapse it to Router<()> via with_state.
     // SwaggerUi is Into<Router<()>>, so it must be merged after state is resolved.
@@ -160,13 +291,17 @@ pub fn create_router(state: AppState) -> Router {
             "/api/configs/validate-regex",
             get(configs::validate_regex),
         )
+        .route("/api/configs/backtest", post(configs::backtest_config))
         .route("/api/configs/{id}/tail", get(configs::tail_config_log))
+        .route("/api/configs/{id}/files", get(configs::get_config_files))
         .route("/api/matches", get(matches::get_matches))
         .route(
             "/api/matches/{config_id}",
             get(matches::get_matches_by_config),
         )
-        .route("/api/bans", get(bans::get_bans))
+        .route("/api/bans", get(bans::get_bans).post(bans::create_ban))
+        .route("/api/bans/active", get(bans::get_active_bans))
+        .route("/api/bans/active/{config_id}", get(bans::get_active_bans_by_config))
         .route("/api/bans/{config_id}", get(bans::get_bans_by_config))
         .route("/api/bans/{id}/disable", post(bans::disable_ban))
         .route("/api/unbans", get(unbans::get_unbans))
@@ -179,6 +314,7 @@ pub fn create_router(state: AppState) -> Router {
         .route("/api/ips/by-country", get(ips::get_country_stats))
         .route("/api/version", get(meta::get_version))
         .route("/api/health", get(meta::get_health))
+        .route("/metrics", get(meta::get_metrics))
         .route("/api/logs", get(logs::get_logs))
         .route("/api/logs/stream", get(logs::stream_logs))
         .route("/api/events/stream", get(events::stream_events))
@@ -193,6 +329,23 @@ pub fn create_router(state: AppState) -> Router {
                 .delete(notifiers::delete_notifier),
         )
         .route("/api/notifiers/{id}/test", post(notifiers::test_notifier))
+        .route("/api/notifiers/{id}/deliveries", get(notifiers::get_deliveries))
+        .route(
+            "/api/allowlist",
+            get(allowlist::get_allowlist).post(allowlist::create_allowlist_entry),
+        )
+        .route(
+            "/api/allowlist/{id}",
+            get(allowlist::get_allowlist_entry)
+                .put(allowlist::update_allowlist_entry)
+                .delete(allowlist::delete_allowlist_entry),
+        )
+        .route("/api/tokens", get(auth::get_tokens).post(auth::create_token))
+        .route("/api/tokens/{id}", axum::routing::delete(auth::delete_token))
         .route("/swagger", get(swagger_ui))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::require_token,
+        ))
         .with_state(state)
 }
@@ -7,10 +7,36 @@ pub struct ConfigResponse {
     pub id: String,
     /// Human-readable label
     pub name: String,
-    /// Absolute path of the log file to watch
+    /// `file` (default) tails `param`; `journal` follows the systemd journal;
+    /// `docker` follows container logs; `syslog` listens for syslog messages;
+    /// `recidive` counts the bans of every other config instead and ignores
+    /// `param` and `regexes`
+    #[serde(default)]
+    pub kind: crate::config::ConfigKind,
+    /// `enforce` (default) bans in the firewall; `monitor` is a dry run that
+    /// records matches and reports would-be bans, flagged `simulated`, but
+    /// never touches the firewall
+    #[serde(default)]
+    pub mode: crate::config::ConfigMode,
+    /// Absolute path or glob of the log files to watch. For a journal config,
+    /// the journal directory or file; empty for the system journal. For a
+    /// docker config, the container name or id; empty to select by
+    /// `docker.labels`. For a syslog config, the address to listen on:
+    /// `udp://host:port` or `tcp://host:port`.
+    #[serde(default)]
     pub param: String,
-    /// Regex pattern — must contain `<IP>` as placeholder for the IPv4 address
-    pub regex: String,
+    /// Fail patterns — each must contain `<IP>` as placeholder for the IPv4 or
+    /// IPv6 address. A line matches when any of them captures an IP.
+    #[serde(default)]
+    pub regexes: Vec<String>,
+    /// Patterns that veto a match even when a fail pattern captured an IP.
+    /// `<IP>` is allowed but optional.
+    #[serde(default)]
+    pub ignore_regexes: Vec<String>,
+    /// Deprecated single-pattern form. Accepted on input when `regexes` is
+    /// empty, and echoed back as the first pattern for older clients.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regex: Option<String>,
     /// How long a ban lasts in milliseconds
     pub ban_time: u64,
     /// Time window for counting matches in milliseconds
@@ -24,6 +50,67 @@ pub struct ConfigResponse {
     /// `null` to keep a flat `ban_time`.
     #[serde(default)]
     pub recidive_multiplicator: Option<f64>,
+    /// Units and `FIELD=value` matches selecting the entries a journal config
+    /// reads
+    #[serde(default)]
+    pub journal: crate::config::JournalFilter,
+    /// Labels selecting the containers a docker config follows
+    #[serde(default)]
+    pub docker: crate::config::DockerFilter,
+    /// Hostnames and app names selecting the messages a syslog config reads
+    #[serde(default)]
+    pub syslog: crate::config::SyslogFilter,
+}
+
+impl From<crate::config::Config> for ConfigResponse {
+    fn from(config: crate::config::Config) -> Self {
+        Self {
+            regex: config.regexes.first().cloned(),
+            id: config.id,
+            name: config.name,
+            kind: config.kind,
+            mode: config.mode,
+            param: config.param,
+            regexes: config.regexes,
+            ignore_regexes: config.ignore_regexes,
+            ban_time: config.ban_time,
+            find_time: config.find_time,
+            max_matches: config.max_matches,
+            ignore_ips: config.ignore_ips,
+            recidive_multiplicator: config.recidive_multiplicator,
+            journal: config.journal,
+            docker: config.docker,
+            syslog: config.syslog,
+        }
+    }
+}
+
+impl From<ConfigResponse> for crate::config::Config {
+    fn from(payload: ConfigResponse) -> Self {
+        // The legacy single pattern only stands in when no list was sent.
+        let regexes = if payload.regexes.is_empty() {
+            payload.regex.into_iter().collect()
+        } else {
+            payload.regexes
+        };
+        Self {
+            id: payload.id,
+            name: payload.name,
+            kind: payload.kind,
+            mode: payload.mode,
+            param: payload.param,
+            regexes,
+            ignore_regexes: payload.ignore_regexes,
+            ban_time: payload.ban_time,
+            find_time: payload.find_time,
+            max_matches: payload.max_matches,
+            ignore_ips: payload.ignore_ips,
+            recidive_multiplicator: payload.recidive_multiplicator,
+            journal: payload.journal,
+            docker: payload.docker,
+            syslog: payload.syslog,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -39,8 +126,55 @@ pub struct MatchResponse {
 pub struct BanResponse {
     pub id: String,
     pub config_id: String,
+    /// Banned address, or network in CIDR notation for a manual range ban
     pub ip: String,
     pub timestamp: u64,
+    /// When the ban lapses (ms epoch), as fixed when it was issued. `null`
+    /// for permanent bans and for bans recorded before expiry was stored.
+    pub expires_at: Option<u64>,
+    /// Never expires; only an explicit unban lifts it
+    pub permanent: bool,
+    /// Placed through `POST /api/bans` rather than by detection
+    pub manual: bool,
+    /// Operator-supplied reason; `null` for detected bans
+    pub reason: Option<String>,
+    /// Would-be ban of a config in monitor mode; the firewall was never
+    /// touched
+    pub simulated: bool,
+}
+
+impl From<crate::database::sqlite_db::BanEvent> for BanResponse {
+    fn from(e: crate::database::sqlite_db::BanEvent) -> Self {
+        Self {
+            expires_at: e.expires_at(),
+            id: e.id,
+            config_id: e.config_id,
+            ip: e.ip,
+            timestamp: e.timestamp,
+            permanent: e.permanent,
+            manual: e.manual,
+            reason: e.reason,
+            simulated: e.simulated,
+        }
+    }
+}
+
+/// Body of `POST /api/bans`: ban an address or network by hand.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ManualBanRequest {
+    /// Config the ban is filed under (its chain holds the firewall rule)
+    pub config_id: String,
+    /// IPv4/IPv6 address or CIDR range, e.g. `203.0.113.7` or `203.0.113.0/24`
+    pub ip: String,
+    /// Ban length in milliseconds; defaults to the config's `ban_time`
+    #[serde(default)]
+    pub duration: Option<u64>,
+    /// Ban until explicitly lifted; excludes `duration`
+    #[serde(default)]
+    pub permanent: bool,
+    /// Free-form note kept on the ban record and shown in notifications
+    #[serde(default)]
+    pub reason: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -51,6 +185,135 @@ pub struct UnbanResponse {
     pub timestamp: u64,
 }
 
+/// Body of `POST /api/tokens`.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CreateTokenRequest {
+    /// Free-form label, e.g. "grafana"
+    pub name: String,
+    pub role: crate::auth::Role,
+}
+
+/// A freshly created token. `secret` is shown this once: only its hash is kept.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CreatedTokenResponse {
+    #[serde(flatten)]
+    pub token: crate::auth::ApiToken,
+    /// Send as `Authorization: Bearer <secret>`
+    pub secret: String,
+}
+
+/// Largest page an event listing returns, whatever `limit` asks for.
+pub const MAX_PAGE_SIZE: u32 = 1000;
+/// Page an event listing returns when no `limit` is given.
+pub const DEFAULT_PAGE_SIZE: u32 = 100;
+
+/// The page size for a requested `limit`: the default when unset, never
+/// above `MAX_PAGE_SIZE`.
+pub fn page_size(limit: Option<u32>) -> u32 {
+    limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE)
+}
+
+/// Filters, ordering and page for `/api/matches`, `/api/bans` and
+/// `/api/unbans`. The total number of matching rows comes back in the
+/// `X-Total-Count` header.
+#[derive(Debug, Default, Deserialize, utoipa::IntoParams)]
+pub(crate) struct EventListQuery {
+    /// Only events of this config (ignored on the per-config routes).
+    pub config_id: Option<String>,
+    /// Only events for this address or CIDR range, as banned.
+    pub ip: Option<String>,
+    /// Only events at or after this timestamp (ms epoch).
+    pub from: Option<u64>,
+    /// Only events at or before this timestamp (ms epoch).
+    pub to: Option<u64>,
+    /// Column to sort by (default `timestamp`).
+    #[serde(default)]
+    #[param(inline)]
+    pub sort: crate::database::EventSort,
+    /// Sort direction (default `desc`).
+    #[serde(default)]
+    #[param(inline)]
+    pub order: crate::database::SortOrder,
+    /// Page size (default 100), capped at 1000.
+    pub limit: Option<u32>,
+    /// Rows to skip before the page starts, at most 2^63 - 1.
+    #[serde(default)]
+    pub offset: u64,
+}
+
+impl EventListQuery {
+    /// Validate and convert to the database query. `config_id` from the path
+    /// takes precedence over the query string. The IP is normalized the way
+    /// bans store it, so `10.0.0.7/24` finds the `10.0.0.0/24` ban.
+    pub(crate) fn into_query(
+        self,
+        config_id: Option<String>,
+    ) -> Result<crate::database::EventQuery, String> {
+        let ip = match self.ip {
+            Some(ip) => Some(ip.parse::<crate::ban_target::BanTarget>()?.to_string()),
+            None => None,
+        };
+        if let (Some(from), Some(to)) = (self.from, self.to) {
+            if from > to {
+                return Err("from must not be after to".to_string());
+            }
+        }
+        // SQLite takes a signed 64-bit OFFSET.
+        if i64::try_from(self.offset).is_err() {
+            return Err("offset out of range".to_string());
+        }
+        Ok(crate::database::EventQuery {
+            config_id: config_id.or(self.config_id),
+            ip,
+            from: self.from,
+            to: self.to,
+            sort: self.sort,
+            order: self.order,
+            limit: Some(page_size(self.limit)),
+            offset: self.offset,
+        })
+    }
+}
+
+/// Page of `/api/bans/active`. The total number of active bans comes back in
+/// the `X-Total-Count` header.
+#[derive(Debug, Default, Deserialize, utoipa::IntoParams)]
+pub(crate) struct ActiveBanQuery {
+    /// Only bans of this config (ignored on the per-config route).
+    pub config_id: Option<String>,
+    /// Page size (default 100), capped at 1000.
+    pub limit: Option<u32>,
+    /// Bans to skip before the page starts.
+    #[serde(default)]
+    pub offset: u64,
+}
+
+/// A ban in force right now, from the in-memory store rather than the audit
+/// log.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ActiveBanResponse {
+    pub config_id: String,
+    /// Banned address, or network in CIDR notation for a manual range ban
+    pub ip: String,
+    /// When the ban started (ms epoch)
+    pub timestamp: u64,
+    /// When the ban lapses (ms epoch); `null` for a permanent ban
+    pub expires_at: Option<u64>,
+    /// Time left (ms), 0 once due until the cleaner lifts it; `null` for a
+    /// permanent ban
+    pub remaining: Option<u64>,
+    pub permanent: bool,
+    /// Times the address has been banned under the config, this ban
+    /// included; 0 for a range
+    pub recidive_count: u32,
+    /// Would-be ban of a config in monitor mode, not enforced by the firewall
+    pub simulated: bool,
+    /// GeoIP country of a host ban; `null` for ranges or without a database
+    pub country_code: Option<String>,
+    pub country_name: Option<String>,
+    pub flag: Option<String>,
+}
+
 /// One raw log line from a config's live tail stream.
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct TailLineResponse {
@@ -59,6 +322,40 @@ pub struct TailLineResponse {
     pub timestamp: u64,
 }
 
+/// Tailing status of one file followed by a file config.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct FileStatusResponse {
+    pub path: String,
+    pub state: crate::file_tail::FileState,
+    /// Why the file cannot be read, when `state` is `error`
+    pub error: Option<String>,
+    /// Current size in bytes; `null` while the file is missing
+    pub size: Option<u64>,
+    /// Bytes read so far from the current file
+    pub offset: u64,
+    /// Lines read since the watcher started
+    pub lines: u64,
+    /// When the last line was read (ms epoch)
+    pub last_line_at: Option<u64>,
+    /// Rotations (rename or truncation) survived since the watcher started
+    pub rotations: u32,
+}
+
+impl From<crate::file_tail::FileStatus> for FileStatusResponse {
+    fn from(s: crate::file_tail::FileStatus) -> Self {
+        Self {
+            path: s.path,
+            state: s.state,
+            error: s.error,
+            size: s.size,
+            offset: s.offset,
+            lines: s.lines,
+            last_line_at: s.last_line_at,
+            rotations: s.rotations,
+        }
+    }
+}
+
 /// Domain event pushed over `/api/events/stream` so the UI can refresh
 /// the affected views without polling.
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -71,9 +368,15 @@ pub enum EventResponse {
         line: String,
     },
     Ban {
+        id: String,
         config_id: String,
         ip: String,
         timestamp: u64,
+        expires_at: Option<u64>,
+        permanent: bool,
+        manual: bool,
+        reason: Option<String>,
+        simulated: bool,
     },
     Unban {
         config_id: String,
@@ -97,13 +400,25 @@ impl From<crate::events::Event> for EventResponse {
                 line,
             },
             crate::events::Event::Ban {
+                id,
                 config_id,
                 ip,
                 timestamp,
+                ban_time,
+                manual,
+                reason,
+                simulated,
+                ..
             } => Self::Ban {
+                id,
                 config_id,
                 ip,
                 timestamp,
+                expires_at: ban_time.map(|t| timestamp.saturating_add(t)),
+                permanent: ban_time.is_none(),
+                manual,
+                reason,
+                simulated,
             },
             crate::events::Event::Unban {
                 config_id,
@@ -118,12 +433,37 @@ impl From<crate::events::Event> for EventResponse {
     }
 }
 
+/// One notification queued for a notifier and how its delivery went.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DeliveryResponse {
+    pub id: String,
+    pub notifier_id: String,
+    /// `ban`, `unban`, `match`, `digest` or `summary`
+    pub event: String,
+    /// The event's IP; none for digests and summaries
+    pub ip: Option<String>,
+    pub title: String,
+    pub status: crate::outbox::DeliveryStatus,
+    pub attempts: u32,
+    /// When it was queued (ms epoch)
+    pub created_at: u64,
+    /// When the next attempt is due (ms epoch), while pending
+    pub next_attempt_at: Option<u64>,
+    /// Why the last attempt failed
+    pub last_error: Option<String>,
+    pub delivered_at: Option<u64>,
+}
+
 /// Outcome of a test notification — failure is reported in the payload, not
 /// as an HTTP error.
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct TestResultResponse {
     pub success: bool,
     pub message: String,
+    /// Title of the sample ban notification, as this notifier renders it
+    pub title: String,
+    /// Body of the sample ban notification, as this notifier renders it
+    pub body: String,
 }
 
 /// Outcome of validating a config regex — an invalid pattern is reported in the
@@ -136,6 +476,32 @@ pub struct RegexValidationResponse {
     pub error: Option<String>,
 }
 
+/// Body of `POST /api/configs/backtest`: a config, or candidate patterns for
+/// one, and the log to replay it against.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BacktestRequest {
+    /// Replay an existing config...
+    #[serde(default)]
+    pub config_id: Option<String>,
+    /// ...or a candidate one, in the `POST /api/configs` shape. Its source
+    /// (`kind`, `param`, filters) is not used.
+    #[serde(default)]
+    pub config: Option<ConfigResponse>,
+    /// Candidate fail patterns, each with `<IP>`, replacing the config's
+    #[serde(default)]
+    pub regexes: Vec<String>,
+    /// Log file on the core's host, in a directory a file config tails...
+    #[serde(default)]
+    pub path: Option<String>,
+    /// ...or the log text itself
+    #[serde(default)]
+    pub sample: Option<String>,
+    /// Year of RFC 3164 (syslog) timestamps, which carry none. Defaults to
+    /// the current year, or the previous one for dates ahead of now.
+    #[serde(default)]
+    pub year: Option<i32>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct IpStatsResponse {
     pub ip: String,
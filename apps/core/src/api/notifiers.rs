@@ -1,13 +1,33 @@
-use super::models::TestResultResponse;
-use super::AppState;
-use crate::notifier::{self, Notification, NotifierConfig};
+use super::models::{page_size, DeliveryResponse, TestResultResponse};
+use super::{paged, AppState, Paged};
+use crate::notifier::{self, NotifierConfig};
+use crate::outbox::DeliveryStatus;
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
     response::Json,
 };
+use serde::Deserialize;
 use uuid::Uuid;
 
+#[derive(Deserialize, utoipa::IntoParams)]
+pub(crate) struct DeliveryListQuery {
+    /// Only deliveries in this state.
+    status: Option<DeliveryStatus>,
+    /// Page size (default 100), at most 1000.
+    limit: Option<u32>,
+    /// Deliveries to skip, newest first, at most 2^63 - 1.
+    #[serde(default)]
+    offset: u64,
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub(crate) struct TestNotifierQuery {
+    /// Only render the sample notification; send nothing.
+    #[serde(default)]
+    dry_run: bool,
+}
+
 fn to_record(config: &NotifierConfig) -> crate::database::NotifierRecord {
     crate::database::NotifierRecord {
         id: config.id.clone(),
@@ -20,6 +40,21 @@ fn to_record(config: &NotifierConfig) -> crate::database::NotifierRecord {
             .signal_config
             .as_ref()
             .and_then(|c| serde_json::to_string(c).ok()),
+        webhook_config: config
+            .webhook_config
+            .as_ref()
+            .and_then(|c| serde_json::to_string(c).ok()),
+        title_template: config.title_template.clone(),
+        message_template: config.message_template.clone(),
+        batch: config
+            .batch
+            .as_ref()
+            .and_then(|c| serde_json::to_string(c).ok()),
+        daily_summary: config
+            .daily_summary
+            .as_ref()
+            .and_then(|c| serde_json::to_string(c).ok()),
+        filters: serde_json::to_string(&config.filters).ok(),
     }
 }
 
@@ -168,6 +203,12 @@ pub(crate) async fn delete_notifier(
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     }
     state.notifiers.write().await.retain(|n| n.id != id);
+    // Its queue goes with it: nothing would deliver it now.
+    {
+        let db = state.sqlite_events_db.lock().await;
+        db.delete_deliveries(&id)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    }
 
     Ok(StatusCode::NO_CONTENT)
 }
@@ -178,15 +219,17 @@ pub(crate) async fn delete_notifier(
     tag = "notifiers",
     params(
         ("id" = String, Path, description = "Notifier ID"),
+        TestNotifierQuery,
     ),
     responses(
-        (status = 200, description = "Test outcome — check the success field", body = TestResultResponse),
+        (status = 200, description = "Test outcome — check the success field — with the rendered sample ban", body = TestResultResponse),
         (status = 404, description = "Notifier not found"),
     )
 )]
 pub(crate) async fn test_notifier(
     State(state): State<AppState>,
     Path(id): Path<String>,
+    Query(query): Query<TestNotifierQuery>,
 ) -> Result<Json<TestResultResponse>, StatusCode> {
     let config = state
         .notifiers
@@ -197,21 +240,83 @@ pub(crate) async fn test_notifier(
         .cloned()
         .ok_or(StatusCode::NOT_FOUND)?;
 
-    let notification = Notification {
-        title: "Banalize test title".to_string(),
-        message: "Banalize test message".to_string(),
-        html: None,
+    // A sample ban, so the preview exercises the notifier's templates.
+    let sample = notifier::sample_ban_notification();
+    let notification = sample.render_for(&config);
+    let (success, message) = if query.dry_run {
+        (true, "Notification rendered, not sent".to_string())
+    } else {
+        match notifier::send(state.outbox.http(), &config, &notification).await {
+            Ok(()) => (true, "Notification sent".to_string()),
+            Err(e) => (false, format!("Failed to send notification: {}", e)),
+        }
     };
-    let result = notifier::send(&config, &notification).await;
-
-    Ok(Json(match result {
-        Ok(()) => TestResultResponse {
-            success: true,
-            message: "Notification sent".to_string(),
-        },
-        Err(e) => TestResultResponse {
-            success: false,
-            message: format!("Failed to send notification: {}", e),
-        },
+
+    Ok(Json(TestResultResponse {
+        success,
+        message,
+        title: notification.title.clone(),
+        body: notification.message.clone(),
     }))
 }
+
+#[utoipa::path(
+    get,
+    path = "/api/notifiers/{id}/deliveries",
+    tag = "notifiers",
+    params(
+        ("id" = String, Path, description = "Notifier ID"),
+        DeliveryListQuery,
+    ),
+    responses(
+        (status = 200, description = "The notifier's deliveries, newest first", body = Vec<DeliveryResponse>,
+            headers(("x-total-count" = u64, description = "Matching deliveries before paging"))),
+        (status = 400, description = "Offset out of range"),
+        (status = 404, description = "Notifier not found"),
+        (status = 500, description = "Internal server error"),
+    )
+)]
+pub(crate) async fn get_deliveries(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<DeliveryListQuery>,
+) -> Result<Paged<DeliveryResponse>, StatusCode> {
+    if !state.notifiers.read().await.iter().any(|n| n.id == id) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    if i64::try_from(query.offset).is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let db = state.sqlite_events_db.lock().await;
+    let (deliveries, total) = db
+        .query_deliveries(
+            &id,
+            query.status.map(|s| s.as_str()),
+            Some(page_size(query.limit)),
+            query.offset,
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let responses = deliveries
+        .into_iter()
+        .map(|d| {
+            let status = d.status.parse().unwrap_or(DeliveryStatus::Failed);
+            DeliveryResponse {
+                id: d.id,
+                notifier_id: d.notifier_id,
+                event: d.event,
+                ip: d.ip,
+                title: d.title,
+                status,
+                attempts: d.attempts,
+                created_at: d.created_at,
+                next_attempt_at: (status == DeliveryStatus::Pending).then_some(d.next_attempt_at),
+                last_error: d.last_error,
+                delivered_at: d.delivered_at,
+            }
+        })
+        .collect();
+
+    Ok(paged(responses, total))
+}
@@ -1,26 +1,21 @@
-use super::models::UnbanResponse;
-use super::AppState;
+use super::models::{EventListQuery, UnbanResponse};
+use super::{paged, AppState, Paged};
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
-    response::Json,
 };
 
-#[utoipa::path(
-    get,
-    path = "/api/unbans",
-    tag = "unbans",
-    responses(
-        (status = 200, description = "All unban events across all configs", body = Vec<UnbanResponse>),
-        (status = 500, description = "Internal server error"),
-    )
-)]
-pub(crate) async fn get_unbans(
-    State(state): State<AppState>,
-) -> Result<Json<Vec<UnbanResponse>>, StatusCode> {
+async fn list_unbans(
+    state: &AppState,
+    query: EventListQuery,
+    config_id: Option<String>,
+) -> Result<Paged<UnbanResponse>, StatusCode> {
+    let query = query
+        .into_query(config_id)
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
     let db = state.sqlite_events_db.lock().await;
-    let events = db
-        .get_unban_events(None)
+    let (events, total) = db
+        .query_unban_events(&query)
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     let responses = events
@@ -33,7 +28,26 @@ pub(crate) async fn get_unbans(
         })
         .collect();
 
-    Ok(Json(responses))
+    Ok(paged(responses, total))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/unbans",
+    tag = "unbans",
+    params(EventListQuery),
+    responses(
+        (status = 200, description = "Unban events across all configs", body = Vec<UnbanResponse>,
+            headers(("x-total-count" = u64, description = "Matching events before paging"))),
+        (status = 400, description = "Invalid IP filter or time range"),
+        (status = 500, description = "Internal server error"),
+    )
+)]
+pub(crate) async fn get_unbans(
+    State(state): State<AppState>,
+    Query(query): Query<EventListQuery>,
+) -> Result<Paged<UnbanResponse>, StatusCode> {
+    list_unbans(&state, query, None).await
 }
 
 #[utoipa::path(
@@ -42,30 +56,19 @@ pub(crate) async fn get_unbans(
     tag = "unbans",
     params(
         ("config_id" = String, Path, description = "Config ID to filter unbans by"),
+        EventListQuery,
     ),
     responses(
-        (status = 200, description = "Unban events for the given config", body = Vec<UnbanResponse>),
+        (status = 200, description = "Unban events for the given config", body = Vec<UnbanResponse>,
+            headers(("x-total-count" = u64, description = "Matching events before paging"))),
+        (status = 400, description = "Invalid IP filter or time range"),
         (status = 500, description = "Internal server error"),
     )
 )]
 pub(crate) async fn get_unbans_by_config(
     State(state): State<AppState>,
     Path(config_id): Path<String>,
-) -> Result<Json<Vec<UnbanResponse>>, StatusCode> {
-    let db = state.sqlite_events_db.lock().await;
-    let events = db
-        .get_unban_events(Some(&config_id))
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    let responses = events
-        .into_iter()
-        .map(|e| UnbanResponse {
-            id: e.id,
-            config_id: e.config_id,
-            ip: e.ip,
-            timestamp: e.timestamp,
-        })
-        .collect();
-
-    Ok(Json(responses))
+    Query(query): Query<EventListQuery>,
+) -> Result<Paged<UnbanResponse>, StatusCode> {
+    list_unbans(&state, query, Some(config_id)).await
 }
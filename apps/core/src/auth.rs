@@ -0,0 +1,232 @@
+use crate::database::ApiTokenRecord;
+use axum::http::Method;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::warn;
+use utoipa::ToSchema;
+
+/// What a token may do. Roles are ordered: an admin can do anything a
+/// read-only token can.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Read the API (configs, events, stats, streams), change nothing.
+    ReadOnly,
+    /// Everything, including token management.
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::ReadOnly => "read_only",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+impl std::str::FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "read_only" => Ok(Role::ReadOnly),
+            "admin" => Ok(Role::Admin),
+            other => Err(format!("unknown role: {}", other)),
+        }
+    }
+}
+
+/// Reads that expose secrets (tokens, notifier credentials) are admin-only.
+const ADMIN_READ_PREFIXES: [&str; 2] = ["/api/tokens", "/api/notifiers"];
+
+/// Served to anyone: liveness probes and the API description.
+const PUBLIC_PATHS: [&str; 4] = ["/api/health", "/api/version", "/api/openapi.json", "/swagger"];
+
+/// The role a request needs, or `None` when the route is public.
+pub fn required_role(method: &Method, path: &str) -> Option<Role> {
+    if PUBLIC_PATHS.contains(&path) {
+        return None;
+    }
+    let is_read = method == Method::GET || method == Method::HEAD;
+    if is_read && !ADMIN_READ_PREFIXES.iter().any(|p| path.starts_with(p)) {
+        Some(Role::ReadOnly)
+    } else {
+        Some(Role::Admin)
+    }
+}
+
+/// Hex SHA-256 of a token secret, the only form it is stored or compared in.
+pub fn hash_secret(secret: &str) -> String {
+    Sha256::digest(secret.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// A fresh random secret. Two v4 UUIDs give 244 bits from the OS RNG.
+pub fn generate_secret() -> String {
+    format!(
+        "bnz_{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+/// One API token as listed by the API; the secret is never part of it.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ApiToken {
+    pub id: String,
+    /// Free-form label, e.g. "grafana"
+    pub name: String,
+    pub role: Role,
+    /// Creation time (ms epoch)
+    pub created_at: u64,
+}
+
+/// The tokens the API accepts, kept hashed in memory so each request is a
+/// lookup rather than a database read. Shared with the token endpoints so
+/// creations and revocations apply immediately.
+#[derive(Default)]
+pub struct TokenRegistry {
+    tokens: Vec<(ApiToken, String)>,
+    /// Hash of the admin token from `BANALIZE_CORE_API_TOKEN`, if set. It is
+    /// never stored, so it can always be rotated through the environment.
+    bootstrap: Option<String>,
+}
+
+impl TokenRegistry {
+    /// Build from stored rows, skipping any with an unknown role.
+    pub fn from_records(records: Vec<ApiTokenRecord>, bootstrap_secret: Option<&str>) -> Self {
+        let mut registry = Self {
+            tokens: Vec::new(),
+            bootstrap: bootstrap_secret.map(hash_secret),
+        };
+        for record in records {
+            match record.role.parse() {
+                Ok(role) => registry.tokens.push((
+                    ApiToken {
+                        id: record.id,
+                        name: record.name,
+                        role,
+                        created_at: record.created_at,
+                    },
+                    record.token_hash,
+                )),
+                Err(e) => warn!("Skipping API token {}: {}", record.id, e),
+            }
+        }
+        registry
+    }
+
+    /// Authentication is enforced once there is any token to present: a
+    /// bootstrap token or a stored one. Until then the API stays open, as it
+    /// was before tokens existed.
+    pub fn enabled(&self) -> bool {
+        self.bootstrap.is_some() || !self.tokens.is_empty()
+    }
+
+    /// The role of the token with this secret, if it is known.
+    pub fn authenticate(&self, secret: &str) -> Option<Role> {
+        let hash = hash_secret(secret);
+        if self.bootstrap.as_deref() == Some(hash.as_str()) {
+            return Some(Role::Admin);
+        }
+        self.tokens
+            .iter()
+            .find(|(_, h)| *h == hash)
+            .map(|(t, _)| t.role)
+    }
+
+    /// Stored admin tokens. The bootstrap token does not count: it goes
+    /// away with the environment variable.
+    pub fn admin_count(&self) -> usize {
+        self.tokens.iter().filter(|(t, _)| t.role == Role::Admin).count()
+    }
+
+    pub fn tokens(&self) -> Vec<ApiToken> {
+        self.tokens.iter().map(|(t, _)| t.clone()).collect()
+    }
+
+    pub fn insert(&mut self, token: ApiToken, hash: String) {
+        self.tokens.push((token, hash));
+    }
+
+    /// Returns true if a token was removed.
+    pub fn remove(&mut self, id: &str) -> bool {
+        let before = self.tokens.len();
+        self.tokens.retain(|(t, _)| t.id != id);
+        self.tokens.len() != before
+    }
+}
+
+impl From<(&ApiToken, &str)> for ApiTokenRecord {
+    fn from((token, hash): (&ApiToken, &str)) -> Self {
+        Self {
+            id: token.id.clone(),
+            name: token.name.clone(),
+            role: token.role.as_str().to_string(),
+            token_hash: hash.to_string(),
+            created_at: token.created_at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(id: &str, role: &str, secret: &str) -> ApiTokenRecord {
+        ApiTokenRecord {
+            id: id.to_string(),
+            name: id.to_string(),
+            role: role.to_string(),
+            token_hash: hash_secret(secret),
+            created_at: 0,
+        }
+    }
+
+    #[test]
+    fn reads_need_read_only_and_writes_need_admin() {
+        assert_eq!(required_role(&Method::GET, "/api/ips/stats"), Some(Role::ReadOnly));
+        assert_eq!(required_role(&Method::GET, "/api/bans"), Some(Role::ReadOnly));
+        assert_eq!(required_role(&Method::POST, "/api/bans/x/disable"), Some(Role::Admin));
+        assert_eq!(required_role(&Method::DELETE, "/api/configs/x"), Some(Role::Admin));
+        assert_eq!(required_role(&Method::GET, "/api/tokens"), Some(Role::Admin));
+        assert_eq!(required_role(&Method::GET, "/api/notifiers/n"), Some(Role::Admin));
+        assert_eq!(required_role(&Method::GET, "/api/health"), None);
+        assert!(Role::Admin > Role::ReadOnly);
+    }
+
+    #[test]
+    fn authenticates_stored_and_bootstrap_tokens() {
+        let registry = TokenRegistry::from_records(
+            vec![record("ro", "read_only", "s1"), record("bad", "root", "s2")],
+            Some("boot"),
+        );
+        assert!(registry.enabled());
+        assert_eq!(registry.authenticate("s1"), Some(Role::ReadOnly));
+        assert_eq!(registry.authenticate("boot"), Some(Role::Admin));
+        // Unknown roles are dropped rather than guessed.
+        assert_eq!(registry.authenticate("s2"), None);
+        assert_eq!(registry.tokens().len(), 1);
+    }
+
+    #[test]
+    fn open_until_a_token_exists() {
+        let mut registry = TokenRegistry::default();
+        assert!(!registry.enabled());
+        let secret = generate_secret();
+        let token = ApiToken {
+            id: "a".to_string(),
+            name: "a".to_string(),
+            role: Role::Admin,
+            created_at: 0,
+        };
+        registry.insert(token, hash_secret(&secret));
+        assert!(registry.enabled());
+        assert_eq!(registry.authenticate(&secret), Some(Role::Admin));
+        assert!(registry.remove("a"));
+        assert!(!registry.enabled());
+    }
+}
@@ -0,0 +1,621 @@
+//! Replay a log against a config, offline: which IPs it would have matched
+//! and banned, and when.
+//!
+//! Lines run through the detector's own pieces — the compiled patterns, the
+//! ignore list, and a private `MemoryStore` for the `find_time` window, the
+//! ban check and recidive escalation — but on the time written in each line
+//! instead of the wall clock. Nothing reaches the firewall, the audit log or
+//! the notifiers. Served at `POST /api/configs/backtest` and by
+//! `banalize-core backtest`.
+
+use crate::allowlist::Allowlist;
+use crate::ban_target::BanTarget;
+use crate::config::{Config, ConfigKind, ConfigMode, DockerFilter, JournalFilter, SyslogFilter};
+use crate::detector::{ignore_nets, now_millis};
+use crate::ip_extract::IpExtractor;
+use crate::notifier::iso8601;
+use crate::store::MemoryStore;
+use ipnet::IpNet;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::{BufRead, Read};
+use std::net::IpAddr;
+use utoipa::ToSchema;
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+const DAY_MS: u64 = 86_400_000;
+
+/// Longest line replayed; longer ones are skipped and counted in `long_lines`.
+const MAX_LINE_LENGTH: usize = 64 * 1024;
+
+/// What replaying a log against a config found.
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct BacktestReport {
+    /// Lines read
+    pub lines: u64,
+    /// Lines without a timestamp of their own. They take the previous line's;
+    /// those before the first dated line are skipped.
+    pub undated_lines: u64,
+    /// Lines yielding an IP that is not ignored
+    pub matched_lines: u64,
+    /// Lines yielding an IP that `ignore_ips` or the allowlist leaves alone
+    pub ignored_lines: u64,
+    /// Lines over 64 KiB, skipped
+    pub long_lines: u64,
+    /// Reading stopped at the byte limit before the end of the log; the
+    /// report covers what came before
+    pub truncated: bool,
+    /// Earliest line timestamp (ms epoch)
+    pub from: Option<u64>,
+    /// Latest line timestamp (ms epoch)
+    pub to: Option<u64>,
+    /// Every IP that matched, most matches first
+    pub ips: Vec<BacktestIp>,
+    /// The bans the config would have issued, in log order
+    pub bans: Vec<BacktestBan>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct BacktestIp {
+    pub ip: String,
+    pub matches: u64,
+    /// Timestamp of its first matching line (ms epoch)
+    pub first_match: u64,
+    /// Timestamp of its last matching line (ms epoch)
+    pub last_match: u64,
+    /// Bans it would have received
+    pub bans: u32,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct BacktestBan {
+    pub ip: String,
+    /// Timestamp of the line that tipped it over `max_matches` (ms epoch)
+    pub timestamp: u64,
+    /// Effective duration (ms), recidive escalation included
+    pub ban_time: u64,
+    pub expires_at: u64,
+    /// Matches within `find_time` when it was issued
+    pub matches: usize,
+    /// 1-based number of the line that triggered it
+    pub line: u64,
+}
+
+pub struct Backtest {
+    config: Config,
+    extractor: IpExtractor,
+    ignore_nets: Vec<IpNet>,
+    allowlist: Allowlist,
+    /// Year of syslog timestamps, which carry none; guessed when unset.
+    year: Option<i32>,
+    /// Bytes of log read at most; unlimited when unset.
+    max_bytes: Option<u64>,
+    now: u64,
+    store: MemoryStore,
+    last_timestamp: Option<u64>,
+    report: BacktestReport,
+    ips: HashMap<IpAddr, BacktestIp>,
+}
+
+impl Backtest {
+    pub fn new(config: &Config, allowlist: Allowlist, year: Option<i32>) -> Result<Self, String> {
+        let patterns = config.fail_patterns();
+        if patterns.is_empty() {
+            return Err("regexes must contain at least one pattern".to_string());
+        }
+        let extractor = IpExtractor::new(&patterns)?.with_ignore(&config.ignore_regexes)?;
+        Ok(Self {
+            config: config.clone(),
+            extractor,
+            ignore_nets: ignore_nets(&config.ignore_ips),
+            allowlist,
+            year,
+            max_bytes: None,
+            now: now_millis(),
+            store: MemoryStore::new(),
+            last_timestamp: None,
+            report: BacktestReport::default(),
+            ips: HashMap::new(),
+        })
+    }
+
+    /// Stop reading after `max_bytes` of log.
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Replay every line of `reader` (up to the byte limit), then report.
+    pub fn run(mut self, mut reader: impl BufRead) -> Result<BacktestReport, String> {
+        // One byte over the longest line, so a full-length line keeps its
+        // newline and a longer one shows as a read without it.
+        let limit = MAX_LINE_LENGTH as u64 + 1;
+        let mut buf = Vec::new();
+        let mut read = 0u64;
+        loop {
+            if self.max_bytes.is_some_and(|max| read >= max) {
+                self.report.truncated = !reader.fill_buf().map_err(|e| e.to_string())?.is_empty();
+                break;
+            }
+            buf.clear();
+            let n = reader
+                .by_ref()
+                .take(limit)
+                .read_until(b'\n', &mut buf)
+                .map_err(|e| e.to_string())?;
+            if n == 0 {
+                break;
+            }
+            read += n as u64;
+            if n as u64 == limit && !buf.ends_with(b"\n") {
+                // Skip the rest of the line without buffering it.
+                loop {
+                    buf.clear();
+                    let n = reader
+                        .by_ref()
+                        .take(limit)
+                        .read_until(b'\n', &mut buf)
+                        .map_err(|e| e.to_string())?;
+                    read += n as u64;
+                    if n == 0 || buf.ends_with(b"\n") {
+                        break;
+                    }
+                }
+                self.report.lines += 1;
+                self.report.long_lines += 1;
+                continue;
+            }
+            let line = String::from_utf8_lossy(&buf);
+            let line = line.strip_suffix('\n').unwrap_or(&line);
+            self.line(line.strip_suffix('\r').unwrap_or(line));
+        }
+        Ok(self.finish())
+    }
+
+    /// Run one line down the detector's path, at the time written in it.
+    pub fn line(&mut self, line: &str) {
+        self.report.lines += 1;
+        let own = line_timestamp(line, self.year, self.now);
+        if own.is_none() {
+            self.report.undated_lines += 1;
+        }
+        let Some(timestamp) = own.or(self.last_timestamp) else {
+            return;
+        };
+        self.last_timestamp = Some(timestamp);
+        self.report.from = Some(self.report.from.map_or(timestamp, |t| t.min(timestamp)));
+        self.report.to = Some(self.report.to.map_or(timestamp, |t| t.max(timestamp)));
+
+        let id = &self.config.id;
+        // Lift what the cleaner would have lifted by now.
+        self.store.take_expired_bans_now(id, timestamp);
+
+        let Some(ip) = self.extractor.extract(line) else {
+            return;
+        };
+        if self.ignore_nets.iter().any(|net| net.contains(&ip)) || self.allowlist.contains(&ip) {
+            self.report.ignored_lines += 1;
+            return;
+        }
+        self.report.matched_lines += 1;
+        self.store.add_match(id, ip, timestamp);
+        let stats = self.ips.entry(ip).or_insert_with(|| BacktestIp {
+            ip: ip.to_string(),
+            matches: 0,
+            first_match: timestamp,
+            last_match: timestamp,
+            bans: 0,
+        });
+        stats.matches += 1;
+        stats.first_match = stats.first_match.min(timestamp);
+        stats.last_match = stats.last_match.max(timestamp);
+
+        let cutoff = timestamp.saturating_sub(self.config.find_time);
+        let count = self.store.count_matches(id, &ip, cutoff);
+        let target = BanTarget::from(ip);
+        if count >= self.config.max_matches as usize && !self.store.is_banned(id, &target) {
+            let prior = self.store.next_recidive(id, ip);
+            let ban_time = self.config.effective_ban_time(prior);
+            self.store.add_ban_with_duration(id, target, timestamp, Some(ban_time));
+            stats.bans += 1;
+            self.report.bans.push(BacktestBan {
+                ip: ip.to_string(),
+                timestamp,
+                ban_time,
+                expires_at: timestamp.saturating_add(ban_time),
+                matches: count,
+                line: self.report.lines,
+            });
+        }
+    }
+
+    pub fn finish(mut self) -> BacktestReport {
+        let mut ips: Vec<BacktestIp> = self.ips.into_values().collect();
+        ips.sort_by(|a, b| b.matches.cmp(&a.matches).then_with(|| a.ip.cmp(&b.ip)));
+        self.report.ips = ips;
+        self.report
+    }
+}
+
+/// The time of a log line (ms epoch): the first timestamp found in it, as
+/// ISO 8601 (`2026-10-18T22:14:15.123+02:00`, `2026-10-18 22:14:15,123`),
+/// common log format (`18/Oct/2026:22:14:15 +0200`) or RFC 3164
+/// (`Oct 18 22:14:15`). Times without a zone are UTC. RFC 3164 has no year:
+/// `year` when given, else the year of `now`, or the one before for a date
+/// more than a day ahead of `now`.
+pub fn line_timestamp(line: &str, year: Option<i32>, now: u64) -> Option<u64> {
+    let b = line.as_bytes();
+    (0..b.len())
+        .filter(|&i| i == 0 || !b[i - 1].is_ascii_alphanumeric())
+        .find_map(|i| {
+            let s = &b[i..];
+            iso8601_time(s)
+                .or_else(|| common_log_time(s))
+                .or_else(|| rfc3164_time(s, year, now))
+        })
+}
+
+/// Digits of `b` as a number; a leading space counts as a zero (`Oct  5`).
+fn num(b: &[u8]) -> Option<u32> {
+    b.iter().enumerate().try_fold(0, |n, (i, &c)| match c {
+        b'0'..=b'9' => Some(n * 10 + (c - b'0') as u32),
+        b' ' if i == 0 && b.len() > 1 => Some(n),
+        _ => None,
+    })
+}
+
+fn month(b: &[u8]) -> Option<u32> {
+    MONTHS.iter().position(|m| m.as_bytes() == b).map(|m| m as u32 + 1)
+}
+
+fn iso8601_time(b: &[u8]) -> Option<u64> {
+    if b.len() < 19
+        || b[4] != b'-'
+        || b[7] != b'-'
+        || !(b[10] == b'T' || b[10] == b' ')
+        || b[13] != b':'
+        || b[16] != b':'
+    {
+        return None;
+    }
+    let mut rest = &b[19..];
+    let mut ms = 0;
+    if let [b'.' | b',', tail @ ..] = rest {
+        let digits = tail.iter().take_while(|c| c.is_ascii_digit()).count();
+        if digits == 0 {
+            return None;
+        }
+        let scale = [100, 10, 1];
+        ms = tail[..digits.min(3)]
+            .iter()
+            .zip(scale)
+            .map(|(&c, s)| (c - b'0') as u32 * s)
+            .sum();
+        rest = &tail[digits..];
+    }
+    let offset = match rest {
+        [b'Z', ..] => 0,
+        [sign @ (b'+' | b'-'), h1, h2, b':', m1, m2, ..] | [sign @ (b'+' | b'-'), h1, h2, m1, m2, ..]
+            if [h1, h2, m1, m2].iter().all(|c| c.is_ascii_digit()) =>
+        {
+            let minutes = (num(&[*h1, *h2])? * 60 + num(&[*m1, *m2])?) as i64;
+            if *sign == b'-' { -minutes } else { minutes }
+        }
+        _ => 0,
+    };
+    let date = (num(&b[..4])? as i64, num(&b[5..7])?, num(&b[8..10])?);
+    let time = (num(&b[11..13])?, num(&b[14..16])?, num(&b[17..19])?);
+    epoch_ms(date, time, ms, offset)
+}
+
+fn common_log_time(b: &[u8]) -> Option<u64> {
+    if b.len() < 26 || b[2] != b'/' || b[6] != b'/' || b[11] != b':' || b[14] != b':' || b[17] != b':' || b[20] != b' ' {
+        return None;
+    }
+    let minutes = (num(&b[22..24])? * 60 + num(&b[24..26])?) as i64;
+    let offset = match b[21] {
+        b'+' => minutes,
+        b'-' => -minutes,
+        _ => return None,
+    };
+    let date = (num(&b[7..11])? as i64, month(&b[3..6])?, num(&b[..2])?);
+    let time = (num(&b[12..14])?, num(&b[15..17])?, num(&b[18..20])?);
+    epoch_ms(date, time, 0, offset)
+}
+
+fn rfc3164_time(b: &[u8], year: Option<i32>, now: u64) -> Option<u64> {
+    if b.len() < 15 || b[3] != b' ' || b[6] != b' ' || b[9] != b':' || b[12] != b':' {
+        return None;
+    }
+    let (month, day) = (month(&b[..3])?, num(&b[4..6])?);
+    let time = (num(&b[7..9])?, num(&b[10..12])?, num(&b[13..15])?);
+    if let Some(year) = year {
+        return epoch_ms((year as i64, month, day), time, 0, 0);
+    }
+    let this_year = year_of(now);
+    let guess = epoch_ms((this_year, month, day), time, 0, 0)?;
+    if guess > now.saturating_add(DAY_MS) {
+        epoch_ms((this_year - 1, month, day), time, 0, 0)
+    } else {
+        Some(guess)
+    }
+}
+
+/// Days since the epoch of a civil date (Howard Hinnant's days_from_civil).
+fn days_from_civil(y: i64, m: u32, d: u32) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let mp = (m as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + d as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+fn year_of(timestamp_ms: u64) -> i64 {
+    let days = (timestamp_ms / DAY_MS) as i64;
+    let mut year = 1970 + days * 400 / 146_097;
+    while days_from_civil(year + 1, 1, 1) <= days {
+        year += 1;
+    }
+    while days_from_civil(year, 1, 1) > days {
+        year -= 1;
+    }
+    year
+}
+
+/// Milliseconds since the epoch of a local time `offset` minutes east of
+/// UTC; `None` for an impossible date or time, or one before 1970.
+fn epoch_ms((y, mo, d): (i64, u32, u32), (h, mi, s): (u32, u32, u32), ms: u32, offset: i64) -> Option<u64> {
+    if !(1..=12).contains(&mo) || !(1..=31).contains(&d) || h > 23 || mi > 59 || s > 60 {
+        return None;
+    }
+    let secs = days_from_civil(y, mo, d) * 86_400 + (h * 3600 + mi * 60 + s) as i64 - offset * 60;
+    u64::try_from(secs * 1000 + ms as i64).ok()
+}
+
+const USAGE: &str = "\
+usage: banalize-core backtest [options] [LOG_FILE]
+
+Replay LOG_FILE (standard input when omitted or -) against a config and
+print which IPs it would have matched and banned, by the time in each line.
+
+options:
+  --config FILE       config as accepted by POST /api/configs (JSON)
+  --regex PATTERN     fail pattern with <IP>; repeat for several. Replaces
+                      the config's patterns
+  --max-matches N     matches within find_time that ban (default 5)
+  --find-time MS      match window in milliseconds (default 60000)
+  --ban-time MS       ban duration in milliseconds (default 3600000)
+  --year YEAR         year of syslog timestamps, which carry none
+  --json              print the report as JSON
+  -h, --help          show this help";
+
+/// `banalize-core backtest`: replay a log file with no daemon, database or
+/// firewall involved, and print the report.
+pub fn cli(args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut config: Option<Config> = None;
+    let mut regexes = Vec::new();
+    let (mut max_matches, mut find_time, mut ban_time) = (None, None, None);
+    let mut year = None;
+    let mut json = false;
+    let mut path = None;
+    let mut args = args;
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or_else(|| format!("{} needs a value", name));
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            "--config" => {
+                let file = value("--config")?;
+                let text = std::fs::read_to_string(&file).map_err(|e| format!("{}: {}", file, e))?;
+                let payload: crate::api::models::ConfigResponse =
+                    serde_json::from_str(&text).map_err(|e| format!("{}: {}", file, e))?;
+                config = Some(Config::from(payload));
+            }
+            "--regex" => regexes.push(value("--regex")?),
+            "--max-matches" => max_matches = Some(parse_flag("--max-matches", value("--max-matches")?)?),
+            "--find-time" => find_time = Some(parse_flag("--find-time", value("--find-time")?)?),
+            "--ban-time" => ban_time = Some(parse_flag("--ban-time", value("--ban-time")?)?),
+            "--year" => year = Some(parse_flag("--year", value("--year")?)?),
+            "--json" => json = true,
+            flag if flag.starts_with("--") => return Err(format!("unknown option {}\n\n{}", flag, USAGE)),
+            file if path.is_none() => path = Some(file.to_string()),
+            extra => return Err(format!("unexpected argument {}\n\n{}", extra, USAGE)),
+        }
+    }
+
+    let mut config = match config {
+        Some(config) => config,
+        None if regexes.is_empty() => return Err(format!("give --config or --regex\n\n{}", USAGE)),
+        None => cli_config(),
+    };
+    if !regexes.is_empty() {
+        config.regexes = regexes;
+    }
+    config.max_matches = max_matches.unwrap_or(config.max_matches);
+    config.find_time = find_time.unwrap_or(config.find_time);
+    config.ban_time = ban_time.unwrap_or(config.ban_time);
+    config.validate_detection()?;
+
+    let backtest = Backtest::new(&config, Allowlist::default(), year)?;
+    let report = match path.as_deref() {
+        None | Some("-") => backtest.run(std::io::stdin().lock())?,
+        Some(file) => {
+            let f = std::fs::File::open(file).map_err(|e| format!("{}: {}", file, e))?;
+            backtest.run(std::io::BufReader::new(f))?
+        }
+    };
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?);
+    } else {
+        print!("{}", summary(&report));
+    }
+    Ok(())
+}
+
+fn parse_flag<T: std::str::FromStr>(name: &str, value: String) -> Result<T, String> {
+    value.parse().map_err(|_| format!("{}: invalid value {}", name, value))
+}
+
+/// The config `--regex` runs under when no `--config` is given.
+fn cli_config() -> Config {
+    Config {
+        id: "backtest".to_string(),
+        name: "backtest".to_string(),
+        kind: ConfigKind::File,
+        mode: ConfigMode::Enforce,
+        param: String::new(),
+        regexes: Vec::new(),
+        ignore_regexes: Vec::new(),
+        ban_time: 3_600_000,
+        find_time: 60_000,
+        max_matches: 5,
+        ignore_ips: Vec::new(),
+        recidive_multiplicator: None,
+        journal: JournalFilter::default(),
+        docker: DockerFilter::default(),
+        syslog: SyslogFilter::default(),
+    }
+}
+
+/// The report as the CLI prints it by default.
+fn summary(report: &BacktestReport) -> String {
+    let mut out = format!(
+        "{} lines ({} undated), {} matched, {} ignored\n",
+        report.lines, report.undated_lines, report.matched_lines, report.ignored_lines
+    );
+    if report.long_lines > 0 {
+        out.push_str(&format!("{} lines over 64 KiB skipped\n", report.long_lines));
+    }
+    if let (Some(from), Some(to)) = (report.from, report.to) {
+        out.push_str(&format!("from {} to {}\n", iso8601(from), iso8601(to)));
+    }
+    out.push_str(&format!("\n{} bans\n", report.bans.len()));
+    for ban in &report.bans {
+        out.push_str(&format!(
+            "  {}  {}  {} matches, {}s, line {}\n",
+            iso8601(ban.timestamp),
+            ban.ip,
+            ban.matches,
+            ban.ban_time / 1000,
+            ban.line
+        ));
+    }
+    out.push_str(&format!("\n{} IPs matched\n", report.ips.len()));
+    for ip in &report.ips {
+        out.push_str(&format!(
+            "  {}  {} matches, {} bans, {} .. {}\n",
+            ip.ip,
+            ip.matches,
+            ip.bans,
+            iso8601(ip.first_match),
+            iso8601(ip.last_match)
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_792_361_655_000; // 2026-10-18T22:14:15Z
+
+    fn at(line: &str) -> Option<u64> {
+        line_timestamp(line, None, NOW)
+    }
+
+    #[test]
+    fn parses_common_log_timestamps() {
+        assert_eq!(at("2026-10-18T22:14:15Z sshd: fail"), Some(NOW));
+        assert_eq!(at("2026-10-18T22:14:15.250Z x"), Some(NOW + 250));
+        assert_eq!(at("2026-10-19T00:14:15+02:00 x"), Some(NOW));
+        assert_eq!(at("2026-10-18 22:14:15,5 fail2ban.filter"), Some(NOW + 500));
+        assert_eq!(
+            at(r#"192.0.2.1 - - [19/Oct/2026:00:14:15 +0200] "GET / HTTP/1.1" 404"#),
+            Some(NOW)
+        );
+        assert_eq!(at("<38>Oct 18 22:14:15 gw sshd[1]: x"), Some(NOW));
+        assert_eq!(at("Oct  1 00:00:00 gw sshd[1]: x"), Some(1_790_812_800_000));
+        // A syslog date ahead of now belongs to last year, unless told.
+        assert_eq!(at("Dec 31 23:59:59 gw x"), Some(1_767_225_599_000));
+        assert_eq!(line_timestamp("Dec 31 23:59:59 gw x", Some(2026), NOW), Some(1_798_761_599_000));
+        assert_eq!(at("Failed password for root from 192.0.2.1"), None);
+        assert_eq!(at("2026-13-01T00:00:00Z"), None);
+    }
+
+    fn config(max_matches: u32, find_time: u64) -> Config {
+        Config {
+            regexes: vec!["fail from <IP>".to_string()],
+            max_matches,
+            find_time,
+            ban_time: 10_000,
+            recidive_multiplicator: Some(2.0),
+            ignore_ips: vec!["10.0.0.0/8".to_string()],
+            ..cli_config()
+        }
+    }
+
+    #[test]
+    fn replays_on_line_time() {
+        let log = "\
+2026-10-18T22:00:00Z fail from 192.0.2.1
+2026-10-18T22:00:05Z fail from 192.0.2.1
+  continued without a timestamp
+2026-10-18T22:01:00Z fail from 192.0.2.1
+2026-10-18T22:01:01Z fail from 192.0.2.1
+2026-10-18T22:01:02Z fail from 10.1.2.3
+2026-10-18T22:01:03Z fail from 192.0.2.1
+2026-10-18T22:02:00Z fail from 192.0.2.1
+";
+        let report = Backtest::new(&config(2, 30_000), Allowlist::default(), None)
+            .unwrap()
+            .run(log.as_bytes())
+            .unwrap();
+        assert_eq!(report.lines, 8);
+        assert_eq!(report.undated_lines, 1);
+        assert_eq!(report.matched_lines, 6);
+        assert_eq!(report.ignored_lines, 1);
+
+        // 22:00:05 bans; 22:01:00 opens a new window (the first ban expired
+        // at 22:00:15) and 22:01:01 bans again for twice as long; 22:01:03
+        // is still inside it; 22:02:00 is past it, but alone in its window.
+        let bans: Vec<(u64, u64, u64)> = report
+            .bans
+            .iter()
+            .map(|b| (b.timestamp, b.ban_time, b.line))
+            .collect();
+        let t0 = 1_792_360_800_000; // 22:00:00
+        assert_eq!(bans, vec![(t0 + 5_000, 10_000, 2), (t0 + 61_000, 20_000, 5)]);
+        assert_eq!(report.ips.len(), 1);
+        assert_eq!(report.ips[0].matches, 6);
+        assert_eq!(report.ips[0].bans, 2);
+        assert_eq!(report.ips[0].first_match, t0);
+        assert_eq!(report.from, Some(t0));
+        assert_eq!(report.to, Some(t0 + 120_000));
+    }
+
+    #[test]
+    fn skips_long_lines_and_stops_at_the_byte_limit() {
+        let first = "2026-10-18T22:00:00Z fail from 192.0.2.1\n";
+        let log = format!(
+            "{first}{}\n2026-10-18T22:00:01Z fail from 192.0.2.2\n2026-10-18T22:00:02Z fail from 192.0.2.3\n",
+            "x".repeat(3 * MAX_LINE_LENGTH)
+        );
+        let backtest = || Backtest::new(&config(1, 30_000), Allowlist::default(), None).unwrap();
+
+        let report = backtest().run(log.as_bytes()).unwrap();
+        assert_eq!((report.lines, report.long_lines, report.bans.len()), (4, 1, 3));
+        assert!(!report.truncated);
+
+        let report = backtest().with_max_bytes(first.len() as u64).run(log.as_bytes()).unwrap();
+        assert_eq!((report.lines, report.bans.len()), (1, 1));
+        assert!(report.truncated);
+    }
+}
@@ -0,0 +1,131 @@
+use ipnet::{IpNet, Ipv4Net};
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// What a ban applies to: one address or a whole network.
+///
+/// Detected bans are always single addresses; manual bans may name a CIDR.
+/// Single addresses are held as /32 or /128 networks but displayed as the bare
+/// address, so the audit log, the API and the firewall rules of host bans read
+/// exactly as they did before networks could be banned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BanTarget(IpNet);
+
+impl BanTarget {
+    pub fn is_ipv4(&self) -> bool {
+        matches!(self.0, IpNet::V4(_))
+    }
+
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        self.0.contains(&ip.to_canonical())
+    }
+
+    /// Whether `other` lies entirely within this target.
+    pub fn covers(&self, other: &BanTarget) -> bool {
+        self.0.contains(&other.0)
+    }
+
+    /// Whether the two share any address. Networks either nest or are
+    /// disjoint, so one covering the other is the only way to overlap.
+    pub fn overlaps(&self, other: &BanTarget) -> bool {
+        self.covers(other) || other.covers(self)
+    }
+
+    /// The address itself when the target is a single host.
+    pub fn host(&self) -> Option<IpAddr> {
+        (self.0.prefix_len() == self.0.max_prefix_len()).then(|| self.0.addr())
+    }
+}
+
+/// A network in the form detection compares against: host bits cleared, and
+/// an IPv4-mapped network (`::ffff:10.0.0.0/104`) as the IPv4 network it
+/// stands for (`10.0.0.0/8`), since extracted addresses are canonicalized.
+pub fn canonical_net(net: IpNet) -> IpNet {
+    if let IpNet::V6(v6) = net {
+        let mapped = v6.addr().to_ipv4_mapped().zip(v6.prefix_len().checked_sub(96));
+        if let Some(Ok(v4)) = mapped.map(|(addr, prefix)| Ipv4Net::new(addr, prefix)) {
+            return IpNet::V4(v4.trunc());
+        }
+    }
+    net.trunc()
+}
+
+impl From<IpAddr> for BanTarget {
+    fn from(ip: IpAddr) -> Self {
+        Self(IpNet::from(ip.to_canonical()))
+    }
+}
+
+/// Accepts `10.0.0.1`, `2001:db8::1` or a CIDR such as `10.0.0.0/24`. Host
+/// bits of a CIDR are cleared (`10.0.0.7/24` is `10.0.0.0/24`), and IPv4-mapped
+/// addresses and networks become IPv4, matching what detection extracts.
+impl FromStr for BanTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Ok(ip) = s.parse::<IpAddr>() {
+            return Ok(Self::from(ip));
+        }
+        let net: IpNet = s
+            .parse()
+            .map_err(|_| format!("not an IP address or CIDR: {}", s))?;
+        Ok(Self(canonical_net(net)))
+    }
+}
+
+impl fmt::Display for BanTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.host() {
+            Some(ip) => ip.fmt(f),
+            None => self.0.fmt(f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hosts_display_as_bare_addresses() {
+        let target: BanTarget = "10.0.0.1".parse().unwrap();
+        assert_eq!(target.to_string(), "10.0.0.1");
+        assert_eq!(target, BanTarget::from("10.0.0.1".parse::<IpAddr>().unwrap()));
+        // A full-length prefix is the same host.
+        assert_eq!("2001:db8::1/128".parse::<BanTarget>().unwrap().to_string(), "2001:db8::1");
+    }
+
+    #[test]
+    fn networks_are_truncated() {
+        let target: BanTarget = "10.0.0.7/24".parse().unwrap();
+        assert_eq!(target.to_string(), "10.0.0.0/24");
+        assert!(target.host().is_none());
+        assert!(target.is_ipv4());
+        assert!(target.contains(&"10.0.0.200".parse().unwrap()));
+        assert!(target.covers(&"10.0.0.128/25".parse().unwrap()));
+        assert!(!target.covers(&"10.0.0.0/16".parse().unwrap()));
+        assert!(target.overlaps(&"10.0.0.0/16".parse().unwrap()));
+        assert!(!target.overlaps(&"10.0.1.0/24".parse().unwrap()));
+    }
+
+    #[test]
+    fn mapped_addresses_become_ipv4() {
+        let target: BanTarget = "::ffff:192.0.2.1".parse().unwrap();
+        assert_eq!(target.to_string(), "192.0.2.1");
+        assert!(target.is_ipv4());
+
+        let net: BanTarget = "::ffff:10.1.2.3/104".parse().unwrap();
+        assert_eq!(net.to_string(), "10.0.0.0/8");
+        assert!(net.contains(&"10.200.0.1".parse().unwrap()));
+        // Wider than the mapped range: it stays an IPv6 network.
+        assert!(!"::ffff:0:0/95".parse::<BanTarget>().unwrap().is_ipv4());
+    }
+
+    #[test]
+    fn garbage_is_rejected() {
+        assert!("not-an-ip".parse::<BanTarget>().is_err());
+        assert!("10.0.0.0/33".parse::<BanTarget>().is_err());
+    }
+}
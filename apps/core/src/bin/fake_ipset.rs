@@ -0,0 +1,333 @@
+//! Fake `ipset` binary used by the e2e test-suite.
+//!
+//! Modelled on `fake_iptables.rs`: `banalize-core` is pointed at this stand-in
+//! via `BANALIZE_IPSET_BIN` when running the iptables backend in ipset mode. It
+//! keeps a persistent model of the sets (`<log>.state`, lock-guarded) and
+//! supports the subset of commands the firewall emits — `create`, `destroy`,
+//! `add`, `del`, `test`, `flush`, `list -n` and `restore` from stdin — with
+//! ipset-compatible exit codes and stderr, including the global `-exist` flag.
+//!
+//! Every mutating command is appended verbatim to `BANALIZE_FAKE_IPSET_LOG`;
+//! for `restore` the script lines read from stdin follow it, so tests can see
+//! what a batch loaded. `BANALIZE_FAKE_IPSET_FAIL` forces matching commands to
+//! fail.
+
+use ipnet::IpNet;
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Write};
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::process::exit;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const PREFIX: &str = "ipset v7.19: ";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    if args.iter().any(|a| a == "--version" || a == "version") {
+        println!("ipset v7.19, protocol version: 7");
+        return;
+    }
+
+    // `-exist` is a global flag: it may appear anywhere on the command line.
+    let exist = args.iter().any(|a| a == "-exist");
+    let tokens: Vec<&str> = args
+        .iter()
+        .map(String::as_str)
+        .filter(|a| *a != "-exist")
+        .collect();
+    let Some((&command, rest)) = tokens.split_first() else {
+        return;
+    };
+
+    if command == "restore" {
+        let mut script = String::new();
+        let _ = std::io::stdin().read_to_string(&mut script);
+        log_command(&format!("{}\n{}", args.join(" "), script.trim_end()));
+        check_fault(&script);
+        with_state(|state| {
+            for line in script.lines().filter(|l| !l.trim().is_empty()) {
+                let parts: Vec<&str> = line.split_whitespace().collect();
+                let line_exist = exist || parts.contains(&"-exist");
+                let parts: Vec<&str> = parts.into_iter().filter(|p| *p != "-exist").collect();
+                if let Some((cmd, rest)) = parts.split_first() {
+                    apply(state, cmd, rest, line_exist)?;
+                }
+            }
+            Ok(())
+        });
+        return;
+    }
+
+    if command == "list" {
+        let state = load_state();
+        for name in state.sets.keys() {
+            println!("{}", name);
+        }
+        return;
+    }
+
+    let line = args.join(" ");
+    if command != "test" {
+        log_command(&line);
+    }
+    check_fault(&line);
+
+    if command == "test" {
+        let state = load_state();
+        let present = match (rest.first().and_then(|n| state.sets.get(*n)), rest.get(1)) {
+            (Some(set), Some(ip)) => parse_net(ip).is_some_and(|net| set.members.contains(&net)),
+            _ => false,
+        };
+        if !present {
+            fail("Warning: the element is NOT in the set");
+        }
+        return;
+    }
+
+    with_state(|state| apply(state, command, rest, exist));
+}
+
+/// Fail any command whose text contains `BANALIZE_FAKE_IPSET_FAIL`.
+fn check_fault(text: &str) {
+    if let Ok(needle) = env::var("BANALIZE_FAKE_IPSET_FAIL") {
+        if !needle.is_empty() && text.contains(&needle) {
+            fail("ipset: simulated failure (BANALIZE_FAKE_IPSET_FAIL)");
+        }
+    }
+}
+
+fn log_command(line: &str) {
+    if let Ok(log_path) = env::var("BANALIZE_FAKE_IPSET_LOG") {
+        if let Ok(mut f) = OpenOptions::new().create(true).append(true).open(&log_path) {
+            let _ = writeln!(f, "{}", line);
+        }
+    }
+}
+
+/// Exit like ipset does on error: message on stderr, non-zero status.
+fn fail(msg: &str) -> ! {
+    eprintln!("{}", msg);
+    exit(1);
+}
+
+fn err(msg: &str) -> String {
+    format!("{}{}", PREFIX, msg)
+}
+
+/// Apply one mutating command to the model.
+fn apply(state: &mut State, command: &str, rest: &[&str], exist: bool) -> Result<(), String> {
+    let name = rest
+        .first()
+        .map(|n| n.to_string())
+        .ok_or_else(|| err("Syntax error: missing set name"))?;
+    let missing = || err("The set with the given name does not exist");
+    match command {
+        "create" | "-N" => {
+            let family = match rest.iter().position(|t| *t == "family") {
+                Some(i) => rest.get(i + 1).copied().unwrap_or("inet"),
+                None => "inet",
+            };
+            if state.sets.contains_key(&name) {
+                return if exist {
+                    Ok(())
+                } else {
+                    Err(err("Set cannot be created: set with the same name already exists"))
+                };
+            }
+            state.sets.insert(
+                name,
+                Set {
+                    family: family.to_string(),
+                    members: BTreeSet::new(),
+                },
+            );
+            Ok(())
+        }
+        "destroy" | "-X" => state.sets.remove(&name).map(|_| ()).ok_or_else(missing),
+        "flush" | "-F" => {
+            state.sets.get_mut(&name).ok_or_else(missing)?.members.clear();
+            Ok(())
+        }
+        "add" | "-A" => {
+            let set = state.sets.get_mut(&name).ok_or_else(missing)?;
+            let ip = set.parse_member(rest.get(1))?;
+            if !set.members.insert(ip) && !exist {
+                return Err(err("Element cannot be added to the set: it's already added"));
+            }
+            Ok(())
+        }
+        "del" | "-D" => {
+            let set = state.sets.get_mut(&name).ok_or_else(missing)?;
+            let ip = set.parse_member(rest.get(1))?;
+            if !set.members.remove(&ip) && !exist {
+                return Err(err("Element cannot be deleted from the set: it's not added"));
+            }
+            Ok(())
+        }
+        // Anything else is accepted as a no-op success.
+        _ => Ok(()),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Persistent set model
+// ---------------------------------------------------------------------------
+
+struct Set {
+    /// `inet` or `inet6`.
+    family: String,
+    members: BTreeSet<IpNet>,
+}
+
+/// Parse a `hash:net` element: a bare address is its own /32 or /128.
+fn parse_net(value: &str) -> Option<IpNet> {
+    match value.parse::<IpAddr>() {
+        Ok(ip) => Some(IpNet::from(ip)),
+        Err(_) => value.parse::<IpNet>().ok().map(|net| net.trunc()),
+    }
+}
+
+/// Render an element the way `ipset list` does: hosts without a prefix.
+fn show_net(net: &IpNet) -> String {
+    if net.prefix_len() == net.max_prefix_len() {
+        net.addr().to_string()
+    } else {
+        net.to_string()
+    }
+}
+
+impl Set {
+    /// Parse a member, rejecting addresses of the other family.
+    fn parse_member(&self, value: Option<&&str>) -> Result<IpNet, String> {
+        let value = value.ok_or_else(|| err("Syntax error: missing element"))?;
+        let ip = parse_net(value)
+            .ok_or_else(|| err(&format!("Syntax error: cannot parse {}: resolving to IP address failed", value)))?;
+        let ok = match ip {
+            IpNet::V4(_) => self.family == "inet",
+            IpNet::V6(_) => self.family == "inet6",
+        };
+        if !ok {
+            return Err(err(&format!(
+                "Syntax error: cannot parse {}: resolving to {} address failed",
+                value,
+                if self.family == "inet6" { "IPv6" } else { "IPv4" }
+            )));
+        }
+        Ok(ip)
+    }
+}
+
+#[derive(Default)]
+struct State {
+    sets: BTreeMap<String, Set>,
+}
+
+impl State {
+    fn render(&self) -> String {
+        let mut out = String::new();
+        for (name, set) in &self.sets {
+            out.push_str(&format!("set {} {}\n", name, set.family));
+            for ip in &set.members {
+                out.push_str(&format!("member {} {}\n", name, show_net(ip)));
+            }
+        }
+        out
+    }
+
+    fn parse(text: &str) -> Self {
+        let mut state = State::default();
+        for line in text.lines() {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            match parts.as_slice() {
+                ["set", name, family] => {
+                    state.sets.insert(
+                        name.to_string(),
+                        Set {
+                            family: family.to_string(),
+                            members: BTreeSet::new(),
+                        },
+                    );
+                }
+                ["member", name, ip] => {
+                    if let (Some(set), Some(ip)) = (state.sets.get_mut(*name), parse_net(ip)) {
+                        set.members.insert(ip);
+                    }
+                }
+                _ => {}
+            }
+        }
+        state
+    }
+}
+
+fn state_path() -> Option<PathBuf> {
+    env::var("BANALIZE_FAKE_IPSET_LOG")
+        .ok()
+        .map(|log| PathBuf::from(format!("{}.state", log)))
+}
+
+fn load_state() -> State {
+    match state_path().and_then(|p| fs::read_to_string(p).ok()) {
+        Some(text) => State::parse(&text),
+        None => State::default(),
+    }
+}
+
+fn save_state(state: &State) {
+    if let Some(path) = state_path() {
+        let _ = fs::write(path, state.render());
+    }
+}
+
+/// Best-effort cross-process advisory lock around the read-modify-write cycle,
+/// implemented with an exclusive-create lockfile (see `fake_iptables.rs`).
+struct StateLock {
+    path: Option<PathBuf>,
+}
+
+impl StateLock {
+    fn acquire() -> Self {
+        let path = state_path().map(|p| p.with_extension("state.lock"));
+        if let Some(ref p) = path {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while Instant::now() < deadline {
+                if OpenOptions::new().write(true).create_new(true).open(p).is_ok() {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(5));
+            }
+        }
+        StateLock { path }
+    }
+}
+
+impl Drop for StateLock {
+    fn drop(&mut self) {
+        if let Some(ref p) = self.path {
+            let _ = fs::remove_file(p);
+        }
+    }
+}
+
+/// Run a read-modify-write transaction; state is only persisted on `Ok`, and
+/// the lock is released before any `exit`. A failing `restore` line fails
+/// the whole batch.
+fn with_state<F: FnOnce(&mut State) -> Result<(), String>>(f: F) {
+    let result = {
+        let _lock = StateLock::acquire();
+        let mut state = load_state();
+        let r = f(&mut state);
+        if r.is_ok() {
+            save_state(&state);
+        }
+        r
+    };
+    if let Err(msg) = result {
+        fail(&msg);
+    }
+}
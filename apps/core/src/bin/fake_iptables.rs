@@ -21,6 +21,10 @@
 //!
 //! Every mutating command is still appended verbatim to
 //! `BANALIZE_FAKE_IPTABLES_LOG` so existing substring assertions keep working.
+//!
+//! Invoked through a link whose name contains `ip6tables` (the way the real
+//! xtables-multi binary dispatches), it models the IPv6 family instead: same
+//! grammar, but logging to `BANALIZE_FAKE_IP6TABLES_LOG` with its own state.
 
 use std::collections::BTreeMap;
 use std::env;
@@ -41,7 +45,8 @@ fn main() {
     // --version: probed by the iptables crate in IPTables::new(). The firewall's
     // fake path constructs IPTables directly, but answer anyway for safety.
     if args.iter().any(|a| a == "--version") {
-        println!("iptables v1.8.9 (nf_tables)");
+        let tool = if is_ipv6() { "ip6tables" } else { "iptables" };
+        println!("{} v1.8.9 (nf_tables)", tool);
         return;
     }
 
@@ -104,8 +109,26 @@ fn normalize(args: &[String]) -> Vec<String> {
     out
 }
 
+/// Whether this invocation models `ip6tables` rather than `iptables`.
+fn is_ipv6() -> bool {
+    env::args()
+        .next()
+        .map(PathBuf::from)
+        .and_then(|p| p.file_name().map(|n| n.to_string_lossy().contains("ip6tables")))
+        .unwrap_or(false)
+}
+
+/// Env var naming this family's command log (the state file sits next to it).
+fn log_var() -> &'static str {
+    if is_ipv6() {
+        "BANALIZE_FAKE_IP6TABLES_LOG"
+    } else {
+        "BANALIZE_FAKE_IPTABLES_LOG"
+    }
+}
+
 fn log_command(args: &[String]) {
-    if let Ok(log_path) = env::var("BANALIZE_FAKE_IPTABLES_LOG") {
+    if let Ok(log_path) = env::var(log_var()) {
         if let Ok(mut f) = OpenOptions::new().create(true).append(true).open(&log_path) {
             let _ = writeln!(f, "{}", args.join(" "));
         }
@@ -177,7 +200,7 @@ impl State {
 }
 
 fn state_path() -> Option<PathBuf> {
-    env::var("BANALIZE_FAKE_IPTABLES_LOG")
+    env::var(log_var())
         .ok()
         .map(|log| PathBuf::from(format!("{}.state", log)))
 }
@@ -0,0 +1,453 @@
+//! Fake `nft` binary used by the e2e test-suite.
+//!
+//! Modelled on `fake_iptables.rs`: the real `nft` requires root and mutates
+//! the host firewall, so `banalize-core` is pointed at this stand-in via
+//! `BANALIZE_NFT_BIN`. It keeps a small persistent model of the ruleset so it
+//! behaves like the real tool for the subset of commands the nftables backend
+//! emits:
+//!
+//!   * state is persisted next to the command log (`<log>.state`) and guarded by
+//!     a cross-process lock so concurrent invocations don't corrupt it;
+//!   * tables, chains (with their rules), typed sets and set elements are
+//!     modelled, and commands fail with nft-compatible stderr where the real
+//!     tool would: missing objects, deleting a set a rule still references,
+//!     adding an address of the wrong family to a set;
+//!   * element timeouts are honoured: an element whose timeout has elapsed is
+//!     gone, exactly as if the kernel had expired it;
+//!   * `;`-separated commands run as one transaction, like an nft batch: if
+//!     any of them fails, none takes effect;
+//!   * `list ruleset` renders the current state;
+//!   * `BANALIZE_FAKE_NFT_FAIL` lets a test force matching commands to fail.
+//!
+//! Every mutating command is appended verbatim to `BANALIZE_FAKE_NFT_LOG` so
+//! tests can assert on it with plain substring checks.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use ipnet::IpNet;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::process::exit;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const NO_SUCH_FILE: &str = "Error: Could not process rule: No such file or directory";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    if args.iter().any(|a| a == "--version" || a == "-v") {
+        println!("nftables v1.0.9 (Old Doc Yak #3)");
+        return;
+    }
+
+    let line = args.join(" ");
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    let verb = match tokens.first() {
+        Some(verb) => *verb,
+        // Nothing we model: succeed silently.
+        None => return,
+    };
+
+    // Listing is read-only and must not be logged.
+    if verb == "list" {
+        print!("{}", load_state().render());
+        return;
+    }
+    log_command(&line);
+
+    // Optional fault injection: fail any command whose line contains the
+    // configured substring.
+    if let Ok(needle) = env::var("BANALIZE_FAKE_NFT_FAIL") {
+        if !needle.is_empty() && line.contains(&needle) {
+            fail("Error: simulated failure (BANALIZE_FAKE_NFT_FAIL)");
+        }
+    }
+
+    with_state(|s| {
+        for command in split_batch(&tokens) {
+            apply(s, command)?;
+        }
+        Ok(())
+    });
+}
+
+/// Split a command line at the `;` separating commands. A `;` inside braces
+/// belongs to a set or chain definition.
+fn split_batch<'a>(tokens: &'a [&'a str]) -> Vec<&'a [&'a str]> {
+    let mut commands = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        match *token {
+            "{" => depth += 1,
+            "}" => depth = depth.saturating_sub(1),
+            ";" if depth == 0 => {
+                commands.push(&tokens[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    commands.push(&tokens[start..]);
+    commands
+}
+
+/// Apply one command of a batch to the model.
+fn apply(s: &mut State, command: &[&str]) -> Result<(), String> {
+    let (verb, object, rest) = match command {
+        [verb, object, rest @ ..] => (*verb, *object, rest),
+        // Nothing we model: succeed silently.
+        _ => return Ok(()),
+    };
+
+    match (verb, object) {
+        ("add", "table") => {
+            s.tables.entry(table_key(rest)?).or_default();
+        }
+        ("delete", "table") => {
+            s.tables.remove(&table_key(rest)?).ok_or_else(no_such_file)?;
+        }
+        ("add", "chain") => {
+            let name = arg(rest, 2)?;
+            s.table_mut(rest)?.chains.entry(name).or_default();
+        }
+        ("flush", "chain") => {
+            let name = arg(rest, 2)?;
+            s.table_mut(rest)?
+                .chains
+                .get_mut(&name)
+                .ok_or_else(no_such_file)?
+                .clear();
+        }
+        ("add", "rule") => {
+            let chain = arg(rest, 2)?;
+            let rule = rest.get(3..).unwrap_or_default().join(" ");
+            let table = s.table_mut(rest)?;
+            // A rule can only reference sets that exist.
+            if let Some(set) = rule.split_whitespace().find_map(|t| t.strip_prefix('@')) {
+                if !table.sets.contains_key(set) {
+                    return Err(no_such_file());
+                }
+            }
+            table.chains.get_mut(&chain).ok_or_else(no_such_file)?.push(rule);
+        }
+        ("add", "set") => {
+            let name = arg(rest, 2)?;
+            let addr_type = match rest.iter().position(|t| *t == "type") {
+                Some(i) => arg(rest, i + 1)?,
+                None => return Err("Error: set definition does not specify key".to_string()),
+            };
+            s.table_mut(rest)?.sets.entry(name).or_insert_with(|| Set {
+                addr_type,
+                elements: BTreeMap::new(),
+            });
+        }
+        ("delete", "set") => {
+            let name = arg(rest, 2)?;
+            let table = s.table_mut(rest)?;
+            let referenced = table
+                .chains
+                .values()
+                .flatten()
+                .any(|rule| rule.split_whitespace().any(|t| t.strip_prefix('@') == Some(name.as_str())));
+            if referenced {
+                return Err("Error: Could not process rule: Device or resource busy".to_string());
+            }
+            table.sets.remove(&name).ok_or_else(no_such_file)?;
+        }
+        ("add", "element") => {
+            let name = arg(rest, 2)?;
+            let now = now_millis();
+            let set = s.table_mut(rest)?.sets.get_mut(&name).ok_or_else(no_such_file)?;
+            for (ip, timeout) in parse_elements(rest)? {
+                set.check_family(&ip)?;
+                let expires = timeout.map(|ms| now + ms).unwrap_or(0);
+                // Like the kernel, re-adding a live element leaves it untouched.
+                set.elements.entry(ip).or_insert(expires);
+            }
+        }
+        ("delete", "element") => {
+            let name = arg(rest, 2)?;
+            let set = s.table_mut(rest)?.sets.get_mut(&name).ok_or_else(no_such_file)?;
+            for (ip, _) in parse_elements(rest)? {
+                set.elements.remove(&ip).ok_or_else(no_such_file)?;
+            }
+        }
+        // Anything else is accepted as a no-op success so unforeseen commands
+        // never spuriously fail a test.
+        _ => {}
+    }
+    Ok(())
+}
+
+fn log_command(line: &str) {
+    if let Ok(log_path) = env::var("BANALIZE_FAKE_NFT_LOG") {
+        if let Ok(mut f) = OpenOptions::new().create(true).append(true).open(&log_path) {
+            let _ = writeln!(f, "{}", line);
+        }
+    }
+}
+
+/// Exit like nft does on error: message on stderr, non-zero status.
+fn fail(msg: &str) -> ! {
+    eprintln!("{}", msg);
+    exit(1);
+}
+
+fn no_such_file() -> String {
+    NO_SUCH_FILE.to_string()
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// The positional argument at `i` (after the verb and object).
+fn arg(rest: &[&str], i: usize) -> Result<String, String> {
+    rest.get(i)
+        .map(|s| s.to_string())
+        .ok_or_else(|| "Error: syntax error, unexpected end of file".to_string())
+}
+
+/// `<family> <table>`, the key of a table in the model.
+fn table_key(rest: &[&str]) -> Result<String, String> {
+    Ok(format!("{} {}", arg(rest, 0)?, arg(rest, 1)?))
+}
+
+/// The elements between `{` and `}`: comma-separated addresses, each with an
+/// optional `timeout <N>s`.
+fn parse_elements(rest: &[&str]) -> Result<Vec<(IpNet, Option<u64>)>, String> {
+    let open = rest.iter().position(|t| *t == "{");
+    let close = rest.iter().rposition(|t| *t == "}");
+    let body = match (open, close) {
+        (Some(o), Some(c)) if o < c => rest[o + 1..c].join(" "),
+        _ => return Err("Error: syntax error, expected element list".to_string()),
+    };
+    let mut out = Vec::new();
+    for element in body.split(',') {
+        let parts: Vec<&str> = element.split_whitespace().collect();
+        let ip = match parts.first().and_then(|p| parse_net(p)) {
+            Some(ip) => ip,
+            None => return Err(format!("Error: invalid element '{}'", element.trim())),
+        };
+        let timeout = match parts.as_slice() {
+            [_, "timeout", value] => Some(parse_duration(value)?),
+            _ => None,
+        };
+        out.push((ip, timeout));
+    }
+    Ok(out)
+}
+
+/// An interval-set element: a bare address is its own /32 or /128.
+fn parse_net(value: &str) -> Option<IpNet> {
+    match value.parse::<IpAddr>() {
+        Ok(ip) => Some(IpNet::from(ip)),
+        Err(_) => value.parse::<IpNet>().ok().map(|net| net.trunc()),
+    }
+}
+
+/// Render an element the way `nft list` does: hosts without a prefix.
+fn show_net(net: &IpNet) -> String {
+    if net.prefix_len() == net.max_prefix_len() {
+        net.addr().to_string()
+    } else {
+        net.to_string()
+    }
+}
+
+/// `<N>s` or `<N>ms`, in milliseconds.
+fn parse_duration(value: &str) -> Result<u64, String> {
+    let parsed = match value.strip_suffix("ms") {
+        Some(ms) => ms.parse::<u64>().ok(),
+        None => value
+            .strip_suffix('s')
+            .and_then(|s| s.parse::<u64>().ok())
+            .map(|s| s * 1000),
+    };
+    parsed.ok_or_else(|| format!("Error: invalid timeout '{}'", value))
+}
+
+// ---------------------------------------------------------------------------
+// Persistent ruleset model
+// ---------------------------------------------------------------------------
+
+struct Set {
+    /// `ipv4_addr` or `ipv6_addr`.
+    addr_type: String,
+    /// Element → absolute expiry in epoch millis (0: never expires).
+    elements: BTreeMap<IpNet, u64>,
+}
+
+impl Set {
+    fn check_family(&self, ip: &IpNet) -> Result<(), String> {
+        let ok = match ip {
+            IpNet::V4(_) => self.addr_type == "ipv4_addr",
+            IpNet::V6(_) => self.addr_type == "ipv6_addr",
+        };
+        if ok {
+            Ok(())
+        } else {
+            Err(format!("Error: datatype mismatch, expected {}", self.addr_type))
+        }
+    }
+}
+
+#[derive(Default)]
+struct Table {
+    /// Chain → ordered rules.
+    chains: BTreeMap<String, Vec<String>>,
+    sets: BTreeMap<String, Set>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Keyed by `<family> <name>`.
+    tables: BTreeMap<String, Table>,
+}
+
+impl State {
+    fn table_mut(&mut self, rest: &[&str]) -> Result<&mut Table, String> {
+        let key = table_key(rest)?;
+        self.tables.get_mut(&key).ok_or_else(no_such_file)
+    }
+
+    /// One line per object, in dependency order so `parse` can replay it.
+    fn render(&self) -> String {
+        let mut out = String::new();
+        for (key, table) in &self.tables {
+            out.push_str(&format!("table {}\n", key));
+            for (name, set) in &table.sets {
+                out.push_str(&format!("set {} {} {}\n", key, name, set.addr_type));
+                for (ip, expires) in &set.elements {
+                    out.push_str(&format!("element {} {} {} {}\n", key, name, show_net(ip), expires));
+                }
+            }
+            for (name, rules) in &table.chains {
+                out.push_str(&format!("chain {} {}\n", key, name));
+                for rule in rules {
+                    out.push_str(&format!("rule {} {} {}\n", key, name, rule));
+                }
+            }
+        }
+        out
+    }
+
+    /// Rebuild the model, dropping elements whose timeout has elapsed.
+    fn parse(text: &str) -> Self {
+        let now = now_millis();
+        let mut state = State::default();
+        for line in text.lines() {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            match parts.as_slice() {
+                ["table", family, name] => {
+                    state.tables.entry(format!("{} {}", family, name)).or_default();
+                }
+                ["set", family, table, name, addr_type] => {
+                    let table = state.tables.entry(format!("{} {}", family, table)).or_default();
+                    table.sets.insert(
+                        name.to_string(),
+                        Set {
+                            addr_type: addr_type.to_string(),
+                            elements: BTreeMap::new(),
+                        },
+                    );
+                }
+                ["element", family, table, name, ip, expires] => {
+                    let expires: u64 = expires.parse().unwrap_or(0);
+                    if expires != 0 && expires <= now {
+                        continue;
+                    }
+                    let table = state.tables.entry(format!("{} {}", family, table)).or_default();
+                    if let (Some(set), Some(ip)) = (table.sets.get_mut(*name), parse_net(ip)) {
+                        set.elements.insert(ip, expires);
+                    }
+                }
+                ["chain", family, table, name] => {
+                    let table = state.tables.entry(format!("{} {}", family, table)).or_default();
+                    table.chains.entry(name.to_string()).or_default();
+                }
+                ["rule", family, table, name, rule @ ..] => {
+                    let table = state.tables.entry(format!("{} {}", family, table)).or_default();
+                    table.chains.entry(name.to_string()).or_default().push(rule.join(" "));
+                }
+                _ => {}
+            }
+        }
+        state
+    }
+}
+
+fn state_path() -> Option<PathBuf> {
+    env::var("BANALIZE_FAKE_NFT_LOG")
+        .ok()
+        .map(|log| PathBuf::from(format!("{}.state", log)))
+}
+
+fn load_state() -> State {
+    match state_path().and_then(|p| fs::read_to_string(p).ok()) {
+        Some(text) => State::parse(&text),
+        None => State::default(),
+    }
+}
+
+fn save_state(state: &State) {
+    if let Some(path) = state_path() {
+        let _ = fs::write(path, state.render());
+    }
+}
+
+/// Best-effort cross-process advisory lock around the read-modify-write cycle,
+/// implemented with an exclusive-create lockfile (see `fake_iptables.rs`).
+struct StateLock {
+    path: Option<PathBuf>,
+}
+
+impl StateLock {
+    fn acquire() -> Self {
+        let path = state_path().map(|p| p.with_extension("state.lock"));
+        if let Some(ref p) = path {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while Instant::now() < deadline {
+                if OpenOptions::new().write(true).create_new(true).open(p).is_ok() {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(5));
+            }
+            // Timed out (likely a stale lock from a crashed run): proceed
+            // anyway and take ownership so the lockfile gets cleaned up.
+        }
+        StateLock { path }
+    }
+}
+
+impl Drop for StateLock {
+    fn drop(&mut self) {
+        if let Some(ref p) = self.path {
+            let _ = fs::remove_file(p);
+        }
+    }
+}
+
+/// Run a read-modify-write transaction against the persisted state; state is
+/// only persisted on `Ok`, and the lock is released before any `exit`.
+fn with_state<F: FnOnce(&mut State) -> Result<(), String>>(f: F) {
+    let result = {
+        let _lock = StateLock::acquire();
+        let mut state = load_state();
+        let r = f(&mut state);
+        if r.is_ok() {
+            save_state(&state);
+        }
+        r
+    };
+    if let Err(msg) = result {
+        fail(&msg);
+    }
+}
@@ -63,6 +63,9 @@ impl Cleaner {
         for (config_id, config) in configs.iter() {
             let cutoff = now.saturating_sub(config.find_time);
             self.store.prune_matches(config_id, cutoff);
+            // Would-be bans of monitor-mode configs lapse silently: the
+            // firewall never saw them, so there is nothing to unban.
+            self.store.prune_simulated_bans(config_id, now);
         }
 
         // Expire bans: take everything past ban_time and push the unban out to
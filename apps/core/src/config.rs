@@ -1,12 +1,190 @@
+use crate::database::ConfigRecord;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+/// Where a config's lines come from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigKind {
+    /// Tail the file named by `param` and match it against `regexes`.
+    #[default]
+    File,
+    /// Watch the bans of every other config, like fail2ban's recidive jail:
+    /// each one counts as a match, so an IP banned `max_matches` times within
+    /// `find_time` — by any mix of configs — is banned again here.
+    Recidive,
+    /// Follow the systemd journal in `param` (a directory or a single journal
+    /// file; empty for the system journal), narrowed by `journal`, and match
+    /// each entry's message against `regexes`.
+    Journal,
+    /// Follow the stdout/stderr of the Docker container named by `param`, or
+    /// of every container carrying `docker.labels`, and match it against
+    /// `regexes`.
+    Docker,
+    /// Listen for syslog messages on the address in `param`
+    /// (`udp://host:port` or `tcp://host:port`), narrowed by `syslog`, and
+    /// match each message against `regexes`.
+    Syslog,
+}
+
+impl ConfigKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConfigKind::File => "file",
+            ConfigKind::Recidive => "recidive",
+            ConfigKind::Journal => "journal",
+            ConfigKind::Docker => "docker",
+            ConfigKind::Syslog => "syslog",
+        }
+    }
+}
+
+impl std::str::FromStr for ConfigKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "file" => Ok(ConfigKind::File),
+            "recidive" => Ok(ConfigKind::Recidive),
+            "journal" => Ok(ConfigKind::Journal),
+            "docker" => Ok(ConfigKind::Docker),
+            "syslog" => Ok(ConfigKind::Syslog),
+            other => Err(format!("unknown config kind: {}", other)),
+        }
+    }
+}
+
+/// Whether a config's bans reach the firewall.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigMode {
+    /// Ban offenders in the firewall.
+    #[default]
+    Enforce,
+    /// Dry run: record matches and report would-be bans (audit log,
+    /// notifications, active bans) without touching the firewall.
+    Monitor,
+}
+
+impl ConfigMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConfigMode::Enforce => "enforce",
+            ConfigMode::Monitor => "monitor",
+        }
+    }
+}
+
+impl std::str::FromStr for ConfigMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "enforce" => Ok(ConfigMode::Enforce),
+            "monitor" => Ok(ConfigMode::Monitor),
+            other => Err(format!("unknown config mode: {}", other)),
+        }
+    }
+}
+
+/// Line a recidive config sees for each ban issued by another config. The
+/// source config id comes first so `ignore_regexes` can exclude configs.
+pub fn recidive_line(config_id: &str, ip: &str) -> String {
+    format!("[{}] Ban {}", config_id, ip)
+}
+
+/// The fail pattern matching `recidive_line`. Range bans never match: `<IP>`
+/// only captures single addresses and the pattern is anchored.
+pub const RECIDIVE_PATTERN: &str = r"^\[.*\] Ban <IP>$";
+
+/// Which journal entries a journal config reads. Values of the same field are
+/// alternatives and different fields must all match, as with `journalctl`;
+/// an empty filter reads every entry.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct JournalFilter {
+    /// systemd units (`_SYSTEMD_UNIT`); a name without a suffix means
+    /// `<name>.service`
+    #[serde(default)]
+    pub units: Vec<String>,
+    /// `FIELD=value` matches, e.g. `SYSLOG_IDENTIFIER=sshd`
+    #[serde(default)]
+    pub matches: Vec<String>,
+}
+
+impl JournalFilter {
+    pub fn is_empty(&self) -> bool {
+        self.units.is_empty() && self.matches.is_empty()
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.units.iter().any(|u| u.is_empty()) {
+            return Err("journal units cannot be empty".to_string());
+        }
+        for item in &self.matches {
+            let valid = item.split_once('=').is_some_and(|(field, _)| {
+                !field.is_empty()
+                    && !field.starts_with(|c: char| c.is_ascii_digit())
+                    && field.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+            });
+            if !valid {
+                return Err(format!("invalid journal match (expected FIELD=value): {}", item));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Which containers a docker config follows when it names none in `param`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct DockerFilter {
+    /// Labels (`key` or `key=value`) a container must all carry
+    #[serde(default)]
+    pub labels: Vec<String>,
+}
+
+/// Which senders a syslog config listens to. Each non-empty list must contain
+/// the message's value; an empty filter takes every message.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct SyslogFilter {
+    /// Sender hostnames (case-insensitive). A message without one is known by
+    /// the address it came from.
+    #[serde(default)]
+    pub hostnames: Vec<String>,
+    /// RFC 5424 APP-NAME, or the RFC 3164 tag without its `[pid]`
+    #[serde(default)]
+    pub app_names: Vec<String>,
+}
+
+impl SyslogFilter {
+    pub fn is_empty(&self) -> bool {
+        self.hostnames.is_empty() && self.app_names.is_empty()
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.hostnames.iter().chain(&self.app_names).any(|v| v.is_empty()) {
+            return Err("syslog hostnames and app names cannot be empty".to_string());
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub id: String,
     pub name: String,
-    pub param: String, // File path to watch
-    pub regex: String, // Regex pattern with <IP> placeholder
+    #[serde(default)]
+    pub kind: ConfigKind,
+    /// Enforce bans, or only report them.
+    #[serde(default)]
+    pub mode: ConfigMode,
+    pub param: String, // File path, journal location, container or listen address; unused by recidive configs
+    /// Fail patterns, each with the <IP> placeholder. A line counts as a match
+    /// when any of them captures an IP (tried in order).
+    pub regexes: Vec<String>,
+    /// Patterns that veto a match: a line matching any of them is skipped even
+    /// when a fail pattern captured an IP. `<IP>` is allowed but optional.
+    #[serde(default)]
+    pub ignore_regexes: Vec<String>,
     pub ban_time: u64, // Ban duration in milliseconds
     pub find_time: u64, // Time window for matches in milliseconds
     pub max_matches: u32, // Maximum matches before ban
@@ -17,6 +195,15 @@ pub struct Config {
     /// `None` keeps the flat `ban_time` for every ban.
     #[serde(default)]
     pub recidive_multiplicator: Option<f64>,
+    /// Journal entries to read; journal configs only.
+    #[serde(default)]
+    pub journal: JournalFilter,
+    /// Containers to follow by label; docker configs only.
+    #[serde(default)]
+    pub docker: DockerFilter,
+    /// Senders to listen to; syslog configs only.
+    #[serde(default)]
+    pub syslog: SyslogFilter,
 }
 
 /// Validate a config regex the way the watcher will actually use it: it must
@@ -28,11 +215,13 @@ pub fn validate_regex_pattern(regex: &str) -> Result<(), String> {
     if !regex.contains("<IP>") {
         return Err("regex must contain <IP> placeholder".to_string());
     }
-    let pattern = regex.replace("<IP>", crate::ip_extract::IP_PATTERN);
-    if let Err(e) = fancy_regex::Regex::new(&pattern) {
-        return Err(format!("regex does not compile: {}", e));
-    }
-    Ok(())
+    crate::ip_extract::compile_pattern(regex).map(|_| ())
+}
+
+/// Validate an ignore regex: like a fail pattern it must compile once `<IP>` is
+/// substituted, but the placeholder is optional since it only vetoes lines.
+pub fn validate_ignore_regex_pattern(regex: &str) -> Result<(), String> {
+    crate::ip_extract::compile_pattern(regex).map(|_| ())
 }
 
 impl Config {
@@ -47,6 +236,17 @@ impl Config {
         }
     }
 
+    /// The fail patterns the detector runs: the configured ones for a file,
+    /// the built-in ban line pattern for a recidive jail.
+    pub fn fail_patterns(&self) -> Vec<String> {
+        match self.kind {
+            ConfigKind::File | ConfigKind::Journal | ConfigKind::Docker | ConfigKind::Syslog => {
+                self.regexes.clone()
+            }
+            ConfigKind::Recidive => vec![RECIDIVE_PATTERN.to_string()],
+        }
+    }
+
     pub fn validate(&self) -> Result<(), String> {
         if self.id.is_empty() {
             return Err("id cannot be empty".to_string());
@@ -54,10 +254,70 @@ impl Config {
         if self.name.is_empty() {
             return Err("name cannot be empty".to_string());
         }
-        if self.param.is_empty() {
-            return Err("param cannot be empty".to_string());
+        match self.kind {
+            ConfigKind::File => {
+                if self.param.is_empty() {
+                    return Err("param cannot be empty".to_string());
+                }
+                if self.regexes.is_empty() {
+                    return Err("regexes must contain at least one pattern".to_string());
+                }
+            }
+            ConfigKind::Recidive => {
+                if !self.regexes.is_empty() {
+                    return Err("recidive configs match bans, not regexes".to_string());
+                }
+            }
+            ConfigKind::Journal => {
+                if self.regexes.is_empty() {
+                    return Err("regexes must contain at least one pattern".to_string());
+                }
+            }
+            ConfigKind::Docker => {
+                if self.param.is_empty() == self.docker.labels.is_empty() {
+                    return Err("docker configs need either a container in param or labels".to_string());
+                }
+                if self.docker.labels.iter().any(|l| l.is_empty() || l.starts_with('=')) {
+                    return Err("docker labels must be key or key=value".to_string());
+                }
+                if self.regexes.is_empty() {
+                    return Err("regexes must contain at least one pattern".to_string());
+                }
+            }
+            ConfigKind::Syslog => {
+                self.param.parse::<crate::syslog::Listen>()?;
+                if self.regexes.is_empty() {
+                    return Err("regexes must contain at least one pattern".to_string());
+                }
+            }
+        }
+        if self.kind == ConfigKind::Journal {
+            self.journal.validate()?;
+        } else if !self.journal.is_empty() {
+            return Err("journal filters only apply to journal configs".to_string());
+        }
+        if self.kind != ConfigKind::Docker && !self.docker.labels.is_empty() {
+            return Err("docker labels only apply to docker configs".to_string());
+        }
+        if self.kind == ConfigKind::Syslog {
+            self.syslog.validate()?;
+        } else if !self.syslog.is_empty() {
+            return Err("syslog filters only apply to syslog configs".to_string());
+        }
+        self.validate_detection()
+    }
+
+    /// The part of `validate` the detection itself depends on: patterns,
+    /// thresholds and durations. A backtest needs only this, as it reads no
+    /// source of its own.
+    pub fn validate_detection(&self) -> Result<(), String> {
+        for regex in &self.regexes {
+            validate_regex_pattern(regex)?;
+        }
+        for regex in &self.ignore_regexes {
+            validate_ignore_regex_pattern(regex)
+                .map_err(|e| format!("ignore regex {}", e))?;
         }
-        validate_regex_pattern(&self.regex)?;
         if self.ban_time == 0 {
             return Err("ban_time must be greater than 0".to_string());
         }
@@ -77,6 +337,53 @@ impl Config {
     }
 }
 
+/// Rebuild a config from its SQLite row. Records written before multiple
+/// patterns existed carry only the legacy single `regex`; the database layer
+/// folds it into `regexes` on open.
+impl From<ConfigRecord> for Config {
+    fn from(record: ConfigRecord) -> Self {
+        Self {
+            id: record.id,
+            name: record.name,
+            kind: record.kind.parse().unwrap_or_default(),
+            mode: record.mode.parse().unwrap_or_default(),
+            param: record.param,
+            regexes: serde_json::from_str(&record.regexes).unwrap_or_default(),
+            ignore_regexes: serde_json::from_str(&record.ignore_regexes).unwrap_or_default(),
+            ban_time: record.ban_time,
+            find_time: record.find_time,
+            max_matches: record.max_matches,
+            ignore_ips: serde_json::from_str(&record.ignore_ips).unwrap_or_default(),
+            recidive_multiplicator: record.recidive_multiplicator,
+            journal: serde_json::from_str(&record.journal).unwrap_or_default(),
+            docker: serde_json::from_str(&record.docker).unwrap_or_default(),
+            syslog: serde_json::from_str(&record.syslog).unwrap_or_default(),
+        }
+    }
+}
+
+impl From<&Config> for ConfigRecord {
+    fn from(config: &Config) -> Self {
+        Self {
+            id: config.id.clone(),
+            name: config.name.clone(),
+            param: config.param.clone(),
+            regexes: serde_json::to_string(&config.regexes).unwrap_or_default(),
+            ignore_regexes: serde_json::to_string(&config.ignore_regexes).unwrap_or_default(),
+            ban_time: config.ban_time,
+            find_time: config.find_time,
+            max_matches: config.max_matches,
+            ignore_ips: serde_json::to_string(&config.ignore_ips).unwrap_or_default(),
+            recidive_multiplicator: config.recidive_multiplicator,
+            kind: config.kind.as_str().to_string(),
+            mode: config.mode.as_str().to_string(),
+            journal: serde_json::to_string(&config.journal).unwrap_or_default(),
+            docker: serde_json::to_string(&config.docker).unwrap_or_default(),
+            syslog: serde_json::to_string(&config.syslog).unwrap_or_default(),
+        }
+    }
+}
+
 pub type ConfigMap = HashMap<String, Config>;
 
 #[cfg(test)]
@@ -87,13 +394,19 @@ mod tests {
         Config {
             id: "c".to_string(),
             name: "c".to_string(),
+            kind: ConfigKind::File,
+            mode: ConfigMode::Enforce,
             param: "/tmp/log".to_string(),
-            regex: "<IP>".to_string(),
+            regexes: vec!["<IP>".to_string()],
+            ignore_regexes: vec![],
             ban_time: 1000,
             find_time: 1000,
             max_matches: 3,
             ignore_ips: vec![],
             recidive_multiplicator: None,
+            journal: JournalFilter::default(),
+            docker: DockerFilter::default(),
+            syslog: SyslogFilter::default(),
         }
     }
 
@@ -150,6 +463,164 @@ mod tests {
         assert!(err.contains("<IP>"), "unexpected message: {err}");
     }
 
+    #[test]
+    fn validate_requires_at_least_one_regex() {
+        let config = Config {
+            regexes: vec![],
+            ..base_config()
+        };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn validate_checks_every_pattern() {
+        let config = Config {
+            regexes: vec!["from <IP>".to_string(), "no placeholder".to_string()],
+            ..base_config()
+        };
+        assert!(config.validate().is_err());
+
+        // Ignore patterns don't need <IP>, but must compile.
+        let config = Config {
+            ignore_regexes: vec!["Accepted publickey".to_string()],
+            ..base_config()
+        };
+        assert!(config.validate().is_ok());
+        let config = Config {
+            ignore_regexes: vec!["(unclosed".to_string()],
+            ..base_config()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(err.starts_with("ignore regex"), "unexpected message: {err}");
+    }
+
+    #[test]
+    fn record_round_trip_preserves_patterns() {
+        let config = Config {
+            regexes: vec!["a <IP>".to_string(), "b <IP>".to_string()],
+            ignore_regexes: vec!["trusted".to_string()],
+            ..base_config()
+        };
+        let back = Config::from(ConfigRecord::from(&config));
+        assert_eq!(back.regexes, config.regexes);
+        assert_eq!(back.ignore_regexes, config.ignore_regexes);
+    }
+
+    #[test]
+    fn recidive_config_needs_no_file_or_regexes() {
+        let config = Config {
+            kind: ConfigKind::Recidive,
+            param: String::new(),
+            regexes: vec![],
+            ..base_config()
+        };
+        assert!(config.validate().is_ok());
+        assert_eq!(config.fail_patterns(), vec![RECIDIVE_PATTERN.to_string()]);
+        let back = Config::from(ConfigRecord::from(&config));
+        assert_eq!(back.kind, ConfigKind::Recidive);
+
+        let config = Config {
+            kind: ConfigKind::Recidive,
+            ..base_config()
+        };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn journal_config_takes_filters_others_do_not() {
+        let journal = JournalFilter {
+            units: vec!["sshd".to_string()],
+            matches: vec!["SYSLOG_IDENTIFIER=sshd".to_string()],
+        };
+        let config = Config {
+            kind: ConfigKind::Journal,
+            param: String::new(),
+            journal: journal.clone(),
+            ..base_config()
+        };
+        assert!(config.validate().is_ok());
+        let back = Config::from(ConfigRecord::from(&config));
+        assert_eq!(back.kind, ConfigKind::Journal);
+        assert_eq!(back.journal, journal);
+
+        for bad in ["SYSLOG_IDENTIFIER", "=sshd", "syslog_identifier=sshd", "1FIELD=x"] {
+            let config = Config {
+                journal: JournalFilter { units: vec![], matches: vec![bad.to_string()] },
+                ..config.clone()
+            };
+            assert!(config.validate().is_err(), "expected {bad} to be rejected");
+        }
+        let config = Config { journal, ..base_config() };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn docker_config_names_a_container_or_labels() {
+        let by_name = Config {
+            kind: ConfigKind::Docker,
+            param: "web".to_string(),
+            ..base_config()
+        };
+        assert!(by_name.validate().is_ok());
+        let by_label = Config {
+            param: String::new(),
+            docker: DockerFilter { labels: vec!["com.example.tier=edge".to_string()] },
+            ..by_name.clone()
+        };
+        assert!(by_label.validate().is_ok());
+        let back = Config::from(ConfigRecord::from(&by_label));
+        assert_eq!(back.kind, ConfigKind::Docker);
+        assert_eq!(back.docker, by_label.docker);
+
+        let both = Config { param: "web".to_string(), ..by_label.clone() };
+        assert!(both.validate().is_err());
+        let neither = Config { param: String::new(), ..by_name.clone() };
+        assert!(neither.validate().is_err());
+        let file = Config { kind: ConfigKind::File, ..both };
+        assert!(file.validate().is_err());
+    }
+
+    #[test]
+    fn syslog_config_listens_on_an_address() {
+        let syslog = Config {
+            kind: ConfigKind::Syslog,
+            param: "udp://0.0.0.0:5514".to_string(),
+            syslog: SyslogFilter {
+                hostnames: vec!["edge-1".to_string()],
+                app_names: vec![],
+            },
+            ..base_config()
+        };
+        assert!(syslog.validate().is_ok());
+        let back = Config::from(ConfigRecord::from(&syslog));
+        assert_eq!(back.kind, ConfigKind::Syslog);
+        assert_eq!(back.syslog, syslog.syslog);
+
+        let no_scheme = Config { param: "0.0.0.0:5514".to_string(), ..syslog.clone() };
+        assert!(no_scheme.validate().is_err());
+        let empty_host = Config {
+            syslog: SyslogFilter { hostnames: vec![String::new()], app_names: vec![] },
+            ..syslog.clone()
+        };
+        assert!(empty_host.validate().is_err());
+        let file = Config { kind: ConfigKind::File, ..syslog };
+        assert!(file.validate().is_err());
+    }
+
+    #[test]
+    fn recidive_pattern_matches_host_bans_only() {
+        let extractor = crate::ip_extract::IpExtractor::new(&[RECIDIVE_PATTERN]).unwrap();
+        assert_eq!(
+            extractor.extract(&recidive_line("sshd", "192.0.2.1")),
+            Some("192.0.2.1".parse().unwrap())
+        );
+        assert_eq!(
+            extractor.extract(&recidive_line("web [v2]", "2001:db8::1")),
+            Some("2001:db8::1".parse().unwrap())
+        );
+        assert_eq!(extractor.extract(&recidive_line("sshd", "10.0.0.0/24")), None);
+    }
+
     #[test]
     fn validate_regex_pattern_rejects_non_compiling_regex() {
         let err = validate_regex_pattern("(<IP>").unwrap_err();
@@ -1,4 +1,7 @@
 pub mod sqlite_db;
 
-pub use sqlite_db::{SqliteDatabase, ConfigRecord, NotifierRecord};
+pub use sqlite_db::{
+    SqliteDatabase, ConfigRecord, NotifierRecord, AllowlistRecord, ApiTokenRecord,
+    EventQuery, EventSort, SortOrder, IpStats, DeliveryRecord,
+};
 
@@ -1,4 +1,5 @@
 use crate::events::Event;
+use crate::health::Progress;
 use rusqlite::{Connection, Result as SqliteResult};
 use serde::{Deserialize, Serialize};
 use std::path::Path;
@@ -12,12 +13,18 @@ pub struct ConfigRecord {
     pub id: String,
     pub name: String,
     pub param: String,
-    pub regex: String,
+    pub regexes: String,        // JSON array
+    pub ignore_regexes: String, // JSON array
     pub ban_time: u64,
     pub find_time: u64,
     pub max_matches: u32,
     pub ignore_ips: String, // JSON array
     pub recidive_multiplicator: Option<f64>,
+    pub kind: String,    // "file", "recidive", "journal", "docker" or "syslog"
+    pub journal: String, // JSON object
+    pub docker: String,  // JSON object
+    pub syslog: String,  // JSON object
+    pub mode: String,    // "enforce" or "monitor"
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,6 +33,139 @@ pub struct NotifierRecord {
     pub events: String,                // JSON array of event types
     pub email_config: Option<String>,  // JSON object
     pub signal_config: Option<String>, // JSON object
+    pub webhook_config: Option<String>, // JSON object
+    pub title_template: Option<String>,
+    pub message_template: Option<String>,
+    pub batch: Option<String>,         // JSON object
+    pub daily_summary: Option<String>, // JSON object
+    pub filters: Option<String>,       // JSON object
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AllowlistRecord {
+    pub id: String,
+    pub ip: String, // IP or CIDR
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiTokenRecord {
+    pub id: String,
+    pub name: String,
+    pub role: String,       // "read_only" or "admin"
+    pub token_hash: String, // hex SHA-256 of the secret; the secret itself is never stored
+    pub created_at: u64,
+}
+
+/// One notification on its way to one notifier, kept until delivered or out
+/// of retries.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeliveryRecord {
+    pub id: String,
+    pub notifier_id: String,
+    pub event: String,      // "ban", "unban", "match", "digest" or "summary"
+    pub ip: Option<String>, // the event's IP; none for digests and summaries
+    pub title: String,
+    pub payload: String, // JSON of the rendered notification
+    pub status: String,  // "pending", "delivered" or "failed"
+    pub attempts: u32,
+    pub created_at: u64,
+    pub next_attempt_at: u64,
+    pub last_error: Option<String>,
+    pub delivered_at: Option<u64>,
+}
+
+/// Column an event listing is ordered by. A closed set, so it can be spliced
+/// into the SQL.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EventSort {
+    #[default]
+    Timestamp,
+    Ip,
+    ConfigId,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+/// Filters, ordering and page for an event listing. The default selects every
+/// row, newest first.
+#[derive(Debug, Clone, Default)]
+pub struct EventQuery {
+    pub config_id: Option<String>,
+    pub ip: Option<String>,
+    /// Inclusive lower bound on `timestamp` (ms epoch).
+    pub from: Option<u64>,
+    /// Inclusive upper bound on `timestamp` (ms epoch).
+    pub to: Option<u64>,
+    pub sort: EventSort,
+    pub order: SortOrder,
+    /// `None` returns every row from `offset` on.
+    pub limit: Option<u32>,
+    pub offset: u64,
+}
+
+impl EventQuery {
+    pub fn for_config(config_id: &str) -> Self {
+        Self {
+            config_id: Some(config_id.to_string()),
+            ..Self::default()
+        }
+    }
+
+    /// The WHERE clause with its bound values, shared by the page and the
+    /// total count. Only the filters that are set become conditions, so each
+    /// can use its index.
+    fn where_clause(&self) -> (String, Vec<rusqlite::types::Value>) {
+        use rusqlite::types::Value;
+
+        let mut conditions = Vec::new();
+        let mut values = Vec::new();
+        if let Some(config_id) = &self.config_id {
+            conditions.push("config_id = ?");
+            values.push(Value::Text(config_id.clone()));
+        }
+        if let Some(ip) = &self.ip {
+            conditions.push("ip = ?");
+            values.push(Value::Text(ip.clone()));
+        }
+        if let Some(from) = self.from {
+            conditions.push("timestamp >= ?");
+            values.push(Value::Integer(from as i64));
+        }
+        if let Some(to) = self.to {
+            conditions.push("timestamp <= ?");
+            values.push(Value::Integer(to as i64));
+        }
+        if conditions.is_empty() {
+            (String::new(), values)
+        } else {
+            (format!(" WHERE {}", conditions.join(" AND ")), values)
+        }
+    }
+
+    /// ORDER BY and LIMIT/OFFSET. Ties fall back to time, then id, so pages
+    /// never overlap or skip rows.
+    fn page_clause(&self) -> String {
+        let dir = match self.order {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        };
+        let order = match self.sort {
+            EventSort::Timestamp => format!("timestamp {dir}, id {dir}"),
+            EventSort::Ip => format!("ip {dir}, timestamp DESC, id DESC"),
+            EventSort::ConfigId => format!("config_id {dir}, timestamp DESC, id DESC"),
+        };
+        // SQLite reads a negative LIMIT as "no limit".
+        let limit = self.limit.map(i64::from).unwrap_or(-1);
+        format!(" ORDER BY {order} LIMIT {limit} OFFSET {}", self.offset)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -43,6 +183,22 @@ pub struct BanEvent {
     pub config_id: String,
     pub ip: String,
     pub timestamp: u64,
+    /// Effective duration in ms as applied when the ban was issued. `None`
+    /// for permanent bans and for rows recorded before it was persisted.
+    pub ban_time: Option<u64>,
+    pub permanent: bool,
+    pub manual: bool,
+    pub reason: Option<String>,
+    /// Would-be ban of a config in monitor mode; never reached the firewall.
+    pub simulated: bool,
+}
+
+impl BanEvent {
+    /// When the ban lapses (ms epoch); `None` when it never does or when the
+    /// row predates stored durations.
+    pub fn expires_at(&self) -> Option<u64> {
+        self.ban_time.map(|t| self.timestamp.saturating_add(t))
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -103,6 +259,46 @@ impl SqliteDatabase {
             "ALTER TABLE configs ADD COLUMN recidive_multiplicator REAL",
             [],
         );
+        // Multiple fail patterns and ignore patterns, both JSON arrays. Rows
+        // from the single-pattern era get their `regex` folded into `regexes`
+        // once the `kind` column exists, below. The legacy `regex` column
+        // stays populated with the first pattern.
+        let _ = self.conn.execute(
+            "ALTER TABLE configs ADD COLUMN regexes TEXT NOT NULL DEFAULT '[]'",
+            [],
+        );
+        let _ = self.conn.execute(
+            "ALTER TABLE configs ADD COLUMN ignore_regexes TEXT NOT NULL DEFAULT '[]'",
+            [],
+        );
+        // Configs predating recidive jails all tail a file.
+        let _ = self.conn.execute(
+            "ALTER TABLE configs ADD COLUMN kind TEXT NOT NULL DEFAULT 'file'",
+            [],
+        );
+        // Runs on every open, so it must only ever match legacy rows: a
+        // recidive config legitimately stores `[]` with an empty `regex`.
+        self.conn.execute(
+            "UPDATE configs SET regexes = json_array(regex)
+             WHERE regexes = '[]' AND kind = 'file' AND regex != ''",
+            [],
+        )?;
+        let _ = self.conn.execute(
+            "ALTER TABLE configs ADD COLUMN journal TEXT NOT NULL DEFAULT '{}'",
+            [],
+        );
+        let _ = self.conn.execute(
+            "ALTER TABLE configs ADD COLUMN docker TEXT NOT NULL DEFAULT '{}'",
+            [],
+        );
+        let _ = self.conn.execute(
+            "ALTER TABLE configs ADD COLUMN syslog TEXT NOT NULL DEFAULT '{}'",
+            [],
+        );
+        let _ = self.conn.execute(
+            "ALTER TABLE configs ADD COLUMN mode TEXT NOT NULL DEFAULT 'enforce'",
+            [],
+        );
 
         // Create match_events table
         self.conn.execute(
@@ -132,6 +328,27 @@ impl SqliteDatabase {
             )",
             [],
         )?;
+        // Manual bans (placed through the API) are flagged and may carry the
+        // operator's reason. Same add-in-place migration as above.
+        let _ = self.conn.execute(
+            "ALTER TABLE ban_events ADD COLUMN manual INTEGER NOT NULL DEFAULT 0",
+            [],
+        );
+        let _ = self.conn.execute("ALTER TABLE ban_events ADD COLUMN reason TEXT", []);
+        // The effective duration each ban was issued with, so restore and the
+        // API never re-derive it from a config that may have changed since.
+        // Rows from before this column keep NULL (and `permanent = 0`).
+        let _ = self.conn.execute("ALTER TABLE ban_events ADD COLUMN ban_time INTEGER", []);
+        let _ = self.conn.execute(
+            "ALTER TABLE ban_events ADD COLUMN permanent INTEGER NOT NULL DEFAULT 0",
+            [],
+        );
+        // Would-be bans of monitor-mode configs, kept in the audit log but
+        // never restored into the firewall.
+        let _ = self.conn.execute(
+            "ALTER TABLE ban_events ADD COLUMN simulated INTEGER NOT NULL DEFAULT 0",
+            [],
+        );
 
         // Create unban_events table
         self.conn.execute(
@@ -144,8 +361,23 @@ impl SqliteDatabase {
             [],
         )?;
 
+        // Event listings filter by config or IP and order by time; these keep
+        // a page read from scanning the whole table.
+        for table in ["match_events", "ban_events", "unban_events"] {
+            self.conn.execute(
+                &format!(
+                    "CREATE INDEX IF NOT EXISTS idx_{table}_config_ts ON {table} (config_id, timestamp)"
+                ),
+                [],
+            )?;
+            self.conn.execute(
+                &format!("CREATE INDEX IF NOT EXISTS idx_{table}_ip_ts ON {table} (ip, timestamp)"),
+                [],
+            )?;
+        }
+
         // Create notifiers table. The channel kind is derivable from which of
-        // the two JSON columns is non-NULL.
+        // the JSON columns is non-NULL.
         self.conn.execute(
             "CREATE TABLE IF NOT EXISTS notifiers (
                 id TEXT PRIMARY KEY,
@@ -155,6 +387,64 @@ impl SqliteDatabase {
             )",
             [],
         )?;
+        let _ = self.conn.execute("ALTER TABLE notifiers ADD COLUMN webhook_config TEXT", []);
+        let _ = self.conn.execute("ALTER TABLE notifiers ADD COLUMN title_template TEXT", []);
+        let _ = self.conn.execute("ALTER TABLE notifiers ADD COLUMN message_template TEXT", []);
+        let _ = self.conn.execute("ALTER TABLE notifiers ADD COLUMN batch TEXT", []);
+        let _ = self.conn.execute("ALTER TABLE notifiers ADD COLUMN daily_summary TEXT", []);
+        let _ = self.conn.execute("ALTER TABLE notifiers ADD COLUMN filters TEXT", []);
+
+        // Create allowlist table: addresses/networks no config may ban.
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS allowlist (
+                id TEXT PRIMARY KEY,
+                ip TEXT NOT NULL,
+                comment TEXT
+            )",
+            [],
+        )?;
+
+        // Create api_tokens table: bearer tokens for the API, stored hashed.
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS api_tokens (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                role TEXT NOT NULL,
+                token_hash TEXT NOT NULL UNIQUE,
+                created_at INTEGER NOT NULL
+            )",
+            [],
+        )?;
+
+        // Create notification_outbox table: every notification is written
+        // here before it is sent, and retried from here until delivered.
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS notification_outbox (
+                id TEXT PRIMARY KEY,
+                notifier_id TEXT NOT NULL,
+                event TEXT NOT NULL,
+                ip TEXT,
+                title TEXT NOT NULL,
+                payload TEXT NOT NULL,
+                status TEXT NOT NULL,
+                attempts INTEGER NOT NULL DEFAULT 0,
+                created_at INTEGER NOT NULL,
+                next_attempt_at INTEGER NOT NULL,
+                last_error TEXT,
+                delivered_at INTEGER
+            )",
+            [],
+        )?;
+        self.conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_notification_outbox_due
+             ON notification_outbox (status, next_attempt_at)",
+            [],
+        )?;
+        self.conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_notification_outbox_notifier
+             ON notification_outbox (notifier_id, created_at)",
+            [],
+        )?;
 
         Ok(())
     }
@@ -162,18 +452,24 @@ impl SqliteDatabase {
     // Config operations
     pub fn insert_config(&self, config: &ConfigRecord) -> SqliteResult<()> {
         self.conn.execute(
-            "INSERT OR REPLACE INTO configs (id, name, param, regex, ban_time, find_time, max_matches, ignore_ips, recidive_multiplicator)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            "INSERT OR REPLACE INTO configs (id, name, param, regex, regexes, ignore_regexes, ban_time, find_time, max_matches, ignore_ips, recidive_multiplicator, kind, journal, docker, syslog, mode)
+             VALUES (?1, ?2, ?3, COALESCE(json_extract(?4, '$[0]'), ''), ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
             rusqlite::params![
                 config.id,
                 config.name,
                 config.param,
-                config.regex,
+                config.regexes,
+                config.ignore_regexes,
                 config.ban_time,
                 config.find_time,
                 config.max_matches,
                 config.ignore_ips,
-                config.recidive_multiplicator
+                config.recidive_multiplicator,
+                config.kind,
+                config.journal,
+                config.docker,
+                config.syslog,
+                config.mode
             ],
         )?;
         Ok(())
@@ -181,7 +477,7 @@ impl SqliteDatabase {
 
     pub fn get_config(&self, id: &str) -> SqliteResult<Option<ConfigRecord>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, name, param, regex, ban_time, find_time, max_matches, ignore_ips, recidive_multiplicator
+            "SELECT id, name, param, regexes, ignore_regexes, ban_time, find_time, max_matches, ignore_ips, recidive_multiplicator, kind, journal, docker, syslog, mode
              FROM configs WHERE id = ?1"
         )?;
 
@@ -190,12 +486,18 @@ impl SqliteDatabase {
                 id: row.get(0)?,
                 name: row.get(1)?,
                 param: row.get(2)?,
-                regex: row.get(3)?,
-                ban_time: row.get(4)?,
-                find_time: row.get(5)?,
-                max_matches: row.get(6)?,
-                ignore_ips: row.get(7)?,
-                recidive_multiplicator: row.get(8)?,
+                regexes: row.get(3)?,
+                ignore_regexes: row.get(4)?,
+                ban_time: row.get(5)?,
+                find_time: row.get(6)?,
+                max_matches: row.get(7)?,
+                ignore_ips: row.get(8)?,
+                recidive_multiplicator: row.get(9)?,
+                kind: row.get(10)?,
+                journal: row.get(11)?,
+                docker: row.get(12)?,
+                syslog: row.get(13)?,
+                mode: row.get(14)?,
             })
         })?;
 
@@ -208,7 +510,7 @@ impl SqliteDatabase {
 
     pub fn get_all_configs(&self) -> SqliteResult<Vec<ConfigRecord>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, name, param, regex, ban_time, find_time, max_matches, ignore_ips, recidive_multiplicator
+            "SELECT id, name, param, regexes, ignore_regexes, ban_time, find_time, max_matches, ignore_ips, recidive_multiplicator, kind, journal, docker, syslog, mode
              FROM configs"
         )?;
 
@@ -217,12 +519,18 @@ impl SqliteDatabase {
                 id: row.get(0)?,
                 name: row.get(1)?,
                 param: row.get(2)?,
-                regex: row.get(3)?,
-                ban_time: row.get(4)?,
-                find_time: row.get(5)?,
-                max_matches: row.get(6)?,
-                ignore_ips: row.get(7)?,
-                recidive_multiplicator: row.get(8)?,
+                regexes: row.get(3)?,
+                ignore_regexes: row.get(4)?,
+                ban_time: row.get(5)?,
+                find_time: row.get(6)?,
+                max_matches: row.get(7)?,
+                ignore_ips: row.get(8)?,
+                recidive_multiplicator: row.get(9)?,
+                kind: row.get(10)?,
+                journal: row.get(11)?,
+                docker: row.get(12)?,
+                syslog: row.get(13)?,
+                mode: row.get(14)?,
             })
         })?;
 
@@ -241,13 +549,20 @@ impl SqliteDatabase {
     // Notifier operations
     pub fn insert_notifier(&self, notifier: &NotifierRecord) -> SqliteResult<()> {
         self.conn.execute(
-            "INSERT OR REPLACE INTO notifiers (id, events, email_config, signal_config)
-             VALUES (?1, ?2, ?3, ?4)",
+            "INSERT OR REPLACE INTO notifiers (id, events, email_config, signal_config, webhook_config,
+                                               title_template, message_template, batch, daily_summary, filters)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
             rusqlite::params![
                 notifier.id,
                 notifier.events,
                 notifier.email_config,
-                notifier.signal_config
+                notifier.signal_config,
+                notifier.webhook_config,
+                notifier.title_template,
+                notifier.message_template,
+                notifier.batch,
+                notifier.daily_summary,
+                notifier.filters
             ],
         )?;
         Ok(())
@@ -255,7 +570,9 @@ impl SqliteDatabase {
 
     pub fn get_all_notifiers(&self) -> SqliteResult<Vec<NotifierRecord>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, events, email_config, signal_config FROM notifiers"
+            "SELECT id, events, email_config, signal_config, webhook_config,
+                    title_template, message_template, batch, daily_summary, filters
+             FROM notifiers"
         )?;
 
         let rows = stmt.query_map([], |row| {
@@ -264,6 +581,12 @@ impl SqliteDatabase {
                 events: row.get(1)?,
                 email_config: row.get(2)?,
                 signal_config: row.get(3)?,
+                webhook_config: row.get(4)?,
+                title_template: row.get(5)?,
+                message_template: row.get(6)?,
+                batch: row.get(7)?,
+                daily_summary: row.get(8)?,
+                filters: row.get(9)?,
             })
         })?;
 
@@ -279,6 +602,210 @@ impl SqliteDatabase {
         Ok(())
     }
 
+    // Allowlist operations
+    pub fn insert_allowlist_entry(&self, entry: &AllowlistRecord) -> SqliteResult<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO allowlist (id, ip, comment) VALUES (?1, ?2, ?3)",
+            rusqlite::params![entry.id, entry.ip, entry.comment],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_allowlist(&self) -> SqliteResult<Vec<AllowlistRecord>> {
+        let mut stmt = self.conn.prepare("SELECT id, ip, comment FROM allowlist")?;
+
+        let rows = stmt.query_map([], |row| {
+            Ok(AllowlistRecord {
+                id: row.get(0)?,
+                ip: row.get(1)?,
+                comment: row.get(2)?,
+            })
+        })?;
+
+        let mut entries = Vec::new();
+        for row in rows {
+            entries.push(row?);
+        }
+        Ok(entries)
+    }
+
+    /// Whether an entry with this id existed.
+    pub fn delete_allowlist_entry(&self, id: &str) -> SqliteResult<bool> {
+        let deleted = self.conn.execute("DELETE FROM allowlist WHERE id = ?1", rusqlite::params![id])?;
+        Ok(deleted > 0)
+    }
+
+    pub fn insert_api_token(&self, token: &ApiTokenRecord) -> SqliteResult<()> {
+        self.conn.execute(
+            "INSERT INTO api_tokens (id, name, role, token_hash, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![token.id, token.name, token.role, token.token_hash, token.created_at],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_api_tokens(&self) -> SqliteResult<Vec<ApiTokenRecord>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, name, role, token_hash, created_at FROM api_tokens ORDER BY created_at")?;
+
+        let rows = stmt.query_map([], |row| {
+            Ok(ApiTokenRecord {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                role: row.get(2)?,
+                token_hash: row.get(3)?,
+                created_at: row.get(4)?,
+            })
+        })?;
+
+        let mut tokens = Vec::new();
+        for row in rows {
+            tokens.push(row?);
+        }
+        Ok(tokens)
+    }
+
+    pub fn delete_api_token(&self, id: &str) -> SqliteResult<()> {
+        self.conn.execute("DELETE FROM api_tokens WHERE id = ?1", rusqlite::params![id])?;
+        Ok(())
+    }
+
+    // Outbox operations
+    /// Queue deliveries in a single transaction, for the same reason audit
+    /// events are batched: one commit per notification cannot keep up with a
+    /// burst.
+    pub fn insert_deliveries(&self, deliveries: &[DeliveryRecord]) -> SqliteResult<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        {
+            let mut insert = tx.prepare_cached(
+                "INSERT INTO notification_outbox (id, notifier_id, event, ip, title, payload, status, attempts,
+                                                  created_at, next_attempt_at, last_error, delivered_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            )?;
+            for delivery in deliveries {
+                insert.execute(rusqlite::params![
+                    delivery.id,
+                    delivery.notifier_id,
+                    delivery.event,
+                    delivery.ip,
+                    delivery.title,
+                    delivery.payload,
+                    delivery.status,
+                    delivery.attempts,
+                    delivery.created_at,
+                    delivery.next_attempt_at,
+                    delivery.last_error,
+                    delivery.delivered_at
+                ])?;
+            }
+        }
+        tx.commit()
+    }
+
+    /// Record the outcome of a delivery attempt.
+    pub fn update_delivery(&self, delivery: &DeliveryRecord) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE notification_outbox
+             SET status = ?2, attempts = ?3, next_attempt_at = ?4, last_error = ?5, delivered_at = ?6
+             WHERE id = ?1",
+            rusqlite::params![
+                delivery.id,
+                delivery.status,
+                delivery.attempts,
+                delivery.next_attempt_at,
+                delivery.last_error,
+                delivery.delivered_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn map_delivery(row: &rusqlite::Row) -> rusqlite::Result<DeliveryRecord> {
+        Ok(DeliveryRecord {
+            id: row.get(0)?,
+            notifier_id: row.get(1)?,
+            event: row.get(2)?,
+            ip: row.get(3)?,
+            title: row.get(4)?,
+            payload: row.get(5)?,
+            status: row.get(6)?,
+            attempts: row.get(7)?,
+            created_at: row.get(8)?,
+            next_attempt_at: row.get(9)?,
+            last_error: row.get(10)?,
+            delivered_at: row.get(11)?,
+        })
+    }
+
+    /// Pending deliveries whose next attempt is due by `now`, oldest first.
+    pub fn get_due_deliveries(&self, now: u64, limit: u32) -> SqliteResult<Vec<DeliveryRecord>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, notifier_id, event, ip, title, payload, status, attempts,
+                    created_at, next_attempt_at, last_error, delivered_at
+             FROM notification_outbox
+             WHERE status = 'pending' AND next_attempt_at <= ?1
+             ORDER BY next_attempt_at LIMIT ?2",
+        )?;
+        let rows = stmt.query_map(rusqlite::params![now, limit], Self::map_delivery)?;
+        let mut deliveries = Vec::new();
+        for row in rows {
+            deliveries.push(row?);
+        }
+        Ok(deliveries)
+    }
+
+    /// A notifier's deliveries, newest first, optionally of one status, with
+    /// the total before paging.
+    pub fn query_deliveries(
+        &self,
+        notifier_id: &str,
+        status: Option<&str>,
+        limit: Option<u32>,
+        offset: u64,
+    ) -> SqliteResult<(Vec<DeliveryRecord>, u64)> {
+        let where_clause = "WHERE notifier_id = ?1 AND (?2 IS NULL OR status = ?2)";
+        let total: u64 = self.conn.query_row(
+            &format!("SELECT COUNT(*) FROM notification_outbox {where_clause}"),
+            rusqlite::params![notifier_id, status],
+            |row| row.get(0),
+        )?;
+
+        // SQLite reads a negative LIMIT as "no limit".
+        let limit = limit.map(i64::from).unwrap_or(-1);
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT id, notifier_id, event, ip, title, payload, status, attempts,
+                    created_at, next_attempt_at, last_error, delivered_at
+             FROM notification_outbox {where_clause}
+             ORDER BY created_at DESC, id DESC LIMIT ?3 OFFSET ?4"
+        ))?;
+        let rows = stmt.query_map(
+            rusqlite::params![notifier_id, status, limit, offset],
+            Self::map_delivery,
+        )?;
+        let mut deliveries = Vec::new();
+        for row in rows {
+            deliveries.push(row?);
+        }
+        Ok((deliveries, total))
+    }
+
+    /// Drop finished (delivered or failed) deliveries created before `before`.
+    /// Returns how many went.
+    pub fn prune_deliveries(&self, before: u64) -> SqliteResult<usize> {
+        self.conn.execute(
+            "DELETE FROM notification_outbox WHERE status != 'pending' AND created_at < ?1",
+            rusqlite::params![before],
+        )
+    }
+
+    pub fn delete_deliveries(&self, notifier_id: &str) -> SqliteResult<()> {
+        self.conn.execute(
+            "DELETE FROM notification_outbox WHERE notifier_id = ?1",
+            rusqlite::params![notifier_id],
+        )?;
+        Ok(())
+    }
+
     // Event operations
     /// Persist a batch of audit events in a single transaction. One commit per
     /// batch is what lets the writer outrun the emitters: per-event commits
@@ -314,8 +841,22 @@ impl SqliteDatabase {
                         ])
                         .map(|_| ())?
                     }
-                    Event::Ban { config_id, ip, timestamp } => {
-                        insert("ban_events", config_id, ip, *timestamp)?
+                    Event::Ban { id, config_id, ip, timestamp, ban_time, manual, reason, simulated, .. } => {
+                        tx.prepare_cached(
+                            "INSERT INTO ban_events (id, config_id, ip, timestamp, ban_time, permanent, manual, reason, simulated) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                        )?
+                        .execute(rusqlite::params![
+                            id,
+                            config_id,
+                            ip,
+                            timestamp,
+                            ban_time,
+                            ban_time.is_none(),
+                            manual,
+                            reason,
+                            simulated
+                        ])
+                        .map(|_| ())?
                     }
                     Event::Unban { config_id, ip, timestamp } => {
                         insert("unban_events", config_id, ip, *timestamp)?
@@ -337,28 +878,42 @@ impl SqliteDatabase {
         })
     }
 
-    pub fn get_match_events(&self, config_id: Option<&str>) -> SqliteResult<Vec<MatchEvent>> {
-        let mut events = Vec::new();
+    /// One page of match events, and how many match the filters in total.
+    pub fn query_match_events(&self, query: &EventQuery) -> SqliteResult<(Vec<MatchEvent>, u64)> {
+        self.query_events(
+            "SELECT id, config_id, ip, timestamp, line FROM match_events",
+            "match_events",
+            query,
+            Self::map_match_event,
+        )
+    }
 
-        if let Some(cid) = config_id {
-            let mut stmt = self.conn.prepare(
-                "SELECT id, config_id, ip, timestamp, line FROM match_events WHERE config_id = ?1 ORDER BY timestamp DESC"
-            )?;
-            let rows = stmt.query_map(rusqlite::params![cid], Self::map_match_event)?;
-            for row in rows {
-                events.push(row?);
-            }
-        } else {
-            let mut stmt = self.conn.prepare(
-                "SELECT id, config_id, ip, timestamp, line FROM match_events ORDER BY timestamp DESC"
-            )?;
-            let rows = stmt.query_map([], Self::map_match_event)?;
-            for row in rows {
-                events.push(row?);
-            }
-        }
+    /// Run `select` under the query's filters and page, plus a COUNT(*) over
+    /// `table` with the same filters.
+    fn query_events<T>(
+        &self,
+        select: &str,
+        table: &str,
+        query: &EventQuery,
+        map: fn(&rusqlite::Row) -> rusqlite::Result<T>,
+    ) -> SqliteResult<(Vec<T>, u64)> {
+        let (where_clause, values) = query.where_clause();
+
+        let total: u64 = self.conn.query_row(
+            &format!("SELECT COUNT(*) FROM {table}{where_clause}"),
+            rusqlite::params_from_iter(values.iter()),
+            |row| row.get(0),
+        )?;
 
-        Ok(events)
+        let mut stmt = self
+            .conn
+            .prepare(&format!("{select}{where_clause}{}", query.page_clause()))?;
+        let rows = stmt.query_map(rusqlite::params_from_iter(values.iter()), map)?;
+        let mut events = Vec::new();
+        for row in rows {
+            events.push(row?);
+        }
+        Ok((events, total))
     }
 
     fn map_ban_event(row: &rusqlite::Row) -> rusqlite::Result<BanEvent> {
@@ -367,12 +922,17 @@ impl SqliteDatabase {
             config_id: row.get(1)?,
             ip: row.get(2)?,
             timestamp: row.get(3)?,
+            ban_time: row.get(4)?,
+            permanent: row.get(5)?,
+            manual: row.get(6)?,
+            reason: row.get(7)?,
+            simulated: row.get(8)?,
         })
     }
 
     pub fn get_ban_event_by_id(&self, id: &str) -> SqliteResult<Option<BanEvent>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, config_id, ip, timestamp FROM ban_events WHERE id = ?1"
+            "SELECT id, config_id, ip, timestamp, ban_time, permanent, manual, reason, simulated FROM ban_events WHERE id = ?1"
         )?;
         
         let mut rows = stmt.query_map(rusqlite::params![id], Self::map_ban_event)?;
@@ -384,28 +944,19 @@ impl SqliteDatabase {
         }
     }
 
+    /// One page of ban events, and how many match the filters in total.
+    pub fn query_ban_events(&self, query: &EventQuery) -> SqliteResult<(Vec<BanEvent>, u64)> {
+        self.query_events(
+            "SELECT id, config_id, ip, timestamp, ban_time, permanent, manual, reason, simulated FROM ban_events",
+            "ban_events",
+            query,
+            Self::map_ban_event,
+        )
+    }
+
     pub fn get_ban_events(&self, config_id: Option<&str>) -> SqliteResult<Vec<BanEvent>> {
-        let mut events = Vec::new();
-        
-        if let Some(cid) = config_id {
-            let mut stmt = self.conn.prepare(
-                "SELECT id, config_id, ip, timestamp FROM ban_events WHERE config_id = ?1 ORDER BY timestamp DESC"
-            )?;
-            let rows = stmt.query_map(rusqlite::params![cid], Self::map_ban_event)?;
-            for row in rows {
-                events.push(row?);
-            }
-        } else {
-            let mut stmt = self.conn.prepare(
-                "SELECT id, config_id, ip, timestamp FROM ban_events ORDER BY timestamp DESC"
-            )?;
-            let rows = stmt.query_map([], Self::map_ban_event)?;
-            for row in rows {
-                events.push(row?);
-            }
-        }
-        
-        Ok(events)
+        let query = config_id.map(EventQuery::for_config).unwrap_or_default();
+        Ok(self.query_ban_events(&query)?.0)
     }
 
     fn map_unban_event(row: &rusqlite::Row) -> rusqlite::Result<UnbanEvent> {
@@ -417,28 +968,19 @@ impl SqliteDatabase {
         })
     }
 
+    /// One page of unban events, and how many match the filters in total.
+    pub fn query_unban_events(&self, query: &EventQuery) -> SqliteResult<(Vec<UnbanEvent>, u64)> {
+        self.query_events(
+            "SELECT id, config_id, ip, timestamp FROM unban_events",
+            "unban_events",
+            query,
+            Self::map_unban_event,
+        )
+    }
+
     pub fn get_unban_events(&self, config_id: Option<&str>) -> SqliteResult<Vec<UnbanEvent>> {
-        let mut events = Vec::new();
-        
-        if let Some(cid) = config_id {
-            let mut stmt = self.conn.prepare(
-                "SELECT id, config_id, ip, timestamp FROM unban_events WHERE config_id = ?1 ORDER BY timestamp DESC"
-            )?;
-            let rows = stmt.query_map(rusqlite::params![cid], Self::map_unban_event)?;
-            for row in rows {
-                events.push(row?);
-            }
-        } else {
-            let mut stmt = self.conn.prepare(
-                "SELECT id, config_id, ip, timestamp FROM unban_events ORDER BY timestamp DESC"
-            )?;
-            let rows = stmt.query_map([], Self::map_unban_event)?;
-            for row in rows {
-                events.push(row?);
-            }
-        }
-        
-        Ok(events)
+        let query = config_id.map(EventQuery::for_config).unwrap_or_default();
+        Ok(self.query_unban_events(&query)?.0)
     }
 
     /// Aggregate the audit log per IP: match/ban counts, distinct configs and
@@ -498,7 +1040,7 @@ impl SqliteDatabase {
         collect(
             "SELECT ip, COUNT(*), MAX(timestamp), GROUP_CONCAT(DISTINCT config_id)
              FROM ban_events
-             WHERE (?1 IS NULL OR config_id = ?1) AND (?2 IS NULL OR timestamp >= ?2)
+             WHERE simulated = 0 AND (?1 IS NULL OR config_id = ?1) AND (?2 IS NULL OR timestamp >= ?2)
              GROUP BY ip",
             false,
         )?;
@@ -519,7 +1061,14 @@ impl SqliteDatabase {
     /// security-relevant, so they should be durable and API-visible
     /// immediately. The channel is lossless; once the senders drop at
     /// shutdown, the loop drains whatever is left and exits.
-    pub async fn handle_events(db: Arc<Mutex<Self>>, mut rx: tokio::sync::mpsc::Receiver<Event>) {
+    ///
+    /// Each batch is recorded in `progress`, from the moment it is collected
+    /// until it is committed, so `/api/health` can spot a stuck writer.
+    pub async fn handle_events(
+        db: Arc<Mutex<Self>>,
+        mut rx: tokio::sync::mpsc::Receiver<Event>,
+        progress: Arc<Progress>,
+    ) {
         const BATCH_SIZE: usize = 256;
         const LINGER: std::time::Duration = std::time::Duration::from_millis(200);
 
@@ -530,6 +1079,7 @@ impl SqliteDatabase {
             if rx.recv_many(&mut buf, BATCH_SIZE).await == 0 {
                 break; // all senders gone
             }
+            progress.start();
 
             let deadline = tokio::time::sleep(LINGER);
             tokio::pin!(deadline);
@@ -546,10 +1096,12 @@ impl SqliteDatabase {
             }
 
             let db = db.lock().await;
-            if let Err(e) = db.insert_events_batch(&buf) {
+            let result = db.insert_events_batch(&buf).map_err(|e| {
                 error!("Failed to persist {} audit events: {}", buf.len(), e);
-            }
+                e.to_string()
+            });
             drop(db);
+            progress.finish(result);
             buf.clear();
         }
         info!("SQLite event handler shutting down");
@@ -1,25 +1,38 @@
-use crate::config::Config;
+use crate::allowlist::Allowlist;
+use crate::ban_target::{canonical_net, BanTarget};
+use crate::config::{Config, ConfigMode};
 use crate::events::{Event, EventEmitter, FirewallCommand};
-use crate::ip_extract::extract_ip;
+use crate::ip_extract::IpExtractor;
+use crate::metrics::ConfigCounters;
 use crate::store::MemoryStore;
 use ipnet::IpNet;
 use std::net::IpAddr;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
-use tokio::sync::{broadcast, mpsc};
+use tokio::sync::{broadcast, mpsc, RwLock};
 use tracing::{error, info, warn};
+use uuid::Uuid;
 
 /// Owns the ban policy for a single config: regex extraction, ignore-list
 /// filtering, match recording, threshold counting and the ban decision.
 ///
 /// It consumes raw lines from a tailer over an mpsc channel (lossless work
 /// path) and drives side effects out: match/ban events onto the notification
-/// bus, and `Deny` commands to the firewall actor.
+/// bus, and `Deny` commands to the firewall actor. In monitor mode the ban
+/// decision is only reported, as a simulated ban, and the firewall is left
+/// alone.
 pub struct Detector {
     config: Config,
     store: Arc<MemoryStore>,
     event_emitter: Arc<EventEmitter>,
     firewall_tx: mpsc::Sender<FirewallCommand>,
     ignore_nets: Vec<IpNet>,
+    /// Global allowlist, shared with the API so edits apply immediately.
+    allowlist: Arc<RwLock<Allowlist>>,
+    /// The config's patterns, compiled once here rather than per line.
+    extractor: IpExtractor,
+    /// Lines, matches and bans of this config, for `/metrics`.
+    counters: Arc<ConfigCounters>,
 }
 
 impl Detector {
@@ -28,24 +41,11 @@ impl Detector {
         store: Arc<MemoryStore>,
         event_emitter: Arc<EventEmitter>,
         firewall_tx: mpsc::Sender<FirewallCommand>,
+        allowlist: Arc<RwLock<Allowlist>>,
+        counters: Arc<ConfigCounters>,
     ) -> Result<Self, String> {
-        // Parse ignore_ips into IpNet (single IPs become /32 networks).
-        let mut ignore_nets = Vec::new();
-        for ip_str in &config.ignore_ips {
-            match ip_str.parse::<IpNet>() {
-                Ok(net) => ignore_nets.push(net),
-                Err(_) => match ip_str.parse::<IpAddr>() {
-                    Ok(ip) => {
-                        if let Ok(net) = format!("{}/32", ip).parse::<IpNet>() {
-                            ignore_nets.push(net);
-                        }
-                    }
-                    Err(e) => {
-                        warn!("Invalid ignore IP/CIDR: {} - {}", ip_str, e);
-                    }
-                },
-            }
-        }
+        let ignore_nets = ignore_nets(&config.ignore_ips);
+        let extractor = IpExtractor::new(&config.fail_patterns())?.with_ignore(&config.ignore_regexes)?;
 
         Ok(Self {
             config,
@@ -53,6 +53,9 @@ impl Detector {
             event_emitter,
             firewall_tx,
             ignore_nets,
+            allowlist,
+            extractor,
+            counters,
         })
     }
 
@@ -85,17 +88,22 @@ impl Detector {
     }
 
     async fn handle_line(&self, line: &str) -> Result<(), String> {
-        // Extract IP from line using regex
-        let ip = match extract_ip(&self.config.regex, line) {
+        self.counters.lines.fetch_add(1, Ordering::Relaxed);
+
+        // Extract IP from line using the precompiled patterns (an ignore
+        // regex match vetoes the line)
+        let ip = match self.extractor.extract(line) {
             Some(ip) => ip,
             None => return Ok(()), // No IP found, skip
         };
 
-        // Check if IP should be ignored
-        if self.should_ignore_ip(&ip) {
+        // Check if IP should be ignored, by this config or globally
+        if self.should_ignore_ip(&ip) || self.allowlist.read().await.contains(&ip) {
             return Ok(());
         }
 
+        self.counters.matches.fetch_add(1, Ordering::Relaxed);
+
         // Get current timestamp in milliseconds
         let timestamp = now_millis();
 
@@ -116,8 +124,12 @@ impl Detector {
         // and ban if the threshold is reached and the IP is not already banned.
         let cutoff = timestamp.saturating_sub(self.config.find_time);
         let count = self.store.count_matches(&self.config.id, &ip, cutoff);
-        if count >= self.config.max_matches as usize && !self.store.is_banned(&self.config.id, &ip) {
-            if let Err(e) = self.ban_ip(&ip).await {
+        if count >= self.config.max_matches as usize && !self.already_banned(&ip) {
+            let result = match self.config.mode {
+                ConfigMode::Enforce => self.ban_ip(&ip).await,
+                ConfigMode::Monitor => self.simulate_ban(&ip).await,
+            };
+            if let Err(e) = result {
                 error!("Failed to ban IP {}: {}", ip, e);
                 // Continue anyway, don't block critical path
             }
@@ -145,13 +157,15 @@ impl Detector {
         let prior = self.store.next_recidive(&self.config.id, *ip);
         let ban_time = self.config.effective_ban_time(prior);
         self.store
-            .add_ban_with_duration(&self.config.id, *ip, timestamp, ban_time);
+            .add_ban_with_duration(&self.config.id, BanTarget::from(*ip), timestamp, Some(ban_time));
+        self.counters.bans.fetch_add(1, Ordering::Relaxed);
 
         // Hand the firewall mutation to the actor (lossless mpsc). Errors there
         // are logged and ignored, so a full channel is the only failure mode.
         let deny = FirewallCommand::Deny {
             config_id: self.config.id.clone(),
-            ip: *ip,
+            ip: BanTarget::from(*ip),
+            timeout_ms: ban_time,
         };
         if self.firewall_tx.send(deny).await.is_err() {
             warn!("Firewall actor gone, could not deny IP {}", ip);
@@ -160,20 +174,89 @@ impl Detector {
         // Emit ban event (async, non-blocking) for the audit log and UI.
         self.event_emitter
             .emit(Event::Ban {
+                id: Uuid::new_v4().to_string(),
+                config_id: self.config.id.clone(),
+                ip: ip.to_string(),
+                timestamp,
+                ban_time: Some(ban_time),
+                recidive_level: Some(prior + 1),
+                manual: false,
+                reason: None,
+                simulated: false,
+            })
+            .await;
+
+        Ok(())
+    }
+
+    /// Monitor mode: report the ban `ban_ip` would issue, through the same
+    /// audit log and notifications, without sending anything to the firewall.
+    /// The duration is what the IP's real ban history would give it, and that
+    /// history is left untouched.
+    async fn simulate_ban(&self, ip: &IpAddr) -> Result<(), String> {
+        info!("Would ban IP {} for config {} (monitor mode)", ip, self.config.id);
+
+        let timestamp = now_millis();
+        let prior = self.store.recidive_level(&self.config.id, *ip);
+        let ban_time = self.config.effective_ban_time(prior);
+        self.store
+            .add_simulated_ban(&self.config.id, BanTarget::from(*ip), timestamp, Some(ban_time));
+        self.counters.simulated_bans.fetch_add(1, Ordering::Relaxed);
+
+        self.event_emitter
+            .emit(Event::Ban {
+                id: Uuid::new_v4().to_string(),
                 config_id: self.config.id.clone(),
                 ip: ip.to_string(),
                 timestamp,
+                ban_time: Some(ban_time),
+                // The level `ban_time` was derived from: the history is left
+                // alone, so the would-be ban is not counted in it yet.
+                recidive_level: Some(prior + 1),
+                manual: false,
+                reason: None,
+                simulated: true,
             })
             .await;
 
         Ok(())
     }
 
+    /// Whether a ban for `ip` is already in place. In monitor mode an
+    /// unexpired would-be ban counts too, so an offender is reported once per
+    /// ban_time; an enforcing config ignores those, so one switched over from
+    /// monitor mode bans right away.
+    fn already_banned(&self, ip: &IpAddr) -> bool {
+        let target = BanTarget::from(*ip);
+        self.store.is_banned(&self.config.id, &target)
+            || (self.config.mode == ConfigMode::Monitor && self.store.is_simulated_ban(&self.config.id, &target))
+    }
+
     fn should_ignore_ip(&self, ip: &IpAddr) -> bool {
         self.ignore_nets.iter().any(|net| net.contains(ip))
     }
 }
 
+/// Parse a config's `ignore_ips` into networks; single IPs become /32 or /128
+/// host networks, matching their family.
+pub fn ignore_nets(ignore_ips: &[String]) -> Vec<IpNet> {
+    let mut nets = Vec::new();
+    for ip_str in ignore_ips {
+        match ip_str.parse::<IpNet>() {
+            Ok(net) => nets.push(canonical_net(net)),
+            Err(_) => match ip_str.parse::<IpAddr>() {
+                // Extraction canonicalizes IPv4-mapped addresses, so the
+                // ignore entry must be compared in the same form.
+                Ok(ip) => nets.push(IpNet::from(ip.to_canonical())),
+                Err(e) => {
+                    warn!("Invalid ignore IP/CIDR: {} - {}", ip_str, e);
+                }
+            },
+        }
+    }
+    nets
+}
+
 pub fn now_millis() -> u64 {
     std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
@@ -0,0 +1,359 @@
+use crate::database::{IpStats, SqliteDatabase};
+use crate::detector::now_millis;
+use crate::geoip::{GeoIp, IpInfo};
+use crate::notifier::{iso8601, Notification, NotifierConfig};
+use crate::outbox::Outbox;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, Mutex, RwLock};
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+/// How many countries and IPs a daily summary lists.
+const SUMMARY_TOP: usize = 5;
+const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+
+struct Pending {
+    deadline: Instant,
+    items: Vec<Arc<Notification>>,
+}
+
+/// Notifications held back per batching notifier (by id) until its window
+/// closes or the batch fills. The window opens with a batch's first
+/// notification. The notifier itself is looked up when a batch is flushed,
+/// so edits made meanwhile apply and a deleted notifier gets nothing.
+///
+/// Open batches live in memory only: they go to the outbox on shutdown, but a
+/// crash loses them.
+#[derive(Default)]
+pub struct Batches {
+    pending: HashMap<String, Pending>,
+}
+
+impl Batches {
+    /// Queue a notification for `notifier`, which must have a batch config.
+    /// Returns the notifier id and the batch when this notification fills it.
+    pub fn push(
+        &mut self,
+        notifier: &NotifierConfig,
+        notification: Arc<Notification>,
+    ) -> Option<(String, Vec<Arc<Notification>>)> {
+        let batch = notifier.batch.as_ref()?;
+        let pending = self.pending.entry(notifier.id.clone()).or_insert_with(|| Pending {
+            deadline: Instant::now() + Duration::from_millis(batch.window),
+            items: Vec::new(),
+        });
+        pending.items.push(notification);
+        if pending.items.len() < batch.max_size {
+            return None;
+        }
+        self.pending
+            .remove_entry(&notifier.id)
+            .map(|(id, p)| (id, p.items))
+    }
+
+    /// When the earliest open window closes.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.pending.values().map(|p| p.deadline).min()
+    }
+
+    /// Every batch whose window has closed by `now`.
+    pub fn take_due(&mut self, now: Instant) -> Vec<(String, Vec<Arc<Notification>>)> {
+        let due: Vec<String> = self
+            .pending
+            .iter()
+            .filter(|(_, p)| p.deadline <= now)
+            .map(|(id, _)| id.clone())
+            .collect();
+        due.into_iter()
+            .filter_map(|id| self.pending.remove_entry(&id))
+            .map(|(id, p)| (id, p.items))
+            .collect()
+    }
+
+    pub fn take_all(&mut self) -> Vec<(String, Vec<Arc<Notification>>)> {
+        self.pending.drain().map(|(id, p)| (id, p.items)).collect()
+    }
+}
+
+fn var<'a>(n: &'a Notification, name: &str) -> Option<&'a str> {
+    n.vars.get(name).and_then(Value::as_str)
+}
+
+fn plural(count: u64, word: &str) -> String {
+    let suffix = match (count, word.ends_with("ch")) {
+        (1, _) => "",
+        (_, true) => "es",
+        _ => "s",
+    };
+    format!("{} {}{}", count, word, suffix)
+}
+
+/// One notification standing for several: a line per event with its IP,
+/// config and country, then how many came from each country. Templates
+/// describe a single event, so a digest uses its own wording; webhooks get
+/// `{count}` and every event's variables in `{events}`.
+pub fn digest(items: &[Arc<Notification>]) -> Notification {
+    let mut per_event: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut per_country: BTreeMap<String, usize> = BTreeMap::new();
+    let mut lines = Vec::with_capacity(items.len());
+    for n in items {
+        let event = match var(n, "event") {
+            Some("ban") if n.vars.get("simulated") == Some(&Value::Bool(true)) => "simulated ban",
+            Some(event) => event,
+            None => "event",
+        };
+        *per_event.entry(event).or_default() += 1;
+        let country = var(n, "country").map(|name| match var(n, "country_flag") {
+            Some(flag) => format!("{} {}", flag, name),
+            None => name.to_string(),
+        });
+        let mut line = format!(
+            "{} {} [{}]",
+            event,
+            var(n, "ip").unwrap_or("?"),
+            var(n, "config_name").unwrap_or("?")
+        );
+        if let Some(country) = country {
+            line.push_str(&format!(" {}", country));
+            *per_country.entry(country).or_default() += 1;
+        }
+        if let Some(time) = var(n, "time") {
+            line.push_str(&format!(" at {}", time));
+        }
+        lines.push(line);
+    }
+
+    let counts: Vec<String> = per_event
+        .iter()
+        .map(|(event, count)| plural(*count as u64, event))
+        .collect();
+    let title = format!("Banalize digest: {}", counts.join(", "));
+    let mut message = lines.join("\n");
+    if !per_country.is_empty() {
+        let mut countries: Vec<(String, usize)> = per_country.into_iter().collect();
+        countries.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        let countries: Vec<String> = countries
+            .iter()
+            .map(|(country, count)| format!("{} ({})", country, count))
+            .collect();
+        message.push_str(&format!("\nCountries: {}", countries.join(", ")));
+    }
+
+    let mut notification = Notification::aggregate("digest", title, message, now_millis());
+    notification.vars.insert("count".into(), json!(items.len()));
+    notification.vars.insert(
+        "events".into(),
+        Value::Array(items.iter().map(|n| json!(n.vars)).collect()),
+    );
+    notification
+}
+
+/// Totals over the last 24 hours from the per-IP stats, with the countries
+/// and IPs that banned most.
+pub fn summary(stats: &[IpStats], lookup: impl Fn(IpAddr) -> IpInfo, now: u64) -> Notification {
+    let bans: u64 = stats.iter().map(|s| s.ban_count).sum();
+    let matches: u64 = stats.iter().map(|s| s.match_count).sum();
+
+    // (label, bans, ips) per country; unresolved IPs are left out.
+    let mut by_country: HashMap<String, (String, u64, u64)> = HashMap::new();
+    for s in stats {
+        let Ok(ip) = s.ip.parse::<IpAddr>() else {
+            continue;
+        };
+        let info = lookup(ip);
+        let Some(code) = info.country_code else {
+            continue;
+        };
+        let entry = by_country.entry(code.clone()).or_insert_with(|| {
+            let name = info.country_name.unwrap_or(code);
+            let label = match info.flag {
+                Some(flag) => format!("{} {}", flag, name),
+                None => name,
+            };
+            (label, 0, 0)
+        });
+        entry.1 += s.ban_count;
+        entry.2 += 1;
+    }
+    let mut countries: Vec<(String, u64, u64)> = by_country.into_values().collect();
+    countries.sort_by(|a, b| b.1.cmp(&a.1).then(b.2.cmp(&a.2)).then_with(|| a.0.cmp(&b.0)));
+
+    let mut lines = vec![format!(
+        "Last 24 hours: {}, {} from {}",
+        plural(bans, "ban"),
+        plural(matches, "match"),
+        plural(stats.len() as u64, "IP")
+    )];
+    if !countries.is_empty() {
+        lines.push("Top countries:".to_string());
+        for (label, bans, ips) in countries.iter().take(SUMMARY_TOP) {
+            lines.push(format!("  {}: {}, {}", label, plural(*bans, "ban"), plural(*ips, "IP")));
+        }
+    }
+    // `stats` comes heaviest first.
+    if !stats.is_empty() {
+        lines.push("Top IPs:".to_string());
+        for s in stats.iter().take(SUMMARY_TOP) {
+            lines.push(format!(
+                "  {}: {}, {} ({})",
+                s.ip,
+                plural(s.ban_count, "ban"),
+                plural(s.match_count, "match"),
+                s.config_ids.join(", ")
+            ));
+        }
+    }
+
+    let mut notification = Notification::aggregate(
+        "summary",
+        format!("Banalize daily summary for {}", &iso8601(now)[..10]),
+        lines.join("\n"),
+        now,
+    );
+    notification.vars.insert("count".into(), json!(bans));
+    notification
+}
+
+/// Queues each notifier's daily summary once its hour comes round. Checked
+/// every minute; the day each notifier last got one is kept in memory, so a
+/// restart within the hour can send it again.
+pub async fn run_daily_summaries(
+    mut shutdown_rx: broadcast::Receiver<()>,
+    notifiers: Arc<RwLock<Vec<NotifierConfig>>>,
+    events_db: Arc<Mutex<SqliteDatabase>>,
+    geoip: Arc<GeoIp>,
+    outbox: Arc<Outbox>,
+) {
+    let mut last_sent: HashMap<String, u64> = HashMap::new();
+    let mut tick = tokio::time::interval(Duration::from_secs(60));
+
+    loop {
+        tokio::select! {
+            _ = shutdown_rx.recv() => return,
+            _ = tick.tick() => {}
+        }
+
+        let now = now_millis();
+        let (day, hour) = (now / DAY_MS, (now % DAY_MS / 3_600_000) as u8);
+        let due: Vec<NotifierConfig> = notifiers
+            .read()
+            .await
+            .iter()
+            .filter(|n| n.daily_summary.as_ref().is_some_and(|d| d.hour == hour))
+            .filter(|n| last_sent.get(&n.id) != Some(&day))
+            .cloned()
+            .collect();
+        if due.is_empty() {
+            continue;
+        }
+
+        let stats = match events_db.lock().await.get_ip_stats(None, Some(now.saturating_sub(DAY_MS))) {
+            Ok(stats) => stats,
+            Err(e) => {
+                warn!("Daily summary: failed to read stats: {}", e);
+                continue;
+            }
+        };
+        let notification = summary(&stats, |ip| geoip.lookup(ip), now);
+        for notifier in due {
+            last_sent.insert(notifier.id.clone(), day);
+            info!("Queueing daily summary for notifier {}", notifier.id);
+            outbox.enqueue(&notifier.id, &notification).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ban(ip: &str, config: &str, country: Option<(&str, &str)>) -> Arc<Notification> {
+        let mut n = Notification::new("t".to_string(), "m".to_string(), None);
+        n.vars.insert("event".into(), json!("ban"));
+        n.vars.insert("ip".into(), json!(ip));
+        n.vars.insert("config_name".into(), json!(config));
+        n.vars.insert("country_flag".into(), json!(country.map(|c| c.0)));
+        n.vars.insert("country".into(), json!(country.map(|c| c.1)));
+        Arc::new(n)
+    }
+
+    fn batching(window: u64, max_size: usize) -> NotifierConfig {
+        serde_json::from_value(json!({
+            "id": "b",
+            "events": ["ban"],
+            "email_config": null,
+            "signal_config": null,
+            "webhook_config": { "url": "http://localhost/x" },
+            "batch": { "window": window, "max_size": max_size },
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn batches_flush_when_full_or_due() {
+        let mut batches = Batches::default();
+        assert!(batches.next_deadline().is_none());
+        assert!(batches.push(&batching(60_000, 2), ban("192.0.2.1", "ssh", None)).is_none());
+        let (id, items) = batches.push(&batching(60_000, 2), ban("192.0.2.2", "ssh", None)).unwrap();
+        assert_eq!((id.as_str(), items.len()), ("b", 2));
+        assert!(batches.next_deadline().is_none());
+
+        assert!(batches.push(&batching(1_000, 10), ban("192.0.2.3", "ssh", None)).is_none());
+        let deadline = batches.next_deadline().unwrap();
+        assert!(batches.take_due(deadline - Duration::from_millis(1)).is_empty());
+        assert_eq!(batches.take_due(deadline).len(), 1);
+        assert!(batches.take_all().is_empty());
+    }
+
+    #[test]
+    fn digest_lists_events_and_countries() {
+        let nl = Some(("🇳🇱", "Netherlands"));
+        let n = digest(&[
+            ban("192.0.2.1", "ssh", nl),
+            ban("192.0.2.2", "nginx", nl),
+            ban("10.0.0.1", "ssh", None),
+        ]);
+        assert_eq!(n.title, "Banalize digest: 3 bans");
+        assert!(n.message.starts_with("ban 192.0.2.1 [ssh] 🇳🇱 Netherlands\nban 192.0.2.2 [nginx]"));
+        assert!(n.message.contains("\nban 10.0.0.1 [ssh]\n"));
+        assert!(n.message.ends_with("Countries: 🇳🇱 Netherlands (2)"));
+        assert_eq!(n.vars["event"], "digest");
+        assert_eq!(n.vars["count"], 3);
+        assert_eq!(n.vars["events"][1]["ip"], "192.0.2.2");
+        assert_eq!(n.vars["ip"], Value::Null);
+    }
+
+    #[test]
+    fn summary_reports_totals_and_top_countries() {
+        let stat = |ip: &str, bans, matches| IpStats {
+            ip: ip.to_string(),
+            match_count: matches,
+            ban_count: bans,
+            config_ids: vec!["ssh".to_string()],
+            last_seen: 0,
+        };
+        let stats = [stat("192.0.2.1", 2, 10), stat("192.0.2.2", 1, 1), stat("10.0.0.1", 1, 3)];
+        let lookup = |ip: IpAddr| match ip.to_string().starts_with("192.0.2.") {
+            true => IpInfo {
+                country_code: Some("NL".to_string()),
+                country_name: Some("Netherlands".to_string()),
+                flag: Some("🇳🇱".to_string()),
+            },
+            false => IpInfo::default(),
+        };
+        let n = summary(&stats, lookup, 0);
+        assert_eq!(n.title, "Banalize daily summary for 1970-01-01");
+        assert_eq!(
+            n.message,
+            "Last 24 hours: 4 bans, 14 matches from 3 IPs\n\
+             Top countries:\n  🇳🇱 Netherlands: 3 bans, 2 IPs\n\
+             Top IPs:\n  192.0.2.1: 2 bans, 10 matches (ssh)\n  \
+             192.0.2.2: 1 ban, 1 match (ssh)\n  10.0.0.1: 1 ban, 3 matches (ssh)"
+        );
+        assert_eq!(n.vars["event"], "summary");
+    }
+}
@@ -0,0 +1,412 @@
+//! Follows container logs through the Docker Engine API on its unix socket.
+//!
+//! Each container is streamed with `GET /containers/{id}/logs?follow=1` and
+//! `timestamps=1`. When the stream ends (the container stopped or restarted,
+//! or the daemon went away) the follower waits for the container to run again
+//! and resumes with `since` set to the last line seen, dropping anything at or
+//! before it, so a restart neither loses nor repeats lines.
+
+use crate::config::DockerFilter;
+use crate::log_source::LogSource;
+use bytes::Bytes;
+use http_body_util::{BodyExt, Empty};
+use hyper::body::Incoming;
+use hyper::{Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::net::UnixStream;
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+use tracing::{info, warn};
+
+/// The Docker daemon's socket, unless `BANALIZE_CORE_DOCKER_SOCKET` says
+/// otherwise.
+pub const DEFAULT_DOCKER_SOCKET: &str = "/var/run/docker.sock";
+
+/// Wait between attempts to reach a stopped container or the daemon, and
+/// between label lookups.
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+/// Lines buffered between the followers and the detector.
+const LINE_CAPACITY: usize = 1024;
+/// Multiplexed stream frames start with an 8-byte header.
+const FRAME_HEADER_SIZE: usize = 8;
+
+/// A log line's time as (seconds, nanoseconds) since the epoch.
+type Timestamp = (i64, u32);
+
+fn now() -> Timestamp {
+    let elapsed = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    (elapsed.as_secs() as i64, elapsed.subsec_nanos())
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Parse Docker's RFC 3339 timestamp (UTC, up to nine fraction digits).
+fn parse_timestamp(s: &str) -> Option<Timestamp> {
+    let s = s.strip_suffix('Z')?;
+    let (date, time) = s.split_once('T')?;
+    let mut date = date.splitn(3, '-').map(str::parse::<u32>);
+    let (year, month, day) = (date.next()?.ok()?, date.next()?.ok()?, date.next()?.ok()?);
+    let (hms, fraction) = time.split_once('.').unwrap_or((time, ""));
+    let mut hms = hms.splitn(3, ':').map(str::parse::<i64>);
+    let (h, m, sec) = (hms.next()?.ok()?, hms.next()?.ok()?, hms.next()?.ok()?);
+    if fraction.len() > 9 || !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let nanos = format!("{:0<9}", fraction).parse().ok()?;
+    let days = days_from_civil(year as i64, month, day);
+    Some((days * 86_400 + h * 3600 + m * 60 + sec, nanos))
+}
+
+/// Percent-encode a query value.
+fn encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// A minimal Docker Engine API client: one connection per request.
+#[derive(Clone)]
+struct DockerClient {
+    socket: Arc<PathBuf>,
+}
+
+impl DockerClient {
+    async fn get(&self, path: &str) -> Result<Response<Incoming>, String> {
+        let stream = UnixStream::connect(self.socket.as_path())
+            .await
+            .map_err(|e| format!("cannot reach {}: {}", self.socket.display(), e))?;
+        let (mut sender, conn) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+            .await
+            .map_err(|e| e.to_string())?;
+        tokio::spawn(conn);
+        let request = Request::get(path)
+            .header("Host", "docker")
+            .body(Empty::<Bytes>::new())
+            .map_err(|e| e.to_string())?;
+        sender.send_request(request).await.map_err(|e| e.to_string())
+    }
+
+    /// GET a JSON document; `None` on 404.
+    async fn get_json(&self, path: &str) -> Result<Option<Value>, String> {
+        let response = self.get(path).await?;
+        let status = response.status();
+        if status == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let body = response.into_body().collect().await.map_err(|e| e.to_string())?;
+        if !status.is_success() {
+            return Err(format!("{} from {}", status, path));
+        }
+        serde_json::from_slice(&body.to_bytes()).map(Some).map_err(|e| e.to_string())
+    }
+
+    /// The container's id when it is running; `Ok(None)` when it is not, and
+    /// an error when it does not exist.
+    async fn running_id(&self, container: &str) -> Result<Option<String>, String> {
+        let path = format!("/containers/{}/json", encode(container));
+        let info = self
+            .get_json(&path)
+            .await?
+            .ok_or_else(|| format!("no such container: {}", container))?;
+        let running = info["State"]["Running"].as_bool().unwrap_or(false);
+        Ok(running.then(|| info["Id"].as_str().unwrap_or(container).to_string()))
+    }
+
+    /// Ids of the running containers carrying every one of `labels`.
+    async fn labelled(&self, labels: &[String]) -> Result<Vec<String>, String> {
+        let filters = serde_json::json!({ "label": labels }).to_string();
+        let path = format!("/containers/json?filters={}", encode(&filters));
+        let list = self.get_json(&path).await?.unwrap_or_default();
+        Ok(list
+            .as_array()
+            .map(|containers| {
+                containers
+                    .iter()
+                    .filter_map(|c| c["Id"].as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+}
+
+/// Splits a log stream into timestamped lines. Non-TTY containers send
+/// stdout and stderr multiplexed in frames; each stream keeps its own partial
+/// line.
+struct LineSplitter {
+    multiplexed: bool,
+    frame: Vec<u8>,
+    partial: [Vec<u8>; 3],
+}
+
+impl LineSplitter {
+    fn new(multiplexed: bool) -> Self {
+        Self {
+            multiplexed,
+            frame: Vec::new(),
+            partial: Default::default(),
+        }
+    }
+
+    /// Feed a chunk of the response body; returns the lines it completed.
+    fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        let mut lines = Vec::new();
+        if !self.multiplexed {
+            Self::split(&mut self.partial[0], chunk, &mut lines);
+            return lines;
+        }
+        self.frame.extend_from_slice(chunk);
+        while self.frame.len() >= FRAME_HEADER_SIZE {
+            let size = u32::from_be_bytes(self.frame[4..8].try_into().unwrap()) as usize;
+            if self.frame.len() < FRAME_HEADER_SIZE + size {
+                break;
+            }
+            let stream = (self.frame[0] as usize).min(2);
+            let payload: Vec<u8> = self.frame.drain(..FRAME_HEADER_SIZE + size).skip(FRAME_HEADER_SIZE).collect();
+            Self::split(&mut self.partial[stream], &payload, &mut lines);
+        }
+        lines
+    }
+
+    fn split(partial: &mut Vec<u8>, data: &[u8], lines: &mut Vec<String>) {
+        partial.extend_from_slice(data);
+        while let Some(end) = partial.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = partial.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line[..end]);
+            lines.push(line.trim_end_matches('\r').to_string());
+        }
+    }
+}
+
+/// Follow one container until it is gone (`give_up_when_gone`) or the
+/// receiver is dropped, streaming lines newer than `since`.
+async fn follow(
+    client: DockerClient,
+    container: String,
+    mut since: Timestamp,
+    give_up_when_gone: bool,
+    tx: mpsc::Sender<String>,
+) {
+    let mut logged_wait = false;
+    loop {
+        let id = match client.running_id(&container).await {
+            Ok(Some(id)) => id,
+            Ok(None) => {
+                if !logged_wait {
+                    info!("Container {} is not running, waiting for it", container);
+                    logged_wait = true;
+                }
+                tokio::time::sleep(RETRY_INTERVAL).await;
+                continue;
+            }
+            Err(e) => {
+                if give_up_when_gone {
+                    info!("Stopped following container {}: {}", container, e);
+                    return;
+                }
+                if !logged_wait {
+                    warn!("Cannot follow container {}: {}", container, e);
+                    logged_wait = true;
+                }
+                tokio::time::sleep(RETRY_INTERVAL).await;
+                continue;
+            }
+        };
+        logged_wait = false;
+        info!("Following logs of container {}", container);
+        match stream_logs(&client, &id, &mut since, &tx).await {
+            Ok(()) => info!("Log stream of container {} ended", container),
+            Err(e) => warn!("Log stream of container {} failed: {}", container, e),
+        }
+        if tx.is_closed() {
+            return;
+        }
+        tokio::time::sleep(RETRY_INTERVAL).await;
+    }
+}
+
+/// Stream a running container's logs from `since` until the stream ends,
+/// moving `since` to each line forwarded.
+async fn stream_logs(
+    client: &DockerClient,
+    id: &str,
+    since: &mut Timestamp,
+    tx: &mpsc::Sender<String>,
+) -> Result<(), String> {
+    let path = format!(
+        "/containers/{}/logs?follow=1&stdout=1&stderr=1&timestamps=1&since={}.{:09}",
+        encode(id),
+        since.0,
+        since.1
+    );
+    let response = client.get(&path).await?;
+    if !response.status().is_success() {
+        return Err(format!("{} from the logs endpoint", response.status()));
+    }
+    let multiplexed = response
+        .headers()
+        .get(hyper::header::CONTENT_TYPE)
+        .is_some_and(|v| v.as_bytes() == b"application/vnd.docker.multiplexed-stream");
+    let mut splitter = LineSplitter::new(multiplexed);
+    let mut body = response.into_body();
+    while let Some(frame) = body.frame().await {
+        let frame = frame.map_err(|e| e.to_string())?;
+        let Some(chunk) = frame.data_ref() else {
+            continue;
+        };
+        for line in splitter.push(chunk) {
+            let (stamp, message) = line.split_once(' ').unwrap_or((line.as_str(), ""));
+            let Some(stamp) = parse_timestamp(stamp) else {
+                continue;
+            };
+            // `since` is inclusive, and a resumed stream starts at the last
+            // line already forwarded.
+            if stamp <= *since {
+                continue;
+            }
+            *since = stamp;
+            if tx.send(message.to_string()).await.is_err() {
+                return Ok(());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Find containers carrying `labels` and follow each one, from `start` on.
+async fn follow_labelled(client: DockerClient, labels: Vec<String>, start: Timestamp, tx: mpsc::Sender<String>) {
+    let mut followers = JoinSet::new();
+    let mut followed: HashSet<String> = HashSet::new();
+    loop {
+        while let Some(Ok(id)) = followers.try_join_next() {
+            followed.remove(&id);
+        }
+        match client.labelled(&labels).await {
+            Ok(ids) => {
+                for id in ids {
+                    if followed.insert(id.clone()) {
+                        let (client, tx) = (client.clone(), tx.clone());
+                        followers.spawn(async move {
+                            follow(client, id.clone(), start, true, tx).await;
+                            id
+                        });
+                    }
+                }
+            }
+            Err(e) => warn!("Cannot list containers labelled {}: {}", labels.join(", "), e),
+        }
+        if tx.is_closed() {
+            return;
+        }
+        tokio::time::sleep(RETRY_INTERVAL).await;
+    }
+}
+
+/// The lines of a container, or of every container with the given labels,
+/// starting from when it is created. Dropping it stops the followers.
+pub struct DockerLogs {
+    lines: mpsc::Receiver<String>,
+    _tasks: JoinSet<()>,
+}
+
+impl DockerLogs {
+    /// Follow `container` (a name or id) or, when it is empty, the containers
+    /// matching `filter.labels`, through the daemon at `socket`.
+    pub fn new(socket: &Path, container: &str, filter: &DockerFilter) -> Self {
+        let client = DockerClient {
+            socket: Arc::new(socket.to_path_buf()),
+        };
+        let (tx, lines) = mpsc::channel(LINE_CAPACITY);
+        let mut tasks = JoinSet::new();
+        let start = now();
+        if container.is_empty() {
+            info!("Watching containers labelled {}", filter.labels.join(", "));
+            tasks.spawn(follow_labelled(client, filter.labels.clone(), start, tx));
+        } else {
+            info!("Watching container {}", container);
+            tasks.spawn(follow(client, container.to_string(), start, false, tx));
+        }
+        Self { lines, _tasks: tasks }
+    }
+}
+
+impl LogSource for DockerLogs {
+    async fn next_line(&mut self) -> Option<String> {
+        self.lines.recv().await
+    }
+}
+
+/// The socket to reach the Docker daemon on.
+pub fn docker_socket() -> PathBuf {
+    let socket = std::env::var("BANALIZE_CORE_DOCKER_SOCKET")
+        .unwrap_or_else(|_| DEFAULT_DOCKER_SOCKET.to_string());
+    // Accept DOCKER_HOST's `unix://` form as well as a bare path.
+    PathBuf::from(socket.strip_prefix("unix://").unwrap_or(&socket))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamps_parse_with_any_fraction() {
+        assert_eq!(parse_timestamp("1970-01-01T00:00:00Z"), Some((0, 0)));
+        assert_eq!(
+            parse_timestamp("2024-02-29T12:34:56.5Z"),
+            Some((1_709_210_096, 500_000_000))
+        );
+        assert_eq!(
+            parse_timestamp("2024-02-29T12:34:56.000000001Z"),
+            Some((1_709_210_096, 1))
+        );
+        assert_eq!(parse_timestamp("2024-02-29T12:34:56+01:00"), None);
+        assert_eq!(parse_timestamp("not a time"), None);
+    }
+
+    #[test]
+    fn multiplexed_frames_split_into_lines_per_stream() {
+        let frame = |stream: u8, payload: &str| {
+            let mut f = vec![stream, 0, 0, 0];
+            f.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+            f.extend_from_slice(payload.as_bytes());
+            f
+        };
+        let mut splitter = LineSplitter::new(true);
+        let mut bytes = frame(1, "out one\nout ");
+        bytes.extend(frame(2, "err one\r\n"));
+        bytes.extend(frame(1, "two\n"));
+        // Frames may arrive split anywhere.
+        let (a, b) = bytes.split_at(5);
+        assert!(splitter.push(a).is_empty());
+        assert_eq!(splitter.push(b), vec!["out one", "err one", "out two"]);
+
+        let mut raw = LineSplitter::new(false);
+        assert_eq!(raw.push(b"tty line\npartial"), vec!["tty line"]);
+        assert_eq!(raw.push(b"\n"), vec!["partial"]);
+    }
+
+    #[test]
+    fn query_values_are_encoded() {
+        assert_eq!(encode("web-1_a.b"), "web-1_a.b");
+        assert_eq!(encode(r#"{"label":["a=b"]}"#), "%7B%22label%22%3A%5B%22a%3Db%22%5D%7D");
+    }
+}
@@ -1,15 +1,29 @@
-use std::net::IpAddr;
+use crate::ban_target::BanTarget;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use tokio::sync::mpsc::error::TrySendError;
 use tokio::sync::{broadcast, mpsc};
+use tracing::{info, warn};
 
 /// Command sent to the firewall actor over a lossless mpsc channel.
-/// The actor is the single owner of the iptables state, so every ban/unban
+/// The actor is the single owner of the firewall state, so every ban/unban
 /// mutation goes through here rather than the lossy notification bus.
 /// Commands carry the config id because every config owns its own child
 /// chain — rules for the same IP under different configs are independent.
 #[derive(Debug, Clone)]
 pub enum FirewallCommand {
-    Deny { config_id: String, ip: IpAddr },
-    Allow { config_id: String, ip: IpAddr },
+    Deny {
+        config_id: String,
+        ip: BanTarget,
+        /// Remaining ban duration, for backends that expire bans natively;
+        /// 0 for a permanent ban.
+        timeout_ms: u64,
+    },
+    /// Many bans of one config at once (restore): `(ip, timeout_ms)` pairs.
+    DenyBatch {
+        config_id: String,
+        bans: Vec<(BanTarget, u64)>,
+    },
+    Allow { config_id: String, ip: BanTarget },
     /// Tear down a config's chain entirely (config deletion).
     RemoveChain { config_id: String },
 }
@@ -24,9 +38,26 @@ pub enum Event {
         line: String,
     },
     Ban {
+        /// Audit row id, assigned up front so a manual ban can be returned
+        /// (and later disabled) by the id it is stored under.
+        id: String,
         config_id: String,
         ip: String,
         timestamp: u64,
+        /// Effective duration in ms, recidive escalation included; `None` for
+        /// a permanent ban. Persisted so restore never re-derives it.
+        ban_time: Option<u64>,
+        /// Bans of this IP under the config, this one included (1 for a first
+        /// offence), as counted when `ban_time` was derived. `None` for a
+        /// network ban, which has no recidive history.
+        recidive_level: Option<u32>,
+        /// Placed through the API rather than by the detector.
+        manual: bool,
+        /// Operator-supplied reason (manual bans only).
+        reason: Option<String>,
+        /// Would-be ban of a config in monitor mode: reported and audited,
+        /// but never sent to the firewall.
+        simulated: bool,
     },
     Unban {
         config_id: String,
@@ -40,6 +71,14 @@ pub enum Event {
 /// dropping audit events. The batched writer drains far faster than events
 /// arrive, so the buffer absorbing a burst is the expected case and
 /// backpressure the pathological one.
+///
+/// The notification channel has the same capacity. Bans and unbans are
+/// lossless on it too; its dispatcher batches outbox inserts the way the
+/// audit writer does, so it keeps up with them. Matches are the one lossy
+/// part: a notifier subscribed to `match` during a scan wave must not stall
+/// every detector and the firewall path behind it, so once the channel is
+/// full further matches skip the notifiers and are counted (the audit log
+/// still has them).
 const EVENT_CHANNEL_CAPACITY: usize = 8192;
 
 /// Buffer for the lossy notification bus feeding live SSE subscribers.
@@ -50,27 +89,157 @@ const EVENT_BROADCAST_CAPACITY: usize = 1024;
 
 pub struct EventEmitter {
     tx: mpsc::Sender<Event>,
+    /// Feeds the notification dispatcher: lossless for bans and unbans, while
+    /// matches are dropped and counted in `notifications_dropped` when full.
+    notify_tx: mpsc::Sender<Event>,
+    notifications_dropped: AtomicU64,
+    /// Set while matches are being dropped, so each overflow logs once.
+    dropping_matches: AtomicBool,
     broadcast_tx: broadcast::Sender<Event>,
 }
 
 impl EventEmitter {
-    pub fn new() -> (Self, mpsc::Receiver<Event>) {
+    /// Returns the emitter with the receivers of the audit and notification
+    /// channels.
+    pub fn new() -> (Self, mpsc::Receiver<Event>, mpsc::Receiver<Event>) {
         let (tx, rx) = mpsc::channel(EVENT_CHANNEL_CAPACITY);
+        let (notify_tx, notify_rx) = mpsc::channel(EVENT_CHANNEL_CAPACITY);
         let (broadcast_tx, _) = broadcast::channel(EVENT_BROADCAST_CAPACITY);
-        (Self { tx, broadcast_tx }, rx)
+        (
+            Self {
+                tx,
+                notify_tx,
+                notifications_dropped: AtomicU64::new(0),
+                dropping_matches: AtomicBool::new(false),
+                broadcast_tx,
+            },
+            rx,
+            notify_rx,
+        )
     }
 
-    /// Emit an event for the durable audit log and live subscribers.
+    /// Emit an event for the durable audit log, the notifiers and live
+    /// subscribers.
     pub async fn emit(&self, event: Event) {
         // No live subscribers: nobody to notify.
         let _ = self.broadcast_tx.send(event.clone());
-        // Receiver gone (shutdown): nothing left to record.
+        // Receivers gone (shutdown): nothing left to record or notify.
+        if matches!(event, Event::Match { .. }) {
+            self.notify_match(event.clone());
+        } else {
+            let _ = self.notify_tx.send(event.clone()).await;
+        }
         let _ = self.tx.send(event).await;
     }
 
+    /// Hand a match to the notifiers unless their queue is full (see
+    /// EVENT_CHANNEL_CAPACITY).
+    fn notify_match(&self, event: Event) {
+        match self.notify_tx.try_send(event) {
+            Err(TrySendError::Full(_)) => {
+                self.notifications_dropped.fetch_add(1, Ordering::Relaxed);
+                if !self.dropping_matches.swap(true, Ordering::Relaxed) {
+                    warn!("Notification queue full, match notifications are dropped until it drains");
+                }
+            }
+            Ok(()) => {
+                if self.dropping_matches.swap(false, Ordering::Relaxed) {
+                    info!(
+                        "Notification queue drained ({} match notifications dropped so far)",
+                        self.notifications_dropped()
+                    );
+                }
+            }
+            Err(TrySendError::Closed(_)) => {}
+        }
+    }
+
+    /// Matches dropped for notifiers because their queue was full.
+    pub fn notifications_dropped(&self) -> u64 {
+        self.notifications_dropped.load(Ordering::Relaxed)
+    }
+
     /// Subscribe to live event notifications (lossy under load).
     pub fn subscribe(&self) -> broadcast::Receiver<Event> {
         self.broadcast_tx.subscribe()
     }
+
+    /// Events waiting in the audit and notification channels.
+    pub fn queue_depths(&self) -> [(&'static str, usize); 2] {
+        let depth = |tx: &mpsc::Sender<Event>| tx.max_capacity() - tx.capacity();
+        [("events", depth(&self.tx)), ("notifications", depth(&self.notify_tx))]
+    }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn event(ban: bool, timestamp: u64) -> Event {
+        let (config_id, ip) = ("ssh".to_string(), "10.0.0.1".to_string());
+        if ban {
+            Event::Ban {
+                id: timestamp.to_string(),
+                config_id,
+                ip,
+                timestamp,
+                ban_time: Some(60_000),
+                recidive_level: Some(1),
+                manual: false,
+                reason: None,
+                simulated: false,
+            }
+        } else {
+            Event::Match {
+                config_id,
+                ip,
+                timestamp,
+                line: String::new(),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn a_full_notification_queue_drops_matches_but_not_bans() {
+        let (emitter, mut rx, mut notify_rx) = EventEmitter::new();
+        let audit = tokio::spawn(async move {
+            let mut n = 0;
+            while rx.recv().await.is_some() {
+                n += 1;
+            }
+            n
+        });
+
+        // WHEN matches overflow the notification queue
+        for i in 0..EVENT_CHANNEL_CAPACITY as u64 + 2 {
+            emitter.emit(event(false, i)).await;
+        }
+        assert_eq!(emitter.notifications_dropped(), 2);
+        assert!(emitter.dropping_matches.load(Ordering::Relaxed));
+
+        // THEN a ban waits for room instead of being dropped
+        let emitter = Arc::new(emitter);
+        let ban = tokio::spawn({
+            let emitter = emitter.clone();
+            async move { emitter.emit(event(true, 0)).await }
+        });
+        let mut seen_ban = false;
+        while let Some(e) = notify_rx.recv().await {
+            if matches!(e, Event::Ban { .. }) {
+                seen_ban = true;
+                break;
+            }
+        }
+        ban.await.unwrap();
+        assert!(seen_ban);
+        assert_eq!(emitter.notifications_dropped(), 2);
+
+        // AND the next match that fits re-arms the overflow warning
+        emitter.emit(event(false, 1)).await;
+        assert!(!emitter.dropping_matches.load(Ordering::Relaxed));
+        drop(emitter);
+        assert_eq!(audit.await.unwrap(), EVENT_CHANNEL_CAPACITY + 4);
+    }
+}
@@ -0,0 +1,752 @@
+//! Follows the files named by a file config's `param`: a path or a glob such as
+//! `/var/log/nginx/*access.log`, re-expanded on every rescan so new files are
+//! picked up.
+//!
+//! Each file is read from a remembered offset rather than through a
+//! long-lived stream, which makes rotation explicit:
+//!
+//!   * rename rotation: the path now names another inode. The old handle is
+//!     read to its end first, then the new file is read from its start;
+//!   * copytruncate: the file shrank below the offset, so reading restarts
+//!     from the top;
+//!   * a rotated file that a glob matches under its new name keeps its inode
+//!     and is read on from where it was left, not again from its start.
+//!
+//! Only complete lines are emitted; a trailing partial line waits for its
+//! newline, and one growing past `MAX_LINE_LENGTH` is dropped. Files present
+//! when tailing starts are read from their end, files appearing later from
+//! their start. Changes wake the tailer through inotify (via `notify`) on the
+//! pattern's base directory; a periodic rescan covers everything else (deeper
+//! globs, directories created later). Globbing and reads run on the blocking
+//! pool.
+
+use crate::detector::now_millis;
+use crate::log_source::LogSource;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io;
+use std::os::unix::fs::{FileExt, MetadataExt};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+use tracing::{info, warn};
+
+/// How often globs are re-expanded and files checked when no change
+/// notification arrives.
+const RESCAN_INTERVAL: Duration = Duration::from_secs(1);
+/// Bytes read from one file per pass, so a burst cannot stall the runtime;
+/// the rest is read straight away by the next pass.
+const READ_BUDGET: usize = 1024 * 1024;
+/// Rotated-away files remembered, so a glob matching their new name resumes
+/// them instead of reading them again.
+const MAX_RETIRED: usize = 32;
+/// Longest line kept; the rest of a longer one is discarded up to its
+/// newline, so a file without newlines cannot grow the buffer unbounded.
+const MAX_LINE_LENGTH: usize = 64 * 1024;
+
+/// Whether a file is being followed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FileState {
+    Tailing,
+    /// The path does not exist (yet), or the glob matches nothing
+    Missing,
+    /// The file exists but cannot be read
+    Error,
+}
+
+/// Tailing status of one file, as reported by `GET /api/configs/{id}/files`.
+#[derive(Debug, Clone)]
+pub struct FileStatus {
+    pub path: String,
+    pub state: FileState,
+    pub error: Option<String>,
+    pub size: Option<u64>,
+    pub offset: u64,
+    pub lines: u64,
+    pub last_line_at: Option<u64>,
+    pub rotations: u32,
+}
+
+/// Shared view of a tailer's files, updated after every pass.
+pub type TailStatus = Arc<Mutex<Vec<FileStatus>>>;
+
+fn has_wildcards(s: &str) -> bool {
+    s.contains(['*', '?', '['])
+}
+
+/// Shell-style match of one path component: `*`, `?` and `[...]` (with `!`
+/// or `^` negation and ranges). Wildcards never match a leading dot.
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    if name.starts_with('.') && !pattern.starts_with('.') {
+        return false;
+    }
+    let p: Vec<char> = pattern.chars().collect();
+    let n: Vec<char> = name.chars().collect();
+    match_from(&p, &n)
+}
+
+/// Iterative matching that only ever backtracks to the last `*`: a later
+/// star can absorb whatever an earlier one would, so this is O(p * n).
+fn match_from(p: &[char], n: &[char]) -> bool {
+    let (mut pi, mut ni) = (0, 0);
+    // Position after the last star, and the name position it resumes from
+    let mut star: Option<(usize, usize)> = None;
+    while ni < n.len() {
+        if p.get(pi) == Some(&'*') {
+            pi += 1;
+            star = Some((pi, ni));
+            continue;
+        }
+        if let Some(len) = match_one(&p[pi..], n[ni]) {
+            pi += len;
+            ni += 1;
+            continue;
+        }
+        let Some((after_star, from)) = star else {
+            return false;
+        };
+        pi = after_star;
+        ni = from + 1;
+        star = Some((after_star, ni));
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+/// Length of the token at the start of `p` (a character, `?` or a `[...]`
+/// set) if it matches `c`.
+fn match_one(p: &[char], c: char) -> Option<usize> {
+    match p.first()? {
+        '?' => Some(1),
+        '[' => {
+            let Some(close) = p.iter().skip(2).position(|&c| c == ']').map(|i| i + 2) else {
+                return (c == '[').then_some(1);
+            };
+            let (negate, set) = match p[1] {
+                '!' | '^' => (true, &p[2..close]),
+                _ => (false, &p[1..close]),
+            };
+            let mut found = false;
+            let mut i = 0;
+            while i < set.len() {
+                if i + 2 < set.len() && set[i + 1] == '-' {
+                    found |= set[i] <= c && c <= set[i + 2];
+                    i += 3;
+                } else {
+                    found |= set[i] == c;
+                    i += 1;
+                }
+            }
+            (found != negate).then_some(close + 1)
+        }
+        &literal => (literal == c).then_some(1),
+    }
+}
+
+/// The regular files matching `pattern`, sorted.
+fn expand(pattern: &str) -> Vec<PathBuf> {
+    let mut candidates = vec![PathBuf::new()];
+    for component in Path::new(pattern).components() {
+        let part = component.as_os_str().to_string_lossy();
+        let literal = !matches!(component, Component::Normal(_)) || !has_wildcards(&part);
+        candidates = candidates
+            .into_iter()
+            .flat_map(|dir| {
+                if literal {
+                    return vec![dir.join(component.as_os_str())];
+                }
+                let Ok(entries) = std::fs::read_dir(if dir.as_os_str().is_empty() {
+                    Path::new(".")
+                } else {
+                    &dir
+                }) else {
+                    return Vec::new();
+                };
+                entries
+                    .flatten()
+                    .filter(|e| wildcard_match(&part, &e.file_name().to_string_lossy()))
+                    .map(|e| dir.join(e.file_name()))
+                    .collect()
+            })
+            .collect();
+    }
+    let mut files: Vec<PathBuf> = candidates.into_iter().filter(|p| p.is_file()).collect();
+    files.sort();
+    files
+}
+
+/// The directory to watch for changes: the last one before any wildcard.
+pub fn base_dir(pattern: &str) -> PathBuf {
+    let mut base = PathBuf::new();
+    for component in Path::new(pattern).components() {
+        if has_wildcards(&component.as_os_str().to_string_lossy()) {
+            return base;
+        }
+        base.push(component);
+    }
+    base.parent().map(Path::to_path_buf).unwrap_or_default()
+}
+
+/// One followed file.
+struct TailedFile {
+    path: PathBuf,
+    /// Open handle and its (device, inode); `None` while the path is missing
+    handle: Option<(File, (u64, u64))>,
+    offset: u64,
+    partial: Vec<u8>,
+    /// The line being read passed `MAX_LINE_LENGTH`: drop it up to its newline
+    oversized: bool,
+    lines: u64,
+    last_line_at: Option<u64>,
+    rotations: u32,
+    error: Option<String>,
+    size: Option<u64>,
+    /// (device, inode) and final offset of a handle given up since the last
+    /// pass, for the tailer to remember
+    retired: Option<((u64, u64), u64)>,
+}
+
+impl TailedFile {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            handle: None,
+            offset: 0,
+            partial: Vec::new(),
+            oversized: false,
+            lines: 0,
+            last_line_at: None,
+            rotations: 0,
+            error: None,
+            size: None,
+            retired: None,
+        }
+    }
+
+    fn inode(&self) -> Option<(u64, u64)> {
+        self.handle.as_ref().map(|(_, inode)| *inode)
+    }
+
+    /// Open the file at `path`, from its end or from its start.
+    fn open(&mut self, from_end: bool) -> io::Result<()> {
+        let file = File::open(&self.path)?;
+        let meta = file.metadata()?;
+        self.size = Some(meta.len());
+        self.offset = if from_end { meta.len() } else { 0 };
+        self.partial.clear();
+        self.oversized = false;
+        self.handle = Some((file, (meta.dev(), meta.ino())));
+        Ok(())
+    }
+
+    /// Read whatever is new, following rotation. Returns true when the read
+    /// budget ran out.
+    fn poll(&mut self, out: &mut VecDeque<String>) -> bool {
+        match std::fs::metadata(&self.path) {
+            Ok(meta) => {
+                self.size = Some(meta.len());
+                let inode = (meta.dev(), meta.ino());
+                match self.inode() {
+                    None => {
+                        if let Err(e) = self.open(false) {
+                            self.error = Some(e.to_string());
+                            return false;
+                        }
+                    }
+                    Some(current) if current != inode => {
+                        // Renamed away and recreated: finish the old file.
+                        if self.read(out) {
+                            return true;
+                        }
+                        self.flush_partial(out);
+                        self.retired = Some((current, self.offset));
+                        self.rotations += 1;
+                        info!(
+                            "{} was rotated, following the new file",
+                            self.path.display()
+                        );
+                        if let Err(e) = self.open(false) {
+                            self.handle = None;
+                            self.error = Some(e.to_string());
+                            return false;
+                        }
+                    }
+                    Some(_) if meta.len() < self.offset => {
+                        self.rotations += 1;
+                        info!(
+                            "{} was truncated, reading from the start",
+                            self.path.display()
+                        );
+                        self.offset = 0;
+                        self.partial.clear();
+                        self.oversized = false;
+                    }
+                    Some(_) => {}
+                }
+                self.error = None;
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                // Renamed away, not recreated yet: the old handle may still
+                // hold unread lines.
+                self.size = None;
+                self.error = None;
+            }
+            Err(e) => {
+                self.error = Some(e.to_string());
+                return false;
+            }
+        }
+        self.read(out)
+    }
+
+    /// Read from the offset to the end of the open handle (within budget).
+    fn read(&mut self, out: &mut VecDeque<String>) -> bool {
+        let Some((file, inode)) = self.handle.take() else {
+            return false;
+        };
+        let more = self.read_from(&file, out);
+        self.handle = Some((file, inode));
+        more
+    }
+
+    fn read_from(&mut self, file: &File, out: &mut VecDeque<String>) -> bool {
+        let mut buf = vec![0u8; 64 * 1024];
+        let mut total = 0;
+        loop {
+            let n = match file.read_at(&mut buf, self.offset) {
+                Ok(n) => n,
+                Err(e) => {
+                    self.error = Some(e.to_string());
+                    return false;
+                }
+            };
+            if n == 0 {
+                return false;
+            }
+            self.offset += n as u64;
+            self.partial.extend_from_slice(&buf[..n]);
+            while let Some(end) = self.partial.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = self.partial.drain(..=end).collect();
+                if end > MAX_LINE_LENGTH && !self.oversized {
+                    self.drop_oversized();
+                }
+                if !std::mem::take(&mut self.oversized) {
+                    self.emit(&line[..end], out);
+                }
+            }
+            if self.partial.len() > MAX_LINE_LENGTH {
+                if !self.oversized {
+                    self.drop_oversized();
+                }
+                self.partial.clear();
+            }
+            total += n;
+            if total >= READ_BUDGET {
+                return true;
+            }
+        }
+    }
+
+    fn drop_oversized(&mut self) {
+        warn!(
+            "Dropping a line longer than {} bytes in {}",
+            MAX_LINE_LENGTH,
+            self.path.display()
+        );
+        self.oversized = true;
+    }
+
+    /// Give up a handle whose path is gone, once it has been read to its end.
+    fn retire(&mut self, out: &mut VecDeque<String>) {
+        self.flush_partial(out);
+        if let Some((_, inode)) = self.handle.take() {
+            self.retired = Some((inode, self.offset));
+            self.rotations += 1;
+        }
+    }
+
+    /// A rotated file's last line may lack its newline: it will get no more.
+    fn flush_partial(&mut self, out: &mut VecDeque<String>) {
+        if std::mem::take(&mut self.oversized) {
+            self.partial.clear();
+        } else if !self.partial.is_empty() {
+            let line = std::mem::take(&mut self.partial);
+            self.emit(&line, out);
+        }
+    }
+
+    fn emit(&mut self, line: &[u8], out: &mut VecDeque<String>) {
+        let line = String::from_utf8_lossy(line);
+        out.push_back(line.strip_suffix('\r').unwrap_or(&line).to_string());
+        self.lines += 1;
+        self.last_line_at = Some(now_millis());
+    }
+
+    fn status(&self) -> FileStatus {
+        let state = if self.error.is_some() {
+            FileState::Error
+        } else if self.size.is_none() {
+            FileState::Missing
+        } else {
+            FileState::Tailing
+        };
+        FileStatus {
+            path: self.path.display().to_string(),
+            state,
+            error: self.error.clone(),
+            size: self.size,
+            offset: self.offset,
+            lines: self.lines,
+            last_line_at: self.last_line_at,
+            rotations: self.rotations,
+        }
+    }
+}
+
+/// Every file matching a path or glob. Its methods block on file IO.
+struct Tail {
+    pattern: String,
+    files: Vec<TailedFile>,
+    status: TailStatus,
+    wake: Arc<Notify>,
+    /// Kept alive for its change notifications; `None` until the base
+    /// directory exists
+    watcher: Option<RecommendedWatcher>,
+    /// Rotated-away files and how far they were read, newest last
+    retired: VecDeque<((u64, u64), u64)>,
+    last_rescan: Instant,
+}
+
+impl Tail {
+    /// Track `pattern` from the current end of its files.
+    fn new(pattern: &str, status: TailStatus, wake: Arc<Notify>) -> Self {
+        let mut tail = Self {
+            pattern: pattern.to_string(),
+            files: Vec::new(),
+            status,
+            wake,
+            watcher: None,
+            retired: VecDeque::new(),
+            last_rescan: Instant::now(),
+        };
+        tail.rescan(true);
+        tail.publish();
+        info!("Watching {} ({} files)", pattern, tail.files.len());
+        tail
+    }
+
+    fn watch(&mut self) {
+        let dir = base_dir(&self.pattern);
+        if self.watcher.is_some() || !dir.is_dir() {
+            return;
+        }
+        let wake = self.wake.clone();
+        let watcher = notify::recommended_watcher(move |_: notify::Result<notify::Event>| {
+            wake.notify_one();
+        })
+        .and_then(|mut w| w.watch(&dir, RecursiveMode::NonRecursive).map(|_| w));
+        match watcher {
+            Ok(w) => self.watcher = Some(w),
+            Err(e) => warn!("Cannot watch {} for changes, polling: {}", dir.display(), e),
+        }
+    }
+
+    /// Track newly matching files. A literal path is tracked even while
+    /// missing, so its status says so.
+    fn rescan(&mut self, at_start: bool) {
+        self.last_rescan = Instant::now();
+        self.watch();
+        let mut paths = expand(&self.pattern);
+        if paths.is_empty() && !has_wildcards(&self.pattern) {
+            paths.push(PathBuf::from(&self.pattern));
+        }
+        for path in paths {
+            if self.files.iter().any(|f| f.path == path) {
+                continue;
+            }
+            // A rotated file showing up under a matching name is one we
+            // already follow.
+            let inode = std::fs::metadata(&path).ok().map(|m| (m.dev(), m.ino()));
+            if inode.is_some() && self.files.iter().any(|f| f.inode() == inode) {
+                continue;
+            }
+            let resume = self
+                .retired
+                .iter()
+                .find(|(retired, _)| Some(*retired) == inode)
+                .map(|(_, offset)| *offset);
+            let mut file = TailedFile::new(path);
+            if (at_start || resume.is_some()) && inode.is_some() {
+                match file.open(true) {
+                    Ok(()) => file.offset = resume.unwrap_or(file.offset),
+                    Err(e) => file.error = Some(e.to_string()),
+                }
+            }
+            self.files.push(file);
+        }
+        // Files of a glob that are gone and fully read are dropped.
+        if has_wildcards(&self.pattern) {
+            self.files.retain(|f| f.path.exists() || f.handle.is_some());
+        }
+    }
+
+    /// Read new lines from every file into `out`. Returns true when a file
+    /// stopped on its budget.
+    fn read_all(&mut self, out: &mut VecDeque<String>) -> bool {
+        let mut any_behind = false;
+        for file in &mut self.files {
+            let behind = file.poll(out);
+            // Renamed away and fully read: nothing more will come from it.
+            if file.size.is_none() && !behind {
+                file.retire(out);
+            }
+            if let Some(retired) = file.retired.take() {
+                if self.retired.len() == MAX_RETIRED {
+                    self.retired.pop_front();
+                }
+                self.retired.push_back(retired);
+            }
+            any_behind |= behind;
+        }
+        self.publish();
+        any_behind
+    }
+
+    fn publish(&self) {
+        let mut status: Vec<FileStatus> = self.files.iter().map(TailedFile::status).collect();
+        if status.is_empty() {
+            status.push(TailedFile::new(PathBuf::from(&self.pattern)).status());
+        }
+        *self.status.lock().unwrap() = status;
+    }
+}
+
+/// Tails every file matching a path or glob. Reads happen on the blocking
+/// pool, the `Tail` moving there and back for each pass.
+pub struct FileTailer {
+    /// None while a pass runs, and for good if one panicked
+    tail: Option<Tail>,
+    wake: Arc<Notify>,
+    pending: VecDeque<String>,
+    behind: bool,
+}
+
+impl FileTailer {
+    /// Start tailing `pattern` from the current end of its files.
+    pub async fn new(pattern: &str, status: TailStatus) -> Self {
+        let wake = Arc::new(Notify::new());
+        let (pattern, notify) = (pattern.to_string(), wake.clone());
+        let tail = tokio::task::spawn_blocking(move || Tail::new(&pattern, status, notify))
+            .await
+            .map_err(|e| warn!("Tailer stopped: {}", e))
+            .ok();
+        Self {
+            tail,
+            wake,
+            pending: VecDeque::new(),
+            behind: false,
+        }
+    }
+
+    /// One pass on the blocking pool: rescan when due, then read every file.
+    async fn pass(&mut self) -> Option<()> {
+        let mut tail = self.tail.take()?;
+        let task = tokio::task::spawn_blocking(move || {
+            if tail.last_rescan.elapsed() >= RESCAN_INTERVAL {
+                tail.rescan(false);
+            }
+            let mut pending = VecDeque::new();
+            let behind = tail.read_all(&mut pending);
+            (tail, pending, behind)
+        });
+        match task.await {
+            Ok((tail, pending, behind)) => {
+                self.tail = Some(tail);
+                self.pending = pending;
+                self.behind = behind;
+                Some(())
+            }
+            Err(e) => {
+                warn!("Tailer stopped: {}", e);
+                None
+            }
+        }
+    }
+}
+
+impl LogSource for FileTailer {
+    async fn next_line(&mut self) -> Option<String> {
+        loop {
+            if let Some(line) = self.pending.pop_front() {
+                return Some(line);
+            }
+            if !self.behind {
+                tokio::select! {
+                    _ = self.wake.notified() => {}
+                    _ = tokio::time::sleep(RESCAN_INTERVAL) => {}
+                }
+            }
+            self.pass().await?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn wildcards_match_like_a_shell() {
+        assert!(wildcard_match("*access.log", "shop-access.log"));
+        assert!(wildcard_match("*access.log", "access.log"));
+        assert!(!wildcard_match("*access.log", "access.log.1"));
+        assert!(!wildcard_match("*.log", ".hidden.log"));
+        assert!(wildcard_match("app-?.log", "app-1.log"));
+        assert!(!wildcard_match("app-?.log", "app-10.log"));
+        assert!(wildcard_match("app-[0-9].log", "app-7.log"));
+        assert!(!wildcard_match("app-[!0-9].log", "app-7.log"));
+        assert!(wildcard_match("app-[ab].log", "app-b.log"));
+        assert!(wildcard_match("*a*b*", "xaxxbx"));
+        assert!(!wildcard_match("*a*b", "xaxxbx"));
+        assert!(wildcard_match("[a", "[a"));
+
+        // Stars that all fail to match late in the name would take
+        // exponential time with a recursive matcher.
+        let name = "a".repeat(200);
+        assert!(!wildcard_match(&format!("{}b", "*a".repeat(20)), &name));
+    }
+
+    #[test]
+    fn oversized_lines_are_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        std::fs::write(&path, "").unwrap();
+        let mut file = TailedFile::new(path.clone());
+        file.open(true).unwrap();
+        let mut out = VecDeque::new();
+
+        let long = "x".repeat(3 * MAX_LINE_LENGTH);
+        std::fs::write(&path, &long).unwrap();
+        file.poll(&mut out);
+        assert!(file.partial.len() <= MAX_LINE_LENGTH, "buffer not capped");
+        std::fs::write(&path, format!("{long}tail\nnext\n{long}\nlast\n")).unwrap();
+        file.poll(&mut out);
+        assert_eq!(out.drain(..).collect::<Vec<_>>(), ["next", "last"]);
+    }
+
+    #[test]
+    fn globs_expand_across_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        for path in [
+            "a/access.log",
+            "b/access.log",
+            "b/error.log",
+            "c/x/access.log",
+        ] {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        let root = dir.path().to_str().unwrap();
+        let found = expand(&format!("{root}/*/access.log"));
+        assert_eq!(
+            found,
+            vec![
+                dir.path().join("a/access.log"),
+                dir.path().join("b/access.log")
+            ]
+        );
+        assert_eq!(
+            expand(&format!("{root}/b/error.log")),
+            vec![dir.path().join("b/error.log")]
+        );
+        assert!(expand(&format!("{root}/b/missing.log")).is_empty());
+        assert_eq!(base_dir(&format!("{root}/*/access.log")), dir.path());
+        assert_eq!(
+            base_dir(&format!("{root}/b/error.log")),
+            dir.path().join("b")
+        );
+    }
+
+    #[test]
+    fn partial_lines_wait_and_rotation_is_followed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        std::fs::write(&path, "old line\n").unwrap();
+        let mut file = TailedFile::new(path.clone());
+        file.open(true).unwrap();
+        let mut out = VecDeque::new();
+        let append = |text: &str| {
+            let mut f = std::fs::OpenOptions::new()
+                .append(true)
+                .open(&path)
+                .unwrap();
+            f.write_all(text.as_bytes()).unwrap();
+        };
+
+        append("first");
+        file.poll(&mut out);
+        assert!(out.is_empty(), "partial line emitted");
+        append(" half\r\nsecond\n");
+        file.poll(&mut out);
+        assert_eq!(out.drain(..).collect::<Vec<_>>(), ["first half", "second"]);
+
+        // copytruncate
+        std::fs::write(&path, "").unwrap();
+        file.poll(&mut out);
+        append("after truncate\n");
+        file.poll(&mut out);
+        assert_eq!(out.drain(..).collect::<Vec<_>>(), ["after truncate"]);
+
+        // rename rotation, with a line still unread in the old file
+        append("last of old");
+        std::fs::rename(&path, dir.path().join("app.log.1")).unwrap();
+        std::fs::write(&path, "new file\n").unwrap();
+        file.poll(&mut out);
+        assert_eq!(
+            out.drain(..).collect::<Vec<_>>(),
+            ["last of old", "new file"]
+        );
+        assert_eq!(file.rotations, 2);
+        assert_eq!(file.lines, 5);
+    }
+
+    #[test]
+    fn glob_resumes_a_rotated_file_under_its_new_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        std::fs::write(&path, "before start\n").unwrap();
+        let status = TailStatus::default();
+        let pattern = format!("{}/*.log*", dir.path().display());
+        let mut tail = Tail::new(&pattern, status.clone(), Arc::new(Notify::new()));
+        let mut pending = VecDeque::new();
+        let append = |path: &Path, text: &str| {
+            let mut f = std::fs::OpenOptions::new()
+                .append(true)
+                .open(path)
+                .unwrap();
+            f.write_all(text.as_bytes()).unwrap();
+        };
+
+        append(&path, "one\n");
+        std::fs::rename(&path, dir.path().join("app.log.1")).unwrap();
+        std::fs::write(&path, "two\n").unwrap();
+        tail.rescan(false);
+        tail.read_all(&mut pending);
+        tail.rescan(false);
+        append(&dir.path().join("app.log.1"), "late\n");
+        tail.read_all(&mut pending);
+
+        assert_eq!(
+            pending.drain(..).collect::<Vec<_>>(),
+            ["one", "two", "late"]
+        );
+        let status = status.lock().unwrap();
+        assert_eq!(status.len(), 2);
+        assert!(status.iter().all(|f| f.state == FileState::Tailing));
+    }
+}
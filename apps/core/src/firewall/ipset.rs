@@ -0,0 +1,83 @@
+//! Thin wrapper around the `ipset` binary, used by the iptables backend in
+//! ipset mode: each config chain then holds a single `--match-set` rule and
+//! bans become set members, so a ban costs the same with ten or ten thousand
+//! IPs already banned.
+
+use crate::ban_target::BanTarget;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+#[derive(Clone)]
+pub struct Ipset {
+    /// The `ipset` binary; `BANALIZE_IPSET_BIN` overrides it (the e2e suite
+    /// points it at the fake).
+    bin: String,
+}
+
+impl Ipset {
+    pub fn new() -> Result<Self, String> {
+        let bin = std::env::var("BANALIZE_IPSET_BIN")
+            .ok()
+            .filter(|b| !b.is_empty())
+            .unwrap_or_else(|| "ipset".to_string());
+        let ipset = Self { bin };
+        ipset
+            .run(&["--version"], None)
+            .map_err(|e| format!("Failed to run {}: {}", ipset.bin, e))?;
+        Ok(ipset)
+    }
+
+    fn run(&self, args: &[&str], stdin: Option<&str>) -> Result<String, String> {
+        let mut child = Command::new(&self.bin)
+            .args(args)
+            .stdin(if stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| e.to_string())?;
+        if let (Some(input), Some(mut pipe)) = (stdin, child.stdin.take()) {
+            pipe.write_all(input.as_bytes()).map_err(|e| e.to_string())?;
+        }
+        let output = child.wait_with_output().map_err(|e| e.to_string())?;
+        if output.status.success() {
+            Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+        } else {
+            Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+        }
+    }
+
+    /// Create a `hash:net` set for one family (`inet` / `inet6`), tolerating
+    /// an existing one. `hash:net` holds single hosts and CIDR bans alike.
+    pub fn create(&self, set: &str, family: &str) -> Result<(), String> {
+        self.run(&["create", set, "hash:net", "family", family, "-exist"], None)
+            .map(|_| ())
+    }
+
+    pub fn destroy(&self, set: &str) -> Result<(), String> {
+        self.run(&["destroy", set], None).map(|_| ())
+    }
+
+    /// Add a member; already being a member is not an error.
+    pub fn add(&self, set: &str, ip: &BanTarget) -> Result<(), String> {
+        self.run(&["add", set, &ip.to_string(), "-exist"], None)
+            .map(|_| ())
+    }
+
+    /// Remove a member; not being a member (already removed) is not an error.
+    pub fn del(&self, set: &str, ip: &BanTarget) -> Result<(), String> {
+        self.run(&["del", set, &ip.to_string(), "-exist"], None)
+            .map(|_| ())
+    }
+
+    /// Add many members in a single `ipset restore` invocation.
+    pub fn add_batch(&self, set: &str, ips: &[BanTarget]) -> Result<(), String> {
+        let script: String = ips.iter().map(|ip| format!("add {} {}\n", set, ip)).collect();
+        self.run(&["restore", "-exist"], Some(&script)).map(|_| ())
+    }
+
+    /// Names of every set on the host.
+    pub fn list_names(&self) -> Result<Vec<String>, String> {
+        let out = self.run(&["list", "-n"], None)?;
+        Ok(out.lines().map(str::to_string).collect())
+    }
+}
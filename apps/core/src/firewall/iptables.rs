@@ -0,0 +1,447 @@
+//! The iptables backend: a `banalize` parent chain linked from the configured
+//! chain, with one child chain per config holding its DROP rules. IPv4 and
+//! IPv6 are driven through `iptables` and `ip6tables` respectively.
+//!
+//! In ipset mode a child chain instead holds a single `--match-set` rule
+//! against the config's ipset, and bans are set members.
+
+use super::ipset::Ipset;
+use super::{chain_name, sweep_ipsets, FirewallBackend, CHILD_PREFIX};
+use std::collections::HashSet;
+use crate::ban_target::BanTarget;
+use tracing::{error, info, warn};
+
+const TABLE: &str = "filter";
+const CHAIN_NAME: &str = "banalize";
+
+/// One address family's view of the firewall: the `iptables` or `ip6tables`
+/// binary plus the child chains created in it. The chain layout is identical in
+/// both families; a ban simply lands in the family of the banned address.
+struct FamilyTables {
+    ipt: ::iptables::IPTables,
+    /// `iptables` / `ip6tables`, for log messages.
+    label: &'static str,
+    /// Child chains created during this process lifetime. The actor is the
+    /// single owner of iptables state, so this set is authoritative.
+    chains: HashSet<String>,
+    /// Set in ipset mode; each child chain then matches against one set.
+    ipset: Option<Ipset>,
+    /// `v4` / `v6`: suffix of the config's set name in this family.
+    set_suffix: &'static str,
+}
+
+impl FamilyTables {
+    /// Build the handle for one family. `bin_var` overrides the binary (the
+    /// e2e suite points it at the fake); otherwise the crate probes the real
+    /// tool, which fails when it isn't installed.
+    fn new(is_ipv6: bool, bin_var: &str, ipset: Option<Ipset>) -> Result<Self, String> {
+        let ipt = match std::env::var(bin_var) {
+            Ok(bin_path) if !bin_path.is_empty() => {
+                // Leak the string to produce a &'static str required by IPTables::cmd.
+                // Acceptable since Firewall is created once per process lifetime.
+                let leaked: &'static str = Box::leak(bin_path.into_boxed_str());
+                ::iptables::IPTables {
+                    cmd: leaked,
+                    has_check: false, // use -S list check; fake binary returns empty → rule "not found"
+                    has_wait: true,   // avoids lock file at /var/run/xtables_old.lock (requires root)
+                    is_numeric: false,
+                }
+            }
+            _ => ::iptables::new(is_ipv6).map_err(|e| e.to_string())?,
+        };
+        Ok(Self {
+            ipt,
+            label: if is_ipv6 { "ip6tables" } else { "iptables" },
+            chains: HashSet::new(),
+            ipset,
+            set_suffix: if is_ipv6 { "v6" } else { "v4" },
+        })
+    }
+
+    /// The config's ipset in this family. Chain names are at most 28
+    /// characters, so the name stays within ipset's 31-character limit.
+    fn set_name(&self, config_id: &str) -> String {
+        format!("{}-{}", chain_name(config_id), self.set_suffix)
+    }
+
+    /// Destroy a set, logging failures (a set still referenced by a rule
+    /// cannot be destroyed).
+    fn destroy_set(&self, ipset: &Ipset, set: &str) {
+        match ipset.destroy(set) {
+            Ok(_) => info!("Destroyed ipset: {}", set),
+            Err(e) => warn!("Failed to destroy ipset {}: {}", set, e),
+        }
+    }
+
+    /// Create and link the parent chain, flush it (dropping any stale jumps to
+    /// child chains), then sweep orphaned child chains left behind by a
+    /// crashed run.
+    fn init(&mut self, link_chain: &str) -> Result<(), String> {
+        info!("Initializing {} chain: {}", self.label, CHAIN_NAME);
+
+        // Create chain (ignore error if it already exists)
+        let create_cmd = format!("-N {}", CHAIN_NAME);
+        match self.ipt.execute(TABLE, &create_cmd) {
+            Ok(_) => info!("Created chain: {}", CHAIN_NAME),
+            Err(e) => {
+                let err_str = e.to_string();
+                if err_str.contains("already exists") || err_str.contains("File exists") {
+                    info!("Chain {} already exists", CHAIN_NAME);
+                } else {
+                    warn!("Failed to create chain (may already exist): {}", e);
+                }
+            }
+        }
+
+        // Link chain (insert jump rule at the beginning)
+        let rule = format!("-j {}", CHAIN_NAME);
+        match self.ipt.insert_unique(TABLE, link_chain, &rule, 1) {
+            Ok(_) => info!("Linked chain {} to {}", CHAIN_NAME, link_chain),
+            Err(e) => {
+                // Check if rule already exists
+                if let Ok(exists) = self.ipt.exists(TABLE, link_chain, &rule) {
+                    if exists {
+                        info!("Chain {} already linked to {}", CHAIN_NAME, link_chain);
+                    } else {
+                        // Try append as fallback
+                        if let Err(e2) = self.ipt.append(TABLE, link_chain, &rule) {
+                            return Err(format!("Failed to link chain: {} / {}", e, e2));
+                        } else {
+                            info!("Linked chain {} to {} (via append)", CHAIN_NAME, link_chain);
+                        }
+                    }
+                } else {
+                    return Err(format!("Failed to link chain: {}", e));
+                }
+            }
+        }
+
+        // Flush chain (clears existing rules, including jumps to child chains)
+        let flush_cmd = format!("-F {}", CHAIN_NAME);
+        match self.ipt.execute(TABLE, &flush_cmd) {
+            Ok(_) => info!("Flushed chain: {}", CHAIN_NAME),
+            Err(e) => {
+                warn!("Failed to flush chain (may be empty): {}", e);
+            }
+        }
+
+        // Orphan sweep: child chains from a crashed run are now unreferenced
+        // (the parent flush above removed their jump rules) — delete them so
+        // restore starts from a clean slate.
+        if let Ok(existing) = self.ipt.list_chains(TABLE) {
+            for chain in existing.iter().filter(|c| c.starts_with(CHILD_PREFIX)) {
+                let _ = self.ipt.execute(TABLE, &format!("-F {}", chain));
+                match self.ipt.execute(TABLE, &format!("-X {}", chain)) {
+                    Ok(_) => info!("Swept orphaned chain: {}", chain),
+                    Err(e) => warn!("Failed to sweep orphaned chain {}: {}", chain, e),
+                }
+            }
+        }
+
+        // Their sets are unreferenced now too.
+        sweep_ipsets(self.ipset.clone(), &format!("-{}", self.set_suffix));
+
+        Ok(())
+    }
+
+    /// Flush the parent (unreferencing the children), delete every child
+    /// chain, then unlink and delete the parent.
+    fn cleanup(&mut self, link_chain: &str) {
+        info!("Cleaning up {} chain: {}", self.label, CHAIN_NAME);
+
+        // Flush the parent first: a chain cannot be deleted while referenced.
+        let flush_cmd = format!("-F {}", CHAIN_NAME);
+        if let Err(e) = self.ipt.execute(TABLE, &flush_cmd) {
+            warn!("Failed to flush chain: {}", e);
+        }
+
+        for chain in std::mem::take(&mut self.chains) {
+            let _ = self.ipt.execute(TABLE, &format!("-F {}", chain));
+            if let Err(e) = self.ipt.execute(TABLE, &format!("-X {}", chain)) {
+                warn!("Failed to delete chain {}: {}", chain, e);
+            }
+            if let Some(ipset) = &self.ipset {
+                self.destroy_set(ipset, &format!("{}-{}", chain, self.set_suffix));
+            }
+        }
+
+        // Unlink chain (remove jump rule)
+        let rule = format!("-j {}", CHAIN_NAME);
+        if let Err(e) = self.ipt.delete(TABLE, link_chain, &rule) {
+            warn!("Failed to unlink chain: {}", e);
+        } else {
+            info!("Unlinked chain {} from {}", CHAIN_NAME, link_chain);
+        }
+
+        // Delete chain
+        let delete_cmd = format!("-X {}", CHAIN_NAME);
+        match self.ipt.execute(TABLE, &delete_cmd) {
+            Ok(_) => info!("Deleted chain: {}", CHAIN_NAME),
+            Err(e) => {
+                warn!("Failed to delete chain: {}", e);
+            }
+        }
+    }
+
+    /// Create a config's child chain and link it from the parent, once per
+    /// process lifetime. Tolerates pre-existing chains like the parent init.
+    fn ensure_chain(&mut self, config_id: &str) -> String {
+        let chain = chain_name(config_id);
+        if self.chains.contains(&chain) {
+            return chain;
+        }
+
+        match self.ipt.execute(TABLE, &format!("-N {}", chain)) {
+            Ok(_) => info!("Created chain: {}", chain),
+            Err(e) => {
+                let err_str = e.to_string();
+                if err_str.contains("already exists") || err_str.contains("File exists") {
+                    info!("Chain {} already exists", chain);
+                } else {
+                    warn!("Failed to create chain {} (may already exist): {}", chain, e);
+                }
+            }
+        }
+
+        // In ipset mode the chain's only rule matches the config's set; the
+        // set must exist before a rule can reference it.
+        if let Some(ipset) = &self.ipset {
+            let set = self.set_name(config_id);
+            let family = if self.set_suffix == "v6" { "inet6" } else { "inet" };
+            match ipset.create(&set, family) {
+                Ok(_) => info!("Created ipset: {}", set),
+                Err(e) => error!("Failed to create ipset {}: {}", set, e),
+            }
+            let rule = format!("-m set --match-set {} src -j DROP", set);
+            let present = matches!(self.ipt.exists(TABLE, &chain, &rule), Ok(true));
+            if !present {
+                if let Err(e) = self.ipt.append(TABLE, &chain, &rule) {
+                    error!("Failed to add set rule to chain {}: {}", chain, e);
+                }
+            }
+        }
+
+        let link = format!("-j {}", chain);
+        let linked = matches!(self.ipt.exists(TABLE, CHAIN_NAME, &link), Ok(true));
+        if !linked {
+            if let Err(e) = self.ipt.append(TABLE, CHAIN_NAME, &link) {
+                error!("Failed to link chain {} to {}: {}", chain, CHAIN_NAME, e);
+            } else {
+                info!("Linked chain {} to {}", chain, CHAIN_NAME);
+            }
+        }
+
+        self.chains.insert(chain.clone());
+        chain
+    }
+
+    fn deny(&mut self, config_id: &str, ip: &BanTarget) -> Result<(), String> {
+        let chain = self.ensure_chain(config_id);
+        if let Some(ipset) = &self.ipset {
+            let set = self.set_name(config_id);
+            return match ipset.add(&set, ip) {
+                Ok(_) => {
+                    info!("Added IP {} to ipset {}", ip, set);
+                    Ok(())
+                }
+                Err(e) => {
+                    error!("Failed to add IP {} to ipset {}: {}", ip, set, e);
+                    Err(format!("Failed to deny IP: {}", e))
+                }
+            };
+        }
+        let rule = format!("-s {} -j DROP", ip);
+
+        // Check if rule already exists
+        if let Ok(exists) = self.ipt.exists(TABLE, &chain, &rule) {
+            if exists {
+                return Ok(()); // Rule already exists
+            }
+        }
+
+        match self.ipt.append(TABLE, &chain, &rule) {
+            Ok(_) => {
+                info!("Added firewall rule to deny IP {} in {}", ip, chain);
+                Ok(())
+            }
+            Err(e) => {
+                error!("Failed to add firewall rule for IP {}: {}", ip, e);
+                Err(format!("Failed to deny IP: {}", e))
+            }
+        }
+    }
+
+    /// Deny many IPs at once: a single `ipset restore` in ipset mode, one
+    /// rule at a time otherwise.
+    fn deny_batch(&mut self, config_id: &str, ips: &[BanTarget]) -> Result<(), String> {
+        let Some(ipset) = self.ipset.clone() else {
+            let mut result = Ok(());
+            for ip in ips {
+                if let Err(e) = self.deny(config_id, ip) {
+                    result = result.and(Err(e));
+                }
+            }
+            return result;
+        };
+        self.ensure_chain(config_id);
+        let set = self.set_name(config_id);
+        match ipset.add_batch(&set, ips) {
+            Ok(_) => {
+                info!("Added {} IPs to ipset {}", ips.len(), set);
+                Ok(())
+            }
+            Err(e) => {
+                error!("Failed to batch-add {} IPs to ipset {}: {}", ips.len(), set, e);
+                Err(format!("Failed to deny IPs: {}", e))
+            }
+        }
+    }
+
+    fn allow(&self, config_id: &str, ip: &BanTarget) -> Result<(), String> {
+        if let Some(ipset) = &self.ipset {
+            let set = self.set_name(config_id);
+            return match ipset.del(&set, ip) {
+                Ok(_) => {
+                    info!("Removed IP {} from ipset {}", ip, set);
+                    Ok(())
+                }
+                Err(e) => {
+                    warn!("Failed to remove IP {} from ipset {}: {}", ip, set, e);
+                    Err(format!("Failed to allow IP: {}", e))
+                }
+            };
+        }
+        let chain = chain_name(config_id);
+        let rule = format!("-s {} -j DROP", ip);
+
+        match self.ipt.delete(TABLE, &chain, &rule) {
+            Ok(_) => {
+                info!("Removed firewall rule for IP {} from {}", ip, chain);
+                Ok(())
+            }
+            Err(e) => {
+                warn!("Failed to remove firewall rule for IP {}: {}", ip, e);
+                Err(format!("Failed to allow IP: {}", e))
+            }
+        }
+    }
+
+    fn remove_chain(&mut self, config_id: &str) {
+        let chain = chain_name(config_id);
+        if !self.chains.remove(&chain) {
+            return;
+        }
+
+        let _ = self.ipt.execute(TABLE, &format!("-F {}", chain));
+        if let Err(e) = self.ipt.delete(TABLE, CHAIN_NAME, &format!("-j {}", chain)) {
+            warn!("Failed to unlink chain {}: {}", chain, e);
+        }
+        match self.ipt.execute(TABLE, &format!("-X {}", chain)) {
+            Ok(_) => info!("Removed chain: {}", chain),
+            Err(e) => warn!("Failed to delete chain {}: {}", chain, e),
+        }
+        if let Some(ipset) = &self.ipset {
+            self.destroy_set(ipset, &self.set_name(config_id));
+        }
+    }
+}
+
+pub struct IptablesBackend {
+    v4: FamilyTables,
+    /// `None` when `ip6tables` is unavailable: IPv6 bans are then refused
+    /// (and logged) while IPv4 keeps working.
+    v6: Option<FamilyTables>,
+    link_chain: String, // The chain to link to (e.g., INPUT, FORWARD)
+}
+
+impl IptablesBackend {
+    /// `ipset` selects ipset mode for both families.
+    pub fn new(link_chain: String, ipset: Option<Ipset>) -> Result<Self, String> {
+        let v4 = FamilyTables::new(false, "BANALIZE_IPTABLE_BIN", ipset.clone())
+            .map_err(|e| format!("Failed to create iptables instance: {}", e))?;
+        let v6 = match FamilyTables::new(true, "BANALIZE_IP6TABLE_BIN", ipset) {
+            Ok(family) => Some(family),
+            Err(e) => {
+                warn!("ip6tables unavailable, IPv6 bans disabled: {}", e);
+                None
+            }
+        };
+        Ok(Self { v4, v6, link_chain })
+    }
+
+    /// The family a rule for `ip` belongs in.
+    fn family(&mut self, ip: &BanTarget) -> Result<&mut FamilyTables, String> {
+        if ip.is_ipv4() {
+            Ok(&mut self.v4)
+        } else {
+            self.v6
+                .as_mut()
+                .ok_or_else(|| "ip6tables unavailable".to_string())
+        }
+    }
+}
+
+impl FirewallBackend for IptablesBackend {
+    /// Initialize both families. An IPv4 failure is returned to the caller;
+    /// an IPv6 failure only disables IPv6 bans, since many hosts have no IPv6
+    /// filtering at all.
+    fn init(&mut self) -> Result<(), String> {
+        let result = self.v4.init(&self.link_chain);
+        if let Some(v6) = self.v6.as_mut() {
+            if let Err(e) = v6.init(&self.link_chain) {
+                warn!("Failed to initialize ip6tables, IPv6 bans disabled: {}", e);
+                self.v6 = None;
+            }
+        }
+        result
+    }
+
+    /// Tear down the chain set of every family.
+    fn cleanup(&mut self) {
+        self.v4.cleanup(&self.link_chain);
+        if let Some(v6) = self.v6.as_mut() {
+            v6.cleanup(&self.link_chain);
+        }
+    }
+
+    /// Rules carry no expiry here: the cleaner lifts them with `allow`.
+    fn deny(&mut self, config_id: &str, ip: &BanTarget, _timeout_ms: u64) -> Result<(), String> {
+        match self.family(ip) {
+            Ok(family) => family.deny(config_id, ip),
+            Err(e) => {
+                error!("Cannot deny IP {}: {}", ip, e);
+                Err(format!("Failed to deny IP: {}", e))
+            }
+        }
+    }
+
+    fn deny_batch(&mut self, config_id: &str, bans: &[(BanTarget, u64)]) -> Result<(), String> {
+        let (v4, v6): (Vec<BanTarget>, Vec<BanTarget>) =
+            bans.iter().map(|(ip, _)| *ip).partition(BanTarget::is_ipv4);
+        let mut result = Ok(());
+        for ips in [v4, v6].into_iter().filter(|ips| !ips.is_empty()) {
+            let outcome = self
+                .family(&ips[0])
+                .and_then(|family| family.deny_batch(config_id, &ips));
+            if let Err(e) = outcome {
+                error!("Cannot deny {} IPs for {}: {}", ips.len(), config_id, e);
+                result = result.and(Err(e));
+            }
+        }
+        result
+    }
+
+    fn allow(&mut self, config_id: &str, ip: &BanTarget) -> Result<(), String> {
+        self.family(ip)
+            .and_then(|family| family.allow(config_id, ip))
+    }
+
+    /// Tear down a config's chain in every family: flush it, unlink it from
+    /// the parent and delete it. No-op where the chain was never created.
+    fn remove_config(&mut self, config_id: &str) {
+        self.v4.remove_chain(config_id);
+        if let Some(v6) = self.v6.as_mut() {
+            v6.remove_chain(config_id);
+        }
+    }
+}
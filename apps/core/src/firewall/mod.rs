@@ -0,0 +1,237 @@
+mod ipset;
+mod iptables;
+mod nftables;
+
+use self::ipset::Ipset;
+use crate::events::FirewallCommand;
+use crate::ban_target::BanTarget;
+use crate::health::Progress;
+use crate::metrics::Metrics;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::{broadcast, mpsc};
+use tracing::{info, warn};
+
+/// Prefix of per-config child chains, kept short so sanitized config ids fit
+/// within the iptables chain-name limit.
+const CHILD_PREFIX: &str = "bnz-";
+/// iptables chain names are limited to 28 characters.
+const MAX_CHAIN_LEN: usize = 28;
+
+/// Deterministic iptables chain name for a config. The nftables backend
+/// derives its per-config set names from it too.
+///
+/// Clean short ids stay readable (`bnz-cfg-ssh`). Ids that need sanitizing or
+/// truncation get an FNV-1a hash of the *original* id appended, so distinct
+/// ids can never collide after sanitization ("cfg a" vs "cfg-a") and the name
+/// is stable across restarts.
+pub fn chain_name(config_id: &str) -> String {
+    let sanitized: String = config_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+
+    let fits = CHILD_PREFIX.len() + sanitized.len() <= MAX_CHAIN_LEN;
+    if fits && sanitized == config_id {
+        return format!("{}{}", CHILD_PREFIX, sanitized);
+    }
+
+    let keep = MAX_CHAIN_LEN - CHILD_PREFIX.len() - 9; // room for "-XXXXXXXX"
+    let prefix: String = sanitized.chars().take(keep).collect();
+    format!("{}{}-{:08x}", CHILD_PREFIX, prefix, fnv1a32(config_id))
+}
+
+fn fnv1a32(input: &str) -> u32 {
+    let mut hash: u32 = 0x811c9dc5;
+    for byte in input.bytes() {
+        hash ^= byte as u32;
+        hash = hash.wrapping_mul(0x0100_0193);
+    }
+    hash
+}
+
+/// Destroy the per-config ipsets (`bnz-*<suffix>`) an earlier run left behind.
+/// Every backend sweeps them, since that run may have been in ipset mode; a
+/// host without the `ipset` binary has none. Sets still referenced by a rule
+/// cannot be destroyed and are only logged.
+fn sweep_ipsets(ipset: Option<Ipset>, suffix: &str) {
+    let Some(ipset) = ipset.or_else(|| Ipset::new().ok()) else {
+        return;
+    };
+    for set in ipset.list_names().unwrap_or_default() {
+        if set.starts_with(CHILD_PREFIX) && set.ends_with(suffix) {
+            match ipset.destroy(&set) {
+                Ok(_) => info!("Swept stale ipset: {}", set),
+                Err(e) => warn!("Failed to sweep stale ipset {}: {}", set, e),
+            }
+        }
+    }
+}
+
+/// A concrete firewall the actor drives. Implementations own all of their
+/// kernel state; every call arrives from the single actor task, in order.
+pub trait FirewallBackend: Send {
+    /// Set up the backend's own tables/chains, sweeping leftovers of a
+    /// crashed run.
+    fn init(&mut self) -> Result<(), String>;
+
+    /// Remove everything the backend created.
+    fn cleanup(&mut self);
+
+    /// Drop traffic from `ip` under a config. `timeout_ms` is the remaining
+    /// ban duration (0: permanent); backends with native expiry may use it as
+    /// a safety net in case the process dies before the cleaner lifts the ban.
+    fn deny(&mut self, config_id: &str, ip: &BanTarget, timeout_ms: u64) -> Result<(), String>;
+
+    /// Deny many IPs of one config at once (restore). Backends that can load
+    /// them in one go override this; errors are logged per IP either way.
+    fn deny_batch(&mut self, config_id: &str, bans: &[(BanTarget, u64)]) -> Result<(), String> {
+        let mut result = Ok(());
+        for (ip, timeout_ms) in bans {
+            if let Err(e) = self.deny(config_id, ip, *timeout_ms) {
+                result = result.and(Err(e));
+            }
+        }
+        result
+    }
+
+    /// Lift a ban created by `deny`.
+    fn allow(&mut self, config_id: &str, ip: &BanTarget) -> Result<(), String>;
+
+    /// Drop all state of a config (config deletion).
+    fn remove_config(&mut self, config_id: &str);
+}
+
+/// Build the backend named by `BANALIZE_CORE_FIREWALL_BACKEND`.
+pub fn backend_from_name(name: &str, link_chain: String) -> Result<Box<dyn FirewallBackend>, String> {
+    match name {
+        "iptables" => Ok(Box::new(self::iptables::IptablesBackend::new(link_chain, None)?)),
+        "ipset" => Ok(Box::new(self::iptables::IptablesBackend::new(
+            link_chain,
+            Some(self::ipset::Ipset::new()?),
+        )?)),
+        "nftables" => Ok(Box::new(self::nftables::NftablesBackend::new(link_chain)?)),
+        other => Err(format!(
+            "unknown firewall backend '{}' (expected iptables, ipset or nftables)",
+            other
+        )),
+    }
+}
+
+pub struct Firewall {
+    backend: Box<dyn FirewallBackend>,
+    metrics: Arc<Metrics>,
+    /// Outcome of `init`, kept until a successful re-init, and of each
+    /// command since, for `/api/health`.
+    progress: Arc<Progress>,
+}
+
+impl Firewall {
+    pub fn new(backend: Box<dyn FirewallBackend>, metrics: Arc<Metrics>, progress: Arc<Progress>) -> Self {
+        Self {
+            backend,
+            metrics,
+            progress,
+        }
+    }
+
+    pub fn init(&mut self) -> Result<(), String> {
+        let result = self.backend.init();
+        self.progress.init(result.clone());
+        result
+    }
+
+    /// Run the firewall actor: the single owner of the firewall state.
+    ///
+    /// All ban/unban mutations arrive as `FirewallCommand`s over a lossless
+    /// mpsc channel, so a banned IP can never be silently dropped (unlike the
+    /// lossy notification bus). On shutdown — or once every sender is gone —
+    /// the backend removes everything it created.
+    pub async fn run(
+        mut self,
+        mut cmd_rx: mpsc::Receiver<FirewallCommand>,
+        mut shutdown_rx: broadcast::Receiver<()>,
+    ) {
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.recv() => {
+                    info!("Firewall actor received shutdown signal");
+                    break;
+                }
+                cmd = cmd_rx.recv() => {
+                    let started = Instant::now();
+                    self.progress.start();
+                    let (command, result) = match cmd {
+                        Some(FirewallCommand::Deny { config_id, ip, timeout_ms }) => {
+                            // Errors are logged inside; firewall failures must
+                            // never block detection.
+                            ("deny", self.backend.deny(&config_id, &ip, timeout_ms))
+                        }
+                        Some(FirewallCommand::DenyBatch { config_id, bans }) => {
+                            ("deny_batch", self.backend.deny_batch(&config_id, &bans))
+                        }
+                        Some(FirewallCommand::Allow { config_id, ip }) => {
+                            let result = self.backend.allow(&config_id, &ip);
+                            if let Err(e) = &result {
+                                warn!("Failed to remove firewall rule for {}: {}", ip, e);
+                            }
+                            ("allow", result)
+                        }
+                        Some(FirewallCommand::RemoveChain { config_id }) => {
+                            self.backend.remove_config(&config_id);
+                            ("remove_chain", Ok(()))
+                        }
+                        None => {
+                            info!("Firewall actor: all senders dropped, shutting down");
+                            break;
+                        }
+                    };
+                    self.metrics.observe_firewall(command, started.elapsed());
+                    self.progress.finish(result);
+                }
+            }
+        }
+
+        self.backend.cleanup();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clean_short_ids_pass_through() {
+        assert_eq!(chain_name("cfg-ssh"), "bnz-cfg-ssh");
+        assert_eq!(chain_name("nginx_404.v2"), "bnz-nginx_404.v2");
+    }
+
+    #[test]
+    fn sanitized_ids_get_a_hash_suffix() {
+        let name = chain_name("cfg a");
+        assert!(name.starts_with("bnz-cfg-a-"), "{}", name);
+        assert!(name.len() <= MAX_CHAIN_LEN);
+        // Distinct ids that sanitize identically must not collide.
+        assert_ne!(chain_name("cfg a"), chain_name("cfg-a"));
+        assert_ne!(chain_name("cfg a"), chain_name("cfg.a"));
+    }
+
+    #[test]
+    fn long_ids_are_truncated_with_hash() {
+        let long = "a-very-long-config-identifier-beyond-the-limit";
+        let name = chain_name(long);
+        assert!(name.len() <= MAX_CHAIN_LEN, "{}", name);
+        assert_ne!(name, chain_name(&long[..long.len() - 1]));
+    }
+
+    #[test]
+    fn chain_names_are_deterministic() {
+        assert_eq!(chain_name("héllo wörld"), chain_name("héllo wörld"));
+    }
+}
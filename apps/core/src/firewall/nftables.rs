@@ -0,0 +1,295 @@
+//! The nftables backend: an `inet banalize` table whose base chain hooks into
+//! the configured chain, plus one named set per config and address family
+//! (nftables sets are typed, so IPv4 and IPv6 cannot share one). A ban is a
+//! set element carrying a timeout, so bans still expire in the kernel if the
+//! process dies before the cleaner lifts them. Sets are interval sets so a
+//! manual CIDR ban is a single element too.
+
+use super::{chain_name, sweep_ipsets, FirewallBackend};
+use crate::ban_target::BanTarget;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::process::Command;
+use tracing::{error, info, warn};
+
+const FAMILY: &str = "inet";
+const TABLE: &str = "banalize";
+const CHAIN_NAME: &str = "banalize";
+
+/// Per-family suffix of a config's sets, with the set's element type.
+const SET_FAMILIES: [(&str, &str); 2] = [("v4", "ipv4_addr"), ("v6", "ipv6_addr")];
+
+/// A failed `nft` run: its exit status and what it printed on stderr.
+struct NftError {
+    status: Option<i32>,
+    stderr: String,
+}
+
+impl NftError {
+    /// The error class on nft's first stderr line: the kernel's `strerror`,
+    /// with nft's "Could not process rule:" prefix and hints stripped.
+    fn class(&self) -> Option<&str> {
+        let message = self.stderr.lines().next()?.strip_prefix("Error: ")?;
+        let message = message.strip_prefix("Could not process rule: ").unwrap_or(message);
+        message.split(';').next().map(str::trim)
+    }
+
+    /// The kernel answered ENOENT: the table, set or element does not exist.
+    fn is_missing(&self) -> bool {
+        self.status == Some(1) && self.class() == Some("No such file or directory")
+    }
+}
+
+impl fmt::Display for NftError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.stderr)
+    }
+}
+
+impl From<NftError> for String {
+    fn from(e: NftError) -> Self {
+        e.stderr
+    }
+}
+
+fn set_name(config_id: &str, suffix: &str) -> String {
+    format!("{}-{}", chain_name(config_id), suffix)
+}
+
+/// The config's set holding `ip`.
+fn set_for(config_id: &str, ip: &BanTarget) -> String {
+    set_name(config_id, if ip.is_ipv4() { "v4" } else { "v6" })
+}
+
+/// The netfilter hook standing in for an iptables built-in chain. nftables
+/// has no user chains to jump from, so only the built-ins can be targeted.
+fn hook_for(link_chain: &str) -> Result<&'static str, String> {
+    match link_chain.to_ascii_uppercase().as_str() {
+        "INPUT" => Ok("input"),
+        "FORWARD" => Ok("forward"),
+        "OUTPUT" => Ok("output"),
+        other => Err(format!("nftables backend cannot hook into chain '{}'", other)),
+    }
+}
+
+pub struct NftablesBackend {
+    /// The `nft` binary; `BANALIZE_NFT_BIN` overrides it (the e2e suite
+    /// points it at the fake).
+    bin: String,
+    hook: &'static str,
+    /// Configs whose sets and drop rules exist. The actor is the single owner
+    /// of the table, so this set is authoritative.
+    configs: BTreeSet<String>,
+}
+
+impl NftablesBackend {
+    pub fn new(link_chain: String) -> Result<Self, String> {
+        let bin = std::env::var("BANALIZE_NFT_BIN")
+            .ok()
+            .filter(|b| !b.is_empty())
+            .unwrap_or_else(|| "nft".to_string());
+        let backend = Self {
+            hook: hook_for(&link_chain)?,
+            bin,
+            configs: BTreeSet::new(),
+        };
+        backend
+            .nft("--version")
+            .map_err(|e| format!("Failed to run {}: {}", backend.bin, e))?;
+        Ok(backend)
+    }
+
+    /// Run one `nft` command. Names never contain whitespace (`chain_name`
+    /// sanitizes them), so splitting the command line is safe.
+    fn nft(&self, command: &str) -> Result<(), NftError> {
+        let output = Command::new(&self.bin)
+            .args(command.split_whitespace())
+            .output()
+            .map_err(|e| NftError {
+                status: None,
+                stderr: e.to_string(),
+            })?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(NftError {
+                status: output.status.code(),
+                stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            })
+        }
+    }
+
+    /// The drop rules of one config in the base chain.
+    fn add_rules(&self, config_id: &str) -> Result<(), String> {
+        let v4 = set_name(config_id, "v4");
+        let v6 = set_name(config_id, "v6");
+        self.nft(&format!("add rule {} {} {} ip saddr @{} drop", FAMILY, TABLE, CHAIN_NAME, v4))?;
+        self.nft(&format!("add rule {} {} {} ip6 saddr @{} drop", FAMILY, TABLE, CHAIN_NAME, v6))?;
+        Ok(())
+    }
+
+    /// Create a config's sets and drop rules, once per process lifetime.
+    fn ensure_config(&mut self, config_id: &str) -> Result<(), String> {
+        if self.configs.contains(config_id) {
+            return Ok(());
+        }
+        for (suffix, addr_type) in SET_FAMILIES {
+            self.nft(&format!(
+                "add set {} {} {} {{ type {} ; flags interval,timeout ; }}",
+                FAMILY,
+                TABLE,
+                set_name(config_id, suffix),
+                addr_type
+            ))?;
+        }
+        self.add_rules(config_id)?;
+        info!("Created nftables sets for config {}", config_id);
+        self.configs.insert(config_id.to_string());
+        Ok(())
+    }
+}
+
+impl FirewallBackend for NftablesBackend {
+    /// Recreate the table from scratch: deleting it first sweeps the sets of
+    /// a crashed run, and restore re-applies the bans that are still active.
+    fn init(&mut self) -> Result<(), String> {
+        info!("Initializing nftables table: {} {}", FAMILY, TABLE);
+
+        match self.nft(&format!("delete table {} {}", FAMILY, TABLE)) {
+            Ok(_) => info!("Swept stale nftables table {}", TABLE),
+            Err(e) if e.is_missing() => {}
+            Err(e) => warn!("Failed to sweep stale nftables table {}: {}", TABLE, e),
+        }
+        self.nft(&format!("add table {} {}", FAMILY, TABLE))
+            .map_err(|e| format!("Failed to create table: {}", e))?;
+        self.nft(&format!(
+            "add chain {} {} {} {{ type filter hook {} priority 0 ; policy accept ; }}",
+            FAMILY, TABLE, CHAIN_NAME, self.hook
+        ))
+        .map_err(|e| format!("Failed to create chain: {}", e))?;
+        info!("Hooked chain {} into {}", CHAIN_NAME, self.hook);
+        // A previous run may have used ipset mode.
+        sweep_ipsets(None, "");
+        Ok(())
+    }
+
+    fn cleanup(&mut self) {
+        info!("Cleaning up nftables table: {} {}", FAMILY, TABLE);
+        self.configs.clear();
+        match self.nft(&format!("delete table {} {}", FAMILY, TABLE)) {
+            Ok(_) => info!("Deleted table: {}", TABLE),
+            Err(e) => warn!("Failed to delete table: {}", e),
+        }
+    }
+
+    fn deny(&mut self, config_id: &str, ip: &BanTarget, timeout_ms: u64) -> Result<(), String> {
+        if let Err(e) = self.ensure_config(config_id) {
+            error!("Failed to create nftables sets for {}: {}", config_id, e);
+            return Err(format!("Failed to deny IP: {}", e));
+        }
+
+        let set = set_for(config_id, ip);
+        // Whole seconds, rounded up so the kernel never lifts a ban early.
+        let timeout = match timeout_ms {
+            0 => String::new(),
+            ms => format!(" timeout {}s", ms.div_ceil(1000)),
+        };
+        // Re-adding a live element keeps its old timeout, so a re-ban would be
+        // lifted early. Add, delete and re-add it in one transaction instead:
+        // the element ends up with the new timeout whether or not it existed,
+        // and is never briefly absent.
+        let element = format!("element {} {} {} {{ {} }}", FAMILY, TABLE, set, ip);
+        let command = format!(
+            "add {} ; delete {} ; add element {} {} {} {{ {}{} }}",
+            element, element, FAMILY, TABLE, set, ip, timeout
+        );
+        match self.nft(&command) {
+            Ok(_) => {
+                info!("Added IP {} to nftables set {}", ip, set);
+                Ok(())
+            }
+            Err(e) => {
+                error!("Failed to add IP {} to nftables set {}: {}", ip, set, e);
+                Err(format!("Failed to deny IP: {}", e))
+            }
+        }
+    }
+
+    fn allow(&mut self, config_id: &str, ip: &BanTarget) -> Result<(), String> {
+        let set = set_for(config_id, ip);
+        match self.nft(&format!("delete element {} {} {} {{ {} }}", FAMILY, TABLE, set, ip)) {
+            Ok(_) => {
+                info!("Removed IP {} from nftables set {}", ip, set);
+                Ok(())
+            }
+            // The element timed out on its own before the cleaner got to it.
+            Err(e) if e.is_missing() => {
+                info!("IP {} already expired from nftables set {}", ip, set);
+                Ok(())
+            }
+            Err(e) => {
+                warn!("Failed to remove IP {} from nftables set {}: {}", ip, set, e);
+                Err(format!("Failed to allow IP: {}", e))
+            }
+        }
+    }
+
+    /// A set cannot be deleted while a rule references it, and rules can only
+    /// be deleted by handle: flush the base chain and re-add the drop rules of
+    /// the remaining configs instead.
+    fn remove_config(&mut self, config_id: &str) {
+        if !self.configs.remove(config_id) {
+            return;
+        }
+
+        if let Err(e) = self.nft(&format!("flush chain {} {} {}", FAMILY, TABLE, CHAIN_NAME)) {
+            warn!("Failed to flush chain {}: {}", CHAIN_NAME, e);
+        }
+        for remaining in &self.configs {
+            if let Err(e) = self.add_rules(remaining) {
+                error!("Failed to re-add nftables rules for {}: {}", remaining, e);
+            }
+        }
+        for (suffix, _) in SET_FAMILIES {
+            let set = set_name(config_id, suffix);
+            match self.nft(&format!("delete set {} {} {}", FAMILY, TABLE, set)) {
+                Ok(_) => info!("Removed nftables set: {}", set),
+                Err(e) => warn!("Failed to delete nftables set {}: {}", set, e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_names_are_per_family() {
+        let v4: BanTarget = "10.0.0.1".parse().unwrap();
+        let v6: BanTarget = "2001:db8::1".parse().unwrap();
+        assert_eq!(set_for("cfg-ssh", &v4), "bnz-cfg-ssh-v4");
+        assert_eq!(set_for("cfg-ssh", &v6), "bnz-cfg-ssh-v6");
+    }
+
+    #[test]
+    fn only_builtin_chains_map_to_hooks() {
+        assert_eq!(hook_for("INPUT"), Ok("input"));
+        assert_eq!(hook_for("forward"), Ok("forward"));
+        assert!(hook_for("DOCKER-USER").is_err());
+    }
+
+    #[test]
+    fn only_enoent_counts_as_missing() {
+        let error = |status, stderr: &str| NftError {
+            status,
+            stderr: stderr.to_string(),
+        };
+        let enoent = "Error: Could not process rule: No such file or directory\ndelete element inet banalize s { 10.0.0.1 }";
+        assert!(error(Some(1), enoent).is_missing());
+        assert!(!error(Some(1), "Error: Could not process rule: Device or resource busy").is_missing());
+        assert!(error(Some(1), "Error: No such file or directory; did you mean table 'x' in family inet?").is_missing());
+        assert!(!error(Some(1), "Error: syntax error, unexpected newline").is_missing());
+        assert!(!error(None, "No such file or directory").is_missing());
+    }
+}
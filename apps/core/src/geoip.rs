@@ -1,3 +1,4 @@
+use crate::health::{Component, ComponentHealth};
 use maxminddb::{geoip2, Reader};
 use serde::Serialize;
 use std::net::IpAddr;
@@ -40,6 +41,23 @@ impl GeoIp {
         geoip
     }
 
+    /// When the loaded database was built (Unix seconds); `None` without one.
+    pub fn build_epoch(&self) -> Option<u64> {
+        let guard = self.reader.read().unwrap();
+        guard.as_ref().map(|reader| reader.metadata().build_epoch)
+    }
+
+    /// Degraded while auto-download is on but no database could be loaded.
+    pub fn health(&self) -> ComponentHealth {
+        if self.reader.read().unwrap().is_some() {
+            ComponentHealth::new(Component::Geoip, None, None)
+        } else if self.auto_download {
+            ComponentHealth::new(Component::Geoip, None, Some("no database downloaded yet".to_string()))
+        } else {
+            ComponentHealth::disabled(Component::Geoip)
+        }
+    }
+
     pub fn lookup(&self, ip: IpAddr) -> IpInfo {
         let guard = self.reader.read().unwrap();
         let Some(reader) = guard.as_ref() else {
@@ -0,0 +1,238 @@
+//! Component health, reported by `/api/health`.
+//!
+//! Loops that do work on behalf of the whole process (the firewall actor, the
+//! audit writer) record each unit of work in a `Progress`, so the endpoint can
+//! tell a failing or stuck loop from an idle one. Everything else (watchers,
+//! GeoIP, notifiers) is read from its owner when the endpoint is called.
+
+use crate::detector::now_millis;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use utoipa::ToSchema;
+
+/// A unit of work running longer than this makes its loop count as stuck.
+const STALL_AFTER_MS: u64 = 30_000;
+
+/// Components failing the health check (503) when degraded, unless
+/// `BANALIZE_CORE_HEALTH_CRITICAL` says otherwise. Tailers are left out: a
+/// log file that is briefly missing around rotation is routine.
+const DEFAULT_CRITICAL: [Component; 3] = [Component::Firewall, Component::EventsDb, Component::Detector];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Component {
+    /// The firewall actor and its backend
+    Firewall,
+    /// The writer persisting matches, bans and unbans to events.db
+    EventsDb,
+    /// A config's log source
+    Tailer,
+    /// A config's detector
+    Detector,
+    /// The GeoIP country database
+    Geoip,
+    /// A notifier's deliveries
+    Notifier,
+}
+
+impl FromStr for Component {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "firewall" => Ok(Component::Firewall),
+            "events_db" => Ok(Component::EventsDb),
+            "tailer" => Ok(Component::Tailer),
+            "detector" => Ok(Component::Detector),
+            "geoip" => Ok(Component::Geoip),
+            "notifier" => Ok(Component::Notifier),
+            other => Err(format!(
+                "unknown health component '{}' (expected firewall, events_db, tailer, detector, geoip or notifier)",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ComponentStatus {
+    Ok,
+    Degraded,
+    /// Not in use, e.g. GeoIP without a database and auto-download off
+    Disabled,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ComponentHealth {
+    pub component: Component,
+    /// Config id (tailer, detector) or notifier id; unset for the singletons
+    pub id: Option<String>,
+    pub status: ComponentStatus,
+    /// Whether this component being degraded fails the check
+    pub critical: bool,
+    /// What is wrong, when degraded
+    pub detail: Option<String>,
+}
+
+impl ComponentHealth {
+    pub fn new(component: Component, id: Option<&str>, detail: Option<String>) -> Self {
+        Self {
+            component,
+            id: id.map(str::to_string),
+            status: if detail.is_some() {
+                ComponentStatus::Degraded
+            } else {
+                ComponentStatus::Ok
+            },
+            critical: false,
+            detail,
+        }
+    }
+
+    pub fn disabled(component: Component) -> Self {
+        Self {
+            component,
+            id: None,
+            status: ComponentStatus::Disabled,
+            critical: false,
+            detail: None,
+        }
+    }
+}
+
+/// The unit of work a loop is on, and how its latest one ended.
+#[derive(Default)]
+pub struct Progress {
+    /// When the current unit of work started (ms); 0 while idle.
+    busy_since: AtomicU64,
+    last_error: Mutex<Option<String>>,
+    /// Why setup failed, if it did. Unlike `last_error` a later unit of work
+    /// does not clear it, only a successful setup does.
+    init_error: Mutex<Option<String>>,
+}
+
+impl Progress {
+    pub fn start(&self) {
+        self.busy_since.store(now_millis(), Ordering::Relaxed);
+    }
+
+    pub fn finish(&self, result: Result<(), String>) {
+        self.busy_since.store(0, Ordering::Relaxed);
+        *self.last_error.lock().unwrap() = result.err();
+    }
+
+    /// Record how the loop's setup went, e.g. `Firewall::init`.
+    pub fn init(&self, result: Result<(), String>) {
+        *self.init_error.lock().unwrap() = result.err();
+    }
+
+    /// What is wrong with the loop, if anything: its setup failed, it is
+    /// stuck on its current unit of work, or its latest one failed.
+    pub fn problem(&self) -> Option<String> {
+        if let Some(e) = self.init_error.lock().unwrap().clone() {
+            return Some(format!("init failed: {}", e));
+        }
+        let since = self.busy_since.load(Ordering::Relaxed);
+        let busy_for = if since == 0 { 0 } else { now_millis().saturating_sub(since) };
+        if busy_for > STALL_AFTER_MS {
+            return Some(format!("stuck for {}s", busy_for / 1000));
+        }
+        self.last_error.lock().unwrap().clone()
+    }
+}
+
+pub struct Health {
+    pub firewall: Arc<Progress>,
+    pub events_db: Arc<Progress>,
+    critical: HashSet<Component>,
+}
+
+impl Health {
+    /// `critical` is the comma-separated value of
+    /// `BANALIZE_CORE_HEALTH_CRITICAL`; `None` keeps the defaults.
+    pub fn new(critical: Option<&str>) -> Result<Self, String> {
+        let critical = match critical {
+            Some(list) => list
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::parse)
+                .collect::<Result<_, _>>()?,
+            None => DEFAULT_CRITICAL.into_iter().collect(),
+        };
+        Ok(Self {
+            firewall: Arc::default(),
+            events_db: Arc::default(),
+            critical,
+        })
+    }
+
+    /// Flag the critical components; true when none of them is degraded.
+    pub fn assess(&self, components: &mut [ComponentHealth]) -> bool {
+        let mut healthy = true;
+        for c in components.iter_mut() {
+            c.critical = self.critical.contains(&c.component);
+            if c.critical && c.status == ComponentStatus::Degraded {
+                healthy = false;
+            }
+        }
+        healthy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress_reports_failures_until_the_next_success() {
+        let progress = Progress::default();
+        assert_eq!(progress.problem(), None);
+        progress.start();
+        progress.finish(Err("iptables: exit 4".to_string()));
+        assert_eq!(progress.problem().as_deref(), Some("iptables: exit 4"));
+        progress.start();
+        assert_eq!(progress.problem().as_deref(), Some("iptables: exit 4"));
+        progress.finish(Ok(()));
+        assert_eq!(progress.problem(), None);
+
+        progress.busy_since.store(now_millis() - 45_000, Ordering::Relaxed);
+        assert_eq!(progress.problem().as_deref(), Some("stuck for 45s"));
+    }
+
+    #[test]
+    fn init_failures_outlast_later_successes() {
+        let progress = Progress::default();
+        progress.init(Err("no INPUT chain".to_string()));
+        progress.start();
+        progress.finish(Ok(()));
+        assert_eq!(progress.problem().as_deref(), Some("init failed: no INPUT chain"));
+        progress.init(Ok(()));
+        assert_eq!(progress.problem(), None);
+    }
+
+    #[test]
+    fn only_critical_components_fail_the_check() {
+        let health = Health::new(Some("firewall, notifier")).unwrap();
+        let mut components = vec![
+            ComponentHealth::new(Component::Tailer, Some("ssh"), Some("missing".to_string())),
+            ComponentHealth::new(Component::Firewall, None, None),
+        ];
+        assert!(health.assess(&mut components));
+        assert!(!components[0].critical);
+        assert!(components[1].critical);
+
+        components.push(ComponentHealth::new(Component::Notifier, Some("ops"), Some("503".to_string())));
+        assert!(!health.assess(&mut components));
+
+        assert!(Health::new(Some("firewall,disk")).is_err());
+        let defaults = Health::new(None).unwrap();
+        assert!(defaults.critical.contains(&Component::Detector));
+        assert!(!defaults.critical.contains(&Component::Tailer));
+        assert!(!defaults.critical.contains(&Component::Geoip));
+    }
+}
@@ -3,35 +3,98 @@ use std::net::IpAddr;
 
 /// Capture pattern substituted for the <IP> placeholder. Shared with config
 /// validation so the compiled pattern there can't drift from extraction.
-pub const IP_PATTERN: &str = r"(\b(?:\d{1,3}\.){3}\d{1,3}\b)";
-
-/// Extract IP address from a line using a regex pattern that contains <IP> placeholder
-pub fn extract_ip(regex_pattern: &str, line: &str) -> Option<IpAddr> {
-    let final_pattern = regex_pattern.replace("<IP>", IP_PATTERN);
-    
-    let re = match Regex::new(&final_pattern) {
-        Ok(re) => re,
-        Err(_) => return None,
-    };
-    
-    if let Ok(Some(captures)) = re.captures(line) {
+///
+/// Matches dotted IPv4 or IPv6 in its full, compressed (`2001:db8::1`) and
+/// IPv4-suffixed (`::ffff:192.0.2.1`) forms. The IPv6 branch is fenced by
+/// look-around instead of `\b`, since `:` is not a word character: without it
+/// a greedy `.*<IP>` would happily capture just the `::1` tail of an address.
+/// The IPv4-suffixed forms come first so a `::ffff:10` prefix can never win
+/// over the whole mapped address. Candidates are still parsed afterwards, so
+/// the pattern only has to be tight enough to pick the right span.
+pub const IP_PATTERN: &str = concat!(
+    r"(\b(?:\d{1,3}\.){3}\d{1,3}\b",
+    r"|(?<![0-9A-Fa-f:])(?:",
+    r"(?:[0-9A-Fa-f]{1,4}:){6}(?:\d{1,3}\.){3}\d{1,3}",
+    r"|(?:(?:[0-9A-Fa-f]{1,4}:){1,5}|:):(?:[0-9A-Fa-f]{1,4}:){0,4}(?:\d{1,3}\.){3}\d{1,3}",
+    r"|(?:[0-9A-Fa-f]{1,4}:){7}[0-9A-Fa-f]{1,4}",
+    r"|(?:(?:[0-9A-Fa-f]{1,4}:){1,6}|:)(?::[0-9A-Fa-f]{1,4}){1,6}",
+    r"|(?:[0-9A-Fa-f]{1,4}:){1,7}:",
+    r")(?![0-9A-Fa-f:]|\.\d))",
+);
+
+/// Compile a config regex into its extraction form: `<IP>` is substituted for
+/// the capture pattern. The one place the substitution happens, so validation
+/// and extraction compile exactly the same regex.
+pub fn compile_pattern(regex_pattern: &str) -> Result<Regex, String> {
+    Regex::new(&regex_pattern.replace("<IP>", IP_PATTERN))
+        .map_err(|e| format!("regex does not compile: {}", e))
+}
+
+/// The compiled `<IP>` patterns of one config, built once when the detector
+/// starts and reused for every line. Recompiling per line used to dominate the
+/// detector's hot path under load.
+pub struct IpExtractor {
+    patterns: Vec<Regex>,
+    /// Ignore regexes: a line matching any of these yields no IP at all.
+    ignore: Vec<Regex>,
+}
+
+impl IpExtractor {
+    /// Compile every pattern up front; the first one that fails is reported.
+    pub fn new<S: AsRef<str>>(patterns: &[S]) -> Result<Self, String> {
+        Ok(Self {
+            patterns: compile_all(patterns)?,
+            ignore: Vec::new(),
+        })
+    }
+
+    /// Add ignore regexes that veto an otherwise matching line.
+    pub fn with_ignore<S: AsRef<str>>(mut self, ignore_patterns: &[S]) -> Result<Self, String> {
+        self.ignore = compile_all(ignore_patterns)?;
+        Ok(self)
+    }
+
+    /// The IP captured by the first pattern that matches the line, tried in
+    /// configuration order, unless an ignore regex matches the line too.
+    pub fn extract(&self, line: &str) -> Option<IpAddr> {
+        let ip = self.patterns.iter().find_map(|re| capture_ip(re, line))?;
+        // Checked only once a fail pattern hit: most lines never get here.
+        if self.ignore.iter().any(|re| re.is_match(line).unwrap_or(false)) {
+            return None;
+        }
+        Some(ip)
+    }
+}
+
+fn compile_all<S: AsRef<str>>(patterns: &[S]) -> Result<Vec<Regex>, String> {
+    patterns.iter().map(|p| compile_pattern(p.as_ref())).collect()
+}
+
+fn capture_ip(re: &Regex, line: &str) -> Option<IpAddr> {
+    for captures in re.captures_iter(line).flatten() {
         // Find the IP capture group (should be the first capture group)
         for i in 1..captures.len() {
             if let Some(ip_str) = captures.get(i) {
                 if let Ok(ip) = ip_str.as_str().parse::<IpAddr>() {
-                    return Some(ip);
+                    // An IPv4-mapped IPv6 address (`::ffff:a.b.c.d`, common on
+                    // dual-stack sockets) is the IPv4 client: ban it there.
+                    return Some(ip.to_canonical());
                 }
             }
         }
     }
-    
+
     None
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    
+
+    fn extract_ip(pattern: &str, line: &str) -> Option<IpAddr> {
+        IpExtractor::new(&[pattern]).unwrap().extract(line)
+    }
+
     #[test]
     fn test_extract_ip() {
         let pattern = ".*<IP>.*";
@@ -40,5 +103,65 @@ mod tests {
         assert!(ip.is_some());
         assert_eq!(ip.unwrap().to_string(), "192.168.1.1");
     }
-}
 
+    #[test]
+    fn test_extract_ipv6_forms() {
+        let cases = [
+            ("Connection from 2001:db8::1 port 22", "2001:db8::1"),
+            ("Connection from 2001:0db8:0000:0000:0000:ff00:0042:8329", "2001:db8::ff00:42:8329"),
+            ("Connection from ::1", "::1"),
+            ("Connection from fe80:: now", "fe80::"),
+        ];
+        for (line, expected) in cases {
+            // The greedy prefix is the worst case: it must still capture the
+            // whole address, not a trailing fragment of it.
+            let ip = extract_ip(".*<IP>", line);
+            assert_eq!(ip.map(|ip| ip.to_string()).as_deref(), Some(expected), "{line}");
+        }
+    }
+
+    #[test]
+    fn test_extract_ipv6_with_ipv4_suffix() {
+        let ip = extract_ip("from <IP>\\.", "from 64:ff9b::192.0.2.33.").unwrap();
+        assert_eq!(ip.to_string(), "64:ff9b::c000:221");
+    }
+
+    #[test]
+    fn test_extract_ipv4_mapped_is_canonicalized() {
+        let ip = extract_ip("from <IP> port", "from ::ffff:10.0.0.7 port 22").unwrap();
+        assert_eq!(ip, "10.0.0.7".parse::<IpAddr>().unwrap());
+    }
+
+    #[test]
+    fn test_extractor_tries_patterns_in_order() {
+        let extractor =
+            IpExtractor::new(&["Invalid user \\S+ from <IP>", "Failed password .* from <IP>"]).unwrap();
+        let ip = extractor.extract("Failed password for root from 10.0.0.3 port 22");
+        assert_eq!(ip.map(|ip| ip.to_string()).as_deref(), Some("10.0.0.3"));
+        let ip = extractor.extract("Invalid user admin from 10.0.0.4 port 22");
+        assert_eq!(ip.map(|ip| ip.to_string()).as_deref(), Some("10.0.0.4"));
+        assert!(extractor.extract("Accepted publickey for root").is_none());
+    }
+
+    #[test]
+    fn test_ignore_regex_vetoes_match() {
+        let extractor = IpExtractor::new(&["from <IP>"])
+            .and_then(|e| e.with_ignore(&[r"\(trusted\)$"]))
+            .unwrap();
+        assert!(extractor.extract("Failed login from 10.0.0.1 (trusted)").is_none());
+        assert!(extractor.extract("Failed login from 10.0.0.1").is_some());
+    }
+
+    #[test]
+    fn test_extractor_rejects_non_compiling_pattern() {
+        let err = IpExtractor::new(&["<IP>", "(<IP>"]).err().unwrap();
+        assert!(err.contains("does not compile"), "unexpected message: {err}");
+    }
+
+    #[test]
+    fn test_extract_ip_skips_non_address_candidates() {
+        // A syslog timestamp looks hex-and-colon-ish but is not an address.
+        let ip = extract_ip("<IP>", "Oct 18 12:34:56 sshd: Failed password from 10.0.0.9");
+        assert_eq!(ip.map(|ip| ip.to_string()).as_deref(), Some("10.0.0.9"));
+    }
+}
@@ -0,0 +1,535 @@
+//! Reads the systemd journal straight from its files, following the on-disk
+//! format (<https://systemd.io/JOURNAL_FILE_FORMAT/>) rather than linking
+//! libsystemd or spawning `journalctl`.
+//!
+//! Only the entry array chain is walked: every entry of a file is reachable
+//! from `entry_array_offset`, in write order, and the header's `n_entries` says
+//! how many are complete. Hash tables, tags and field objects are never needed.
+//! Both the regular and the compact layout are understood; compressed data
+//! objects (only payloads above journald's compression threshold, 512 bytes by
+//! default) are skipped.
+//!
+//! All file reads run on the blocking pool, and an entry that still cannot
+//! be read after a few polls is skipped so one corrupt object cannot stall a
+//! file for good.
+
+use crate::config::JournalFilter;
+use crate::log_source::LogSource;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fs::File;
+use std::io;
+use std::os::unix::fs::{FileExt, MetadataExt};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Where journald keeps persistent and volatile journals, each holding one
+/// directory per machine id.
+pub const DEFAULT_JOURNAL_DIRS: [&str; 2] = ["/var/log/journal", "/run/log/journal"];
+
+/// How often the journal files are checked for new entries.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// Entries read from one file per poll, so a large backlog cannot stall the
+/// runtime; the rest is picked up straight away by the next poll.
+const POLL_BUDGET: usize = 1024;
+/// Objects larger than this are treated as corruption.
+const MAX_OBJECT_SIZE: u64 = 64 * 1024 * 1024;
+/// Polls an unreadable entry is retried for (it may be caught mid-write)
+/// before it is skipped, and an unreadable entry array chain before the
+/// file is given up on.
+const MAX_READ_RETRIES: u32 = 3;
+
+const SIGNATURE: &[u8; 8] = b"LPKSHHRH";
+const HEADER_INCOMPATIBLE_COMPACT: u32 = 1 << 4;
+/// `n_entries` and `entry_array_offset` are the last header fields read.
+const HEADER_READ_SIZE: usize = 184;
+
+const OBJECT_HEADER_SIZE: u64 = 16;
+const OBJECT_DATA: u8 = 1;
+const OBJECT_ENTRY: u8 = 3;
+const OBJECT_ENTRY_ARRAY: u8 = 6;
+const OBJECT_COMPRESSED_MASK: u8 = 0b111;
+
+/// Offset of the first item in an entry object and an entry array object.
+const ENTRY_ITEMS_OFFSET: u64 = 64;
+const ENTRY_ARRAY_ITEMS_OFFSET: u64 = 24;
+
+fn le64(buf: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(buf[at..at + 8].try_into().unwrap())
+}
+
+fn le32(buf: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(buf[at..at + 4].try_into().unwrap())
+}
+
+fn corrupt(what: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, what.into())
+}
+
+struct Header {
+    n_entries: u64,
+    entry_array_offset: u64,
+}
+
+/// One journal file being followed: where the next unread entry sits in its
+/// entry array chain.
+struct JournalFile {
+    path: PathBuf,
+    file: File,
+    compact: bool,
+    /// Entries read (or skipped) so far
+    consumed: u64,
+    /// Entry array holding the next entry (0 until the file has one), and the
+    /// slot within it
+    array: u64,
+    slot: u64,
+    /// Polls in a row that failed
+    failures: u32,
+    /// The entry array chain stayed unreadable: the file is no longer read
+    given_up: bool,
+}
+
+impl JournalFile {
+    fn open(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let mut buf = [0u8; HEADER_READ_SIZE];
+        file.read_exact_at(&mut buf, 0)?;
+        if &buf[..8] != SIGNATURE {
+            return Err(corrupt("not a journal file"));
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            compact: le32(&buf, 12) & HEADER_INCOMPATIBLE_COMPACT != 0,
+            consumed: 0,
+            array: 0,
+            slot: 0,
+            failures: 0,
+            given_up: false,
+        })
+    }
+
+    fn header(&self) -> io::Result<Header> {
+        let mut buf = [0u8; HEADER_READ_SIZE];
+        self.file.read_exact_at(&mut buf, 0)?;
+        Ok(Header {
+            n_entries: le64(&buf, 152),
+            entry_array_offset: le64(&buf, 176),
+        })
+    }
+
+    /// Size of the array items and entry items: offsets shrink to 32 bits in
+    /// compact files, and entry items drop their hash.
+    fn item_sizes(&self) -> (u64, u64) {
+        if self.compact {
+            (4, 4)
+        } else {
+            (8, 16)
+        }
+    }
+
+    /// Read a whole object, checking its type.
+    fn object(&self, offset: u64, kind: u8) -> io::Result<(u8, Vec<u8>)> {
+        let mut head = [0u8; OBJECT_HEADER_SIZE as usize];
+        self.file.read_exact_at(&mut head, offset)?;
+        let size = le64(&head, 8);
+        if head[0] != kind || !(OBJECT_HEADER_SIZE..=MAX_OBJECT_SIZE).contains(&size) {
+            return Err(corrupt(format!("bad object at {}", offset)));
+        }
+        let mut buf = vec![0u8; size as usize];
+        self.file.read_exact_at(&mut buf, offset)?;
+        Ok((head[1], buf))
+    }
+
+    /// Slot count of an entry array and the offset of the next one.
+    fn array_info(&self, offset: u64) -> io::Result<(u64, u64)> {
+        let mut head = [0u8; ENTRY_ARRAY_ITEMS_OFFSET as usize];
+        self.file.read_exact_at(&mut head, offset)?;
+        if head[0] != OBJECT_ENTRY_ARRAY {
+            return Err(corrupt(format!("bad entry array at {}", offset)));
+        }
+        let size = le64(&head, 8).saturating_sub(ENTRY_ARRAY_ITEMS_OFFSET);
+        Ok((size / self.item_sizes().0, le64(&head, 16)))
+    }
+
+    fn array_item(&self, array: u64, slot: u64) -> io::Result<u64> {
+        let (size, _) = self.item_sizes();
+        let mut buf = [0u8; 8];
+        let at = array + ENTRY_ARRAY_ITEMS_OFFSET + slot * size;
+        self.file.read_exact_at(&mut buf[..size as usize], at)?;
+        Ok(le64(&buf, 0))
+    }
+
+    /// Move `array`/`slot` forward past a full array. False at the end of
+    /// the chain.
+    fn advance_array(&mut self) -> io::Result<bool> {
+        let (capacity, next) = self.array_info(self.array)?;
+        if self.slot < capacity {
+            return Ok(true);
+        }
+        if next == 0 {
+            return Ok(false);
+        }
+        self.array = next;
+        self.slot = 0;
+        Ok(true)
+    }
+
+    /// Start following from the current end of the file, like a tail.
+    fn skip_to_end(&mut self) -> io::Result<()> {
+        let header = self.header()?;
+        self.array = header.entry_array_offset;
+        self.slot = 0;
+        self.consumed = 0;
+        if self.array == 0 {
+            return Ok(());
+        }
+        let mut remaining = header.n_entries;
+        loop {
+            let (capacity, next) = self.array_info(self.array)?;
+            if remaining <= capacity || next == 0 {
+                self.slot = remaining.min(capacity);
+                break;
+            }
+            remaining -= capacity;
+            self.array = next;
+        }
+        self.consumed = header.n_entries;
+        Ok(())
+    }
+
+    /// The field payloads (`FIELD=value`) of the entry at `offset`.
+    fn entry_fields(&self, offset: u64) -> io::Result<Vec<Vec<u8>>> {
+        let (_, entry) = self.object(offset, OBJECT_ENTRY)?;
+        let (_, size) = self.item_sizes();
+        let data_offset = if self.compact { 72 } else { 64 };
+        let mut fields = Vec::new();
+        for item in entry[ENTRY_ITEMS_OFFSET as usize..].chunks_exact(size as usize) {
+            let at = if self.compact {
+                le32(item, 0) as u64
+            } else {
+                le64(item, 0)
+            };
+            let (flags, data) = self.object(at, OBJECT_DATA)?;
+            if flags & OBJECT_COMPRESSED_MASK != 0 || data.len() < data_offset {
+                continue;
+            }
+            fields.push(data[data_offset..].to_vec());
+        }
+        Ok(fields)
+    }
+
+    /// Append the messages of new entries passing `filter` to `out`. Returns
+    /// true when the budget ran out before the end of the file.
+    fn read_new(&mut self, filter: &CompiledFilter, out: &mut VecDeque<String>) -> io::Result<bool> {
+        let header = self.header()?;
+        if self.array == 0 {
+            self.array = header.entry_array_offset;
+            if self.array == 0 {
+                return Ok(false);
+            }
+        }
+        let mut budget = POLL_BUDGET;
+        while self.consumed < header.n_entries {
+            if budget == 0 {
+                return Ok(true);
+            }
+            if !self.advance_array()? {
+                break;
+            }
+            let entry = self.array_item(self.array, self.slot)?;
+            if entry == 0 {
+                // Counted but not linked yet: pick it up next time.
+                break;
+            }
+            match self.entry_fields(entry) {
+                Ok(fields) => {
+                    if filter.matches(&fields) {
+                        if let Some(message) = message(&fields) {
+                            out.push_back(message);
+                        }
+                    }
+                }
+                Err(e) if self.failures < MAX_READ_RETRIES => return Err(e),
+                Err(e) => warn!(
+                    "Skipping unreadable journal entry at {} in {}: {}",
+                    entry,
+                    self.path.display(),
+                    e
+                ),
+            }
+            self.failures = 0;
+            self.slot += 1;
+            self.consumed += 1;
+            budget -= 1;
+        }
+        Ok(false)
+    }
+}
+
+/// The `MESSAGE` field of an entry, which is what a config's regexes see.
+fn message(fields: &[Vec<u8>]) -> Option<String> {
+    fields
+        .iter()
+        .find_map(|f| f.strip_prefix(b"MESSAGE="))
+        .map(|m| String::from_utf8_lossy(m).into_owned())
+}
+
+/// A `JournalFilter` as exact payloads grouped by field: an entry passes when
+/// it carries one of the payloads of every group, like `journalctl` matches.
+#[derive(Debug, Default)]
+struct CompiledFilter {
+    groups: BTreeMap<String, Vec<Vec<u8>>>,
+}
+
+impl CompiledFilter {
+    fn new(filter: &JournalFilter) -> Self {
+        let mut groups: BTreeMap<String, Vec<Vec<u8>>> = BTreeMap::new();
+        let units = filter.units.iter().map(|unit| format!("_SYSTEMD_UNIT={}", unit_name(unit)));
+        for item in units.chain(filter.matches.iter().cloned()) {
+            if let Some((field, _)) = item.split_once('=') {
+                groups.entry(field.to_string()).or_default().push(item.into_bytes());
+            }
+        }
+        Self { groups }
+    }
+
+    fn matches(&self, fields: &[Vec<u8>]) -> bool {
+        self.groups
+            .values()
+            .all(|payloads| fields.iter().any(|f| payloads.contains(f)))
+    }
+}
+
+/// `sshd` is short for `sshd.service`, as with `journalctl --unit`.
+fn unit_name(unit: &str) -> String {
+    if unit.contains('.') {
+        unit.to_string()
+    } else {
+        format!("{}.service", unit)
+    }
+}
+
+/// The journal files under `root`: the file itself, or the `*.journal` files
+/// in it and in its per-machine subdirectories.
+fn journal_files(root: &Path) -> Vec<PathBuf> {
+    if root.is_file() {
+        return vec![root.to_path_buf()];
+    }
+    let mut files = Vec::new();
+    let mut dirs = vec![(root.to_path_buf(), true)];
+    while let Some((dir, descend)) = dirs.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                if descend {
+                    dirs.push((path, false));
+                }
+            } else if path.extension().is_some_and(|ext| ext == "journal") {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+/// Every journal file under a set of roots, tracked by inode so a rotated
+/// (renamed) file is read to its end and the new active file is read from its
+/// start. Its methods block on file IO.
+struct Journal {
+    roots: Vec<PathBuf>,
+    filter: CompiledFilter,
+    files: HashMap<(u64, u64), JournalFile>,
+}
+
+impl Journal {
+    fn describe(&self) -> String {
+        let roots: Vec<String> = self.roots.iter().map(|r| r.display().to_string()).collect();
+        format!("{} ({} files)", roots.join(", "), self.files.len())
+    }
+
+    /// Pick up new files and forget deleted ones. Files present at startup
+    /// are skipped to their end; later ones are new and read from the start.
+    fn scan(&mut self, at_start: bool) {
+        let mut seen = Vec::new();
+        for path in self.roots.iter().flat_map(|root| journal_files(root)) {
+            let Ok(meta) = std::fs::metadata(&path) else {
+                continue;
+            };
+            let key = (meta.dev(), meta.ino());
+            seen.push(key);
+            if self.files.contains_key(&key) {
+                continue;
+            }
+            let opened = JournalFile::open(&path).and_then(|mut file| {
+                if at_start {
+                    file.skip_to_end()?;
+                }
+                Ok(file)
+            });
+            match opened {
+                Ok(file) => {
+                    self.files.insert(key, file);
+                }
+                Err(e) => warn!("Skipping journal file {}: {}", path.display(), e),
+            }
+        }
+        self.files.retain(|key, _| seen.contains(key));
+    }
+
+    /// Read new entries from every file into `out`. Returns true when a file
+    /// stopped on its budget.
+    fn poll(&mut self, out: &mut VecDeque<String>) -> bool {
+        self.scan(false);
+        let mut behind = false;
+        for file in self.files.values_mut().filter(|file| !file.given_up) {
+            match file.read_new(&self.filter, out) {
+                Ok(more) => {
+                    file.failures = 0;
+                    behind |= more;
+                }
+                // Probably an entry caught mid-write: retried next poll. Once
+                // the retries run out `read_new` skips a bad entry, so only a
+                // broken entry array chain gets here.
+                Err(e) if file.failures < MAX_READ_RETRIES => {
+                    file.failures += 1;
+                    warn!("Error reading journal file {}: {}", file.path.display(), e);
+                }
+                Err(e) => {
+                    file.given_up = true;
+                    warn!("Giving up on journal file {}: {}", file.path.display(), e);
+                }
+            }
+        }
+        behind
+    }
+}
+
+/// Follows the journal for a config. Reads happen on the blocking pool, the
+/// `Journal` moving there and back for each poll.
+pub struct JournalReader {
+    /// None while a poll runs, and for good if one panicked
+    journal: Option<Journal>,
+    pending: VecDeque<String>,
+    /// Whether the last poll stopped on its budget
+    behind: bool,
+}
+
+impl JournalReader {
+    /// Open the journal at `param` (a directory or a single file; empty for
+    /// the system journal), starting after its current last entry.
+    pub async fn new(param: &str, filter: &JournalFilter) -> Self {
+        let roots = if param.is_empty() {
+            DEFAULT_JOURNAL_DIRS.iter().map(PathBuf::from).collect()
+        } else {
+            vec![PathBuf::from(param)]
+        };
+        let mut reader = Self {
+            journal: Some(Journal {
+                roots,
+                filter: CompiledFilter::new(filter),
+                files: HashMap::new(),
+            }),
+            pending: VecDeque::new(),
+            behind: false,
+        };
+        if let Some(description) = reader
+            .blocking(|journal| {
+                journal.scan(true);
+                journal.describe()
+            })
+            .await
+        {
+            info!("Watching journal: {}", description);
+        }
+        reader
+    }
+
+    /// Run `f` on the journal on the blocking pool.
+    async fn blocking<T: Send + 'static>(
+        &mut self,
+        f: impl FnOnce(&mut Journal) -> T + Send + 'static,
+    ) -> Option<T> {
+        let mut journal = self.journal.take()?;
+        let task = tokio::task::spawn_blocking(move || {
+            let out = f(&mut journal);
+            (journal, out)
+        });
+        match task.await {
+            Ok((journal, out)) => {
+                self.journal = Some(journal);
+                Some(out)
+            }
+            Err(e) => {
+                warn!("Journal reader stopped: {}", e);
+                None
+            }
+        }
+    }
+}
+
+impl LogSource for JournalReader {
+    async fn next_line(&mut self) -> Option<String> {
+        loop {
+            if let Some(line) = self.pending.pop_front() {
+                return Some(line);
+            }
+            if !self.behind {
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+            let (pending, behind) = self
+                .blocking(|journal| {
+                    let mut pending = VecDeque::new();
+                    let behind = journal.poll(&mut pending);
+                    (pending, behind)
+                })
+                .await?;
+            self.pending = pending;
+            self.behind = behind;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(items: &[&str]) -> Vec<Vec<u8>> {
+        items.iter().map(|i| i.as_bytes().to_vec()).collect()
+    }
+
+    #[test]
+    fn filter_ors_values_of_a_field_and_ands_fields() {
+        let filter = CompiledFilter::new(&JournalFilter {
+            units: vec!["sshd".to_string(), "ssh.service".to_string()],
+            matches: vec!["PRIORITY=5".to_string(), "PRIORITY=6".to_string()],
+        });
+        assert!(filter.matches(&fields(&["_SYSTEMD_UNIT=sshd.service", "PRIORITY=6"])));
+        assert!(filter.matches(&fields(&["_SYSTEMD_UNIT=ssh.service", "PRIORITY=5"])));
+        assert!(!filter.matches(&fields(&["_SYSTEMD_UNIT=sshd.service", "PRIORITY=3"])));
+        assert!(!filter.matches(&fields(&["_SYSTEMD_UNIT=cron.service", "PRIORITY=6"])));
+        assert!(!filter.matches(&fields(&["PRIORITY=6"])));
+
+        let everything = CompiledFilter::new(&JournalFilter::default());
+        assert!(everything.matches(&fields(&["MESSAGE=hi"])));
+    }
+
+    #[test]
+    fn message_is_the_message_field() {
+        let entry = fields(&["PRIORITY=6", "MESSAGE=Failed password from 192.0.2.1"]);
+        assert_eq!(message(&entry).as_deref(), Some("Failed password from 192.0.2.1"));
+        assert_eq!(message(&fields(&["PRIORITY=6"])), None);
+    }
+
+    #[test]
+    fn non_journal_files_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("system.journal");
+        std::fs::write(&path, vec![0u8; 512]).unwrap();
+        assert!(JournalFile::open(&path).is_err());
+    }
+}
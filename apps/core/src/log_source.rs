@@ -1,84 +1,105 @@
-use linemux::MuxedLines;
+use crate::config::{recidive_line, DockerFilter, JournalFilter};
+use crate::docker::{docker_socket, DockerLogs};
+use crate::events::Event;
+use crate::file_tail::{FileTailer, TailStatus};
+use crate::journal::JournalReader;
+use crate::metrics::{Bus, Metrics};
+use crate::syslog::SyslogSource;
+use std::sync::Arc;
 use tokio::sync::{broadcast, mpsc};
-use tracing::{error, info};
+use tracing::{info, warn};
 
 /// A source of log lines. Abstracts the underlying tailing mechanism so the
-/// detection pipeline can be driven by files today and other sources (journald,
-/// docker logs, a test mock) later without changing the detector.
+/// detection pipeline can be driven by files, the journal, container logs and
+/// other sources (a test mock) without changing the detector.
 pub trait LogSource: Send {
     /// Await the next line, or `None` when the source is exhausted.
     fn next_line(&mut self) -> impl std::future::Future<Output = Option<String>> + Send;
 }
 
-/// Tails a file with `linemux`, seeking to EOF so only new lines are emitted.
-pub struct FileTailer {
-    path: String,
-    lines: MuxedLines,
+/// Run a tailer task: follow the files matching `param`, then forward every
+/// line to the detector over `line_tx` until shutdown or the detector side is
+/// gone. Every line is also published on `line_bus` for live API tailing;
+/// having no subscribers there is the normal case and not an error.
+///
+/// A missing or unreadable file does not fail config creation: it is waited
+/// for, and shows up as such in `status`.
+pub async fn run_tailer(
+    param: String,
+    config_id: String,
+    status: TailStatus,
+    line_tx: mpsc::Sender<String>,
+    line_bus: broadcast::Sender<String>,
+    shutdown_rx: broadcast::Receiver<()>,
+) {
+    let tailer = FileTailer::new(&param, status).await;
+    info!("Tailer started for config {} (files: {})", config_id, param);
+    forward_lines(tailer, config_id, line_tx, line_bus, shutdown_rx).await;
 }
 
-impl FileTailer {
-    pub async fn new(path: &str) -> Result<Self, String> {
-        let mut lines =
-            MuxedLines::new().map_err(|e| format!("Failed to create MuxedLines: {}", e))?;
-        lines
-            .add_file(path)
-            .await
-            .map_err(|e| format!("Failed to add file {}: {}", path, e))?;
-        info!("Watching file: {}", path);
-        Ok(Self {
-            path: path.to_string(),
-            lines,
-        })
-    }
+/// Run the line source of a journal config: follow the journal at `param`
+/// (the system journal when empty) and forward the message of every entry
+/// passing `filter`, exactly as `run_tailer` does.
+pub async fn run_journal(
+    param: String,
+    filter: JournalFilter,
+    config_id: String,
+    line_tx: mpsc::Sender<String>,
+    line_bus: broadcast::Sender<String>,
+    shutdown_rx: broadcast::Receiver<()>,
+) {
+    let reader = JournalReader::new(&param, &filter).await;
+    info!("Journal reader started for config {}", config_id);
+    forward_lines(reader, config_id, line_tx, line_bus, shutdown_rx).await;
 }
 
-impl LogSource for FileTailer {
-    async fn next_line(&mut self) -> Option<String> {
-        loop {
-            match self.lines.next_line().await {
-                Ok(Some(line)) => return Some(line.line().to_string()),
-                Ok(None) => return None,
-                Err(e) => {
-                    // Transient read error: log and keep tailing.
-                    error!("Error reading line from {}: {}", self.path, e);
-                    continue;
-                }
-            }
-        }
-    }
+/// Run the line source of a docker config: follow the container named by
+/// `param`, or every container carrying `filter.labels`, through the Docker
+/// socket, and forward its output exactly as `run_tailer` does. Stopped
+/// containers are waited for, so the config can exist before its container.
+pub async fn run_docker(
+    param: String,
+    filter: DockerFilter,
+    config_id: String,
+    line_tx: mpsc::Sender<String>,
+    line_bus: broadcast::Sender<String>,
+    shutdown_rx: broadcast::Receiver<()>,
+) {
+    let logs = DockerLogs::new(&docker_socket(), &param, &filter);
+    info!("Docker log reader started for config {}", config_id);
+    forward_lines(logs, config_id, line_tx, line_bus, shutdown_rx).await;
 }
 
-/// Run a tailer task: open the file, then forward every line to the detector
-/// over `line_tx` until shutdown or the detector side is gone. Every line is
-/// also published on `line_bus` for live API tailing; having no subscribers
-/// there is the normal case and not an error.
-///
-/// Opening the file happens here (not when the watcher is registered) so a
-/// missing or unreadable file does not fail config creation — it just produces
-/// no lines, matching the previous behaviour.
-pub async fn run_tailer(
-    param: String,
+/// Run the line source of a syslog config: forward the message of every
+/// datagram or TCP frame passing the config's filter exactly as `run_tailer`
+/// does. The listener is bound before this task starts.
+pub async fn run_syslog(
+    source: SyslogSource,
     config_id: String,
     line_tx: mpsc::Sender<String>,
     line_bus: broadcast::Sender<String>,
-    mut shutdown_rx: broadcast::Receiver<()>,
+    shutdown_rx: broadcast::Receiver<()>,
 ) {
-    let mut tailer = match FileTailer::new(&param).await {
-        Ok(t) => t,
-        Err(e) => {
-            error!("Failed to start tailer for config {}: {}", config_id, e);
-            return;
-        }
-    };
+    info!("Syslog listener started for config {}", config_id);
+    forward_lines(source, config_id, line_tx, line_bus, shutdown_rx).await;
+}
 
-    info!("Tailer started for config {} (file: {})", config_id, param);
+/// Forward every line of `source` to the detector and the live-tail bus
+/// until shutdown, the source ends, or the detector side is gone.
+async fn forward_lines(
+    mut source: impl LogSource,
+    config_id: String,
+    line_tx: mpsc::Sender<String>,
+    line_bus: broadcast::Sender<String>,
+    mut shutdown_rx: broadcast::Receiver<()>,
+) {
     loop {
         tokio::select! {
             _ = shutdown_rx.recv() => {
                 info!("Tailer {} received shutdown signal", config_id);
                 break;
             }
-            line = tailer.next_line() => {
+            line = source.next_line() => {
                 match line {
                     Some(l) => {
                         let _ = line_bus.send(l.clone());
@@ -94,3 +115,49 @@ pub async fn run_tailer(
     }
     info!("Tailer {} stopped", config_id);
 }
+
+/// Run the line source of a recidive config: turn every ban issued by another
+/// config into a `recidive_line` and forward it exactly as `run_tailer` does,
+/// so the detector, live tail and audit log need no special case.
+///
+/// Bans come off the lossy event bus; if this task falls behind, the missed
+/// bans are logged and skipped rather than stalling the other detectors.
+pub async fn run_ban_feed(
+    config_id: String,
+    mut events: broadcast::Receiver<Event>,
+    line_tx: mpsc::Sender<String>,
+    line_bus: broadcast::Sender<String>,
+    mut shutdown_rx: broadcast::Receiver<()>,
+    metrics: Arc<Metrics>,
+) {
+    info!("Ban feed started for config {}", config_id);
+    loop {
+        tokio::select! {
+            _ = shutdown_rx.recv() => {
+                info!("Ban feed {} received shutdown signal", config_id);
+                break;
+            }
+            event = events.recv() => {
+                let line = match event {
+                    // Our own bans would feed back into the count, and
+                    // would-be bans of monitor-mode configs are not offences.
+                    Ok(Event::Ban { config_id: source, ip, simulated: false, .. }) if source != config_id => {
+                        recidive_line(&source, &ip)
+                    }
+                    Ok(_) => continue,
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        warn!("Ban feed {} lagged, missed {} events", config_id, missed);
+                        metrics.lagged(Bus::Events, missed);
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                let _ = line_bus.send(line.clone());
+                if line_tx.send(line).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+    info!("Ban feed {} stopped", config_id);
+}
@@ -1,20 +1,33 @@
+mod allowlist;
 mod api;
+mod auth;
+mod backtest;
+mod ban_target;
 mod cleaner;
 mod config;
 mod database;
 mod detector;
+mod digest;
+mod docker;
 mod events;
+mod file_tail;
 mod firewall;
 mod geoip;
+mod health;
 mod ip_extract;
+mod journal;
 mod log_capture;
 mod log_source;
+mod metrics;
 mod notifier;
+mod outbox;
 mod restore;
 mod store;
+mod syslog;
+mod template;
 mod watcher_manager;
 
-use api::{create_router, AppState};
+use api::{cors_layer, create_router, AppState};
 use cleaner::Cleaner;
 use config::ConfigMap;
 use database::SqliteDatabase;
@@ -29,12 +42,21 @@ use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use tokio::sync::{broadcast, mpsc, RwLock};
 use tower::ServiceBuilder;
-use tower_http::cors::CorsLayer;
 use tracing::{info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // `banalize-core backtest ...` replays a log file offline, then exits.
+    let mut args = env::args().skip(1);
+    if args.next().as_deref() == Some("backtest") {
+        if let Err(e) = backtest::cli(args) {
+            eprintln!("backtest: {}", e);
+            std::process::exit(2);
+        }
+        return Ok(());
+    }
+
     // Initialize logging with an in-memory capture layer for the /api/logs endpoint
     let log_level = env::var("BANALIZE_CORE_LOG_LEVEL").unwrap_or_else(|_| "INFO".to_string());
     let log_buffer: LogBuffer = Arc::new(Mutex::new(VecDeque::with_capacity(LOG_BUFFER_CAPACITY)));
@@ -51,10 +73,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Get configuration from environment
     let firewall_chain = env::var("BANALIZE_CORE_FIREWALL_CHAIN")
         .unwrap_or_else(|_| "INPUT".to_string());
+    let firewall_backend = env::var("BANALIZE_CORE_FIREWALL_BACKEND")
+        .unwrap_or_else(|_| "iptables".to_string());
     let database_path = env::var("BANALIZE_CORE_DATABASE_PATH")
         .unwrap_or_else(|_| "/tmp/banalize-core".to_string());
     let api_addr = env::var("BANALIZE_CORE_API_ADDR")
-        .unwrap_or_else(|_| "0.0.0.0:6040".to_string());
+        .unwrap_or_else(|_| "127.0.0.1:6040".to_string());
+    let cors_origins = env::var("BANALIZE_CORE_CORS_ORIGINS").unwrap_or_default();
 
     // Create database paths
     let db_dir = PathBuf::from(&database_path);
@@ -81,10 +106,25 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Setup the shutdown broadcast early so long-lived actors can subscribe.
     let (shutdown_tx, _shutdown_rx) = broadcast::channel::<()>(16);
 
-    // Initialize the firewall and spawn it as the single owner of the iptables
-    // chain. All ban/unban mutations reach it over a lossless mpsc channel.
-    info!("Initializing firewall with chain: {}", firewall_chain);
-    let mut firewall = Firewall::new(firewall_chain.clone());
+    // Counters served at /metrics, bumped by the firewall actor, detectors
+    // and broadcast subscribers.
+    let metrics = Arc::new(metrics::Metrics::new());
+
+    // Progress of the firewall actor and the audit writer, for /api/health.
+    let health_critical = env::var("BANALIZE_CORE_HEALTH_CRITICAL").ok();
+    let health = Arc::new(health::Health::new(health_critical.as_deref())?);
+
+    // Initialize the firewall and spawn it as the single owner of the firewall
+    // state. All ban/unban mutations reach it over a lossless mpsc channel.
+    info!(
+        "Initializing {} firewall with chain: {}",
+        firewall_backend, firewall_chain
+    );
+    let mut firewall = Firewall::new(
+        firewall::backend_from_name(&firewall_backend, firewall_chain.clone())?,
+        metrics.clone(),
+        health.firewall.clone(),
+    );
     if let Err(e) = firewall.init() {
         warn!("Failed to initialize firewall (continuing anyway): {}", e);
     }
@@ -97,13 +137,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     // Initialize event emitter
-    let (event_emitter, event_rx) = EventEmitter::new();
+    let (event_emitter, event_rx, notify_rx) = EventEmitter::new();
     let event_emitter = Arc::new(event_emitter);
 
     // Spawn SQLite event handler (the sole consumer of the notification bus).
     let sqlite_events_db_handler = sqlite_events_db.clone();
+    let events_db_progress = health.events_db.clone();
     tokio::spawn(async move {
-        SqliteDatabase::handle_events(sqlite_events_db_handler, event_rx).await;
+        SqliteDatabase::handle_events(sqlite_events_db_handler, event_rx, events_db_progress).await;
     });
 
     // GeoIP country lookup: loads an existing mmdb immediately; the background
@@ -120,20 +161,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         let existing_configs = db.get_all_configs()?;
         let mut config_map = configs.write().await;
         for config_record in existing_configs {
-            let ignore_ips: Vec<String> =
-                serde_json::from_str(&config_record.ignore_ips).unwrap_or_default();
-            let config = config::Config {
-                id: config_record.id.clone(),
-                name: config_record.name,
-                param: config_record.param,
-                regex: config_record.regex,
-                ban_time: config_record.ban_time,
-                find_time: config_record.find_time,
-                max_matches: config_record.max_matches,
-                ignore_ips,
-                recidive_multiplicator: config_record.recidive_multiplicator,
-            };
-            config_map.insert(config_record.id, config);
+            let config = config::Config::from(config_record);
+            config_map.insert(config.id.clone(), config);
         }
     }
 
@@ -156,6 +185,25 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                         .signal_config
                         .as_deref()
                         .and_then(|j| serde_json::from_str(j).ok()),
+                    webhook_config: record
+                        .webhook_config
+                        .as_deref()
+                        .and_then(|j| serde_json::from_str(j).ok()),
+                    title_template: record.title_template,
+                    message_template: record.message_template,
+                    batch: record
+                        .batch
+                        .as_deref()
+                        .and_then(|j| serde_json::from_str(j).ok()),
+                    daily_summary: record
+                        .daily_summary
+                        .as_deref()
+                        .and_then(|j| serde_json::from_str(j).ok()),
+                    filters: record
+                        .filters
+                        .as_deref()
+                        .and_then(|j| serde_json::from_str(j).ok())
+                        .unwrap_or_default(),
                 };
                 if let Err(e) = config.validate() {
                     warn!("Skipping invalid notifier {}: {}", record.id, e);
@@ -168,14 +216,64 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         Arc::new(RwLock::new(loaded))
     };
 
-    // Spawn the notification dispatcher on the lossy event bus.
+    // Load the global allowlist, shared by every detector and the API.
+    let allowlist: Arc<RwLock<allowlist::Allowlist>> = {
+        let db = sqlite_configs_db.lock().await;
+        let entries = db
+            .get_allowlist()?
+            .into_iter()
+            .map(allowlist::AllowlistEntry::from)
+            .collect();
+        Arc::new(RwLock::new(allowlist::Allowlist::from_entries(entries)))
+    };
+
+    // Load API tokens. With none stored and no bootstrap token the API stays
+    // open, as before tokens existed.
+    let api_tokens: Arc<RwLock<auth::TokenRegistry>> = {
+        let bootstrap = env::var("BANALIZE_CORE_API_TOKEN")
+            .ok()
+            .filter(|t| !t.is_empty());
+        let db = sqlite_configs_db.lock().await;
+        let registry = auth::TokenRegistry::from_records(db.get_api_tokens()?, bootstrap.as_deref());
+        if !registry.enabled() {
+            warn!("No API tokens configured: the API is open to anyone who can reach it");
+        }
+        Arc::new(RwLock::new(registry))
+    };
+
+    // Notifications go through a durable outbox in events.db; its worker
+    // delivers and retries them, including those left pending by a restart.
+    let retry_base = env::var("BANALIZE_CORE_NOTIFIER_RETRY_BASE")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(30);
+    let retention = env::var("BANALIZE_CORE_NOTIFIER_RETENTION")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(30 * 24 * 60 * 60);
+    let outbox = Arc::new(outbox::Outbox::new(
+        sqlite_events_db.clone(),
+        retry_base * 1000,
+        retention.saturating_mul(1000),
+    ));
+    tokio::spawn(outbox.clone().run(notifiers.clone(), shutdown_tx.subscribe()));
+
+    // Spawn the notification dispatcher on its lossless channel.
     tokio::spawn(notifier::run_dispatcher(
-        event_emitter.subscribe(),
+        notify_rx,
         shutdown_tx.subscribe(),
         notifiers.clone(),
         configs.clone(),
         store.clone(),
         geoip.clone(),
+        outbox.clone(),
+    ));
+    tokio::spawn(digest::run_daily_summaries(
+        shutdown_tx.subscribe(),
+        notifiers.clone(),
+        sqlite_events_db.clone(),
+        geoip.clone(),
+        outbox.clone(),
     ));
 
     // Hydrate in-memory state from the durable store and re-apply active bans.
@@ -192,6 +290,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         store.clone(),
         event_emitter.clone(),
         firewall_tx.clone(),
+        allowlist.clone(),
+        metrics.clone(),
     ));
 
     // Start watchers for existing configs
@@ -274,12 +374,17 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         log_tx: log_tx.clone(),
         geoip: geoip.clone(),
         notifiers: notifiers.clone(),
+        allowlist: allowlist.clone(),
+        api_tokens,
+        metrics,
+        health,
+        outbox,
     };
 
     // Create API router
     let app = create_router(app_state).layer(
         ServiceBuilder::new()
-            .layer(CorsLayer::permissive())
+            .layer(cors_layer(&cors_origins))
             .into_inner(),
     );
 
@@ -0,0 +1,305 @@
+//! Process metrics, served at `/metrics` in the Prometheus text format.
+//!
+//! Counters are bumped where things happen (detectors, the firewall actor,
+//! broadcast subscribers) and only read on scrape. Gauges (active bans, queue
+//! depths, GeoIP age) are not kept here: they are read from their owners at
+//! scrape time and handed to `render`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Upper bounds (seconds) of the firewall command latency buckets.
+const LATENCY_BUCKETS: [f64; 11] = [0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+/// What one config's detector has seen.
+#[derive(Default)]
+pub struct ConfigCounters {
+    /// Lines received from the log source
+    pub lines: AtomicU64,
+    /// Lines that yielded an IP which was not ignored
+    pub matches: AtomicU64,
+    /// Bans issued by the detector
+    pub bans: AtomicU64,
+    /// Would-be bans reported by the detector in monitor mode
+    pub simulated_bans: AtomicU64,
+}
+
+/// A broadcast bus whose slow subscribers skip messages instead of blocking.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Bus {
+    /// Domain events, for `/api/events/stream` and recidive jails
+    Events,
+    /// Raw lines, for `/api/configs/{id}/tail`
+    Lines,
+    /// Captured logs, for `/api/logs/stream`
+    Logs,
+    /// Parsed messages, from a syslog listener to its configs
+    Syslog,
+}
+
+impl Bus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Bus::Events => "events",
+            Bus::Lines => "lines",
+            Bus::Logs => "logs",
+            Bus::Syslog => "syslog",
+        }
+    }
+}
+
+#[derive(Default)]
+struct Histogram {
+    /// Observations per bucket (not cumulative); the last one is `+Inf`.
+    buckets: [u64; LATENCY_BUCKETS.len() + 1],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, value: f64) {
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|&bound| value <= bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.buckets[bucket] += 1;
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+/// Values owned elsewhere, read when `/metrics` is scraped.
+#[derive(Default)]
+pub struct Gauges {
+    /// Configs that exist, whose counters are reported
+    pub config_ids: Vec<String>,
+    /// config_id -> bans currently in force
+    pub active_bans: HashMap<String, usize>,
+    /// Messages waiting in the process-wide queues, by queue name
+    pub queues: Vec<(&'static str, usize)>,
+    /// Lines waiting between each config's source and its detector
+    pub line_queues: Vec<(String, usize)>,
+    /// Matches dropped for notifiers because their queue was full
+    pub notifications_dropped: u64,
+    /// Seconds since the GeoIP database was built; `None` without one
+    pub geoip_age: Option<u64>,
+}
+
+#[derive(Default)]
+pub struct Metrics {
+    configs: Mutex<HashMap<String, Arc<ConfigCounters>>>,
+    lagged: Mutex<BTreeMap<Bus, u64>>,
+    /// Firewall command latency by command
+    firewall: Mutex<BTreeMap<&'static str, Histogram>>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The counters of a config, kept across watcher restarts.
+    pub fn config(&self, config_id: &str) -> Arc<ConfigCounters> {
+        self.configs
+            .lock()
+            .unwrap()
+            .entry(config_id.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// Count messages a subscriber of `bus` skipped because it fell behind.
+    pub fn lagged(&self, bus: Bus, missed: u64) {
+        *self.lagged.lock().unwrap().entry(bus).or_default() += missed;
+    }
+
+    /// Record how long the firewall backend took to apply a command.
+    pub fn observe_firewall(&self, command: &'static str, elapsed: Duration) {
+        self.firewall
+            .lock()
+            .unwrap()
+            .entry(command)
+            .or_default()
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn render(&self, gauges: &Gauges) -> String {
+        let mut out = String::new();
+        let configs = self.configs.lock().unwrap();
+        let counters: Vec<(&String, Option<&Arc<ConfigCounters>>)> = gauges
+            .config_ids
+            .iter()
+            .map(|id| (id, configs.get(id)))
+            .collect();
+        let per_config = |out: &mut String, name: &str, help: &str, read: fn(&ConfigCounters) -> u64| {
+            family(out, name, help, "counter");
+            for (id, c) in &counters {
+                let value = c.map(|c| read(c)).unwrap_or(0);
+                sample(out, name, &[("config", id)], value);
+            }
+        };
+        per_config(
+            &mut out,
+            "banalize_lines_total",
+            "Lines read from the config's log source.",
+            |c| c.lines.load(Ordering::Relaxed),
+        );
+        per_config(
+            &mut out,
+            "banalize_matches_total",
+            "Lines in which the config found an IP it does not ignore.",
+            |c| c.matches.load(Ordering::Relaxed),
+        );
+        per_config(
+            &mut out,
+            "banalize_bans_total",
+            "Bans issued by the config's detector.",
+            |c| c.bans.load(Ordering::Relaxed),
+        );
+        per_config(
+            &mut out,
+            "banalize_simulated_bans_total",
+            "Would-be bans reported by the config's detector in monitor mode.",
+            |c| c.simulated_bans.load(Ordering::Relaxed),
+        );
+        drop(configs);
+
+        family(&mut out, "banalize_active_bans", "Bans currently in force.", "gauge");
+        for id in &gauges.config_ids {
+            let active = gauges.active_bans.get(id).copied().unwrap_or(0);
+            sample(&mut out, "banalize_active_bans", &[("config", id)], active);
+        }
+
+        family(&mut out, "banalize_queue_depth", "Messages waiting in an internal queue.", "gauge");
+        for (queue, depth) in &gauges.queues {
+            sample(&mut out, "banalize_queue_depth", &[("queue", queue)], depth);
+        }
+        family(
+            &mut out,
+            "banalize_line_queue_depth",
+            "Lines waiting between a config's log source and its detector.",
+            "gauge",
+        );
+        for (id, depth) in &gauges.line_queues {
+            sample(&mut out, "banalize_line_queue_depth", &[("config", id)], depth);
+        }
+
+        let name = "banalize_notifications_dropped_total";
+        family(&mut out, name, "Match events not handed to notifiers because their queue was full.", "counter");
+        sample(&mut out, name, &[], gauges.notifications_dropped);
+
+        family(
+            &mut out,
+            "banalize_broadcast_lagged_total",
+            "Messages skipped by broadcast subscribers that fell behind.",
+            "counter",
+        );
+        let lagged = self.lagged.lock().unwrap();
+        for bus in [Bus::Events, Bus::Lines, Bus::Logs, Bus::Syslog] {
+            let missed = lagged.get(&bus).copied().unwrap_or(0);
+            sample(&mut out, "banalize_broadcast_lagged_total", &[("bus", bus.as_str())], missed);
+        }
+        drop(lagged);
+
+        let name = "banalize_firewall_command_duration_seconds";
+        family(&mut out, name, "Time the firewall backend took to apply a command.", "histogram");
+        for (command, histogram) in self.firewall.lock().unwrap().iter() {
+            let mut cumulative = 0;
+            for (i, count) in histogram.buckets.iter().enumerate() {
+                cumulative += count;
+                let le = LATENCY_BUCKETS
+                    .get(i)
+                    .map(|b| b.to_string())
+                    .unwrap_or_else(|| "+Inf".to_string());
+                sample(&mut out, &format!("{name}_bucket"), &[("command", command), ("le", &le)], cumulative);
+            }
+            sample(&mut out, &format!("{name}_sum"), &[("command", command)], histogram.sum);
+            sample(&mut out, &format!("{name}_count"), &[("command", command)], histogram.count);
+        }
+
+        if let Some(age) = gauges.geoip_age {
+            let name = "banalize_geoip_database_age_seconds";
+            family(&mut out, name, "Seconds since the GeoIP database was built.", "gauge");
+            sample(&mut out, name, &[], age);
+        }
+        out
+    }
+}
+
+fn family(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn sample(out: &mut String, name: &str, labels: &[(&str, &str)], value: impl std::fmt::Display) {
+    out.push_str(name);
+    if !labels.is_empty() {
+        let labels: Vec<String> = labels
+            .iter()
+            .map(|(k, v)| {
+                let v = v.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+                format!("{}=\"{}\"", k, v)
+            })
+            .collect();
+        let _ = write!(out, "{{{}}}", labels.join(","));
+    }
+    let _ = writeln!(out, " {}", value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_the_text_format() {
+        let metrics = Metrics::new();
+        let ssh = metrics.config("ssh");
+        ssh.lines.fetch_add(3, Ordering::Relaxed);
+        ssh.matches.fetch_add(2, Ordering::Relaxed);
+        ssh.bans.fetch_add(1, Ordering::Relaxed);
+        ssh.simulated_bans.fetch_add(2, Ordering::Relaxed);
+        metrics.config("deleted").lines.fetch_add(9, Ordering::Relaxed);
+        metrics.lagged(Bus::Lines, 4);
+        metrics.lagged(Bus::Lines, 1);
+        metrics.observe_firewall("deny", Duration::from_millis(3));
+        metrics.observe_firewall("deny", Duration::from_secs(10));
+        let gauges = Gauges {
+            config_ids: vec!["ssh".to_string(), "we\"ird".to_string()],
+            active_bans: HashMap::from([("ssh".to_string(), 1)]),
+            queues: vec![("firewall", 2)],
+            line_queues: vec![("ssh".to_string(), 0)],
+            notifications_dropped: 7,
+            geoip_age: Some(86_400),
+        };
+
+        let text = metrics.render(&gauges);
+        for line in [
+            "# TYPE banalize_lines_total counter",
+            "banalize_lines_total{config=\"ssh\"} 3",
+            "banalize_lines_total{config=\"we\\\"ird\"} 0",
+            "banalize_matches_total{config=\"ssh\"} 2",
+            "banalize_bans_total{config=\"ssh\"} 1",
+            "banalize_simulated_bans_total{config=\"ssh\"} 2",
+            "banalize_active_bans{config=\"ssh\"} 1",
+            "banalize_active_bans{config=\"we\\\"ird\"} 0",
+            "banalize_queue_depth{queue=\"firewall\"} 2",
+            "banalize_line_queue_depth{config=\"ssh\"} 0",
+            "banalize_notifications_dropped_total 7",
+            "banalize_broadcast_lagged_total{bus=\"lines\"} 5",
+            "banalize_broadcast_lagged_total{bus=\"events\"} 0",
+            "# TYPE banalize_firewall_command_duration_seconds histogram",
+            "banalize_firewall_command_duration_seconds_bucket{command=\"deny\",le=\"0.001\"} 0",
+            "banalize_firewall_command_duration_seconds_bucket{command=\"deny\",le=\"0.005\"} 1",
+            "banalize_firewall_command_duration_seconds_bucket{command=\"deny\",le=\"5\"} 1",
+            "banalize_firewall_command_duration_seconds_bucket{command=\"deny\",le=\"+Inf\"} 2",
+            "banalize_firewall_command_duration_seconds_count{command=\"deny\"} 2",
+            "banalize_geoip_database_age_seconds 86400",
+        ] {
+            assert!(text.lines().any(|l| l == line), "missing {:?} in:\n{}", line, text);
+        }
+        assert!(!text.contains("deleted"));
+    }
+}
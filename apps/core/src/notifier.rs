@@ -1,18 +1,24 @@
 use crate::config::ConfigMap;
+use crate::digest::{self, Batches};
 use crate::events::Event;
 use crate::geoip::GeoIp;
+use crate::outbox::Outbox;
 use crate::store::MemoryStore;
+use crate::template::{placeholders, render_json, render_text, TemplateVars};
+use hmac::{Hmac, Mac};
 use lettre::message::{Mailbox, MultiPart};
 use lettre::transport::smtp::authentication::Credentials;
 use lettre::transport::smtp::client::{Tls, TlsParameters};
 use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
 use std::net::IpAddr;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::{broadcast, RwLock};
-use tracing::{info, warn};
+use tokio::sync::{broadcast, mpsc, RwLock};
 use utoipa::ToSchema;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
@@ -44,6 +50,146 @@ pub struct SignalConfig {
     pub recipients: Vec<String>,
 }
 
+/// Header carrying the HMAC-SHA256 of a webhook body, when a secret is set.
+pub const WEBHOOK_SIGNATURE_HEADER: &str = "X-Banalize-Signature";
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct WebhookConfig {
+    /// http(s) endpoint the JSON body is sent to
+    pub url: String,
+    /// `POST` (default), `PUT` or `PATCH`
+    #[serde(default = "default_webhook_method")]
+    pub method: String,
+    /// Extra request headers, e.g. an `Authorization` token
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// When set, the body is signed with HMAC-SHA256 and the hex digest sent
+    /// as `X-Banalize-Signature: sha256=<digest>`
+    #[serde(default)]
+    pub secret: Option<String>,
+    /// JSON body with `{name}` placeholders (`{ip}`, `{config_name}`,
+    /// `{message}`, ...) in its strings. A string that is just one placeholder
+    /// keeps the value's type. Unset sends every variable as one object.
+    #[serde(default)]
+    #[schema(value_type = Option<Object>)]
+    pub body_template: Option<Value>,
+}
+
+fn default_webhook_method() -> String {
+    "POST".to_string()
+}
+
+impl WebhookConfig {
+    fn validate(&self) -> Result<(), String> {
+        let url = reqwest::Url::parse(&self.url).map_err(|e| format!("invalid webhook url: {}", e))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err("webhook url must be http or https".to_string());
+        }
+        if !matches!(self.method.as_str(), "POST" | "PUT" | "PATCH") {
+            return Err("webhook method must be POST, PUT or PATCH".to_string());
+        }
+        for (name, value) in &self.headers {
+            reqwest::header::HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| format!("invalid webhook header name: {}", name))?;
+            reqwest::header::HeaderValue::from_str(value)
+                .map_err(|_| format!("invalid value for webhook header {}", name))?;
+        }
+        Ok(())
+    }
+}
+
+/// Hold notifications back and send them as one digest.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BatchConfig {
+    /// How long a batch collects after its first notification (ms)
+    pub window: u64,
+    /// A batch this full is sent without waiting for the window to close
+    pub max_size: usize,
+}
+
+/// Send a summary of the last 24 hours once a day.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DailySummaryConfig {
+    /// Hour of the day (UTC, 0-23) the summary goes out
+    pub hour: u8,
+}
+
+/// Narrows a notifier to some of the events it subscribes to. Every set
+/// condition must hold; an empty filter lets everything through.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct NotifierFilters {
+    /// Only events of these configs; empty means every config
+    #[serde(default)]
+    pub config_ids: Vec<String>,
+    /// Only IPs located in these countries (ISO 3166-1 alpha-2 codes, e.g.
+    /// `"CN"`); empty means anywhere, including unknown locations
+    #[serde(default)]
+    pub countries: Vec<String>,
+    /// Never IPs located in these countries
+    #[serde(default)]
+    pub exclude_countries: Vec<String>,
+    /// Bans only: at least this many bans of the IP under the config, this
+    /// one included (1 is a first offence). Range bans have no level.
+    #[serde(default)]
+    pub min_recidive_level: Option<u32>,
+    /// Bans only: lasting at least this long (ms). Permanent bans pass.
+    #[serde(default)]
+    pub min_ban_time: Option<u64>,
+}
+
+/// What a notifier filter looks at in an event.
+pub struct EventFacts<'a> {
+    pub config_id: &'a str,
+    pub country_code: Option<&'a str>,
+    /// `Some` for a ban: its duration (`None` when permanent) and its
+    /// recidive level, when it has one.
+    pub ban: Option<(Option<u64>, Option<u32>)>,
+}
+
+impl NotifierFilters {
+    fn validate(&self) -> Result<(), String> {
+        for code in self.countries.iter().chain(&self.exclude_countries) {
+            if code.len() != 2 || !code.chars().all(|c| c.is_ascii_alphabetic()) {
+                return Err(format!("invalid country code: {}", code));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether an event with these facts should reach the notifier. The
+    /// severity thresholds only concern bans; other events pass them.
+    pub fn allows(&self, facts: &EventFacts) -> bool {
+        let listed = |codes: &[String]| {
+            facts
+                .country_code
+                .is_some_and(|cc| codes.iter().any(|c| c.eq_ignore_ascii_case(cc)))
+        };
+        if !self.config_ids.is_empty() && !self.config_ids.iter().any(|c| c == facts.config_id) {
+            return false;
+        }
+        if !self.countries.is_empty() && !listed(&self.countries) {
+            return false;
+        }
+        if listed(&self.exclude_countries) {
+            return false;
+        }
+        let Some((ban_time, level)) = facts.ban else {
+            return true;
+        };
+        if let Some(min) = self.min_recidive_level {
+            if level.is_none_or(|level| level < min) {
+                return false;
+            }
+        }
+        if let (Some(min), Some(ban_time)) = (self.min_ban_time, ban_time) {
+            if ban_time < min {
+                return false;
+            }
+        }
+        true
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct NotifierConfig {
     pub id: String,
@@ -51,36 +197,164 @@ pub struct NotifierConfig {
     pub events: Vec<NotifyEventType>,
     pub email_config: Option<EmailConfig>,
     pub signal_config: Option<SignalConfig>,
+    #[serde(default)]
+    pub webhook_config: Option<WebhookConfig>,
+    /// Replaces the built-in title, e.g. `"{country_flag} {ip} banned"`.
+    /// Placeholders are the webhook template variables; unset keeps the
+    /// default wording.
+    #[serde(default)]
+    pub title_template: Option<String>,
+    /// Replaces the built-in message (and the HTML email body), e.g.
+    /// `"{ip} hit {config_name} {match_count} times: {line}"`.
+    #[serde(default)]
+    pub message_template: Option<String>,
+    /// Unset sends each event as it happens.
+    #[serde(default)]
+    pub batch: Option<BatchConfig>,
+    #[serde(default)]
+    pub daily_summary: Option<DailySummaryConfig>,
+    #[serde(default)]
+    pub filters: NotifierFilters,
+}
+
+/// Every variable a notification carries, in both the title/message
+/// templates and webhook body templates.
+pub const TEMPLATE_VARIABLES: [&str; 21] = [
+    "event",
+    "config_id",
+    "config_name",
+    "ip",
+    "timestamp",
+    "time",
+    "line",
+    "country",
+    "country_flag",
+    "match_count",
+    "manual",
+    "reason",
+    "simulated",
+    "ban_time",
+    "expires_at",
+    "permanent",
+    "recidive_level",
+    "title",
+    "message",
+    "count",
+    "events",
+];
+
+fn validate_template(field: &str, template: &Option<String>) -> Result<(), String> {
+    let Some(template) = template else {
+        return Ok(());
+    };
+    if template.trim().is_empty() {
+        return Err(format!("{} must not be empty", field));
+    }
+    match placeholders(template)
+        .into_iter()
+        .find(|name| !TEMPLATE_VARIABLES.contains(name))
+    {
+        Some(name) => Err(format!("{} uses unknown variable {{{}}}", field, name)),
+        None => Ok(()),
+    }
 }
 
 impl NotifierConfig {
     pub fn validate(&self) -> Result<(), String> {
-        if self.events.is_empty() {
+        // A summary-only notifier needs no per-event subscription.
+        if self.events.is_empty() && self.daily_summary.is_none() {
             return Err("events must not be empty".to_string());
         }
-        match (&self.email_config, &self.signal_config) {
-            (Some(email), None) => {
+        if let Some(batch) = &self.batch {
+            if batch.window == 0 || batch.max_size == 0 {
+                return Err("batch window and max_size must be positive".to_string());
+            }
+        }
+        if self.daily_summary.as_ref().is_some_and(|d| d.hour > 23) {
+            return Err("daily_summary hour must be 0-23".to_string());
+        }
+        self.filters.validate()?;
+        validate_template("title_template", &self.title_template)?;
+        validate_template("message_template", &self.message_template)?;
+        match (&self.email_config, &self.signal_config, &self.webhook_config) {
+            (Some(email), None, None) => {
                 if email.server.is_empty() || email.recipient_email.is_empty() {
                     return Err("email server and recipient_email are required".to_string());
                 }
                 Ok(())
             }
-            (None, Some(signal)) => {
+            (None, Some(signal), None) => {
                 if signal.server.is_empty() || signal.number.is_empty() || signal.recipients.is_empty()
                 {
                     return Err("signal server, number and recipients are required".to_string());
                 }
                 Ok(())
             }
-            _ => Err("exactly one of email_config or signal_config must be set".to_string()),
+            (None, None, Some(webhook)) => webhook.validate(),
+            _ => Err(
+                "exactly one of email_config, signal_config or webhook_config must be set"
+                    .to_string(),
+            ),
         }
     }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Notification {
     pub title: String,
     pub message: String,
     pub html: Option<String>,
+    /// What webhook templates can reference: the event's fields and context,
+    /// plus `title` and `message`.
+    pub vars: TemplateVars,
+}
+
+impl Notification {
+    /// A notification whose only variables are its title and message.
+    pub fn new(title: String, message: String, html: Option<String>) -> Self {
+        let mut vars = TemplateVars::new();
+        vars.insert("title".into(), json!(title));
+        vars.insert("message".into(), json!(message));
+        Self {
+            title,
+            message,
+            html,
+            vars,
+        }
+    }
+
+    /// A notification about several events or a period rather than one
+    /// event: the per-event variables are all `null`.
+    pub fn aggregate(kind: &str, title: String, message: String, timestamp: u64) -> Self {
+        let mut notification = Self::new(title, message, None);
+        for name in TEMPLATE_VARIABLES {
+            notification.vars.entry(name.to_string()).or_insert(Value::Null);
+        }
+        notification.vars.insert("event".into(), json!(kind));
+        notification.vars.insert("timestamp".into(), json!(timestamp));
+        notification.vars.insert("time".into(), json!(iso8601(timestamp)));
+        notification
+    }
+
+    /// This notification as `cfg` words it. Templates render over the
+    /// variables — the title first, so the message can use the new
+    /// `{title}` — and a custom message drops the built-in HTML body.
+    pub fn render_for(&self, cfg: &NotifierConfig) -> Cow<'_, Notification> {
+        if cfg.title_template.is_none() && cfg.message_template.is_none() {
+            return Cow::Borrowed(self);
+        }
+        let mut n = self.clone();
+        if let Some(template) = &cfg.title_template {
+            n.title = render_text(template, &n.vars);
+            n.vars.insert("title".into(), json!(n.title));
+        }
+        if let Some(template) = &cfg.message_template {
+            n.message = render_text(template, &n.vars);
+            n.html = None;
+            n.vars.insert("message".into(), json!(n.message));
+        }
+        Cow::Owned(n)
+    }
 }
 
 async fn send_email(cfg: &EmailConfig, n: &Notification) -> Result<(), String> {
@@ -131,11 +405,17 @@ async fn send_email(cfg: &EmailConfig, n: &Notification) -> Result<(), String> {
         .map_err(|e| e.to_string())
 }
 
-async fn send_signal(cfg: &SignalConfig, n: &Notification) -> Result<(), String> {
-    let client = reqwest::Client::builder()
+/// The HTTP client Signal and webhook deliveries go through. Build it once
+/// and reuse it, so deliveries share its connection pool and TLS sessions.
+pub fn http_client() -> reqwest::Client {
+    reqwest::Client::builder()
         .timeout(Duration::from_secs(10))
         .build()
-        .map_err(|e| e.to_string())?;
+        // Only fails when the TLS backend cannot initialize, like `Client::new`.
+        .expect("HTTP client")
+}
+
+async fn send_signal(client: &reqwest::Client, cfg: &SignalConfig, n: &Notification) -> Result<(), String> {
     let body = serde_json::json!({
         "message": format!("{}\n{}", n.title, n.message),
         "number": cfg.number,
@@ -153,10 +433,52 @@ async fn send_signal(cfg: &SignalConfig, n: &Notification) -> Result<(), String>
     Ok(())
 }
 
-pub async fn send(cfg: &NotifierConfig, n: &Notification) -> Result<(), String> {
-    match (&cfg.email_config, &cfg.signal_config) {
-        (Some(email), _) => send_email(email, n).await,
-        (_, Some(signal)) => send_signal(signal, n).await,
+/// Hex HMAC-SHA256 of `body` under `secret`.
+fn sign(secret: &str, body: &[u8]) -> String {
+    // HMAC accepts keys of any length, so this cannot fail.
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("any key length");
+    mac.update(body);
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+async fn send_webhook(client: &reqwest::Client, cfg: &WebhookConfig, n: &Notification) -> Result<(), String> {
+    let body = match &cfg.body_template {
+        Some(template) => render_json(template, &n.vars),
+        None => Value::Object(
+            n.vars
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.clone()))
+                .collect(),
+        ),
+    };
+    let body = serde_json::to_vec(&body).map_err(|e| e.to_string())?;
+
+    let method = reqwest::Method::from_bytes(cfg.method.as_bytes()).map_err(|e| e.to_string())?;
+    let mut request = client
+        .request(method, &cfg.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json");
+    for (name, value) in &cfg.headers {
+        request = request.header(name, value);
+    }
+    if let Some(secret) = &cfg.secret {
+        request = request.header(WEBHOOK_SIGNATURE_HEADER, format!("sha256={}", sign(secret, &body)));
+    }
+    let res = request.body(body).send().await.map_err(|e| e.to_string())?;
+    if !res.status().is_success() {
+        return Err(format!("webhook returned {}", res.status()));
+    }
+    Ok(())
+}
+
+pub async fn send(client: &reqwest::Client, cfg: &NotifierConfig, n: &Notification) -> Result<(), String> {
+    match (&cfg.email_config, &cfg.signal_config, &cfg.webhook_config) {
+        (Some(email), _, _) => send_email(email, n).await,
+        (_, Some(signal), _) => send_signal(client, signal, n).await,
+        (_, _, Some(webhook)) => send_webhook(client, webhook, n).await,
         _ => Err("notifier has no channel configured".to_string()),
     }
 }
@@ -169,7 +491,7 @@ fn escape_html(s: &str) -> String {
 }
 
 /// ISO 8601 UTC from a ms-epoch timestamp (no date crate in the tree).
-fn iso8601(timestamp_ms: u64) -> String {
+pub fn iso8601(timestamp_ms: u64) -> String {
     let secs = (timestamp_ms / 1000) as i64;
     let ms = timestamp_ms % 1000;
     let days = secs.div_euclid(86_400);
@@ -199,18 +521,31 @@ fn iso8601(timestamp_ms: u64) -> String {
 
 struct BanContext {
     config_name: String,
-    regex: Option<String>,
+    regexes: Vec<String>,
     country: Option<(String, String)>, // (flag, name)
     line: Option<String>,
     match_count: Option<usize>,
+    /// `Some` for a manual ban, holding the operator's reason if any.
+    manual: Option<Option<String>>,
+    recidive_level: Option<u32>,
+    /// Would-be ban of a config in monitor mode
+    simulated: bool,
 }
 
 fn build_ban_text(ip: &str, timestamp: u64, ctx: &BanContext) -> String {
-    let mut lines = vec![format!("[{}] New ban", ctx.config_name), format!("IP: {}", ip)];
+    let headline = if ctx.simulated {
+        format!("[{}] Would ban (monitor mode, not enforced)", ctx.config_name)
+    } else {
+        format!("[{}] New ban", ctx.config_name)
+    };
+    let mut lines = vec![headline, format!("IP: {}", ip)];
     if let Some((flag, name)) = &ctx.country {
         lines.push(format!("Country: {} {}", flag, name));
     }
-    if let Some(regex) = &ctx.regex {
+    if let Some(reason) = &ctx.manual {
+        lines.push(format!("Manual ban: {}", reason.as_deref().unwrap_or("no reason given")));
+    }
+    for regex in &ctx.regexes {
         lines.push(format!("Regex: {}", regex));
     }
     if let Some(line) = &ctx.line {
@@ -239,11 +574,26 @@ fn build_ban_html(ip: &str, timestamp: u64, ctx: &BanContext) -> String {
         .as_ref()
         .map(|(flag, name)| row("Country", &format!("{} {}", flag, escape_html(name))))
         .unwrap_or_default();
-    let regex_row = ctx
-        .regex
+    let manual_row = ctx
+        .manual
         .as_ref()
-        .map(|r| row("Regex Pattern", &format!("<code>{}</code>", escape_html(r))))
+        .map(|reason| {
+            row(
+                "Manual Ban",
+                &escape_html(reason.as_deref().unwrap_or("no reason given")),
+            )
+        })
         .unwrap_or_default();
+    let regex_row = if ctx.regexes.is_empty() {
+        String::new()
+    } else {
+        let patterns: Vec<String> = ctx
+            .regexes
+            .iter()
+            .map(|r| format!("<code>{}</code>", escape_html(r)))
+            .collect();
+        row("Regex Pattern", &patterns.join("<br>"))
+    };
     let log_row = ctx
         .line
         .as_ref()
@@ -261,15 +611,20 @@ fn build_ban_html(ip: &str, timestamp: u64, ctx: &BanContext) -> String {
         .match_count
         .map(|c| row("Match Count", &c.to_string()))
         .unwrap_or_default();
+    let (color, heading) = if ctx.simulated {
+        ("#fd7e14", "IP Would Be Banned (monitor mode)")
+    } else {
+        ("#dc3545", "IP Banned")
+    };
 
     format!(
         r#"
       <div style="font-family: Arial, sans-serif; max-width: 600px; margin: 0 auto;">
-        <div style="background: #dc3545; color: white; padding: 20px; text-align: center;">
-          <h1 style="margin: 0;">IP Banned</h1>
+        <div style="background: {}; color: white; padding: 20px; text-align: center;">
+          <h1 style="margin: 0;">{}</h1>
         </div>
         <div style="padding: 20px; background: #f8f9fa;">
-          <table style="width: 100%; border-collapse: collapse;">{}{}{}{}{}{}
+          <table style="width: 100%; border-collapse: collapse;">{}{}{}{}{}{}{}
             <tr>
               <td style="padding: 10px; font-weight: bold;">Timestamp</td>
               <td style="padding: 10px;">{}</td>
@@ -281,9 +636,12 @@ fn build_ban_html(ip: &str, timestamp: u64, ctx: &BanContext) -> String {
         </div>
       </div>
     "#,
+        color,
+        heading,
         row("IP Address", &escape_html(ip)),
         country_row,
         row("Configuration", &escape_html(&ctx.config_name)),
+        manual_row,
         regex_row,
         log_row,
         match_count_row,
@@ -291,134 +649,321 @@ fn build_ban_html(ip: &str, timestamp: u64, ctx: &BanContext) -> String {
     )
 }
 
+fn ban_notification(ip: &str, timestamp: u64, ctx: &BanContext) -> Notification {
+    let title = if ctx.simulated {
+        "Banalize: IP Would Be Banned (monitor)"
+    } else {
+        "Banalize: IP Banned"
+    };
+    Notification::new(
+        title.to_string(),
+        build_ban_text(ip, timestamp, ctx),
+        Some(build_ban_html(ip, timestamp, ctx)),
+    )
+}
+
+/// A detected ban of a documentation address, with every variable filled
+/// in, so the test endpoint can show how a notifier's templates read.
+pub fn sample_ban_notification() -> Notification {
+    let ip = "192.0.2.1";
+    let timestamp = crate::detector::now_millis();
+    let event = Event::Ban {
+        id: "sample".to_string(),
+        config_id: "sample".to_string(),
+        ip: ip.to_string(),
+        timestamp,
+        ban_time: Some(3_600_000),
+        recidive_level: Some(2),
+        manual: false,
+        reason: None,
+        simulated: false,
+    };
+    let ctx = BanContext {
+        config_name: "Sample config".to_string(),
+        regexes: vec!["Failed password for .* from <IP>".to_string()],
+        country: Some(("🇳🇱".to_string(), "Netherlands".to_string())),
+        line: Some(format!("sshd[4242]: Failed password for root from {} port 22 ssh2", ip)),
+        match_count: Some(5),
+        manual: None,
+        recidive_level: Some(2),
+        simulated: false,
+    };
+    let mut notification = ban_notification(ip, timestamp, &ctx);
+    notification
+        .vars
+        .extend(event_vars(NotifyEventType::Ban, &event, &ctx.config_name, Some(&ctx)));
+    notification
+}
+
 /// Most recent matched line per (config_id, ip), kept so Ban notifications can
 /// include the log line that triggered them (Match is emitted before Ban for
 /// the same line). Bounded: cleared wholesale when it outgrows the cap.
 const LINE_CACHE_CAP: usize = 1024;
 
-/// Consumes the lossy event bus and fans each event out to every notifier
-/// subscribed to its type. Sends run in spawned tasks so a slow SMTP server
-/// never lags the broadcast receiver; failures are logged and dropped.
+/// Events the dispatcher takes off its channel at once. Their notifications
+/// are queued in one outbox transaction.
+const DISPATCH_BATCH: usize = 256;
+
+/// Consumes the notification channel and fans each event out to every
+/// notifier subscribed to its type, through the outbox: the dispatcher only
+/// renders and queues, so a slow SMTP server never holds it up. Events are
+/// drained in batches of up to `DISPATCH_BATCH` so a burst costs one outbox
+/// commit per batch rather than one per notification.
 pub async fn run_dispatcher(
-    mut rx: broadcast::Receiver<Event>,
+    mut rx: mpsc::Receiver<Event>,
     mut shutdown_rx: broadcast::Receiver<()>,
     notifiers: Arc<RwLock<Vec<NotifierConfig>>>,
     configs: Arc<RwLock<ConfigMap>>,
     store: Arc<MemoryStore>,
     geoip: Arc<GeoIp>,
+    outbox: Arc<Outbox>,
 ) {
     let mut line_cache: HashMap<(String, String), String> = HashMap::new();
+    let mut batches = Batches::default();
+    let mut events: Vec<Event> = Vec::with_capacity(DISPATCH_BATCH);
 
     loop {
-        let event = tokio::select! {
-            _ = shutdown_rx.recv() => return,
-            result = rx.recv() => match result {
-                Ok(event) => event,
-                Err(broadcast::error::RecvError::Lagged(n)) => {
-                    warn!("Notification dispatcher lagged, dropped {} events", n);
-                    continue;
+        let next_flush = batches.next_deadline();
+        let mut queued: Vec<(String, Notification)> = Vec::new();
+        tokio::select! {
+            _ = shutdown_rx.recv() => {
+                // Open batches go to the outbox now, to be sent after restart
+                // if not before.
+                outbox.enqueue_all(&flushed(&notifiers, batches.take_all()).await).await;
+                return;
+            }
+            _ = async {
+                match next_flush {
+                    Some(deadline) => tokio::time::sleep_until(deadline).await,
+                    None => std::future::pending().await,
+                }
+            } => {
+                let due = batches.take_due(tokio::time::Instant::now());
+                outbox.enqueue_all(&flushed(&notifiers, due).await).await;
+                continue;
+            }
+            n = rx.recv_many(&mut events, DISPATCH_BATCH) => {
+                if n == 0 {
+                    return;
                 }
-                Err(broadcast::error::RecvError::Closed) => return,
-            },
-        };
-
-        if let Event::Match {
-            config_id, ip, line, ..
-        } = &event
-        {
-            if line_cache.len() >= LINE_CACHE_CAP {
-                line_cache.clear();
             }
-            line_cache.insert((config_id.clone(), ip.clone()), line.clone());
-        }
-
-        let event_type = match &event {
-            Event::Match { .. } => NotifyEventType::Match,
-            Event::Ban { .. } => NotifyEventType::Ban,
-            Event::Unban { .. } => NotifyEventType::Unban,
-        };
-
-        let subscribed: Vec<NotifierConfig> = notifiers
-            .read()
-            .await
-            .iter()
-            .filter(|c| c.events.contains(&event_type))
-            .cloned()
-            .collect();
-        if subscribed.is_empty() {
-            continue;
         }
 
-        let notification = match &event {
-            Event::Match { config_id, ip, .. } => {
-                let name = config_name(&configs, config_id).await;
-                Notification {
-                    title: "Banalize: Match found".to_string(),
-                    message: format!("[{}] New match for IP {}", name, ip),
-                    html: None,
+        for event in events.drain(..) {
+            if let Event::Match {
+                config_id, ip, line, ..
+            } = &event
+            {
+                if line_cache.len() >= LINE_CACHE_CAP {
+                    line_cache.clear();
                 }
+                line_cache.insert((config_id.clone(), ip.clone()), line.clone());
             }
-            Event::Unban { config_id, ip, .. } => {
-                let name = config_name(&configs, config_id).await;
-                Notification {
-                    title: "Banalize: IP Unbanned".to_string(),
-                    message: format!("[{}] IP {} unbanned", name, ip),
-                    html: None,
-                }
+
+            let event_type = match &event {
+                Event::Match { .. } => NotifyEventType::Match,
+                Event::Ban { .. } => NotifyEventType::Ban,
+                Event::Unban { .. } => NotifyEventType::Unban,
+            };
+
+            let subscribed: Vec<NotifierConfig> = notifiers
+                .read()
+                .await
+                .iter()
+                .filter(|c| c.events.contains(&event_type))
+                .cloned()
+                .collect();
+            if subscribed.is_empty() {
+                continue;
             }
-            Event::Ban {
+
+            let (config_id, ip) = match &event {
+                Event::Match { config_id, ip, .. }
+                | Event::Ban { config_id, ip, .. }
+                | Event::Unban { config_id, ip, .. } => (config_id, ip),
+            };
+            let parsed_ip = ip.parse::<IpAddr>().ok();
+            let ip_info = parsed_ip.map(|addr| geoip.lookup(addr)).unwrap_or_default();
+            let recidive_level = match &event {
+                Event::Ban { recidive_level, .. } => *recidive_level,
+                _ => None,
+            };
+            let facts = EventFacts {
                 config_id,
-                ip,
-                timestamp,
-            } => {
-                let config = configs.read().await.get(config_id).cloned();
-                let parsed_ip = ip.parse::<IpAddr>().ok();
-                let country = parsed_ip.and_then(|addr| {
-                    let info = geoip.lookup(addr);
-                    match (info.flag, info.country_name) {
-                        (Some(flag), Some(name)) => Some((flag, name)),
-                        _ => None,
-                    }
-                });
-                let match_count = match (&config, &parsed_ip) {
-                    (Some(cfg), Some(addr)) => Some(store.count_matches(
-                        config_id,
-                        addr,
-                        timestamp.saturating_sub(cfg.find_time),
-                    )),
+                country_code: ip_info.country_code.as_deref(),
+                ban: match &event {
+                    Event::Ban { ban_time, .. } => Some((*ban_time, recidive_level)),
                     _ => None,
-                };
-                let ctx = BanContext {
-                    config_name: config
-                        .as_ref()
-                        .map(|c| c.name.clone())
-                        .unwrap_or_else(|| config_id.clone()),
-                    regex: config.as_ref().map(|c| c.regex.clone()),
-                    country,
-                    line: line_cache.remove(&(config_id.clone(), ip.clone())),
-                    match_count,
-                };
-                Notification {
-                    title: "Banalize: IP Banned".to_string(),
-                    message: build_ban_text(ip, *timestamp, &ctx),
-                    html: Some(build_ban_html(ip, *timestamp, &ctx)),
-                }
+                },
+            };
+            let subscribed: Vec<NotifierConfig> = subscribed
+                .into_iter()
+                .filter(|n| n.filters.allows(&facts))
+                .collect();
+            if subscribed.is_empty() {
+                continue;
             }
-        };
 
-        let notification = Arc::new(notification);
-        for notifier in subscribed {
-            let notification = notification.clone();
-            tokio::spawn(async move {
-                match send(&notifier, &notification).await {
-                    Ok(()) => info!("Notification sent via notifier {}", notifier.id),
-                    Err(e) => warn!("Notifier {} failed: {}", notifier.id, e),
+            let (mut notification, name, ctx) = match &event {
+                Event::Match { config_id, ip, .. } => {
+                    let name = config_name(&configs, config_id).await;
+                    let notification = Notification::new(
+                        "Banalize: Match found".to_string(),
+                        format!("[{}] New match for IP {}", name, ip),
+                        None,
+                    );
+                    (notification, name, None)
                 }
-            });
+                Event::Unban { config_id, ip, .. } => {
+                    let name = config_name(&configs, config_id).await;
+                    let notification = Notification::new(
+                        "Banalize: IP Unbanned".to_string(),
+                        format!("[{}] IP {} unbanned", name, ip),
+                        None,
+                    );
+                    (notification, name, None)
+                }
+                Event::Ban {
+                    config_id,
+                    ip,
+                    timestamp,
+                    manual,
+                    reason,
+                    simulated,
+                    ..
+                } => {
+                    let config = configs.read().await.get(config_id).cloned();
+                    let country = match (&ip_info.flag, &ip_info.country_name) {
+                        (Some(flag), Some(name)) => Some((flag.clone(), name.clone())),
+                        _ => None,
+                    };
+                    // A manual ban has no detection behind it: no pattern, log
+                    // line or match count to report.
+                    let detected = config.as_ref().filter(|_| !manual);
+                    let match_count = match (detected, &parsed_ip) {
+                        (Some(cfg), Some(addr)) => Some(store.count_matches(
+                            config_id,
+                            addr,
+                            timestamp.saturating_sub(cfg.find_time),
+                        )),
+                        _ => None,
+                    };
+                    let ctx = BanContext {
+                        config_name: config
+                            .as_ref()
+                            .map(|c| c.name.clone())
+                            .unwrap_or_else(|| config_id.clone()),
+                        regexes: detected.map(|c| c.regexes.clone()).unwrap_or_default(),
+                        country,
+                        line: line_cache
+                            .remove(&(config_id.clone(), ip.clone()))
+                            .filter(|_| !manual),
+                        match_count,
+                        manual: manual.then(|| reason.clone()),
+                        recidive_level,
+                        simulated: *simulated,
+                    };
+                    let notification = ban_notification(ip, *timestamp, &ctx);
+                    (notification, ctx.config_name.clone(), Some(ctx))
+                }
+            };
+            notification
+                .vars
+                .extend(event_vars(event_type, &event, &name, ctx.as_ref()));
+
+            let notification = Arc::new(notification);
+            for notifier in subscribed {
+                if notifier.batch.is_none() {
+                    queued.push(rendered(&notifier, std::slice::from_ref(&notification)));
+                } else if let Some((_, items)) = batches.push(&notifier, notification.clone()) {
+                    queued.push(rendered(&notifier, &items));
+                }
+            }
         }
+        // Everything the drained events produced goes in one transaction.
+        outbox.enqueue_all(&queued).await;
     }
 }
 
+/// Render batches taken from `Batches` for their notifiers as they are now.
+/// The batch of a notifier deleted since it opened is dropped.
+async fn flushed(
+    notifiers: &RwLock<Vec<NotifierConfig>>,
+    batches: Vec<(String, Vec<Arc<Notification>>)>,
+) -> Vec<(String, Notification)> {
+    if batches.is_empty() {
+        return Vec::new();
+    }
+    let notifiers = notifiers.read().await;
+    batches
+        .into_iter()
+        .filter_map(|(id, items)| {
+            let notifier = notifiers.iter().find(|n| n.id == id)?;
+            Some(rendered(notifier, &items))
+        })
+        .collect()
+}
+
+/// What `notifier` should receive for `items`: one notification goes through
+/// the notifier's templates, several go out as a digest.
+fn rendered(notifier: &NotifierConfig, items: &[Arc<Notification>]) -> (String, Notification) {
+    let notification = match items {
+        [single] => single.render_for(notifier).into_owned(),
+        items => digest::digest(items),
+    };
+    (notifier.id.clone(), notification)
+}
+
+/// Template variables for an event. Every name is always present, `null`
+/// when it does not apply, so one template can serve all event types.
+fn event_vars(
+    event_type: NotifyEventType,
+    event: &Event,
+    config_name: &str,
+    ctx: Option<&BanContext>,
+) -> TemplateVars {
+    let (config_id, ip, timestamp) = match event {
+        Event::Match { config_id, ip, timestamp, .. }
+        | Event::Ban { config_id, ip, timestamp, .. }
+        | Event::Unban { config_id, ip, timestamp } => (config_id, ip, *timestamp),
+    };
+    let line = match event {
+        Event::Match { line, .. } => Some(line.clone()),
+        _ => ctx.and_then(|c| c.line.clone()),
+    };
+    let (ban_time, permanent, simulated) = match event {
+        Event::Ban { ban_time, simulated, .. } => (*ban_time, Some(ban_time.is_none()), Some(*simulated)),
+        _ => (None, None, None),
+    };
+    let country = ctx.and_then(|c| c.country.as_ref());
+    let manual = ctx.map(|c| c.manual.is_some());
+
+    [
+        ("event", json!(event_type)),
+        ("config_id", json!(config_id)),
+        ("config_name", json!(config_name)),
+        ("ip", json!(ip)),
+        ("timestamp", json!(timestamp)),
+        ("time", json!(iso8601(timestamp))),
+        ("line", json!(line)),
+        ("country", json!(country.map(|(_, name)| name))),
+        ("country_flag", json!(country.map(|(flag, _)| flag))),
+        ("match_count", json!(ctx.and_then(|c| c.match_count))),
+        ("manual", json!(manual)),
+        ("reason", json!(ctx.and_then(|c| c.manual.clone().flatten()))),
+        ("simulated", json!(simulated)),
+        ("ban_time", json!(ban_time)),
+        ("expires_at", json!(ban_time.map(|t| timestamp.saturating_add(t)))),
+        ("permanent", json!(permanent)),
+        ("recidive_level", json!(ctx.and_then(|c| c.recidive_level))),
+        ("count", Value::Null),
+        ("events", Value::Null),
+    ]
+    .into_iter()
+    .map(|(name, value)| (name.to_string(), value))
+    .collect()
+}
+
 async fn config_name(configs: &Arc<RwLock<ConfigMap>>, config_id: &str) -> String {
     configs
         .read()
@@ -427,3 +972,168 @@ async fn config_name(configs: &Arc<RwLock<ConfigMap>>, config_id: &str) -> Strin
         .map(|c| c.name.clone())
         .unwrap_or_else(|| config_id.to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn webhook(url: &str, method: &str) -> NotifierConfig {
+        NotifierConfig {
+            id: "w".to_string(),
+            events: vec![NotifyEventType::Ban],
+            email_config: None,
+            signal_config: None,
+            webhook_config: Some(WebhookConfig {
+                url: url.to_string(),
+                method: method.to_string(),
+                headers: BTreeMap::from([("X-Team".to_string(), "sec".to_string())]),
+                secret: None,
+                body_template: None,
+            }),
+            title_template: None,
+            message_template: None,
+            batch: None,
+            daily_summary: None,
+            filters: NotifierFilters::default(),
+        }
+    }
+
+    #[test]
+    fn webhook_validation() {
+        assert!(webhook("https://hooks.example.com/x", "POST").validate().is_ok());
+        assert!(webhook("ftp://example.com", "POST").validate().is_err());
+        assert!(webhook("not a url", "POST").validate().is_err());
+        assert!(webhook("https://example.com", "GET").validate().is_err());
+
+        let mut bad_header = webhook("https://example.com", "PUT");
+        if let Some(w) = bad_header.webhook_config.as_mut() {
+            w.headers.insert("bad header".to_string(), "x".to_string());
+        }
+        assert!(bad_header.validate().is_err());
+
+        // Exactly one channel.
+        let mut two = webhook("https://example.com", "POST");
+        two.signal_config = Some(SignalConfig {
+            server: "http://localhost".to_string(),
+            number: "+1".to_string(),
+            recipients: vec!["+2".to_string()],
+        });
+        assert!(two.validate().is_err());
+    }
+
+    #[test]
+    fn template_validation() {
+        let mut cfg = webhook("https://example.com", "POST");
+        cfg.title_template = Some("{country_flag} {ip} banned".to_string());
+        cfg.message_template = Some("{title}: {line} ({match_count})".to_string());
+        assert!(cfg.validate().is_ok());
+
+        cfg.message_template = Some("{ip} via {config}".to_string());
+        assert!(cfg.validate().unwrap_err().contains("{config}"));
+        cfg.message_template = Some("  ".to_string());
+        assert!(cfg.validate().is_err());
+    }
+
+    #[test]
+    fn batch_and_summary_validation() {
+        let mut cfg = webhook("https://example.com", "POST");
+        cfg.batch = Some(BatchConfig { window: 60_000, max_size: 0 });
+        assert!(cfg.validate().is_err());
+        cfg.batch = Some(BatchConfig { window: 60_000, max_size: 50 });
+        assert!(cfg.validate().is_ok());
+
+        // Summary-only: no events needed, but a valid hour.
+        cfg.events.clear();
+        assert!(cfg.validate().is_err());
+        cfg.daily_summary = Some(DailySummaryConfig { hour: 24 });
+        assert!(cfg.validate().is_err());
+        cfg.daily_summary = Some(DailySummaryConfig { hour: 8 });
+        assert!(cfg.validate().is_ok());
+    }
+
+    #[test]
+    fn filters_scope_by_config_country_and_severity() {
+        let ssh_ban = |country, ban_time, level| EventFacts {
+            config_id: "prod-ssh",
+            country_code: country,
+            ban: Some((ban_time, level)),
+        };
+        let everything = NotifierFilters::default();
+        assert!(everything.allows(&ssh_ban(None, Some(60_000), None)));
+
+        // The on-call group: repeat offenders on production SSH only.
+        let on_call = NotifierFilters {
+            config_ids: vec!["prod-ssh".to_string()],
+            min_recidive_level: Some(2),
+            ..Default::default()
+        };
+        assert!(on_call.allows(&ssh_ban(None, Some(60_000), Some(3))));
+        assert!(!on_call.allows(&ssh_ban(None, Some(60_000), Some(1))));
+        assert!(!on_call.allows(&ssh_ban(None, None, None)), "range bans have no level");
+        let nginx = EventFacts { config_id: "nginx", ..ssh_ban(None, Some(60_000), Some(3)) };
+        assert!(!on_call.allows(&nginx));
+
+        let long = NotifierFilters { min_ban_time: Some(3_600_000), ..Default::default() };
+        assert!(!long.allows(&ssh_ban(None, Some(60_000), None)));
+        assert!(long.allows(&ssh_ban(None, None, None)), "permanent bans pass");
+        let unban = EventFacts { config_id: "prod-ssh", country_code: None, ban: None };
+        assert!(long.allows(&unban), "thresholds only concern bans");
+
+        let countries = NotifierFilters {
+            countries: vec!["cn".to_string(), "RU".to_string()],
+            exclude_countries: vec!["RU".to_string()],
+            ..Default::default()
+        };
+        assert!(countries.allows(&ssh_ban(Some("CN"), None, None)));
+        assert!(!countries.allows(&ssh_ban(Some("RU"), None, None)));
+        assert!(!countries.allows(&ssh_ban(Some("FR"), None, None)));
+        assert!(!countries.allows(&ssh_ban(None, None, None)));
+        let not_fr = NotifierFilters { exclude_countries: vec!["FR".to_string()], ..Default::default() };
+        assert!(not_fr.allows(&ssh_ban(None, None, None)), "unknown locations are not excluded");
+    }
+
+    #[test]
+    fn filter_country_codes_are_validated() {
+        let mut cfg = webhook("https://example.com", "POST");
+        cfg.filters.countries = vec!["CHN".to_string()];
+        assert!(cfg.validate().is_err());
+        cfg.filters.countries = vec!["CN".to_string()];
+        assert!(cfg.validate().is_ok());
+    }
+
+    #[test]
+    fn template_variables_match_event_vars() {
+        let sample = sample_ban_notification();
+        let names: Vec<&str> = sample.vars.keys().map(String::as_str).collect();
+        let mut expected = TEMPLATE_VARIABLES.to_vec();
+        expected.sort_unstable();
+        assert_eq!(names, expected);
+    }
+
+    #[test]
+    fn render_for_applies_templates_or_keeps_defaults() {
+        let sample = sample_ban_notification();
+        let mut cfg = webhook("https://example.com", "POST");
+        assert!(matches!(sample.render_for(&cfg), Cow::Borrowed(_)));
+
+        cfg.title_template = Some("{country_flag} {ip} banned".to_string());
+        let titled = sample.render_for(&cfg);
+        assert_eq!(titled.title, "🇳🇱 192.0.2.1 banned");
+        assert_eq!(titled.message, sample.message);
+        assert!(titled.html.is_some());
+
+        cfg.message_template = Some("{title} by {config_name} after {match_count}".to_string());
+        let rendered = sample.render_for(&cfg);
+        assert_eq!(rendered.message, "🇳🇱 192.0.2.1 banned by Sample config after 5");
+        assert!(rendered.html.is_none());
+        assert_eq!(rendered.vars["message"], json!(rendered.message));
+    }
+
+    #[test]
+    fn sign_matches_rfc4231() {
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+}
@@ -0,0 +1,298 @@
+use crate::database::{DeliveryRecord, SqliteDatabase};
+use crate::detector::now_millis;
+use crate::notifier::{self, Notification, NotifierConfig};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, Mutex, Notify, RwLock};
+use tokio::task::JoinSet;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Attempts before a delivery is marked failed.
+pub const MAX_ATTEMPTS: u32 = 8;
+/// Ceiling for the exponential backoff between attempts.
+const MAX_BACKOFF_MS: u64 = 60 * 60 * 1000;
+/// How often due retries are looked for; new notifications wake the worker
+/// straight away.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Deliveries attempted concurrently per round.
+const ROUND_SIZE: u32 = 32;
+/// Longest wait between retention sweeps; a shorter retention sweeps as often
+/// as it is long.
+const MAX_PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    /// Not sent yet, or waiting for a retry
+    Pending,
+    Delivered,
+    /// Out of attempts, or its notifier is gone
+    Failed,
+}
+
+impl DeliveryStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryStatus::Pending => "pending",
+            DeliveryStatus::Delivered => "delivered",
+            DeliveryStatus::Failed => "failed",
+        }
+    }
+}
+
+impl std::str::FromStr for DeliveryStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(DeliveryStatus::Pending),
+            "delivered" => Ok(DeliveryStatus::Delivered),
+            "failed" => Ok(DeliveryStatus::Failed),
+            other => Err(format!("unknown delivery status: {}", other)),
+        }
+    }
+}
+
+/// Wait before attempt `attempts + 1`: `base` doubled per failed attempt,
+/// capped at an hour.
+fn backoff(base_ms: u64, attempts: u32) -> u64 {
+    let doublings = attempts.saturating_sub(1).min(20);
+    base_ms.saturating_mul(1 << doublings).min(MAX_BACKOFF_MS)
+}
+
+/// Every notification is written to the `notification_outbox` table in
+/// events.db before it is sent, then delivered from there by `run`: retried
+/// with exponential backoff until it goes through or runs out of attempts.
+/// Pending rows survive a restart, so delivery is at-least-once. Delivered
+/// and failed rows are kept for `retention_ms`, then swept.
+pub struct Outbox {
+    db: Arc<Mutex<SqliteDatabase>>,
+    wake: Notify,
+    retry_base_ms: u64,
+    retention_ms: u64,
+    /// notifier_id -> error of its latest attempt, while it keeps failing.
+    failing: std::sync::Mutex<HashMap<String, String>>,
+    /// Shared by every delivery.
+    http: reqwest::Client,
+}
+
+impl Outbox {
+    pub fn new(db: Arc<Mutex<SqliteDatabase>>, retry_base_ms: u64, retention_ms: u64) -> Self {
+        Self {
+            db,
+            wake: Notify::new(),
+            retry_base_ms,
+            retention_ms,
+            failing: std::sync::Mutex::default(),
+            http: notifier::http_client(),
+        }
+    }
+
+    /// The HTTP client deliveries go through, for sends outside the outbox.
+    pub fn http(&self) -> &reqwest::Client {
+        &self.http
+    }
+
+    /// Queue `notification` for `notifier_id` and wake the delivery worker.
+    pub async fn enqueue(&self, notifier_id: &str, notification: &Notification) {
+        self.enqueue_all(&[(notifier_id.to_string(), notification.clone())])
+            .await;
+    }
+
+    /// Queue `(notifier_id, notification)` pairs in one transaction and wake
+    /// the delivery worker.
+    pub async fn enqueue_all(&self, items: &[(String, Notification)]) {
+        let now = now_millis();
+        let deliveries: Vec<DeliveryRecord> = items
+            .iter()
+            .filter_map(|(notifier_id, notification)| delivery(notifier_id, notification, now))
+            .collect();
+        if deliveries.is_empty() {
+            return;
+        }
+        if let Err(e) = self.db.lock().await.insert_deliveries(&deliveries) {
+            warn!("Failed to queue {} notifications: {}", deliveries.len(), e);
+            return;
+        }
+        self.wake.notify_one();
+    }
+
+    /// Notifiers whose latest delivery attempt failed, with its error.
+    pub fn failing(&self) -> HashMap<String, String> {
+        self.failing.lock().unwrap().clone()
+    }
+
+    /// Deliver due notifications until shutdown.
+    pub async fn run(
+        self: Arc<Self>,
+        notifiers: Arc<RwLock<Vec<NotifierConfig>>>,
+        mut shutdown_rx: broadcast::Receiver<()>,
+    ) {
+        let prune_every = Duration::from_millis(self.retention_ms).min(MAX_PRUNE_INTERVAL);
+        let mut next_prune = tokio::time::Instant::now();
+        loop {
+            if tokio::time::Instant::now() >= next_prune {
+                self.prune().await;
+                next_prune = tokio::time::Instant::now() + prune_every;
+            }
+            // A full round may have left more due: go again without waiting.
+            if self.deliver_due(&notifiers).await {
+                continue;
+            }
+            tokio::select! {
+                _ = shutdown_rx.recv() => return,
+                _ = self.wake.notified() => {}
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+            }
+        }
+    }
+
+    /// Drop finished deliveries older than the retention.
+    async fn prune(&self) {
+        let before = now_millis().saturating_sub(self.retention_ms);
+        match self.db.lock().await.prune_deliveries(before) {
+            Ok(0) => {}
+            Ok(n) => info!("Pruned {} finished notification deliveries", n),
+            Err(e) => warn!("Failed to prune the notification outbox: {}", e),
+        }
+    }
+
+    /// Attempt one round of due deliveries concurrently. Returns true when
+    /// the round was full.
+    async fn deliver_due(&self, notifiers: &RwLock<Vec<NotifierConfig>>) -> bool {
+        let due = match self.db.lock().await.get_due_deliveries(now_millis(), ROUND_SIZE) {
+            Ok(due) => due,
+            Err(e) => {
+                warn!("Failed to read the notification outbox: {}", e);
+                return false;
+            }
+        };
+        let full = due.len() == ROUND_SIZE as usize;
+
+        let configs = notifiers.read().await.clone();
+        let mut sends = JoinSet::new();
+        for delivery in due {
+            let notifier = configs.iter().find(|n| n.id == delivery.notifier_id).cloned();
+            let http = self.http.clone();
+            sends.spawn(async move {
+                let result = match notifier {
+                    Some(notifier) => attempt(&http, &notifier, &delivery).await,
+                    None => Err("notifier no longer exists".to_string()),
+                };
+                (delivery, result)
+            });
+        }
+
+        while let Some(joined) = sends.join_next().await {
+            let Ok((mut delivery, result)) = joined else {
+                continue;
+            };
+            let now = now_millis();
+            delivery.attempts += 1;
+            match &result {
+                Ok(()) => self.failing.lock().unwrap().remove(&delivery.notifier_id),
+                Err(e) => self.failing.lock().unwrap().insert(delivery.notifier_id.clone(), e.clone()),
+            };
+            let status = match result {
+                Ok(()) => {
+                    info!("Notification delivered via notifier {}", delivery.notifier_id);
+                    delivery.delivered_at = Some(now);
+                    delivery.last_error = None;
+                    DeliveryStatus::Delivered
+                }
+                Err(e) => {
+                    let gone = !configs.iter().any(|n| n.id == delivery.notifier_id);
+                    let status = if gone || delivery.attempts >= MAX_ATTEMPTS {
+                        DeliveryStatus::Failed
+                    } else {
+                        delivery.next_attempt_at = now + backoff(self.retry_base_ms, delivery.attempts);
+                        DeliveryStatus::Pending
+                    };
+                    warn!(
+                        "Notifier {} failed (attempt {}, {}): {}",
+                        delivery.notifier_id,
+                        delivery.attempts,
+                        status.as_str(),
+                        e
+                    );
+                    delivery.last_error = Some(e);
+                    status
+                }
+            };
+            delivery.status = status.as_str().to_string();
+            if let Err(e) = self.db.lock().await.update_delivery(&delivery) {
+                warn!("Failed to record delivery {}: {}", delivery.id, e);
+            }
+        }
+        full
+    }
+}
+
+/// The outbox row for a new notification, due straight away.
+fn delivery(notifier_id: &str, notification: &Notification, now: u64) -> Option<DeliveryRecord> {
+    let payload = match serde_json::to_string(notification) {
+        Ok(payload) => payload,
+        Err(e) => {
+            warn!("Failed to serialize notification for {}: {}", notifier_id, e);
+            return None;
+        }
+    };
+    let var = |name: &str| {
+        notification
+            .vars
+            .get(name)
+            .and_then(Value::as_str)
+            .map(str::to_string)
+    };
+    Some(DeliveryRecord {
+        id: Uuid::new_v4().to_string(),
+        notifier_id: notifier_id.to_string(),
+        event: var("event").unwrap_or_default(),
+        ip: var("ip"),
+        title: notification.title.clone(),
+        payload,
+        status: DeliveryStatus::Pending.as_str().to_string(),
+        attempts: 0,
+        created_at: now,
+        next_attempt_at: now,
+        last_error: None,
+        delivered_at: None,
+    })
+}
+
+async fn attempt(
+    http: &reqwest::Client,
+    notifier: &NotifierConfig,
+    delivery: &DeliveryRecord,
+) -> Result<(), String> {
+    let notification: Notification = serde_json::from_str(&delivery.payload)
+        .map_err(|e| format!("unreadable notification: {}", e))?;
+    notifier::send(http, notifier, &notification).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_an_hour() {
+        assert_eq!(backoff(30_000, 1), 30_000);
+        assert_eq!(backoff(30_000, 2), 60_000);
+        assert_eq!(backoff(30_000, 4), 240_000);
+        assert_eq!(backoff(30_000, 8), MAX_BACKOFF_MS);
+        assert_eq!(backoff(30_000, u32::MAX), MAX_BACKOFF_MS);
+    }
+
+    #[test]
+    fn status_round_trips() {
+        for status in [DeliveryStatus::Pending, DeliveryStatus::Delivered, DeliveryStatus::Failed] {
+            assert_eq!(status.as_str().parse::<DeliveryStatus>(), Ok(status));
+        }
+        assert!("sent".parse::<DeliveryStatus>().is_err());
+    }
+}
@@ -1,5 +1,7 @@
+use crate::ban_target::BanTarget;
 use crate::config::ConfigMap;
-use crate::database::SqliteDatabase;
+use crate::database::sqlite_db::{BanEvent, MatchEvent};
+use crate::database::{EventQuery, SortOrder, SqliteDatabase};
 use crate::events::FirewallCommand;
 use crate::store::MemoryStore;
 use std::collections::HashMap;
@@ -13,8 +15,11 @@ use tracing::{info, warn};
 ///
 /// SQLite keeps an append-only audit of match/ban/unban events. We reconstruct
 /// the live runtime state from it:
-///   - active bans  = latest ban per IP, within `ban_time`, not undone by a
-///     later unban — each is re-added to memory and re-denied in the firewall.
+///   - active bans  = latest ban per IP, within the `ban_time` stored on it
+///     (or permanent), not undone by a later unban — each is re-added to
+///     memory and re-denied in the firewall. Simulated (monitor-mode) bans
+///     are left out of this and of the recidive history, and only come back
+///     as simulated bans.
 ///   - match window = match events within `find_time`, repopulated so counting
 ///     continues seamlessly across a restart.
 pub async fn restore_state(
@@ -35,7 +40,7 @@ pub async fn restore_state(
 
         // Gather everything we need under a single lock, then release it before
         // awaiting on the firewall channel.
-        let mut to_deny: Vec<IpAddr> = Vec::new();
+        let mut to_deny: Vec<(BanTarget, u64)> = Vec::new();
         {
             let db = events_db.lock().await;
 
@@ -47,7 +52,19 @@ pub async fn restore_state(
                     .into_iter()
                     .map(|e| (e.ip, e.timestamp)),
             );
-            let ban_events = db.get_ban_events(Some(config_id)).unwrap_or_default();
+            // Would-be bans of monitor mode never reached the firewall and
+            // are no offence history: they only come back as simulated bans.
+            let (simulated, ban_events): (Vec<BanEvent>, Vec<BanEvent>) = db
+                .get_ban_events(Some(config_id))
+                .unwrap_or_default()
+                .into_iter()
+                .partition(|e| e.simulated);
+            for e in &simulated {
+                let lapsed = e.expires_at().is_some_and(|at| at <= now);
+                if let (false, Ok(ip)) = (lapsed, e.ip.parse::<BanTarget>()) {
+                    store.add_simulated_ban(config_id, ip, e.timestamp, e.ban_time);
+                }
+            }
 
             // Total bans ever seen per IP — this is the recidive history that
             // drives escalation, and it must survive a restart.
@@ -56,8 +73,17 @@ pub async fn restore_state(
                 *ban_counts.entry(e.ip.clone()).or_insert(0) += 1;
             }
 
-            let latest_ban =
-                latest_per_ip(ban_events.iter().map(|e| (e.ip.clone(), e.timestamp)));
+            let mut latest_ban: HashMap<&str, &BanEvent> = HashMap::new();
+            for e in &ban_events {
+                latest_ban
+                    .entry(e.ip.as_str())
+                    .and_modify(|cur| {
+                        if e.timestamp > cur.timestamp {
+                            *cur = e;
+                        }
+                    })
+                    .or_insert(e);
+            }
 
             for (ip_str, &count) in &ban_counts {
                 if let Ok(ip) = ip_str.parse::<IpAddr>() {
@@ -65,37 +91,46 @@ pub async fn restore_state(
                 }
             }
 
-            for (ip_str, ban_ts) in latest_ban {
-                // The latest ban is the `count`-th, so its effective duration
-                // used the exponent `count - 1`. With the multiplicator off this
-                // collapses to the flat `ban_time` (matching `ban_cutoff`).
-                let prior = ban_counts.get(&ip_str).copied().unwrap_or(1).saturating_sub(1);
-                let effective = config.effective_ban_time(prior);
-                let expired = ban_ts.saturating_add(effective) <= now;
-                let undone = latest_unban.get(&ip_str).is_some_and(|&u| u >= ban_ts);
+            for (ip_str, ban) in latest_ban {
+                // The duration stored on the row is replayed verbatim, so
+                // editing the config never stretches or cuts an active ban.
+                // Rows from before durations were stored fall back to the
+                // current config: the latest ban is the `count`-th, so its
+                // effective duration used the exponent `count - 1`.
+                let ban_time = if ban.permanent {
+                    None
+                } else {
+                    Some(ban.ban_time.unwrap_or_else(|| {
+                        let prior = ban_counts.get(ip_str).copied().unwrap_or(1).saturating_sub(1);
+                        config.effective_ban_time(prior)
+                    }))
+                };
+                let ban_ts = ban.timestamp;
+                let expired = ban_time.is_some_and(|t| ban_ts.saturating_add(t) <= now);
+                let undone = latest_unban.get(ip_str).is_some_and(|&u| u >= ban_ts);
                 if expired || undone {
                     continue; // expired or already lifted
                 }
-                match ip_str.parse::<IpAddr>() {
+                match ip_str.parse::<BanTarget>() {
                     Ok(ip) => {
-                        store.add_ban_with_duration(config_id, ip, ban_ts, effective);
-                        to_deny.push(ip);
+                        store.add_ban_with_duration(config_id, ip, ban_ts, ban_time);
+                        // A permanent ban gets no native timeout (0).
+                        to_deny.push((ip, ban_time.map_or(0, |t| ban_ts + t - now)));
                         restored_bans += 1;
                     }
                     Err(e) => warn!("Invalid IP in ban record {} ({}): {}", ip_str, config_id, e),
                 }
             }
 
-            // Match window: events are DESC, so stop at the first one older
-            // than the window, then replay per IP in ascending order.
-            let mut recent: Vec<(String, u64)> = Vec::new();
-            for ev in db.get_match_events(Some(config_id)).unwrap_or_default() {
-                if ev.timestamp < match_cutoff {
-                    break;
-                }
-                recent.push((ev.ip, ev.timestamp));
-            }
-            for (ip_str, ts) in recent.into_iter().rev() {
+            // Match window: only the events still inside it, oldest first so
+            // each IP's matches replay in order.
+            let window = EventQuery {
+                from: Some(match_cutoff),
+                order: SortOrder::Asc,
+                ..EventQuery::for_config(config_id)
+            };
+            let (recent, _) = db.query_match_events(&window).unwrap_or_default();
+            for MatchEvent { ip: ip_str, timestamp: ts, .. } in recent {
                 match ip_str.parse::<IpAddr>() {
                     Ok(ip) => {
                         store.add_match(config_id, ip, ts);
@@ -106,15 +141,17 @@ pub async fn restore_state(
             }
         }
 
-        for ip in to_deny {
-            let deny = FirewallCommand::Deny {
+        // One batch per config, so set-based backends load it in one go.
+        if !to_deny.is_empty() {
+            let count = to_deny.len();
+            let deny = FirewallCommand::DenyBatch {
                 config_id: config_id.clone(),
-                ip,
+                bans: to_deny,
             };
             if firewall_tx.send(deny).await.is_ok() {
-                info!("Restored firewall ban for {} (config: {})", ip, config_id);
+                info!("Restored {} firewall bans (config: {})", count, config_id);
             } else {
-                warn!("Firewall actor gone, could not restore ban for {}", ip);
+                warn!("Firewall actor gone, could not restore bans for {}", config_id);
             }
         }
     }
@@ -1,3 +1,4 @@
+use crate::ban_target::BanTarget;
 use std::collections::{HashMap, VecDeque};
 use std::net::IpAddr;
 use std::sync::Mutex;
@@ -21,12 +22,27 @@ pub struct MemoryStore {
 ///
 /// `ban_time` carries the *effective* duration for this specific ban, which the
 /// recidive multiplicator can grow beyond the config's base `ban_time`. Every
-/// ban records a real duration (a flat config resolves to its plain `ban_time`),
-/// so the cleaner can always expire off `timestamp + ban_time`.
+/// timed ban records a real duration (a flat config resolves to its plain
+/// `ban_time`), so the cleaner can always expire off `timestamp + ban_time`.
+/// `None` is a permanent ban: only an explicit unban lifts it.
 #[derive(Clone, Copy)]
 struct BanEntry {
     timestamp: u64,
-    ban_time: u64,
+    ban_time: Option<u64>,
+}
+
+/// A ban in force, as reported by `/api/bans/active`.
+pub struct ActiveBan {
+    pub config_id: String,
+    pub target: BanTarget,
+    pub timestamp: u64,
+    /// Effective duration; `None` for a permanent ban
+    pub ban_time: Option<u64>,
+    /// Times the address has been banned under the config, this ban
+    /// included; 0 for a range
+    pub recidive_count: u32,
+    /// Would-be ban of a config in monitor mode
+    pub simulated: bool,
 }
 
 #[derive(Default)]
@@ -35,8 +51,13 @@ struct Inner {
     /// (config, IP): one attacker's burst must never push an unrelated IP
     /// over the ban threshold.
     matches: HashMap<String, HashMap<IpAddr, VecDeque<u64>>>,
-    /// config_id -> ip -> active ban (start timestamp + effective duration).
-    bans: HashMap<String, HashMap<IpAddr, BanEntry>>,
+    /// config_id -> target -> active ban (start timestamp + effective
+    /// duration). Keyed by target so a manual CIDR ban sits beside host bans.
+    bans: HashMap<String, HashMap<BanTarget, BanEntry>>,
+    /// config_id -> target -> would-be ban of a monitor-mode config. Kept
+    /// apart from `bans` so nothing here ever reaches the firewall: these
+    /// only dedupe would-ban reports and show up in `/api/bans/active`.
+    simulated: HashMap<String, HashMap<BanTarget, BanEntry>>,
     /// config_id -> ip -> how many times this IP has ever been banned under the
     /// config. Drives recidive escalation, so it is *not* cleared when a ban
     /// expires — only when the config itself goes away.
@@ -96,24 +117,127 @@ impl MemoryStore {
         inner.matches.remove(config_id);
     }
 
-    pub fn is_banned(&self, config_id: &str, ip: &IpAddr) -> bool {
+    /// Whether a ban of the config covers `target`: the same target, or a
+    /// network ban it falls within.
+    pub fn is_banned(&self, config_id: &str, target: &BanTarget) -> bool {
+        let inner = self.inner.lock().unwrap();
+        inner.bans.get(config_id).is_some_and(|ips| covered(ips, target))
+    }
+
+    /// Whether a monitor-mode config has already reported `target` as a
+    /// would-be ban that has not lapsed yet.
+    pub fn is_simulated_ban(&self, config_id: &str, target: &BanTarget) -> bool {
         let inner = self.inner.lock().unwrap();
         inner
-            .bans
+            .simulated
             .get(config_id)
-            .is_some_and(|ips| ips.contains_key(ip))
+            .is_some_and(|ips| ips.contains_key(target))
+    }
+
+    /// The bans in force, of one config or all of them, newest first.
+    /// Would-be bans of monitor-mode configs are listed too, flagged
+    /// `simulated`.
+    pub fn active_bans(&self, config_id: Option<&str>) -> Vec<ActiveBan> {
+        let inner = self.inner.lock().unwrap();
+        let enforced = inner.bans.iter().map(|entry| (entry, false));
+        let simulated = inner.simulated.iter().map(|entry| (entry, true));
+        let mut bans: Vec<ActiveBan> = enforced
+            .chain(simulated)
+            .filter(|((id, _), _)| config_id.is_none_or(|wanted| wanted == id.as_str()))
+            .flat_map(|((id, targets), simulated)| {
+                let counts = inner.ban_counts.get(id);
+                targets.iter().map(move |(target, entry)| ActiveBan {
+                    config_id: id.clone(),
+                    target: *target,
+                    timestamp: entry.timestamp,
+                    ban_time: entry.ban_time,
+                    recidive_count: target
+                        .host()
+                        .and_then(|ip| counts?.get(&ip).copied())
+                        .unwrap_or(0),
+                    simulated,
+                })
+            })
+            .collect();
+        bans.sort_by(|a, b| {
+            b.timestamp
+                .cmp(&a.timestamp)
+                .then_with(|| a.config_id.cmp(&b.config_id))
+                .then_with(|| a.target.to_string().cmp(&b.target.to_string()))
+        });
+        bans
+    }
+
+    /// Number of bans in force, per config that has any. Would-be bans are
+    /// not counted.
+    pub fn active_ban_counts(&self) -> HashMap<String, usize> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .bans
+            .iter()
+            .map(|(config_id, ips)| (config_id.clone(), ips.len()))
+            .collect()
     }
 
     /// Record a ban that carries its own effective duration, so the cleaner can
     /// expire it on `timestamp + ban_time`. A flat config passes its plain
-    /// `ban_time`; a recidive config passes the escalated duration.
-    pub fn add_ban_with_duration(&self, config_id: &str, ip: IpAddr, timestamp: u64, ban_time: u64) {
+    /// `ban_time`; a recidive config passes the escalated duration; `None`
+    /// records a permanent ban, which never expires.
+    pub fn add_ban_with_duration(
+        &self,
+        config_id: &str,
+        target: BanTarget,
+        timestamp: u64,
+        ban_time: Option<u64>,
+    ) {
         let mut inner = self.inner.lock().unwrap();
         inner
             .bans
             .entry(config_id.to_string())
             .or_default()
-            .insert(ip, BanEntry { timestamp, ban_time });
+            .insert(target, BanEntry { timestamp, ban_time });
+    }
+
+    /// `add_ban_with_duration` unless a ban of the config already covers
+    /// `target`, checked under the same lock so two concurrent requests cannot
+    /// both insert. Returns whether the ban was added.
+    pub fn add_ban_if_absent(
+        &self,
+        config_id: &str,
+        target: BanTarget,
+        timestamp: u64,
+        ban_time: Option<u64>,
+    ) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        let ips = inner.bans.entry(config_id.to_string()).or_default();
+        if covered(ips, &target) {
+            return false;
+        }
+        ips.insert(target, BanEntry { timestamp, ban_time });
+        true
+    }
+
+    /// Record a would-be ban of a monitor-mode config. It is dropped once its
+    /// duration has elapsed (`prune_simulated_bans`) without any unban.
+    pub fn add_simulated_ban(&self, config_id: &str, target: BanTarget, timestamp: u64, ban_time: Option<u64>) {
+        let mut inner = self.inner.lock().unwrap();
+        inner
+            .simulated
+            .entry(config_id.to_string())
+            .or_default()
+            .insert(target, BanEntry { timestamp, ban_time });
+    }
+
+    /// Drop the would-be bans of a config whose duration has elapsed by `now`.
+    pub fn prune_simulated_bans(&self, config_id: &str, now: u64) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(ips) = inner.simulated.get_mut(config_id) {
+            ips.retain(|_, entry| {
+                entry
+                    .ban_time
+                    .is_none_or(|ban_time| entry.timestamp.saturating_add(ban_time) >= now)
+            });
+        }
     }
 
     /// Number of times `ip` has already been banned under the config, then bump
@@ -132,6 +256,18 @@ impl MemoryStore {
         prior
     }
 
+    /// How many times `ip` has been banned under the config, the ban just
+    /// recorded included: 1 for a first offence.
+    pub fn recidive_level(&self, config_id: &str, ip: IpAddr) -> u32 {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .ban_counts
+            .get(config_id)
+            .and_then(|counts| counts.get(&ip))
+            .copied()
+            .unwrap_or(0)
+    }
+
     /// Seed the recidive counter from durable history on startup so escalation
     /// survives a restart.
     pub fn set_recidive(&self, config_id: &str, ip: IpAddr, count: u32) {
@@ -144,25 +280,48 @@ impl MemoryStore {
     }
 
     /// Remove a single ban (e.g. API disable). Returns true if it existed.
-    pub fn remove_ban(&self, config_id: &str, ip: &IpAddr) -> bool {
+    pub fn remove_ban(&self, config_id: &str, target: &BanTarget) -> bool {
         let mut inner = self.inner.lock().unwrap();
         inner
             .bans
             .get_mut(config_id)
-            .is_some_and(|ips| ips.remove(ip).is_some())
+            .is_some_and(|ips| ips.remove(target).is_some())
+    }
+
+    /// Remove and return every ban, across all configs, whose target lies
+    /// within `net` (a new allowlist entry lifting what it now covers). A
+    /// network ban only partly inside `net` is left alone.
+    pub fn take_bans_within(&self, net: &BanTarget) -> Vec<(String, BanTarget)> {
+        let mut inner = self.inner.lock().unwrap();
+        let mut lifted = Vec::new();
+        for (config_id, bans) in inner.bans.iter_mut() {
+            bans.retain(|target, _| {
+                let covered = net.covers(target);
+                if covered {
+                    lifted.push((config_id.clone(), *target));
+                }
+                !covered
+            });
+        }
+        lifted
     }
 
     /// Remove and return every ban for a config whose own effective duration has
     /// elapsed by `now` (each ban expires at `timestamp + ban_time`). Used by the
-    /// cleaner to drive expiry for every config, recidive or flat.
-    pub fn take_expired_bans_now(&self, config_id: &str, now: u64) -> Vec<IpAddr> {
+    /// cleaner to drive expiry for every config, recidive or flat. Permanent
+    /// bans are never returned.
+    pub fn take_expired_bans_now(&self, config_id: &str, now: u64) -> Vec<BanTarget> {
         let mut inner = self.inner.lock().unwrap();
         let Some(ips) = inner.bans.get_mut(config_id) else {
             return Vec::new();
         };
-        let expired: Vec<IpAddr> = ips
+        let expired: Vec<BanTarget> = ips
             .iter()
-            .filter(|(_, entry)| entry.timestamp.saturating_add(entry.ban_time) < now)
+            .filter(|(_, entry)| {
+                entry
+                    .ban_time
+                    .is_some_and(|ban_time| entry.timestamp.saturating_add(ban_time) < now)
+            })
             .map(|(ip, _)| *ip)
             .collect();
         for ip in &expired {
@@ -174,10 +333,11 @@ impl MemoryStore {
     /// Remove and return every ban for a config, regardless of age. Used when
     /// a config is deleted so its bans are lifted rather than leaked (the
     /// cleaner only visits configs that still exist).
-    pub fn take_all_bans(&self, config_id: &str) -> Vec<IpAddr> {
+    pub fn take_all_bans(&self, config_id: &str) -> Vec<BanTarget> {
         let mut inner = self.inner.lock().unwrap();
         // The config is going away, so its recidive history is meaningless now.
         inner.ban_counts.remove(config_id);
+        inner.simulated.remove(config_id);
         inner
             .bans
             .remove(config_id)
@@ -198,6 +358,12 @@ fn prune_front(window: &mut VecDeque<u64>, cutoff: u64) {
     }
 }
 
+/// Whether one of a config's bans covers `target`. Host bans are looked up
+/// directly; only network bans need a scan.
+fn covered(ips: &HashMap<BanTarget, BanEntry>, target: &BanTarget) -> bool {
+    ips.contains_key(target) || ips.keys().any(|ban| ban.host().is_none() && ban.covers(target))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -206,6 +372,10 @@ mod tests {
         s.parse().unwrap()
     }
 
+    fn target(s: &str) -> BanTarget {
+        s.parse().unwrap()
+    }
+
     #[test]
     fn counts_matches_within_window_and_prunes_old() {
         let store = MemoryStore::new();
@@ -256,21 +426,84 @@ mod tests {
     #[test]
     fn ban_lifecycle() {
         let store = MemoryStore::new();
-        assert!(!store.is_banned("c", &ip("10.0.0.1")));
-        store.add_ban_with_duration("c", ip("10.0.0.1"), 5000, 1000);
-        assert!(store.is_banned("c", &ip("10.0.0.1")));
-        assert!(store.remove_ban("c", &ip("10.0.0.1")));
-        assert!(!store.is_banned("c", &ip("10.0.0.1")));
+        assert!(!store.is_banned("c", &target("10.0.0.1")));
+        store.add_ban_with_duration("c", target("10.0.0.1"), 5000, Some(1000));
+        assert!(store.is_banned("c", &target("10.0.0.1")));
+        assert!(store.remove_ban("c", &target("10.0.0.1")));
+        assert!(!store.is_banned("c", &target("10.0.0.1")));
+    }
+
+    #[test]
+    fn add_ban_if_absent_refuses_covered_targets() {
+        let store = MemoryStore::new();
+        assert!(store.add_ban_if_absent("c", target("10.0.0.0/24"), 5000, None));
+        assert!(!store.add_ban_if_absent("c", target("10.0.0.0/24"), 6000, None));
+        assert!(!store.add_ban_if_absent("c", target("10.0.0.7"), 6000, None));
+        assert!(store.is_banned("c", &target("10.0.0.7")));
+        // A wider network is not covered by the narrower one.
+        assert!(store.add_ban_if_absent("c", target("10.0.0.0/16"), 6000, None));
+        assert!(store.add_ban_if_absent("other", target("10.0.0.7"), 6000, None));
+    }
+
+    #[test]
+    fn active_bans_are_listed_newest_first_with_recidive() {
+        let store = MemoryStore::new();
+        store.next_recidive("a", ip("10.0.0.1"));
+        store.next_recidive("a", ip("10.0.0.1"));
+        store.add_ban_with_duration("a", target("10.0.0.1"), 2000, Some(1000));
+        store.add_ban_with_duration("a", target("10.1.0.0/16"), 3000, None);
+        store.add_ban_with_duration("b", target("10.0.0.2"), 1000, Some(500));
+
+        let all = store.active_bans(None);
+        let listed: Vec<(&str, String, u32)> = all
+            .iter()
+            .map(|b| (b.config_id.as_str(), b.target.to_string(), b.recidive_count))
+            .collect();
+        assert_eq!(
+            listed,
+            vec![
+                ("a", "10.1.0.0/16".to_string(), 0),
+                ("a", "10.0.0.1".to_string(), 2),
+                ("b", "10.0.0.2".to_string(), 0),
+            ]
+        );
+        assert_eq!(all[0].ban_time, None);
+        assert_eq!(all[1].ban_time, Some(1000));
+
+        let b = store.active_bans(Some("b"));
+        assert_eq!(b.len(), 1);
+        assert_eq!(b[0].timestamp, 1000);
+        assert!(store.active_bans(Some("c")).is_empty());
+    }
+
+    #[test]
+    fn simulated_bans_stay_out_of_the_enforced_set() {
+        let store = MemoryStore::new();
+        let a = target("10.0.0.1");
+        store.add_simulated_ban("c", a, 1000, Some(1000));
+        assert!(store.is_simulated_ban("c", &a));
+        assert!(!store.is_banned("c", &a));
+        assert!(store.active_ban_counts().is_empty());
+        let listed = store.active_bans(Some("c"));
+        assert_eq!(listed.len(), 1);
+        assert!(listed[0].simulated);
+
+        // Expiry is silent: nothing is handed back for the firewall.
+        assert!(store.take_expired_bans_now("c", 5000).is_empty());
+        store.prune_simulated_bans("c", 1500);
+        assert!(store.is_simulated_ban("c", &a));
+        store.prune_simulated_bans("c", 5000);
+        assert!(!store.is_simulated_ban("c", &a));
     }
 
     #[test]
     fn take_expired_bans_now_uses_per_ban_duration() {
         let store = MemoryStore::new();
-        let short = ip("10.0.0.1");
-        let long = ip("10.0.0.2");
+        let short = target("10.0.0.1");
+        let long = target("10.0.0.2");
         // Both banned at the same instant, but with different durations.
-        store.add_ban_with_duration("c", short, 1000, 1000); // expires at 2000
-        store.add_ban_with_duration("c", long, 1000, 9000); // expires at 10000
+        store.add_ban_with_duration("c", short, 1000, Some(1000)); // expires at 2000
+        store.add_ban_with_duration("c", long, 1000, Some(9000)); // expires at 10000
         // At now=5000 only the short ban has elapsed.
         let expired = store.take_expired_bans_now("c", 5000);
         assert_eq!(expired, vec![short]);
@@ -278,6 +511,37 @@ mod tests {
         assert!(store.is_banned("c", &long));
     }
 
+    #[test]
+    fn take_expired_bans_now_skips_permanent_bans() {
+        let store = MemoryStore::new();
+        let timed = target("10.0.0.1");
+        let permanent = target("10.0.0.2");
+        store.add_ban_with_duration("c", timed, 1000, Some(1000));
+        store.add_ban_with_duration("c", permanent, 1000, None);
+        assert_eq!(store.take_expired_bans_now("c", u64::MAX), vec![timed]);
+        assert!(store.is_banned("c", &permanent));
+        // Only config deletion (or an explicit unban) lifts it.
+        assert_eq!(store.take_all_bans("c"), vec![permanent]);
+    }
+
+    #[test]
+    fn take_bans_within_spans_configs() {
+        let store = MemoryStore::new();
+        store.add_ban_with_duration("a", target("10.0.0.1"), 1000, Some(1000));
+        store.add_ban_with_duration("b", target("10.0.0.2"), 1000, None);
+        store.add_ban_with_duration("b", target("10.0.0.0/16"), 1000, None);
+        store.add_ban_with_duration("b", target("192.0.2.1"), 1000, None);
+        let mut lifted = store.take_bans_within(&target("10.0.0.0/24"));
+        lifted.sort_by_key(|(config_id, _)| config_id.clone());
+        assert_eq!(
+            lifted,
+            vec![("a".to_string(), target("10.0.0.1")), ("b".to_string(), target("10.0.0.2"))]
+        );
+        // The wider network ban is only partly covered, so it stays.
+        assert!(store.is_banned("b", &target("10.0.0.0/16")));
+        assert!(store.is_banned("b", &target("192.0.2.1")));
+    }
+
     #[test]
     fn next_recidive_counts_per_config_and_ip() {
         let store = MemoryStore::new();
@@ -308,9 +572,9 @@ mod tests {
         let store = MemoryStore::new();
         let a = ip("10.0.0.1");
         store.next_recidive("c", a); // count -> 1
-        store.add_ban_with_duration("c", a, 1000, 1000);
+        store.add_ban_with_duration("c", BanTarget::from(a), 1000, Some(1000));
         let lifted = store.take_all_bans("c");
-        assert_eq!(lifted, vec![a]);
+        assert_eq!(lifted, vec![BanTarget::from(a)]);
         // History gone: escalation restarts from zero.
         assert_eq!(store.next_recidive("c", a), 0);
     }
@@ -0,0 +1,600 @@
+//! Receives syslog messages over UDP or TCP for syslog configs, so devices
+//! that cannot run banalize can still feed it.
+//!
+//! Messages are parsed as RFC 5424 (`<PRI>1 TIMESTAMP HOST APP PROCID MSGID
+//! SD MSG`) or, failing that, as RFC 3164 (`<PRI>Mmm dd hh:mm:ss HOST TAG:
+//! MSG`), leniently: a missing timestamp, hostname or tag is tolerated and a
+//! message without a hostname is known by its sender's address. Over TCP,
+//! both RFC 6587 framings are read: octet counting (`LEN SP MSG`) and
+//! newline-delimited.
+//!
+//! One listener serves every config on the same address: each parsed message
+//! is broadcast to the configs' sources, which apply their own filter. The
+//! listener stops once the last of them is gone.
+
+use crate::config::SyslogFilter;
+use crate::log_source::LogSource;
+use crate::metrics::{Bus, Metrics};
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::sync::broadcast;
+use tokio::task::{JoinHandle, JoinSet};
+use tracing::{info, warn};
+
+/// Largest message accepted; longer TCP frames close the connection.
+const MAX_MESSAGE_SIZE: usize = 64 * 1024;
+/// Messages buffered per listener for configs that fall behind.
+const MESSAGE_CAPACITY: usize = 1024;
+/// Concurrent TCP senders per listener; more are turned away.
+const MAX_CONNECTIONS: usize = 256;
+/// How long to retry binding an address still held by a listener that is
+/// shutting down (a config being updated in place).
+const REBIND_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Protocol {
+    Udp,
+    Tcp,
+}
+
+/// Where a syslog config listens: `udp://host:port` or `tcp://host:port`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Listen {
+    pub protocol: Protocol,
+    pub addr: SocketAddr,
+}
+
+impl FromStr for Listen {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (protocol, addr) = if let Some(addr) = s.strip_prefix("udp://") {
+            (Protocol::Udp, addr)
+        } else if let Some(addr) = s.strip_prefix("tcp://") {
+            (Protocol::Tcp, addr)
+        } else {
+            return Err("syslog param must be udp://host:port or tcp://host:port".to_string());
+        };
+        let addr: SocketAddr = addr
+            .parse()
+            .map_err(|_| format!("invalid syslog listen address: {}", addr))?;
+        if addr.port() == 0 {
+            return Err("syslog listen port cannot be 0".to_string());
+        }
+        Ok(Self { protocol, addr })
+    }
+}
+
+impl fmt::Display for Listen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.protocol {
+            Protocol::Udp => write!(f, "udp://{}", self.addr),
+            Protocol::Tcp => write!(f, "tcp://{}", self.addr),
+        }
+    }
+}
+
+/// The parts of a syslog message that configs filter and match on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyslogMessage {
+    pub hostname: String,
+    pub app_name: Option<String>,
+    pub message: String,
+}
+
+/// `-` is the RFC 5424 nil value.
+fn nil_or(value: &str) -> Option<String> {
+    (value != "-").then(|| value.to_string())
+}
+
+/// Strip a `<PRI>` prefix (a value of up to three digits).
+fn strip_pri(text: &str) -> Option<&str> {
+    let rest = text.strip_prefix('<')?;
+    let end = rest.find('>')?;
+    let pri = &rest[..end];
+    (!pri.is_empty() && pri.len() <= 3 && pri.chars().all(|c| c.is_ascii_digit()))
+        .then(|| &rest[end + 1..])
+}
+
+/// Skip RFC 5424 STRUCTURED-DATA: `-` or `[id param="value" ...]...`, where
+/// `\"`, `\\` and `\]` are escaped inside values.
+fn skip_structured_data(s: &str) -> Option<&str> {
+    if let Some(rest) = s.strip_prefix('-') {
+        return Some(rest);
+    }
+    let mut in_element = false;
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        if !in_element {
+            if c != '[' {
+                return (i > 0).then(|| &s[i..]);
+            }
+            in_element = true;
+            continue;
+        }
+        if escaped {
+            escaped = false;
+        } else if quoted {
+            match c {
+                '\\' => escaped = true,
+                '"' => quoted = false,
+                _ => {}
+            }
+        } else {
+            match c {
+                '"' => quoted = true,
+                ']' => in_element = false,
+                _ => {}
+            }
+        }
+    }
+    (!in_element && !s.is_empty()).then_some("")
+}
+
+/// `<PRI>` already stripped; `None` unless this is RFC 5424.
+fn parse_rfc5424(rest: &str, peer: IpAddr) -> Option<SyslogMessage> {
+    let mut fields = rest.splitn(7, ' ');
+    if fields.next()? != "1" {
+        return None;
+    }
+    let _timestamp = fields.next()?;
+    let hostname = fields.next()?;
+    let app_name = fields.next()?;
+    let _procid = fields.next()?;
+    let _msgid = fields.next()?;
+    let after = skip_structured_data(fields.next()?)?;
+    let message = match after.strip_prefix(' ') {
+        Some(message) => message,
+        None if after.is_empty() => "",
+        None => return None,
+    };
+    Some(SyslogMessage {
+        hostname: nil_or(hostname).unwrap_or_else(|| peer.to_string()),
+        app_name: nil_or(app_name),
+        message: message.strip_prefix('\u{feff}').unwrap_or(message).to_string(),
+    })
+}
+
+/// The length of a leading RFC 3164 (`Mmm dd hh:mm:ss `) or RFC 3339
+/// timestamp, if any.
+fn timestamp_len(s: &str) -> Option<usize> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let b = s.as_bytes();
+    if b.len() > 16
+        && MONTHS.iter().any(|m| m.as_bytes() == &b[..3])
+        && b[3] == b' '
+        && (b[4] == b' ' || b[4].is_ascii_digit())
+        && b[5].is_ascii_digit()
+        && b[6] == b' '
+        && b[9] == b':'
+        && b[12] == b':'
+        && b[15] == b' '
+    {
+        return Some(16);
+    }
+    let token = s.split(' ').next()?;
+    let t = token.as_bytes();
+    let rfc3339 =
+        t.len() > 10 && t[..4].iter().all(u8::is_ascii_digit) && t[4] == b'-' && t[10] == b'T';
+    (rfc3339 && s.len() > token.len()).then(|| token.len() + 1)
+}
+
+/// `app[pid]:` or `app:` to `app`.
+fn tag_name(token: &str) -> Option<&str> {
+    let tag = token.strip_suffix(':')?;
+    let name = tag.split('[').next().unwrap_or(tag);
+    (!name.is_empty()).then_some(name)
+}
+
+/// `<PRI>` already stripped. A hostname is only looked for after a timestamp,
+/// as relays insert both together.
+fn parse_rfc3164(rest: &str, peer: IpAddr) -> SyslogMessage {
+    let mut hostname = None;
+    let mut rest = rest;
+    if let Some(len) = timestamp_len(rest) {
+        rest = &rest[len..];
+        if let Some((first, after)) = rest.split_once(' ') {
+            if tag_name(first).is_none() {
+                hostname = Some(first.to_string());
+                rest = after;
+            }
+        }
+    }
+    let mut app_name = None;
+    if let Some((first, after)) = rest.split_once(' ') {
+        if let Some(name) = tag_name(first) {
+            app_name = Some(name.to_string());
+            rest = after;
+        }
+    }
+    SyslogMessage {
+        hostname: hostname.unwrap_or_else(|| peer.to_string()),
+        app_name,
+        message: rest.to_string(),
+    }
+}
+
+/// Parse one syslog message received from `peer`.
+pub fn parse_message(data: &[u8], peer: IpAddr) -> SyslogMessage {
+    let text = String::from_utf8_lossy(data);
+    let text = text.trim_end_matches(['\n', '\r', '\0']);
+    let peer = peer.to_canonical();
+    match strip_pri(text) {
+        Some(rest) => {
+            parse_rfc5424(rest, peer).unwrap_or_else(|| parse_rfc3164(rest, peer))
+        }
+        None => SyslogMessage {
+            hostname: peer.to_string(),
+            app_name: None,
+            message: text.to_string(),
+        },
+    }
+}
+
+/// Whether a syslog config with `filter` reads `message`.
+fn allows(filter: &SyslogFilter, message: &SyslogMessage) -> bool {
+    (filter.hostnames.is_empty()
+        || filter
+            .hostnames
+            .iter()
+            .any(|h| h.eq_ignore_ascii_case(&message.hostname)))
+        && (filter.app_names.is_empty()
+            || message
+                .app_name
+                .as_ref()
+                .is_some_and(|app| filter.app_names.contains(app)))
+}
+
+/// Splits a TCP stream into messages, frame by frame: octet counting when a
+/// frame starts with a digit, newline-delimited otherwise.
+#[derive(Default)]
+struct Framer {
+    buffer: Vec<u8>,
+}
+
+impl Framer {
+    fn push(&mut self, data: &[u8], frames: &mut Vec<Vec<u8>>) -> Result<(), String> {
+        self.buffer.extend_from_slice(data);
+        loop {
+            let Some(&first) = self.buffer.first() else {
+                return Ok(());
+            };
+            if first.is_ascii_digit() {
+                let Some(space) = self.buffer.iter().position(|&b| b == b' ') else {
+                    if self.buffer.len() > 10 {
+                        return Err("invalid octet count".to_string());
+                    }
+                    return Ok(());
+                };
+                let len: usize = std::str::from_utf8(&self.buffer[..space])
+                    .ok()
+                    .and_then(|n| n.parse().ok())
+                    .ok_or("invalid octet count")?;
+                if len > MAX_MESSAGE_SIZE {
+                    return Err(format!("message of {} bytes is too long", len));
+                }
+                if self.buffer.len() < space + 1 + len {
+                    return Ok(());
+                }
+                frames.push(self.buffer[space + 1..space + 1 + len].to_vec());
+                self.buffer.drain(..space + 1 + len);
+            } else {
+                let Some(end) = self.buffer.iter().position(|&b| b == b'\n') else {
+                    if self.buffer.len() > MAX_MESSAGE_SIZE {
+                        return Err("message is too long".to_string());
+                    }
+                    return Ok(());
+                };
+                let frame: Vec<u8> = self.buffer.drain(..=end).collect();
+                if !frame.trim_ascii().is_empty() {
+                    frames.push(frame);
+                }
+            }
+        }
+    }
+
+    /// An unterminated last line, once the sender has closed.
+    fn finish(self) -> Option<Vec<u8>> {
+        (!self.buffer.trim_ascii().is_empty()).then_some(self.buffer)
+    }
+}
+
+async fn receive_udp(socket: UdpSocket, tx: broadcast::Sender<Arc<SyslogMessage>>) {
+    let mut buf = vec![0u8; MAX_MESSAGE_SIZE];
+    loop {
+        match socket.recv_from(&mut buf).await {
+            Ok((n, peer)) => {
+                let _ = tx.send(Arc::new(parse_message(&buf[..n], peer.ip())));
+            }
+            Err(e) => warn!("Syslog receive error: {}", e),
+        }
+    }
+}
+
+async fn accept_tcp(listener: TcpListener, tx: broadcast::Sender<Arc<SyslogMessage>>) {
+    // Dropped with this task, which closes every connection.
+    let mut connections = JoinSet::new();
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, peer)) => {
+                    if connections.len() >= MAX_CONNECTIONS {
+                        warn!("Too many syslog connections, refusing {}", peer);
+                        continue;
+                    }
+                    connections.spawn(read_tcp(stream, peer.ip(), tx.clone()));
+                }
+                Err(e) => {
+                    // Typically out of file descriptors: back off.
+                    warn!("Syslog accept error: {}", e);
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+            },
+            Some(_) = connections.join_next() => {}
+        }
+    }
+}
+
+async fn read_tcp(mut stream: TcpStream, peer: IpAddr, tx: broadcast::Sender<Arc<SyslogMessage>>) {
+    let mut framer = Framer::default();
+    let mut buf = vec![0u8; 16 * 1024];
+    let mut frames = Vec::new();
+    loop {
+        let n = match stream.read(&mut buf).await {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) => {
+                warn!("Syslog connection from {} failed: {}", peer, e);
+                return;
+            }
+        };
+        let result = framer.push(&buf[..n], &mut frames);
+        for frame in frames.drain(..) {
+            let _ = tx.send(Arc::new(parse_message(&frame, peer)));
+        }
+        if let Err(e) = result {
+            warn!("Closing syslog connection from {}: {}", peer, e);
+            return;
+        }
+    }
+    if let Some(frame) = framer.finish() {
+        let _ = tx.send(Arc::new(parse_message(&frame, peer)));
+    }
+}
+
+/// A bound address and the task receiving on it.
+struct Listener {
+    listen: Listen,
+    messages: broadcast::Sender<Arc<SyslogMessage>>,
+    task: JoinHandle<()>,
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        self.task.abort();
+        info!("Stopped listening for syslog on {}", self.listen);
+    }
+}
+
+async fn bind(listen: Listen) -> io::Result<Listener> {
+    let (messages, _) = broadcast::channel(MESSAGE_CAPACITY);
+    let tx = messages.clone();
+    let task = match listen.protocol {
+        Protocol::Udp => {
+            let socket = UdpSocket::bind(listen.addr).await?;
+            tokio::spawn(receive_udp(socket, tx))
+        }
+        Protocol::Tcp => {
+            let listener = TcpListener::bind(listen.addr).await?;
+            tokio::spawn(accept_tcp(listener, tx))
+        }
+    };
+    info!("Listening for syslog on {}", listen);
+    Ok(Listener {
+        listen,
+        messages,
+        task,
+    })
+}
+
+/// The listeners of every running syslog config, one per address.
+#[derive(Clone)]
+pub struct SyslogListeners {
+    listeners: Arc<Mutex<HashMap<Listen, Weak<Listener>>>>,
+    metrics: Arc<Metrics>,
+}
+
+impl SyslogListeners {
+    pub fn new(metrics: Arc<Metrics>) -> Self {
+        Self {
+            listeners: Arc::default(),
+            metrics,
+        }
+    }
+
+    /// Start reading the messages passing `filter` on `param`'s address,
+    /// binding it unless another config already listens there.
+    pub async fn subscribe(&self, param: &str, filter: &SyslogFilter) -> Result<SyslogSource, String> {
+        let listen: Listen = param.parse()?;
+        let existing = self
+            .listeners
+            .lock()
+            .unwrap()
+            .get(&listen)
+            .and_then(Weak::upgrade);
+        let listener = match existing {
+            Some(listener) => listener,
+            None => {
+                let deadline = tokio::time::Instant::now() + REBIND_TIMEOUT;
+                let listener = loop {
+                    match bind(listen).await {
+                        Ok(listener) => break Arc::new(listener),
+                        Err(e)
+                            if e.kind() == io::ErrorKind::AddrInUse
+                                && tokio::time::Instant::now() < deadline =>
+                        {
+                            tokio::time::sleep(Duration::from_millis(50)).await;
+                        }
+                        Err(e) => return Err(format!("Cannot listen on {}: {}", listen, e)),
+                    }
+                };
+                let mut listeners = self.listeners.lock().unwrap();
+                listeners.retain(|_, l| l.strong_count() > 0);
+                listeners.insert(listen, Arc::downgrade(&listener));
+                listener
+            }
+        };
+        Ok(SyslogSource {
+            messages: listener.messages.subscribe(),
+            filter: filter.clone(),
+            listener,
+            metrics: self.metrics.clone(),
+        })
+    }
+}
+
+/// The messages of one syslog config.
+pub struct SyslogSource {
+    messages: broadcast::Receiver<Arc<SyslogMessage>>,
+    filter: SyslogFilter,
+    listener: Arc<Listener>,
+    metrics: Arc<Metrics>,
+}
+
+impl LogSource for SyslogSource {
+    async fn next_line(&mut self) -> Option<String> {
+        loop {
+            match self.messages.recv().await {
+                Ok(message) if allows(&self.filter, &message) => {
+                    return Some(message.message.clone())
+                }
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    warn!("Fell behind on syslog {}, {} messages dropped", self.listener.listen, n);
+                    self.metrics.lagged(Bus::Syslog, n);
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peer() -> IpAddr {
+        "192.0.2.7".parse().unwrap()
+    }
+
+    #[test]
+    fn listen_addresses_parse() {
+        let listen: Listen = "udp://0.0.0.0:514".parse().unwrap();
+        assert_eq!(listen.protocol, Protocol::Udp);
+        assert_eq!(listen.to_string(), "udp://0.0.0.0:514");
+        let listen: Listen = "tcp://[::1]:6514".parse().unwrap();
+        assert_eq!(listen.protocol, Protocol::Tcp);
+        assert_eq!(listen.to_string(), "tcp://[::1]:6514");
+        assert!("0.0.0.0:514".parse::<Listen>().is_err());
+        assert!("udp://localhost:514".parse::<Listen>().is_err());
+        assert!("tcp://0.0.0.0:0".parse::<Listen>().is_err());
+    }
+
+    #[test]
+    fn rfc5424_messages_parse() {
+        let m = parse_message(
+            br#"<34>1 2026-10-18T22:14:15.003Z fw01.example.com sshd 8710 ID47 [exampleSDID@32473 iut="3" eventSource="App\"lic]ation"][x@1 y="z"] Failed password from 203.0.113.9"#,
+            peer(),
+        );
+        assert_eq!(m.hostname, "fw01.example.com");
+        assert_eq!(m.app_name.as_deref(), Some("sshd"));
+        assert_eq!(m.message, "Failed password from 203.0.113.9");
+
+        let m = parse_message("<13>1 - - - - - - \u{feff}hello\n".as_bytes(), peer());
+        assert_eq!(m.hostname, "192.0.2.7");
+        assert_eq!(m.app_name, None);
+        assert_eq!(m.message, "hello");
+
+        let m = parse_message(b"<13>1 2026-10-18T22:14:15Z host app - - -", peer());
+        assert_eq!(m.message, "");
+    }
+
+    #[test]
+    fn rfc3164_messages_parse_leniently() {
+        let m = parse_message(
+            b"<38>Oct  8 22:14:15 gw-2 sshd[4242]: Invalid user admin from 198.51.100.3",
+            peer(),
+        );
+        assert_eq!(m.hostname, "gw-2");
+        assert_eq!(m.app_name.as_deref(), Some("sshd"));
+        assert_eq!(m.message, "Invalid user admin from 198.51.100.3");
+
+        // No hostname after the timestamp
+        let m = parse_message(b"<38>Oct 18 22:14:15 dropbear[7]: Bad password", peer());
+        assert_eq!(m.hostname, "192.0.2.7");
+        assert_eq!(m.app_name.as_deref(), Some("dropbear"));
+        assert_eq!(m.message, "Bad password");
+
+        // RFC 3339 timestamp, as rsyslog forwards by default
+        let m = parse_message(b"<38>2026-10-18T22:14:15+02:00 edge kernel: DROP SRC=1.2.3.4", peer());
+        assert_eq!(m.hostname, "edge");
+        assert_eq!(m.app_name.as_deref(), Some("kernel"));
+        assert_eq!(m.message, "DROP SRC=1.2.3.4");
+
+        // Neither timestamp nor PRI: the whole line is the message
+        let m = parse_message(b"<12>login failed for 10.0.0.1", peer());
+        assert_eq!(m.app_name, None);
+        assert_eq!(m.message, "login failed for 10.0.0.1");
+        let m = parse_message(b"plain text\r\n", "::ffff:10.1.1.1".parse().unwrap());
+        assert_eq!(m.hostname, "10.1.1.1");
+        assert_eq!(m.message, "plain text");
+    }
+
+    #[test]
+    fn filters_match_hostname_and_app_name() {
+        let m = parse_message(b"<38>Oct 18 22:14:15 GW-2 sshd[1]: x", peer());
+        let filter = |hostnames: &[&str], app_names: &[&str]| SyslogFilter {
+            hostnames: hostnames.iter().map(|s| s.to_string()).collect(),
+            app_names: app_names.iter().map(|s| s.to_string()).collect(),
+        };
+        assert!(allows(&filter(&[], &[]), &m));
+        assert!(allows(&filter(&["gw-1", "gw-2"], &[]), &m));
+        assert!(allows(&filter(&["gw-2"], &["sshd"]), &m));
+        assert!(!allows(&filter(&["gw-2"], &["nginx"]), &m));
+        assert!(!allows(&filter(&["gw-3"], &[]), &m));
+    }
+
+    #[test]
+    fn tcp_streams_split_in_both_framings() {
+        let mut framer = Framer::default();
+        let mut frames = Vec::new();
+        framer.push(b"17 <13>1 - - -", &mut frames).unwrap();
+        assert!(frames.is_empty());
+        framer.push(b" - - -<13>first\n\n<13>sec", &mut frames).unwrap();
+        framer.push(b"ond\n4 a\nbc<13>tail", &mut frames).unwrap();
+        let frames: Vec<&[u8]> = frames.iter().map(Vec::as_slice).collect();
+        assert_eq!(
+            frames,
+            [
+                &b"<13>1 - - - - - -"[..],
+                b"<13>first\n",
+                b"<13>second\n",
+                b"a\nbc",
+            ]
+        );
+        assert_eq!(framer.finish().as_deref(), Some(&b"<13>tail"[..]));
+
+        let mut frames = Vec::new();
+        assert!(Framer::default().push(b"99999999 x", &mut frames).is_err());
+    }
+}
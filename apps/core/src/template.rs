@@ -0,0 +1,132 @@
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Values a notification template can reference as `{name}`.
+pub type TemplateVars = BTreeMap<String, Value>;
+
+/// Replace every `{name}` whose name is a known variable. Anything else —
+/// unknown names, stray braces, JSON punctuation — is copied through as is.
+pub fn render_text(template: &str, vars: &TemplateVars) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after.find('}').and_then(|end| vars.get(&after[..end]).map(|v| (end, v))) {
+            Some((end, value)) => {
+                out.push_str(&display(value));
+                rest = &after[end + 1..];
+            }
+            None => {
+                out.push('{');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Render every string inside a JSON template. A string that is exactly one
+/// placeholder takes the variable's own JSON type, so `"{match_count}"`
+/// becomes a number and a missing value becomes `null`.
+pub fn render_json(template: &Value, vars: &TemplateVars) -> Value {
+    match template {
+        Value::String(s) => {
+            let whole = s
+                .strip_prefix('{')
+                .and_then(|s| s.strip_suffix('}'))
+                .and_then(|name| vars.get(name));
+            match whole {
+                Some(value) => value.clone(),
+                None => Value::String(render_text(s, vars)),
+            }
+        }
+        Value::Array(items) => Value::Array(items.iter().map(|v| render_json(v, vars)).collect()),
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(k, v)| (k.clone(), render_json(v, vars)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// The names of the `{name}` placeholders in a text template, in order.
+/// Only identifier-like names count, so stray braces are not reported.
+pub fn placeholders(template: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rest = &rest[start + 1..];
+        if let Some(end) = rest.find('}') {
+            let name = &rest[..end];
+            if !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+            {
+                names.push(name);
+            }
+        }
+    }
+    names
+}
+
+/// How a value reads inside text: strings bare, missing values empty.
+fn display(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn vars() -> TemplateVars {
+        TemplateVars::from([
+            ("ip".to_string(), json!("192.0.2.1")),
+            ("match_count".to_string(), json!(5)),
+            ("country".to_string(), Value::Null),
+        ])
+    }
+
+    #[test]
+    fn text_substitutes_known_names_only() {
+        assert_eq!(
+            render_text("{ip} hit {match_count}x from {country}; {unknown} {", &vars()),
+            "192.0.2.1 hit 5x from ; {unknown} {"
+        );
+    }
+
+    #[test]
+    fn placeholders_lists_identifier_names() {
+        assert_eq!(
+            placeholders("{ip} {{x}} {Bad} {} { ip } {match_count}"),
+            vec!["ip", "x", "match_count"]
+        );
+    }
+
+    #[test]
+    fn json_keeps_types_for_whole_placeholders() {
+        let template = json!({
+            "text": "Banned {ip}",
+            "count": "{match_count}",
+            "country": "{country}",
+            "tags": ["{ip}", 1, true],
+        });
+        assert_eq!(
+            render_json(&template, &vars()),
+            json!({
+                "text": "Banned 192.0.2.1",
+                "count": 5,
+                "country": null,
+                "tags": ["192.0.2.1", 1, true],
+            })
+        );
+    }
+}
@@ -1,8 +1,13 @@
-use crate::config::Config;
+use crate::allowlist::Allowlist;
+use crate::config::{Config, ConfigKind};
 use crate::detector::Detector;
 use crate::events::{EventEmitter, FirewallCommand};
-use crate::log_source::run_tailer;
+use crate::file_tail::{FileState, FileStatus, TailStatus};
+use crate::health::{Component, ComponentHealth};
+use crate::log_source::{run_ban_feed, run_docker, run_journal, run_syslog, run_tailer};
+use crate::metrics::Metrics;
 use crate::store::MemoryStore;
+use crate::syslog::SyslogListeners;
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::{broadcast, mpsc, RwLock};
@@ -25,13 +30,22 @@ struct WatcherTasks {
     shutdown_tx: broadcast::Sender<()>,
     /// Fan-out of raw tailed lines for live API subscribers.
     line_bus: broadcast::Sender<String>,
+    /// Per-file tailing status; stays empty for other sources.
+    files: TailStatus,
+    /// The line channel's sending side, held weakly so the source still
+    /// closes it; read for its backlog.
+    lines: mpsc::WeakSender<String>,
 }
 
 pub struct WatcherManager {
     store: Arc<MemoryStore>,
     event_emitter: Arc<EventEmitter>,
     firewall_tx: mpsc::Sender<FirewallCommand>,
+    allowlist: Arc<RwLock<Allowlist>>,
     watchers: Arc<RwLock<HashMap<String, WatcherTasks>>>,
+    /// Sockets of the syslog configs, shared by configs on the same address.
+    syslog: SyslogListeners,
+    metrics: Arc<Metrics>,
 }
 
 impl WatcherManager {
@@ -39,12 +53,17 @@ impl WatcherManager {
         store: Arc<MemoryStore>,
         event_emitter: Arc<EventEmitter>,
         firewall_tx: mpsc::Sender<FirewallCommand>,
+        allowlist: Arc<RwLock<Allowlist>>,
+        metrics: Arc<Metrics>,
     ) -> Self {
         Self {
             store,
             event_emitter,
             firewall_tx,
+            allowlist,
             watchers: Arc::new(RwLock::new(HashMap::new())),
+            syslog: SyslogListeners::new(metrics.clone()),
+            metrics,
         }
     }
 
@@ -66,22 +85,62 @@ impl WatcherManager {
             self.store.clone(),
             self.event_emitter.clone(),
             self.firewall_tx.clone(),
+            self.allowlist.clone(),
+            self.metrics.config(&config.id),
         )?;
 
         // One shutdown signal fans out to both tasks; one mpsc carries lines.
         let (shutdown_tx, _) = broadcast::channel(16);
         let (line_tx, line_rx) = mpsc::channel::<String>(LINE_CHANNEL_CAPACITY);
         let (line_bus, _) = broadcast::channel::<String>(LINE_BUS_CAPACITY);
+        let files = TailStatus::default();
+        let lines = line_tx.downgrade();
 
-        // Tailer task: read the file, forward lines.
+        // Tailer task: read the file (or the journal, a container's output,
+        // syslog messages, or for a recidive jail the other configs' bans),
+        // forward lines.
         let tailer = {
-            let param = config.param.clone();
             let id = config_id.clone();
             let bus = line_bus.clone();
             let shutdown_rx = shutdown_tx.subscribe();
-            tokio::spawn(async move {
-                run_tailer(param, id, line_tx, bus, shutdown_rx).await;
-            })
+            match config.kind {
+                ConfigKind::File => {
+                    let param = config.param.clone();
+                    let status = files.clone();
+                    tokio::spawn(async move {
+                        run_tailer(param, id, status, line_tx, bus, shutdown_rx).await;
+                    })
+                }
+                ConfigKind::Journal => {
+                    let param = config.param.clone();
+                    let filter = config.journal.clone();
+                    tokio::spawn(async move {
+                        run_journal(param, filter, id, line_tx, bus, shutdown_rx).await;
+                    })
+                }
+                ConfigKind::Docker => {
+                    let param = config.param.clone();
+                    let filter = config.docker.clone();
+                    tokio::spawn(async move {
+                        run_docker(param, filter, id, line_tx, bus, shutdown_rx).await;
+                    })
+                }
+                ConfigKind::Syslog => {
+                    // Bound here, before any task is spawned, so a taken
+                    // address fails the config.
+                    let source = self.syslog.subscribe(&config.param, &config.syslog).await?;
+                    tokio::spawn(async move {
+                        run_syslog(source, id, line_tx, bus, shutdown_rx).await;
+                    })
+                }
+                ConfigKind::Recidive => {
+                    let events = self.event_emitter.subscribe();
+                    let metrics = self.metrics.clone();
+                    tokio::spawn(async move {
+                        run_ban_feed(id, events, line_tx, bus, shutdown_rx, metrics).await;
+                    })
+                }
+            }
         };
 
         // Detector task: apply policy to lines.
@@ -99,6 +158,8 @@ impl WatcherManager {
                 detector: detector_handle,
                 shutdown_tx,
                 line_bus,
+                files,
+                lines,
             },
         );
         info!("Started watcher for config: {}", config_id);
@@ -119,6 +180,65 @@ impl WatcherManager {
             .map(|tasks| tasks.line_bus.subscribe())
     }
 
+    /// Tailing status of each file a config follows (empty for configs that
+    /// read no files). `None` when no watcher is running for this config.
+    pub async fn file_status(&self, config_id: &str) -> Option<Vec<FileStatus>> {
+        self.watchers
+            .read()
+            .await
+            .get(config_id)
+            .map(|tasks| tasks.files.lock().unwrap().clone())
+    }
+
+    /// Health of each running config's source and detector. Either task
+    /// having ended on its own is degraded, and so is a file config that has
+    /// no file to tail or a file it cannot read.
+    pub async fn health(&self) -> Vec<ComponentHealth> {
+        let watchers = self.watchers.read().await;
+        let mut ids: Vec<&String> = watchers.keys().collect();
+        ids.sort();
+        let mut components = Vec::new();
+        for id in ids {
+            let tasks = &watchers[id];
+            let files = tasks.files.lock().unwrap();
+            let tailer = if tasks.tailer.is_finished() {
+                Some("source stopped".to_string())
+            } else if files.iter().any(|f| f.state == FileState::Error)
+                || (!files.is_empty() && !files.iter().any(|f| f.state == FileState::Tailing))
+            {
+                let problems: Vec<String> = files
+                    .iter()
+                    .filter(|f| f.state != FileState::Tailing)
+                    .map(|f| match &f.error {
+                        Some(e) => format!("{}: {}", f.path, e),
+                        None => format!("{}: missing", f.path),
+                    })
+                    .collect();
+                Some(problems.join("; "))
+            } else {
+                None
+            };
+            let detector = tasks.detector.is_finished().then(|| "detector stopped".to_string());
+            components.push(ComponentHealth::new(Component::Tailer, Some(id), tailer));
+            components.push(ComponentHealth::new(Component::Detector, Some(id), detector));
+        }
+        components
+    }
+
+    /// Lines waiting for each running config's detector.
+    pub async fn line_queue_depths(&self) -> Vec<(String, usize)> {
+        let watchers = self.watchers.read().await;
+        let mut depths: Vec<(String, usize)> = watchers
+            .iter()
+            .filter_map(|(id, tasks)| {
+                let tx = tasks.lines.upgrade()?;
+                Some((id.clone(), tx.max_capacity() - tx.capacity()))
+            })
+            .collect();
+        depths.sort();
+        depths
+    }
+
     /// Stop the tailer + detector pair for a config.
     pub async fn stop_watcher(&self, config_id: &str) -> Result<(), String> {
         let mut watchers = self.watchers.write().await;
@@ -161,6 +281,8 @@ async fn stop_tasks(config_id: &str, tasks: WatcherTasks) {
         detector,
         shutdown_tx,
         line_bus: _,
+        files: _,
+        lines: _,
     } = tasks;
 
     let _ = shutdown_tx.send(());
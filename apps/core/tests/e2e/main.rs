@@ -1,19 +1,29 @@
 mod utils;
+mod test_active_bans;
+mod test_allowlist;
 mod test_api_crud;
+mod test_api_tokens;
 mod test_api_edge;
 mod test_api_events;
+mod test_backtest;
 mod test_ban;
+mod test_ban_expiry_persisted;
 mod test_chain_sanitization;
 mod test_cleanup;
 mod test_config_lifecycle_edge;
 mod test_config_tail;
 mod test_config_validation;
+mod test_cors;
 mod test_delete_config;
 mod test_detection_edge;
+mod test_docker_source;
+mod test_event_pagination;
 mod test_expiry;
+mod test_file_globs;
 mod test_find_time;
 mod test_firewall_error;
 mod test_firewall_unban_error;
+mod test_health;
 mod test_heavy_load;
 mod test_ignore;
 mod test_ignore_cidr;
@@ -21,17 +31,32 @@ mod test_ignore_edge;
 mod test_init;
 mod test_ip_infos;
 mod test_ip_stats;
+mod test_ipset;
+mod test_ipv6;
+mod test_journal_source;
 mod test_log_file_edge;
 mod test_lookahead_regex;
+mod test_manual_ban;
 mod test_match_events;
+mod test_metrics;
+mod test_monitor_mode;
 mod test_multi_config_chains;
+mod test_multi_regex;
+mod test_nftables;
+mod test_notifier_batching;
+mod test_notifier_filters;
+mod test_notifier_outbox;
+mod test_notifier_templates;
 mod test_multiple_ips;
 mod test_no_duplicate_ban;
 mod test_orphan_sweep;
 mod test_reban;
+mod test_recidive_jail;
 mod test_restart_idempotent;
 mod test_restore;
 mod test_restore_expired;
 mod test_restore_window;
+mod test_syslog_source;
 mod test_threshold;
 mod test_unban;
+mod test_webhook_notifier;
@@ -0,0 +1,118 @@
+use crate::utils::TestProcess;
+use serde_json::json;
+
+fn active(proc: &TestProcess, path: &str) -> (Option<String>, Vec<serde_json::Value>) {
+    let resp = proc.client().get(proc.api_url(path)).send().unwrap();
+    assert_eq!(resp.status(), 200);
+    let total = resp
+        .headers()
+        .get("x-total-count")
+        .map(|v| v.to_str().unwrap().to_string());
+    (total, resp.json().unwrap())
+}
+
+#[test]
+fn test_active_bans_come_from_memory() {
+    // GIVEN a detected ban under one config and a permanent range ban under
+    // another
+    let proc = TestProcess::start();
+    let log = proc.log_file.to_str().unwrap();
+    proc.create_config_with_ban_time("cfg-active-a", log, "Active hit from <IP>", 1, &[], 600_000);
+    proc.create_config("cfg-active-b", log, "Other hit from <IP>", 5, &[]);
+    proc.append_log_line("Active hit from 10.69.0.1");
+    assert!(proc.wait_for_ban("10.69.0.1", 5000));
+    let resp = proc
+        .client()
+        .post(proc.api_url("/api/bans"))
+        .json(&json!({ "config_id": "cfg-active-b", "ip": "10.70.0.0/16", "permanent": true }))
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    // WHEN the active bans are listed
+    let (total, bans) = active(&proc, "/api/bans/active");
+
+    // THEN both are there, newest first, with their expiry and recidive count
+    assert_eq!(total.as_deref(), Some("2"));
+    assert_eq!(bans.len(), 2, "{:?}", bans);
+    let range = &bans[0];
+    assert_eq!(range["ip"], "10.70.0.0/16");
+    assert_eq!(range["config_id"], "cfg-active-b");
+    assert_eq!(range["permanent"], true);
+    assert!(range["expires_at"].is_null());
+    assert!(range["remaining"].is_null());
+    assert_eq!(range["recidive_count"], 0);
+    let host = &bans[1];
+    assert_eq!(host["ip"], "10.69.0.1");
+    assert_eq!(host["permanent"], false);
+    assert_eq!(host["recidive_count"], 1);
+    let started = host["timestamp"].as_u64().unwrap();
+    assert_eq!(host["expires_at"].as_u64().unwrap(), started + 600_000);
+    let remaining = host["remaining"].as_u64().unwrap();
+    assert!(remaining > 0 && remaining <= 600_000, "{}", remaining);
+    assert!(host["country_code"].is_null());
+
+    // AND each config can be listed on its own, and paged
+    let (_, only_a) = active(&proc, "/api/bans/active/cfg-active-a");
+    assert_eq!(only_a.len(), 1);
+    assert_eq!(only_a[0]["ip"], "10.69.0.1");
+    let (_, by_query) = active(&proc, "/api/bans/active?config_id=cfg-active-b");
+    assert_eq!(by_query.len(), 1);
+    let (total, page) = active(&proc, "/api/bans/active?limit=1&offset=1");
+    assert_eq!(total.as_deref(), Some("2"));
+    assert_eq!(page.len(), 1);
+    assert_eq!(page[0]["ip"], "10.69.0.1");
+
+    // AND without a limit a page is bounded at 100 bans
+    for i in 0..100 {
+        let resp = proc
+            .client()
+            .post(proc.api_url("/api/bans"))
+            .json(&json!({ "config_id": "cfg-active-b", "ip": format!("10.71.0.{i}") }))
+            .send()
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+    }
+    let (total, page) = active(&proc, "/api/bans/active");
+    assert_eq!(total.as_deref(), Some("102"));
+    assert_eq!(page.len(), 100);
+}
+
+#[test]
+fn test_lifted_bans_leave_the_active_list() {
+    // GIVEN a detected ban
+    let proc = TestProcess::start();
+    proc.create_config(
+        "cfg-active-lift",
+        proc.log_file.to_str().unwrap(),
+        "Lift hit from <IP>",
+        1,
+        &[],
+    );
+    proc.append_log_line("Lift hit from 10.71.0.1");
+    assert!(proc.wait_for_ban("10.71.0.1", 5000));
+    let (_, bans) = active(&proc, "/api/bans/active/cfg-active-lift");
+    assert_eq!(bans.len(), 1);
+
+    // WHEN it is disabled by hand
+    let events: Vec<serde_json::Value> = proc
+        .client()
+        .get(proc.api_url("/api/bans/cfg-active-lift"))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    let id = events[0]["id"].as_str().unwrap();
+    let resp = proc
+        .client()
+        .post(proc.api_url(&format!("/api/bans/{}/disable", id)))
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    // THEN it is no longer active, though the audit log keeps it
+    let (total, bans) = active(&proc, "/api/bans/active");
+    assert_eq!(total.as_deref(), Some("0"));
+    assert!(bans.is_empty(), "{:?}", bans);
+    assert!(proc.banned_ips().contains(&"10.71.0.1".to_string()));
+}
@@ -0,0 +1,160 @@
+use crate::utils::{del_drop_rule, drop_rule, TestProcess};
+use serde_json::json;
+use std::thread;
+use std::time::Duration;
+
+fn post_entry(proc: &TestProcess, query: &str, body: serde_json::Value) -> reqwest::blocking::Response {
+    proc.client()
+        .post(proc.api_url(&format!("/api/allowlist{}", query)))
+        .json(&body)
+        .send()
+        .unwrap()
+}
+
+#[test]
+fn test_allowlist_applies_to_every_config() {
+    // GIVEN a global allowlist entry and two configs that know nothing of it
+    let proc = TestProcess::start();
+    let resp = post_entry(&proc, "", json!({ "ip": "10.48.0.0/24", "comment": "office" }));
+    assert_eq!(resp.status(), 200);
+    proc.create_config("cfg-allow-a", proc.log_file.to_str().unwrap(), "A hit from <IP>", 1, &[]);
+    proc.create_config("cfg-allow-b", proc.log_file.to_str().unwrap(), "B hit from <IP>", 1, &[]);
+
+    // WHEN an allowlisted IP hits both, followed by a sentinel IP in each
+    proc.append_log_line("A hit from 10.48.0.5");
+    proc.append_log_line("B hit from 10.48.0.5");
+    proc.append_log_line("A hit from 10.48.1.1");
+    proc.append_log_line("B hit from 10.48.1.2");
+    assert!(proc.wait_for_ban("10.48.1.1", 5000), "sentinel A was not banned");
+    assert!(proc.wait_for_ban("10.48.1.2", 5000), "sentinel B was not banned");
+
+    // THEN the allowlisted IP was neither banned nor recorded as a match
+    assert!(!proc.banned_ips().contains(&"10.48.0.5".to_string()));
+    assert_eq!(proc.match_count("cfg-allow-a"), 1);
+    assert_eq!(proc.match_count("cfg-allow-b"), 1);
+}
+
+#[test]
+fn test_manual_ban_refuses_allowlisted_targets() {
+    // GIVEN an allowlisted office range and a config
+    let proc = TestProcess::start();
+    let resp = post_entry(&proc, "", json!({ "ip": "10.48.8.0/24", "comment": "office" }));
+    assert_eq!(resp.status(), 200);
+    proc.create_config("cfg-allow-manual", proc.log_file.to_str().unwrap(), "never <IP>", 1, &[]);
+    let ban = |ip: &str| {
+        proc.client()
+            .post(proc.api_url("/api/bans"))
+            .json(&json!({ "config_id": "cfg-allow-manual", "ip": ip }))
+            .send()
+            .unwrap()
+            .status()
+    };
+
+    // WHEN an operator bans an address inside it, or a range containing it
+    // THEN both are refused and nothing reaches the firewall
+    assert_eq!(ban("10.48.8.7"), 409);
+    assert_eq!(ban("10.48.0.0/16"), 409);
+    assert!(proc.banned_ips().is_empty());
+    assert!(!proc.read_iptables_log().contains("10.48.8.7"));
+
+    // AND a target outside the allowlist is still banned
+    assert_eq!(ban("10.48.9.7"), 200);
+}
+
+#[test]
+fn test_allowlist_entry_can_lift_covered_bans() {
+    // GIVEN one IP banned under two configs, and another banned under one
+    let proc = TestProcess::start();
+    proc.create_config("cfg-lift-a", proc.log_file.to_str().unwrap(), "LA hit from <IP>", 1, &[]);
+    proc.create_config("cfg-lift-b", proc.log_file.to_str().unwrap(), "LB hit from <IP>", 1, &[]);
+    proc.append_log_line("LA hit from 10.48.2.1");
+    proc.append_log_line("LB hit from 10.48.2.1");
+    proc.append_log_line("LA hit from 10.48.3.1");
+    for config_id in ["cfg-lift-a", "cfg-lift-b"] {
+        assert!(
+            proc.wait_for_iptables_contains(&drop_rule(config_id, "10.48.2.1"), 5000),
+            "IP was not banned in {config_id}:\n{}",
+            proc.read_iptables_log()
+        );
+    }
+    assert!(proc.wait_for_ban("10.48.3.1", 5000), "second IP was not banned");
+
+    // WHEN the first IP's network is allowlisted without lifting bans
+    let resp = post_entry(&proc, "", json!({ "id": "no-lift", "ip": "10.48.3.0/24" }));
+    assert_eq!(resp.status(), 200);
+    // AND the first IP itself is allowlisted with lift_bans
+    let resp = post_entry(&proc, "?lift_bans=true", json!({ "id": "lift", "ip": "10.48.2.1" }));
+    assert_eq!(resp.status(), 200);
+
+    // THEN the covered bans are lifted in every config
+    for config_id in ["cfg-lift-a", "cfg-lift-b"] {
+        assert!(
+            proc.wait_for_iptables_contains(&del_drop_rule(config_id, "10.48.2.1"), 3000),
+            "ban not lifted in {config_id}:\n{}",
+            proc.read_iptables_log()
+        );
+    }
+    assert!(proc.wait_for_unban("10.48.2.1", 3000), "no unban event recorded");
+
+    // AND the entry added without lift_bans left its ban in place
+    thread::sleep(Duration::from_millis(300));
+    assert!(!proc.read_iptables_log().contains(&del_drop_rule("cfg-lift-a", "10.48.3.1")));
+}
+
+#[test]
+fn test_allowlist_crud_persists() {
+    // GIVEN a process with the DB in a known directory
+    let db_dir = tempfile::tempdir().unwrap();
+    let log_file = db_dir.path().join("test.log");
+    let iptables_log = db_dir.path().join("iptables.log");
+    let mut proc1 = TestProcess::start_at(db_dir.path(), &log_file, &iptables_log);
+
+    // WHEN entries are created, normalized and updated
+    let resp = post_entry(&proc1, "", json!({ "id": "mon", "ip": "192.0.2.77/24" }));
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.json::<serde_json::Value>().unwrap()["ip"], "192.0.2.0/24");
+    assert_eq!(post_entry(&proc1, "", json!({ "id": "mon", "ip": "192.0.2.1" })).status(), 409);
+    assert_eq!(post_entry(&proc1, "", json!({ "ip": "not-an-ip" })).status(), 400);
+    let resp = proc1
+        .client()
+        .put(proc1.api_url("/api/allowlist/mon"))
+        .json(&json!({ "id": "mon", "ip": "2001:db8::/64", "comment": "monitoring" }))
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    // THEN they survive a restart
+    proc1.stop();
+    let proc2 = TestProcess::start_at(db_dir.path(), &log_file, &iptables_log);
+    let entry: serde_json::Value = proc2
+        .client()
+        .get(proc2.api_url("/api/allowlist/mon"))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(entry["ip"], "2001:db8::/64");
+    assert_eq!(entry["comment"], "monitoring");
+
+    // AND deleting removes them
+    let resp = proc2
+        .client()
+        .delete(proc2.api_url("/api/allowlist/mon"))
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), 204);
+    let resp = proc2
+        .client()
+        .get(proc2.api_url("/api/allowlist/mon"))
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+
+    // AND deleting an unknown entry is a 404
+    let resp = proc2
+        .client()
+        .delete(proc2.api_url("/api/allowlist/mon"))
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+}
@@ -0,0 +1,151 @@
+use crate::utils::TestProcess;
+use serde_json::json;
+
+const BOOTSTRAP: &str = "bootstrap-secret";
+
+fn create_token(proc: &TestProcess, bearer: &str, name: &str, role: &str) -> serde_json::Value {
+    let resp = proc
+        .client()
+        .post(proc.api_url("/api/tokens"))
+        .bearer_auth(bearer)
+        .json(&json!({ "name": name, "role": role }))
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), 200, "token creation failed");
+    resp.json().unwrap()
+}
+
+fn status(proc: &TestProcess, method: reqwest::Method, path: &str, bearer: Option<&str>) -> u16 {
+    let mut req = proc.client().request(method, proc.api_url(path));
+    if let Some(bearer) = bearer {
+        req = req.bearer_auth(bearer);
+    }
+    req.send().unwrap().status().as_u16()
+}
+
+#[test]
+fn test_roles_gate_reads_and_writes() {
+    // GIVEN a process with a bootstrap admin token, and a read-only token
+    let proc = TestProcess::start_with_env(&[("BANALIZE_CORE_API_TOKEN", BOOTSTRAP)]);
+    create_token(&proc, BOOTSTRAP, "ops", "admin");
+    let reader = create_token(&proc, BOOTSTRAP, "dashboard", "read_only");
+    let reader = reader["secret"].as_str().unwrap();
+
+    // WHEN requests come without a token, or with an unknown one
+    // THEN they are refused, except the public routes
+    assert_eq!(status(&proc, reqwest::Method::GET, "/api/configs", None), 401);
+    assert_eq!(status(&proc, reqwest::Method::GET, "/api/configs", Some("nope")), 401);
+    assert_eq!(status(&proc, reqwest::Method::GET, "/api/health", None), 200);
+
+    // AND the read-only token reads stats but cannot lift bans or see tokens
+    assert_eq!(status(&proc, reqwest::Method::GET, "/api/ips/stats", Some(reader)), 200);
+    assert_eq!(status(&proc, reqwest::Method::GET, "/api/bans", Some(reader)), 200);
+    assert_eq!(
+        status(&proc, reqwest::Method::POST, "/api/bans/some-id/disable", Some(reader)),
+        403
+    );
+    assert_eq!(status(&proc, reqwest::Method::GET, "/api/tokens", Some(reader)), 403);
+    assert_eq!(status(&proc, reqwest::Method::GET, "/metrics", Some(reader)), 200);
+    assert_eq!(status(&proc, reqwest::Method::GET, "/metrics", None), 401);
+
+    // AND the admin token gets through to the handler
+    assert_eq!(
+        status(&proc, reqwest::Method::POST, "/api/bans/some-id/disable", Some(BOOTSTRAP)),
+        404
+    );
+}
+
+#[test]
+fn test_tokens_are_stored_hashed_and_revocable() {
+    // GIVEN a process with a bootstrap admin token
+    let db_dir = tempfile::tempdir().unwrap();
+    let log_file = db_dir.path().join("test.log");
+    let iptables_log = db_dir.path().join("iptables.log");
+    let mut proc1 = TestProcess::start_at_with_env(
+        db_dir.path(),
+        &log_file,
+        &iptables_log,
+        &[("BANALIZE_CORE_API_TOKEN", BOOTSTRAP)],
+    );
+
+    // WHEN a first admin token is created
+    let admin = create_token(&proc1, BOOTSTRAP, "ops", "admin");
+    let secret = admin["secret"].as_str().unwrap().to_string();
+
+    // THEN it survives a restart without the bootstrap token, and keeps the
+    // API locked
+    proc1.stop();
+    let proc2 = TestProcess::start_at(db_dir.path(), &log_file, &iptables_log);
+    assert_eq!(status(&proc2, reqwest::Method::GET, "/api/configs", None), 401);
+    assert_eq!(status(&proc2, reqwest::Method::GET, "/api/configs", Some(&secret)), 200);
+
+    // AND only its hash is kept, and listings never show it
+    let stored = std::fs::read(db_dir.path().join("configs.db")).unwrap();
+    assert!(!String::from_utf8_lossy(&stored).contains(&secret));
+    let listed: Vec<serde_json::Value> = proc2
+        .client()
+        .get(proc2.api_url("/api/tokens"))
+        .bearer_auth(&secret)
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0]["role"], "admin");
+    assert!(listed[0].get("secret").is_none());
+
+    // AND a second token keeps working after the first is revoked
+    let other = create_token(&proc2, &secret, "ops-2", "admin");
+    let path = format!("/api/tokens/{}", admin["id"].as_str().unwrap());
+    assert_eq!(status(&proc2, reqwest::Method::DELETE, &path, Some(&secret)), 204);
+    assert_eq!(status(&proc2, reqwest::Method::GET, "/api/configs", Some(&secret)), 401);
+    let other = other["secret"].as_str().unwrap();
+    assert_eq!(status(&proc2, reqwest::Method::GET, "/api/configs", Some(other)), 200);
+}
+
+#[test]
+fn test_open_api_refuses_to_mint_tokens() {
+    // GIVEN an open process (no token configured)
+    let proc = TestProcess::start();
+    assert_eq!(status(&proc, reqwest::Method::GET, "/api/configs", None), 200);
+
+    // WHEN anyone asks for a first admin token
+    let resp = proc
+        .client()
+        .post(proc.api_url("/api/tokens"))
+        .json(&json!({ "name": "ops", "role": "admin" }))
+        .send()
+        .unwrap();
+
+    // THEN it is refused and the API stays as it was
+    assert_eq!(resp.status(), 403);
+    assert_eq!(status(&proc, reqwest::Method::GET, "/api/configs", None), 200);
+}
+
+#[test]
+fn test_token_changes_cannot_lock_out_admins() {
+    // GIVEN a process with a bootstrap admin token and no stored token
+    let proc = TestProcess::start_with_env(&[("BANALIZE_CORE_API_TOKEN", BOOTSTRAP)]);
+
+    // WHEN the first stored token asked for is read-only
+    let resp = proc
+        .client()
+        .post(proc.api_url("/api/tokens"))
+        .bearer_auth(BOOTSTRAP)
+        .json(&json!({ "name": "dashboard", "role": "read_only" }))
+        .send()
+        .unwrap();
+
+    // THEN it is refused until an admin token is stored
+    assert_eq!(resp.status(), 409);
+    let admin = create_token(&proc, BOOTSTRAP, "ops", "admin");
+    let reader = create_token(&proc, BOOTSTRAP, "dashboard", "read_only");
+
+    // AND the last stored admin token cannot be revoked, while others can
+    let admin_path = format!("/api/tokens/{}", admin["id"].as_str().unwrap());
+    let reader_path = format!("/api/tokens/{}", reader["id"].as_str().unwrap());
+    assert_eq!(status(&proc, reqwest::Method::DELETE, &admin_path, Some(BOOTSTRAP)), 409);
+    assert_eq!(status(&proc, reqwest::Method::DELETE, &reader_path, Some(BOOTSTRAP)), 204);
+    let admin = admin["secret"].as_str().unwrap();
+    assert_eq!(status(&proc, reqwest::Method::GET, "/api/tokens", Some(admin)), 200);
+}
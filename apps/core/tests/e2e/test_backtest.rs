@@ -0,0 +1,157 @@
+use crate::utils::{TestProcess, BINARY};
+use serde_json::json;
+use std::process::Command;
+
+/// sshd failures of two IPs a few seconds apart, with an RFC 3164 date.
+const AUTH_LOG: &str = "\
+Oct 18 22:00:00 gw sshd[1]: Failed password for root from 10.74.0.1 port 22
+Oct 18 22:00:01 gw sshd[1]: Failed password for root from 10.74.0.2 port 22
+Oct 18 22:00:02 gw sshd[1]: Failed password for root from 10.74.0.1 port 22
+Oct 18 22:00:03 gw sshd[1]: Accepted publickey for deploy from 10.74.0.3 port 22
+Oct 18 22:00:04 gw sshd[1]: Failed password for root from 10.74.0.1 port 22
+Oct 18 22:09:00 gw sshd[1]: Failed password for root from 10.74.0.2 port 22
+";
+
+fn backtest(proc: &TestProcess, body: serde_json::Value) -> reqwest::blocking::Response {
+    proc.client()
+        .post(proc.api_url("/api/configs/backtest"))
+        .json(&body)
+        .send()
+        .unwrap()
+}
+
+#[test]
+fn test_backtest_existing_config_against_sample() {
+    // GIVEN a config banning after three failures within a minute
+    let proc = TestProcess::start();
+    proc.create_config(
+        "cfg-backtest",
+        proc.log_file.to_str().unwrap(),
+        "Failed password for .* from <IP>",
+        3,
+        &[],
+    );
+
+    // WHEN an uploaded log is replayed against it
+    let resp = backtest(&proc, json!({ "config_id": "cfg-backtest", "sample": AUTH_LOG, "year": 2026 }));
+
+    // THEN only the IP failing three times within find_time, by the log's own
+    // clock, would have been banned, on its third failure
+    assert_eq!(resp.status(), 200);
+    let report: serde_json::Value = resp.json().unwrap();
+    assert_eq!(report["lines"], 6);
+    assert_eq!(report["matched_lines"], 5);
+    let bans = report["bans"].as_array().unwrap();
+    assert_eq!(bans.len(), 1, "{}", report);
+    assert_eq!(bans[0]["ip"], "10.74.0.1");
+    assert_eq!(bans[0]["line"], 5);
+    assert_eq!(bans[0]["timestamp"], 1_792_360_804_000u64); // 2026-10-18T22:00:04Z
+    assert_eq!(bans[0]["expires_at"], 1_792_360_864_000u64);
+    assert_eq!(report["ips"][0]["ip"], "10.74.0.1");
+    assert_eq!(report["ips"][0]["matches"], 3);
+    assert_eq!(report["ips"][1]["bans"], 0);
+
+    // AND nothing was banned or recorded for real
+    assert!(proc.banned_ips().is_empty());
+    assert_eq!(proc.match_count("cfg-backtest"), 0);
+}
+
+#[test]
+fn test_backtest_candidate_pattern_against_file() {
+    // GIVEN a log file next to a tailed one, and an allowlisted IP
+    let proc = TestProcess::start();
+    proc.create_config("cfg-backtest-re", proc.log_file.to_str().unwrap(), "never <IP>", 1, &[]);
+    let path = proc.log_file.with_file_name("auth-history.log");
+    std::fs::write(&path, AUTH_LOG).unwrap();
+    let resp = proc
+        .client()
+        .post(proc.api_url("/api/allowlist"))
+        .json(&json!({ "ip": "10.74.0.2" }))
+        .send()
+        .unwrap();
+    assert!(resp.status().is_success());
+
+    // WHEN a candidate config with a broader pattern is replayed on it
+    let candidate = json!({
+        "id": "candidate",
+        "name": "candidate",
+        "regexes": ["for \\S+ from <IP>"],
+        "ban_time": 60000,
+        "find_time": 600000,
+        "max_matches": 1,
+        "ignore_ips": ["10.74.0.3"]
+    });
+    let resp = backtest(&proc, json!({ "config": candidate, "path": path.to_str().unwrap() }));
+
+    // THEN it bans on the first hit, once per ban_time, leaving out what is
+    // ignored or allowlisted
+    assert_eq!(resp.status(), 200);
+    let report: serde_json::Value = resp.json().unwrap();
+    assert_eq!(report["ignored_lines"], 3);
+    let banned: Vec<&str> = report["bans"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|b| b["ip"].as_str().unwrap())
+        .collect();
+    assert_eq!(banned, vec!["10.74.0.1"], "{}", report);
+
+    // AND candidate patterns can stand in for an existing config's
+    let resp = backtest(
+        &proc,
+        json!({ "config_id": "cfg-backtest-re", "regexes": ["Accepted .* from <IP>"], "sample": AUTH_LOG }),
+    );
+    let report: serde_json::Value = resp.json().unwrap();
+    assert_eq!(report["bans"][0]["ip"], "10.74.0.3");
+}
+
+#[test]
+fn test_backtest_rejects_bad_requests() {
+    let proc = TestProcess::start();
+    proc.create_config("cfg-backtest-bad", proc.log_file.to_str().unwrap(), "fail <IP>", 1, &[]);
+    let cases = [
+        (json!({ "config_id": "missing", "sample": AUTH_LOG }), 404),
+        (json!({ "sample": AUTH_LOG }), 400),
+        (json!({ "config_id": "cfg-backtest-bad" }), 400),
+        (json!({ "config_id": "cfg-backtest-bad", "sample": "x", "path": "/tmp/x" }), 400),
+        (json!({ "config_id": "cfg-backtest-bad", "regexes": ["no placeholder"], "sample": "x" }), 400),
+        (json!({ "config_id": "cfg-backtest-bad", "path": "/nonexistent/auth.log" }), 400),
+        // Only files where file configs tail can be read.
+        (json!({ "config_id": "cfg-backtest-bad", "path": "/etc/passwd" }), 403),
+    ];
+    for (body, status) in cases {
+        assert_eq!(backtest(&proc, body.clone()).status(), status, "{}", body);
+    }
+}
+
+#[test]
+fn test_backtest_cli() {
+    // GIVEN a log file
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("auth.log");
+    std::fs::write(&path, AUTH_LOG).unwrap();
+
+    // WHEN the CLI replays it with a candidate pattern
+    let output = Command::new(BINARY)
+        .args(["backtest", "--regex", "Failed password for .* from <IP>", "--max-matches", "2"])
+        .args(["--find-time", "600000", "--year", "2026", "--json"])
+        .arg(&path)
+        .output()
+        .unwrap();
+
+    // THEN it prints the report without starting the daemon
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let banned: Vec<&str> = report["bans"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|b| b["ip"].as_str().unwrap())
+        .collect();
+    assert_eq!(banned, vec!["10.74.0.1", "10.74.0.2"]);
+
+    // AND bad usage fails with a message
+    let output = Command::new(BINARY).args(["backtest", "--bogus"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unknown option --bogus"));
+}
@@ -0,0 +1,158 @@
+use crate::utils::{drop_rule, TestProcess};
+use serde_json::json;
+use std::thread;
+use std::time::Duration;
+
+fn ban_for(proc: &TestProcess, ip: &str) -> serde_json::Value {
+    let bans: Vec<serde_json::Value> = proc
+        .client()
+        .get(proc.api_url("/api/bans"))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    bans.into_iter()
+        .find(|b| b["ip"].as_str() == Some(ip))
+        .expect("ban event not found")
+}
+
+#[test]
+fn test_config_edit_does_not_shorten_active_ban() {
+    // GIVEN an IP banned for a minute
+    let test_ip = "10.47.0.1";
+    let db_dir = tempfile::tempdir().unwrap();
+    let log_file = db_dir.path().join("test.log");
+    let iptables_log_1 = db_dir.path().join("iptables_1.log");
+    let iptables_log_2 = db_dir.path().join("iptables_2.log");
+    let mut proc1 = TestProcess::start_at(db_dir.path(), &log_file, &iptables_log_1);
+    proc1.create_config("cfg-expiry-kept", log_file.to_str().unwrap(), "Kept hit from <IP>", 1, &[]);
+    proc1.append_log_line(&format!("Kept hit from {}", test_ip));
+    assert!(proc1.wait_for_ban(test_ip, 5000), "IP {test_ip} was not banned");
+    let ban = ban_for(&proc1, test_ip);
+    assert_eq!(ban["expires_at"].as_u64(), Some(ban["timestamp"].as_u64().unwrap() + 60000));
+    assert_eq!(ban["permanent"], false);
+
+    // WHEN the config's ban_time is cut to one second and the process restarts
+    // after that second has passed
+    let resp = proc1.put_config_raw(
+        "cfg-expiry-kept",
+        &json!({
+            "id": "cfg-expiry-kept",
+            "name": "cfg-expiry-kept",
+            "param": log_file.to_str().unwrap(),
+            "regexes": ["Kept hit from <IP>"],
+            "ban_time": 1000,
+            "find_time": 60000,
+            "max_matches": 1,
+            "ignore_ips": [],
+        }),
+    );
+    assert!(resp.status().is_success(), "config update failed: {}", resp.status());
+    proc1.stop();
+    thread::sleep(Duration::from_millis(1500));
+    let proc2 = TestProcess::start_at(db_dir.path(), &log_file, &iptables_log_2);
+
+    // THEN the ban is restored with the duration it was issued with
+    assert!(
+        proc2.wait_for_iptables_contains(&drop_rule("cfg-expiry-kept", test_ip), 3000),
+        "active ban was not restored:\n{}",
+        proc2.read_iptables_log()
+    );
+    assert_eq!(ban_for(&proc2, test_ip)["expires_at"], ban["expires_at"]);
+}
+
+#[test]
+fn test_permanent_ban_never_expires() {
+    // GIVEN a config whose bans last one second
+    let test_ip = "10.47.1.1";
+    let db_dir = tempfile::tempdir().unwrap();
+    let log_file = db_dir.path().join("test.log");
+    let iptables_log_1 = db_dir.path().join("iptables_1.log");
+    let iptables_log_2 = db_dir.path().join("iptables_2.log");
+    let mut proc1 = TestProcess::start_at(db_dir.path(), &log_file, &iptables_log_1);
+    proc1.create_config_with_ban_time(
+        "cfg-permanent",
+        log_file.to_str().unwrap(),
+        "Permanent hit from <IP>",
+        1,
+        &[],
+        1000,
+    );
+
+    // WHEN an IP is banned permanently by hand
+    let resp = proc1
+        .client()
+        .post(proc1.api_url("/api/bans"))
+        .json(&json!({ "config_id": "cfg-permanent", "ip": test_ip, "permanent": true }))
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let ban: serde_json::Value = resp.json().unwrap();
+    assert_eq!(ban["permanent"], true);
+    assert!(ban["expires_at"].is_null());
+
+    // THEN the cleaner leaves it in place well past the config's ban_time
+    assert!(
+        !proc1.wait_for_unban(test_ip, 3000),
+        "permanent ban was lifted by the cleaner"
+    );
+
+    // AND a restart restores it
+    proc1.stop();
+    let proc2 = TestProcess::start_at(db_dir.path(), &log_file, &iptables_log_2);
+    assert!(
+        proc2.wait_for_iptables_contains(&drop_rule("cfg-permanent", test_ip), 3000),
+        "permanent ban was not restored:\n{}",
+        proc2.read_iptables_log()
+    );
+    let stored = ban_for(&proc2, test_ip);
+    assert_eq!(stored["permanent"], true);
+    assert!(stored["expires_at"].is_null());
+
+    // AND a duration on a permanent ban is rejected
+    let resp = proc2
+        .client()
+        .post(proc2.api_url("/api/bans"))
+        .json(&json!({ "config_id": "cfg-permanent", "ip": "10.47.1.2", "permanent": true, "duration": 1000 }))
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+}
+
+#[test]
+fn test_legacy_ban_row_falls_back_to_config_ban_time() {
+    // GIVEN a ban row written before durations were stored on it
+    let test_ip = "10.47.2.1";
+    let db_dir = tempfile::tempdir().unwrap();
+    let log_file = db_dir.path().join("test.log");
+    let iptables_log_1 = db_dir.path().join("iptables_1.log");
+    let iptables_log_2 = db_dir.path().join("iptables_2.log");
+    let mut proc1 = TestProcess::start_at(db_dir.path(), &log_file, &iptables_log_1);
+    proc1.create_config("cfg-expiry-legacy", log_file.to_str().unwrap(), "Legacy hit from <IP>", 1, &[]);
+    proc1.stop();
+    {
+        let conn = rusqlite::Connection::open(db_dir.path().join("events.db")).unwrap();
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as i64;
+        conn.execute(
+            "INSERT INTO ban_events (id, config_id, ip, timestamp) VALUES ('legacy-ban', 'cfg-expiry-legacy', ?1, ?2)",
+            rusqlite::params![test_ip, now],
+        )
+        .unwrap();
+    }
+
+    // WHEN the process restarts
+    let proc2 = TestProcess::start_at(db_dir.path(), &log_file, &iptables_log_2);
+
+    // THEN the ban is restored off the config's ban_time, with no stored expiry
+    assert!(
+        proc2.wait_for_iptables_contains(&drop_rule("cfg-expiry-legacy", test_ip), 3000),
+        "legacy ban was not restored:\n{}",
+        proc2.read_iptables_log()
+    );
+    let stored = ban_for(&proc2, test_ip);
+    assert!(stored["expires_at"].is_null());
+    assert_eq!(stored["permanent"], false);
+}
@@ -0,0 +1,49 @@
+use crate::utils::TestProcess;
+
+const DASHBOARD: &str = "https://dash.example.com";
+
+/// The `Access-Control-Allow-Origin` a preflight from `origin` gets back.
+fn preflight(proc: &TestProcess, origin: &str) -> Option<String> {
+    let resp = proc
+        .client()
+        .request(reqwest::Method::OPTIONS, proc.api_url("/api/configs"))
+        .header("Origin", origin)
+        .header("Access-Control-Request-Method", "DELETE")
+        .header("Access-Control-Request-Headers", "authorization")
+        .send()
+        .unwrap();
+    resp.headers()
+        .get("access-control-allow-origin")
+        .map(|v| v.to_str().unwrap().to_string())
+}
+
+#[test]
+fn test_cors_closed_by_default() {
+    // GIVEN a process without configured origins
+    let proc = TestProcess::start();
+
+    // WHEN another origin sends a preflight
+    // THEN it is not allowed
+    assert_eq!(preflight(&proc, DASHBOARD), None);
+}
+
+#[test]
+fn test_cors_allows_only_configured_origins() {
+    // GIVEN a process allowing one dashboard origin
+    let proc = TestProcess::start_with_env(&[("BANALIZE_CORE_CORS_ORIGINS", &format!("{}, *", DASHBOARD))]);
+
+    // WHEN that origin and another one send preflights
+    // THEN only the configured origin is allowed, `*` being ignored
+    assert_eq!(preflight(&proc, DASHBOARD).as_deref(), Some(DASHBOARD));
+    assert_eq!(preflight(&proc, "https://evil.example.com"), None);
+
+    // AND it can read the paging header of a list
+    let resp = proc
+        .client()
+        .get(proc.api_url("/api/bans"))
+        .header("Origin", DASHBOARD)
+        .send()
+        .unwrap();
+    let exposed = resp.headers().get("access-control-expose-headers").unwrap();
+    assert!(exposed.to_str().unwrap().contains("x-total-count"));
+}
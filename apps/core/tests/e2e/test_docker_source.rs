@@ -0,0 +1,91 @@
+use crate::utils::{DockerStub, TestProcess};
+use serde_json::json;
+
+fn docker_config(id: &str, container: &str, labels: &[&str]) -> serde_json::Value {
+    json!({
+        "id": id,
+        "name": id,
+        "kind": "docker",
+        "param": container,
+        "regexes": ["Failed login from <IP>"],
+        "ban_time": 60000,
+        "find_time": 60000,
+        "max_matches": 2,
+        "ignore_ips": [],
+        "docker": { "labels": labels }
+    })
+}
+
+fn start(stub: &DockerStub) -> TestProcess {
+    TestProcess::start_with_env(&[("BANALIZE_CORE_DOCKER_SOCKET", &stub.socket())])
+}
+
+#[test]
+fn test_docker_config_follows_container_across_restart() {
+    // GIVEN a running container that already logged failures, and a docker
+    // config following it by name
+    let stub = DockerStub::start();
+    stub.add_container("web", &[]);
+    stub.log("web", "Failed login from 10.57.0.9");
+    stub.log("web", "Failed login from 10.57.0.9");
+    let proc = start(&stub);
+    let resp = proc.post_config_raw(&docker_config("cfg-docker", "web", &[]));
+    assert_eq!(resp.status(), 200);
+    std::thread::sleep(std::time::Duration::from_millis(500));
+
+    // WHEN the container logs failures, restarts, and logs more
+    stub.log("web", "Failed login from 10.57.0.1");
+    stub.log("web", "Failed login from 10.57.0.1");
+    assert!(proc.wait_for_ban("10.57.0.1", 5000), "offender not banned");
+    stub.restart("web");
+    stub.log("web", "Failed login from 10.57.0.2");
+    stub.log("web", "Failed login from 10.57.0.2");
+
+    // THEN the follower reconnects without losing or repeating lines, and
+    // output from before the config is ignored
+    assert!(proc.wait_for_ban("10.57.0.2", 5000), "offender after restart not banned");
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    assert_eq!(proc.match_count("cfg-docker"), 4);
+    assert!(!proc.banned_ips().contains(&"10.57.0.9".to_string()));
+}
+
+#[test]
+fn test_docker_config_follows_labelled_containers() {
+    // GIVEN containers with different labels and a config selecting one label
+    let stub = DockerStub::start();
+    stub.add_container("api-1", &["app=api"]);
+    stub.add_container("db", &["app=db"]);
+    let proc = start(&stub);
+    let resp = proc.post_config_raw(&docker_config("cfg-docker-labels", "", &["app=api"]));
+    assert_eq!(resp.status(), 200);
+    std::thread::sleep(std::time::Duration::from_millis(500));
+
+    // WHEN every container logs failures, including one started later
+    for (container, ip) in [("api-1", "10.58.0.1"), ("db", "10.58.0.2")] {
+        stub.log(container, &format!("Failed login from {ip}"));
+        stub.log(container, &format!("Failed login from {ip}"));
+    }
+    stub.add_container("api-2", &["app=api", "tier=edge"]);
+    std::thread::sleep(std::time::Duration::from_millis(1500));
+    stub.log("api-2", "Failed login from 10.58.0.3");
+    stub.log("api-2", "Failed login from 10.58.0.3");
+
+    // THEN only the labelled containers' offenders are banned
+    assert!(proc.wait_for_ban("10.58.0.1", 5000), "api-1 offender not banned");
+    assert!(proc.wait_for_ban("10.58.0.3", 5000), "api-2 offender not banned");
+    assert!(!proc.banned_ips().contains(&"10.58.0.2".to_string()));
+}
+
+#[test]
+fn test_docker_config_needs_container_or_labels() {
+    // GIVEN a running process
+    let stub = DockerStub::start();
+    let proc = start(&stub);
+
+    // WHEN a docker config names both a container and labels, or neither
+    // THEN it is rejected
+    let both = docker_config("cfg-docker-bad", "web", &["app=api"]);
+    assert_eq!(proc.post_config_raw(&both).status(), 400);
+    let neither = docker_config("cfg-docker-bad", "", &[]);
+    assert_eq!(proc.post_config_raw(&neither).status(), 400);
+}
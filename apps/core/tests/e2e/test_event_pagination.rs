@@ -0,0 +1,93 @@
+use crate::utils::TestProcess;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn list(proc: &TestProcess, path: &str) -> (u64, Vec<serde_json::Value>) {
+    let resp = proc.client().get(proc.api_url(path)).send().unwrap();
+    assert_eq!(resp.status(), 200, "GET {path} failed");
+    let total = resp
+        .headers()
+        .get("x-total-count")
+        .expect("missing X-Total-Count")
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    (total, resp.json().unwrap())
+}
+
+fn ips(events: &[serde_json::Value]) -> Vec<&str> {
+    events.iter().map(|e| e["ip"].as_str().unwrap()).collect()
+}
+
+#[test]
+fn test_event_lists_page_and_filter() {
+    // GIVEN two configs, five matches in one and one in the other
+    let proc = TestProcess::start();
+    proc.create_config("cfg-page-a", proc.log_file.to_str().unwrap(), "PA hit from <IP>", 100, &[]);
+    proc.create_config("cfg-page-b", proc.log_file.to_str().unwrap(), "PB hit from <IP>", 100, &[]);
+    let before = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+    for i in 1..=5 {
+        proc.append_log_line(&format!("PA hit from 10.50.0.{i}"));
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+    proc.append_log_line("PB hit from 10.50.0.1");
+    assert!(proc.wait_for_match_count("cfg-page-a", 5, 5000));
+    assert!(proc.wait_for_match_count("cfg-page-b", 1, 5000));
+
+    // WHEN listing one config two rows at a time
+    let (total, page1) = list(&proc, "/api/matches?config_id=cfg-page-a&limit=2");
+    let (_, page2) = list(&proc, "/api/matches?config_id=cfg-page-a&limit=2&offset=2");
+
+    // THEN pages follow on newest first, and the total ignores the page
+    assert_eq!(total, 5);
+    assert_eq!(ips(&page1), ["10.50.0.5", "10.50.0.4"]);
+    assert_eq!(ips(&page2), ["10.50.0.3", "10.50.0.2"]);
+
+    // AND the per-config route and ascending order page the same rows
+    let (total, oldest) = list(&proc, "/api/matches/cfg-page-a?order=asc&limit=1");
+    assert_eq!(total, 5);
+    assert_eq!(ips(&oldest), ["10.50.0.1"]);
+
+    // AND ip and time filters narrow across configs
+    let (total, by_ip) = list(&proc, "/api/matches?ip=10.50.0.1");
+    assert_eq!(total, 2);
+    assert!(by_ip.iter().all(|e| e["ip"] == "10.50.0.1"));
+    let (total, _) = list(&proc, &format!("/api/matches?from={before}&to={}", before + 3_600_000));
+    assert_eq!(total, 6);
+    let (total, none) = list(&proc, &format!("/api/matches?to={}", before.saturating_sub(1)));
+    assert_eq!((total, none.len()), (0, 0));
+
+    // AND without a limit a page is bounded at 100 rows
+    for i in 0..100 {
+        proc.append_log_line(&format!("PB hit from 10.50.1.{i}"));
+    }
+    assert!(proc.wait_for_match_count("cfg-page-b", 101, 10000));
+    let (total, page) = list(&proc, "/api/matches");
+    assert_eq!((total, page.len()), (106, 100));
+
+    // AND sorting by config groups the rows
+    let (_, sorted) = list(&proc, "/api/matches?sort=config_id&order=desc&limit=1");
+    assert_eq!(sorted[0]["config_id"], "cfg-page-b");
+}
+
+#[test]
+fn test_event_lists_reject_bad_filters() {
+    // GIVEN a running process
+    let proc = TestProcess::start();
+
+    // WHEN a list is filtered by a malformed IP or an inverted time range
+    // THEN the request is rejected
+    for path in [
+        "/api/bans?ip=not-an-ip",
+        "/api/unbans?from=2000&to=1000",
+        "/api/matches?sort=line",
+        "/api/bans?offset=9223372036854775808",
+    ] {
+        let resp = proc.client().get(proc.api_url(path)).send().unwrap();
+        assert_eq!(resp.status(), 400, "{path} was accepted");
+    }
+
+    // AND an unfiltered list still answers with a total
+    let (total, bans) = list(&proc, "/api/bans");
+    assert_eq!((total, bans.len()), (0, 0));
+}
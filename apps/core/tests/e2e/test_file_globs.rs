@@ -0,0 +1,150 @@
+use crate::utils::TestProcess;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn append_to(path: &std::path::Path, line: &str) {
+    let mut f = OpenOptions::new().append(true).open(path).unwrap();
+    writeln!(f, "{}", line).unwrap();
+}
+
+fn file_status(proc: &TestProcess, config_id: &str) -> Vec<serde_json::Value> {
+    proc.client()
+        .get(proc.api_url(&format!("/api/configs/{}/files", config_id)))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap()
+}
+
+fn wait_for_status(
+    proc: &TestProcess,
+    config_id: &str,
+    timeout_ms: u64,
+    done: impl Fn(&[serde_json::Value]) -> bool,
+) -> Vec<serde_json::Value> {
+    let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+    loop {
+        let status = file_status(proc, config_id);
+        if done(&status) || Instant::now() > deadline {
+            return status;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+#[test]
+fn test_glob_picks_up_files_created_later() {
+    // GIVEN a config on a glob matching one existing file
+    let proc = TestProcess::start();
+    let dir = proc.db_path.join("nginx");
+    fs::create_dir(&dir).unwrap();
+    let site_a = dir.join("shop-access.log");
+    fs::write(&site_a, "").unwrap();
+    fs::write(dir.join("shop-error.log"), "").unwrap();
+    proc.create_config(
+        "cfg-glob",
+        &format!("{}/*access.log", dir.display()),
+        "Denied <IP>",
+        1,
+        &[],
+    );
+    let status = wait_for_status(&proc, "cfg-glob", 5000, |s| s.len() == 1);
+    assert_eq!(status.len(), 1, "unexpected files: {:?}", status);
+
+    // WHEN a second matching file appears and both get a line
+    let site_b = dir.join("blog-access.log");
+    fs::write(&site_b, "").unwrap();
+    wait_for_status(&proc, "cfg-glob", 5000, |s| s.len() == 2);
+    append_to(&site_a, "Denied 10.60.0.1");
+    append_to(&site_b, "Denied 10.60.0.2");
+    append_to(&dir.join("shop-error.log"), "Denied 10.60.0.3");
+
+    // THEN lines from both matching files are detected, not the other file
+    assert!(proc.wait_for_ban("10.60.0.1", 5000), "first file not tailed");
+    assert!(
+        proc.wait_for_ban("10.60.0.2", 5000),
+        "file created later not tailed"
+    );
+    assert!(!proc.banned_ips().contains(&"10.60.0.3".to_string()));
+    let status = file_status(&proc, "cfg-glob");
+    let paths: Vec<&str> = status.iter().map(|f| f["path"].as_str().unwrap()).collect();
+    assert_eq!(
+        paths,
+        [site_a.to_str().unwrap(), site_b.to_str().unwrap()]
+    );
+    assert!(status.iter().all(|f| f["state"] == "tailing" && f["lines"] == 1));
+}
+
+#[test]
+fn test_rotated_file_matching_glob_not_replayed() {
+    // GIVEN a glob that also matches rotated files, and a detected line
+    let proc = TestProcess::start();
+    let log = proc.db_path.join("app.log");
+    fs::write(&log, "").unwrap();
+    proc.create_config(
+        "cfg-glob-rotate",
+        &format!("{}/app.log*", proc.db_path.display()),
+        "Bad login from <IP>",
+        2,
+        &[],
+    );
+    wait_for_status(&proc, "cfg-glob-rotate", 5000, |s| s.len() == 1);
+    append_to(&log, "Bad login from 10.61.0.1");
+    assert!(proc.wait_for_match_count("cfg-glob-rotate", 1, 5000));
+
+    // WHEN the file is rotated by rename and a new line lands in the new file
+    fs::rename(&log, proc.db_path.join("app.log.1")).unwrap();
+    fs::write(&log, "").unwrap();
+    wait_for_status(&proc, "cfg-glob-rotate", 5000, |s| s.len() == 2);
+    append_to(&log, "Bad login from 10.61.0.2");
+
+    // THEN the rotated file is followed on, not read again from its start
+    assert!(proc.wait_for_match_count("cfg-glob-rotate", 2, 5000));
+    thread::sleep(Duration::from_millis(1500));
+    assert_eq!(proc.match_count("cfg-glob-rotate"), 2, "rotated lines replayed");
+    assert!(proc.banned_ips().is_empty());
+}
+
+#[test]
+fn test_file_status_reports_missing_then_tailing() {
+    // GIVEN a config whose file does not exist yet
+    let proc = TestProcess::start();
+    let late_log = proc.db_path.join("later.log");
+    proc.create_config(
+        "cfg-status",
+        late_log.to_str().unwrap(),
+        "Hit from <IP>",
+        5,
+        &[],
+    );
+    let status = wait_for_status(&proc, "cfg-status", 5000, |s| !s.is_empty());
+    assert_eq!(status.len(), 1);
+    assert_eq!(status[0]["path"], late_log.to_str().unwrap());
+    assert_eq!(status[0]["state"], "missing");
+    assert!(status[0]["size"].is_null());
+
+    // WHEN the file appears, gets a line, and is truncated in place
+    fs::write(&late_log, "").unwrap();
+    append_to(&late_log, "Hit from 10.62.0.1");
+    assert!(proc.wait_for_match_count("cfg-status", 1, 5000));
+    fs::write(&late_log, "").unwrap();
+    append_to(&late_log, "Hit");
+
+    // THEN the status follows along
+    let status = wait_for_status(&proc, "cfg-status", 5000, |s| s[0]["lines"] == 2);
+    assert_eq!(status[0]["state"], "tailing", "status: {:?}", status);
+    assert_eq!(status[0]["lines"], 2);
+    assert_eq!(status[0]["rotations"], 1);
+    assert_eq!(status[0]["offset"], 4);
+    assert!(status[0]["last_line_at"].as_u64().is_some());
+
+    // AND an unknown config has no status
+    let resp = proc
+        .client()
+        .get(proc.api_url("/api/configs/nope/files"))
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+}
@@ -0,0 +1,189 @@
+use crate::utils::TestProcess;
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn health(proc: &TestProcess) -> (u16, serde_json::Value) {
+    let resp = proc.client().get(proc.api_url("/api/health")).send().unwrap();
+    let code = resp.status().as_u16();
+    (code, resp.json().unwrap())
+}
+
+/// Poll until the health check answers `code`, returning its body.
+fn wait_for_health(proc: &TestProcess, code: u16) -> serde_json::Value {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        let (got, body) = health(proc);
+        if got == code {
+            return body;
+        }
+        assert!(Instant::now() < deadline, "health stayed {}: {}", got, body);
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+fn component<'a>(body: &'a serde_json::Value, name: &str, id: Option<&str>) -> &'a serde_json::Value {
+    body["components"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|c| c["component"] == name && c["id"].as_str() == id)
+        .unwrap_or_else(|| panic!("no {} {:?} in {}", name, id, body))
+}
+
+#[test]
+fn test_health_reports_each_component() {
+    // GIVEN a running instance watching an existing file
+    let proc = TestProcess::start();
+    proc.create_config(
+        "cfg-health",
+        proc.log_file.to_str().unwrap(),
+        "Failed login from <IP>",
+        3,
+        &[],
+    );
+
+    // WHEN the health check is called
+    let body = wait_for_health(&proc, 200);
+
+    // THEN every component is listed and fine; GeoIP without a database and
+    // auto-download off is merely disabled
+    assert_eq!(body["status"], "ok", "{}", body);
+    assert_eq!(component(&body, "firewall", None)["status"], "ok");
+    assert_eq!(component(&body, "firewall", None)["critical"], true);
+    assert_eq!(component(&body, "events_db", None)["status"], "ok");
+    assert_eq!(component(&body, "tailer", Some("cfg-health"))["status"], "ok");
+    assert_eq!(component(&body, "detector", Some("cfg-health"))["status"], "ok");
+    assert_eq!(component(&body, "geoip", None)["status"], "disabled");
+    assert_eq!(component(&body, "geoip", None)["critical"], false);
+}
+
+#[test]
+fn test_health_degrades_on_a_missing_file() {
+    // GIVEN a config on a file that does not exist
+    let proc = TestProcess::start();
+    let missing = proc.db_path.join("nowhere.log");
+    proc.create_config("cfg-health-missing", missing.to_str().unwrap(), "fail <IP>", 1, &[]);
+
+    // WHEN the health check is called
+    thread::sleep(Duration::from_millis(500));
+    let (code, body) = health(&proc);
+
+    // THEN the tailer is degraded, but is not critical by default
+    assert_eq!(code, 200);
+    assert_eq!(body["status"], "degraded");
+    let tailer = component(&body, "tailer", Some("cfg-health-missing"));
+    assert_eq!(tailer["status"], "degraded");
+    assert_eq!(tailer["critical"], false);
+    assert!(
+        tailer["detail"].as_str().unwrap().contains("nowhere.log"),
+        "{}",
+        tailer
+    );
+
+    // AND once the file appears, it is fine again
+    std::fs::write(&missing, "").unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while health(&proc).1["status"] != "ok" {
+        assert!(Instant::now() < deadline, "tailer stayed degraded");
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+#[test]
+fn test_health_critical_components_are_configurable() {
+    // GIVEN an operator who wants missing log files to fail the check, and a
+    // config on a missing file
+    let proc = TestProcess::start_with_env(&[("BANALIZE_CORE_HEALTH_CRITICAL", "firewall,tailer")]);
+    let missing = proc.db_path.join("nowhere.log");
+    proc.create_config("cfg-health-strict", missing.to_str().unwrap(), "fail <IP>", 1, &[]);
+
+    // WHEN the health check is called
+    let body = wait_for_health(&proc, 503);
+
+    // THEN it fails on the tailer
+    assert_eq!(body["status"], "unhealthy");
+    let tailer = component(&body, "tailer", Some("cfg-health-strict"));
+    assert_eq!(tailer["status"], "degraded");
+    assert_eq!(tailer["critical"], true);
+}
+
+#[test]
+fn test_health_fails_when_the_firewall_rejects_a_ban() {
+    // GIVEN an instance whose iptables refuses the per-config DROP rules
+    let proc = TestProcess::start_with_env(&[("BANALIZE_FAKE_IPTABLES_FAIL", "-A bnz-")]);
+    proc.create_config(
+        "cfg-health-fw",
+        proc.log_file.to_str().unwrap(),
+        "Attack from <IP>",
+        1,
+        &[],
+    );
+    let (code, body) = health(&proc);
+    assert_eq!(code, 200, "{}", body);
+
+    // WHEN a ban reaches the firewall
+    proc.append_log_line("Attack from 10.68.0.1");
+    assert!(proc.wait_for_ban("10.68.0.1", 5000));
+
+    // THEN the firewall is degraded with the backend's error
+    let body = wait_for_health(&proc, 503);
+    let firewall = component(&body, "firewall", None);
+    assert_eq!(firewall["status"], "degraded");
+    assert!(firewall["detail"].is_string(), "{}", firewall);
+}
+
+#[test]
+fn test_health_keeps_a_failed_firewall_init() {
+    // GIVEN an instance whose iptables refused to link the parent chain at
+    // startup
+    let proc = TestProcess::start_with_env(&[("BANALIZE_FAKE_IPTABLES_FAIL", "-j banalize")]);
+    proc.create_config(
+        "cfg-health-init",
+        proc.log_file.to_str().unwrap(),
+        "Attack from <IP>",
+        1,
+        &[],
+    );
+
+    // WHEN a later ban goes through
+    proc.append_log_line("Attack from 10.68.1.1");
+    assert!(proc.wait_for_iptables_contains("-A bnz-cfg-health-init", 5000));
+    thread::sleep(Duration::from_millis(200));
+
+    // THEN the firewall is still reported as broken
+    let (code, body) = health(&proc);
+    assert_eq!(code, 503, "{}", body);
+    let firewall = component(&body, "firewall", None);
+    assert_eq!(firewall["status"], "degraded");
+    assert!(
+        firewall["detail"].as_str().unwrap().starts_with("init failed"),
+        "{}",
+        firewall
+    );
+}
+
+#[test]
+fn test_health_components_need_a_token() {
+    // GIVEN a process with API tokens turned on
+    let proc = TestProcess::start_with_env(&[("BANALIZE_CORE_API_TOKEN", "health-secret")]);
+    let get = |bearer: Option<&str>| -> serde_json::Value {
+        let mut req = proc.client().get(proc.api_url("/api/health"));
+        if let Some(bearer) = bearer {
+            req = req.bearer_auth(bearer);
+        }
+        let resp = req.send().unwrap();
+        assert_eq!(resp.status(), 200);
+        resp.json().unwrap()
+    };
+
+    // WHEN the health check is called without a token, with an unknown one,
+    // and with a valid one
+    // THEN only the valid token sees the components
+    for bearer in [None, Some("nope")] {
+        let body = get(bearer);
+        assert_eq!(body["status"], "ok");
+        assert!(body.get("components").is_none(), "{}", body);
+    }
+    let body = get(Some("health-secret"));
+    assert_eq!(component(&body, "firewall", None)["status"], "ok");
+}
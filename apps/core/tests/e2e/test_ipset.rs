@@ -0,0 +1,197 @@
+use crate::utils::{chain, TestProcess};
+use std::thread;
+use std::time::Duration;
+
+const IPSET_BACKEND: &[(&str, &str)] = &[("BANALIZE_CORE_FIREWALL_BACKEND", "ipset")];
+
+fn match_set_rule(config_id: &str, family: &str) -> String {
+    format!(
+        "-A {} -m set --match-set {}-{} src -j DROP",
+        chain(config_id),
+        chain(config_id),
+        family
+    )
+}
+
+/// The script lines of every `ipset restore` in a fake-ipset log, each batch
+/// sorted so assertions don't depend on restore order.
+fn restore_batches(log: &str) -> Vec<Vec<String>> {
+    let mut batches: Vec<Vec<String>> = Vec::new();
+    let mut in_batch = false;
+    for line in log.lines() {
+        if line.starts_with("restore") {
+            batches.push(Vec::new());
+            in_batch = true;
+        } else if in_batch && line.starts_with("add ") && !line.ends_with("-exist") {
+            batches.last_mut().unwrap().push(line.to_string());
+        } else {
+            in_batch = false;
+        }
+    }
+    for batch in &mut batches {
+        batch.sort();
+    }
+    batches
+}
+
+#[test]
+fn test_ipset_ban_and_unban_use_set_members() {
+    // GIVEN a process in ipset mode with a short ban time
+    let proc = TestProcess::start_with_env(IPSET_BACKEND);
+    let ips = ["10.45.0.1", "10.45.0.2", "10.45.0.3"];
+    proc.create_config_with_ban_time(
+        "cfg-ipset",
+        proc.log_file.to_str().unwrap(),
+        "Set hit from <IP>",
+        1,
+        &[],
+        2000,
+    );
+
+    // WHEN several IPs get banned
+    for ip in ips {
+        proc.append_log_line(&format!("Set hit from {}", ip));
+    }
+
+    // THEN each ban is an `ipset add` to the config's set
+    let set = format!("{}-v4", chain("cfg-ipset"));
+    for ip in ips {
+        assert!(proc.wait_for_ban(ip, 5000), "IP {ip} was not banned");
+        assert!(
+            proc.wait_for_ipset_contains(&format!("add {} {} -exist", set, ip), 3000),
+            "ipset add not found:\n{}",
+            proc.read_ipset_log()
+        );
+    }
+
+    // AND the config chain holds a single match-set rule and no per-IP rules
+    let log = proc.read_iptables_log();
+    assert_eq!(log.matches(&match_set_rule("cfg-ipset", "v4")).count(), 1, "{log}");
+    assert!(!log.contains("-s 10.45.0.1"), "per-IP rule in ipset mode:\n{log}");
+
+    // AND expiry removes the members with `ipset del`
+    for ip in ips {
+        assert!(proc.wait_for_unban(ip, 6000), "IP {ip} was not unbanned");
+        assert!(
+            proc.wait_for_ipset_contains(&format!("del {} {}", set, ip), 3000),
+            "ipset del not found:\n{}",
+            proc.read_ipset_log()
+        );
+    }
+    assert!(!proc.ipset_state().contains("member"), "{}", proc.ipset_state());
+}
+
+#[test]
+fn test_ipset_restore_loads_set_in_one_batch() {
+    // GIVEN three active bans persisted by a first ipset-mode process
+    let db_dir = tempfile::tempdir().unwrap();
+    let log_file = db_dir.path().join("test.log");
+    let iptables_log_1 = db_dir.path().join("iptables_1.log");
+    let iptables_log_2 = db_dir.path().join("iptables_2.log");
+    let ips = ["10.45.1.1", "10.45.1.2", "2001:db8:45::1"];
+
+    let mut proc1 =
+        TestProcess::start_at_with_env(db_dir.path(), &log_file, &iptables_log_1, IPSET_BACKEND);
+    proc1.create_config("cfg-ipset-restore", log_file.to_str().unwrap(), "Restore hit from <IP>", 1, &[]);
+    for ip in ips {
+        proc1.append_log_line(&format!("Restore hit from {}", ip));
+    }
+    for ip in ips {
+        assert!(proc1.wait_for_ban(ip, 5000), "IP {ip} was not banned by process 1");
+    }
+    proc1.stop();
+    thread::sleep(Duration::from_millis(300));
+
+    // WHEN a second process starts with the same database
+    let proc2 =
+        TestProcess::start_at_with_env(db_dir.path(), &log_file, &iptables_log_2, IPSET_BACKEND);
+
+    // THEN each family's set is loaded by a single `ipset restore`
+    let v4_set = format!("{}-v4", chain("cfg-ipset-restore"));
+    let v6_set = format!("{}-v6", chain("cfg-ipset-restore"));
+    // The IPv6 batch is sent last.
+    assert!(
+        proc2.wait_for_ipset_contains(&format!("add {} 2001:db8:45::1", v6_set), 3000),
+        "{}",
+        proc2.read_ipset_log()
+    );
+    let batches = restore_batches(&proc2.read_ipset_log());
+    let v4_batch: Vec<String> = ["10.45.1.1", "10.45.1.2"]
+        .iter()
+        .map(|ip| format!("add {} {}", v4_set, ip))
+        .collect();
+    let v6_batch = vec![format!("add {} 2001:db8:45::1", v6_set)];
+    assert!(batches.contains(&v4_batch), "IPv4 batch not found: {batches:?}");
+    assert!(batches.contains(&v6_batch), "IPv6 batch not found: {batches:?}");
+    assert_eq!(batches.len(), 2, "{batches:?}");
+
+    // AND the restored chains each reference their set exactly once
+    let log = proc2.read_iptables_log();
+    assert_eq!(log.matches(&match_set_rule("cfg-ipset-restore", "v4")).count(), 1, "{log}");
+    let v6_log = proc2.read_ip6tables_log();
+    assert_eq!(v6_log.matches(&match_set_rule("cfg-ipset-restore", "v6")).count(), 1, "{v6_log}");
+}
+
+#[test]
+fn test_ipset_config_delete_destroys_set() {
+    // GIVEN an ipset-mode config with a banned IP
+    let proc = TestProcess::start_with_env(IPSET_BACKEND);
+    let test_ip = "10.45.2.1";
+    proc.create_config("cfg-ipset-del", proc.log_file.to_str().unwrap(), "Del hit from <IP>", 1, &[]);
+    proc.append_log_line(&format!("Del hit from {}", test_ip));
+    assert!(proc.wait_for_ban(test_ip, 5000), "IP {test_ip} was not banned");
+
+    // WHEN the config is deleted
+    let resp = proc
+        .client()
+        .delete(proc.api_url("/api/configs/cfg-ipset-del"))
+        .send()
+        .unwrap();
+    assert!(resp.status().is_success());
+
+    // THEN its chain is deleted and its set destroyed
+    let set = format!("{}-v4", chain("cfg-ipset-del"));
+    assert!(
+        proc.wait_for_ipset_contains(&format!("destroy {}", set), 3000),
+        "set not destroyed:\n{}",
+        proc.read_ipset_log()
+    );
+    assert!(
+        proc.read_iptables_log().contains(&format!("-X {}", chain("cfg-ipset-del"))),
+        "chain not deleted:\n{}",
+        proc.read_iptables_log()
+    );
+    assert!(!proc.ipset_state().contains(&set), "{}", proc.ipset_state());
+}
+
+#[test]
+fn test_stale_sets_swept_after_switching_backend() {
+    // GIVEN a set left behind by an ipset-mode process that was killed
+    let db_dir = tempfile::tempdir().unwrap();
+    let log_file = db_dir.path().join("test.log");
+    let iptables_log = db_dir.path().join("iptables.log");
+    let mut proc1 = TestProcess::start_at_with_env(db_dir.path(), &log_file, &iptables_log, IPSET_BACKEND);
+    proc1.create_config("cfg-ipset-switch", log_file.to_str().unwrap(), "Switch hit from <IP>", 1, &[]);
+    proc1.append_log_line("Switch hit from 10.45.3.1");
+    assert!(proc1.wait_for_ban("10.45.3.1", 5000), "initial ban failed");
+    proc1.child.kill().unwrap();
+    let _ = proc1.child.wait();
+    let set = format!("{}-v4", chain("cfg-ipset-switch"));
+    assert!(proc1.ipset_state().contains(&set), "{}", proc1.ipset_state());
+
+    // WHEN the next process runs the nftables backend
+    let proc2 = TestProcess::start_at_with_env(
+        db_dir.path(),
+        &log_file,
+        &iptables_log,
+        &[("BANALIZE_CORE_FIREWALL_BACKEND", "nftables")],
+    );
+
+    // THEN the stale set is destroyed all the same
+    assert!(
+        proc2.wait_for_ipset_contains(&format!("destroy {}", set), 3000),
+        "stale set not swept:\n{}",
+        proc2.read_ipset_log()
+    );
+    assert!(!proc2.ipset_state().contains(&set), "{}", proc2.ipset_state());
+}
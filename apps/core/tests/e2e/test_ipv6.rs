@@ -0,0 +1,96 @@
+use crate::utils::{drop_rule, TestProcess};
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn test_ipv6_ban_lands_in_ip6tables() {
+    // GIVEN a running instance with a config watching the log file
+    let proc = TestProcess::start();
+    let v6_ip = "2001:db8::5";
+    let mapped_ip = "10.9.0.1";
+    proc.create_config(
+        "cfg-v6",
+        proc.log_file.to_str().unwrap(),
+        "Failed login from <IP> port",
+        2,
+        &[],
+    );
+
+    // WHEN an IPv6 address and an IPv4-mapped address reach max_matches
+    for _ in 0..2 {
+        proc.append_log_line(&format!("Failed login from {} port 22", v6_ip));
+        proc.append_log_line(&format!("Failed login from ::ffff:{} port 22", mapped_ip));
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    // THEN the IPv6 address is banned through ip6tables only
+    assert!(proc.wait_for_ban(v6_ip, 5000), "IP {v6_ip} was not banned");
+    assert!(
+        proc.wait_for_ip6tables_contains(&drop_rule("cfg-v6", v6_ip), 3000),
+        "ip6tables -A rule not found in log:\n{}",
+        proc.read_ip6tables_log()
+    );
+    assert!(
+        !proc.read_iptables_log().contains(v6_ip),
+        "IPv6 address leaked into the iptables log:\n{}",
+        proc.read_iptables_log()
+    );
+
+    // AND the mapped address is banned as the IPv4 client it stands for
+    assert!(proc.wait_for_ban(mapped_ip, 5000), "IP {mapped_ip} was not banned");
+    assert!(
+        proc.wait_for_iptables_contains(&drop_rule("cfg-v6", mapped_ip), 3000),
+        "iptables -A rule not found for mapped address:\n{}",
+        proc.read_iptables_log()
+    );
+}
+
+#[test]
+fn test_ignore_ipv6_cidr_and_host() {
+    // GIVEN a config ignoring an IPv6 /48 and a bare IPv6 host
+    let proc = TestProcess::start();
+    proc.create_config(
+        "cfg-v6-ignore",
+        proc.log_file.to_str().unwrap(),
+        "Connection from <IP>",
+        1,
+        &["2001:db8:1::/48", "2001:db8::99"],
+    );
+    let in_range = "2001:db8:1:2::7";
+    let host = "2001:db8::99";
+    let sentinel = "2001:db8:2::1";
+
+    // WHEN all three addresses match, the sentinel last
+    proc.append_log_line(&format!("Connection from {}", in_range));
+    proc.append_log_line(&format!("Connection from {}", host));
+    proc.append_log_line(&format!("Connection from {}", sentinel));
+
+    // THEN only the sentinel is banned
+    assert!(proc.wait_for_ban(sentinel, 5000), "IP {sentinel} was not banned");
+    let banned = proc.banned_ips();
+    assert!(!banned.iter().any(|ip| ip == in_range), "{in_range} inside ignored /48 was banned");
+    assert!(!banned.iter().any(|ip| ip == host), "ignored host {host} was banned");
+}
+
+#[test]
+fn test_ignore_ipv4_mapped_cidr() {
+    // GIVEN a config ignoring an IPv4 /8 written in IPv4-mapped form
+    let proc = TestProcess::start();
+    proc.create_config(
+        "cfg-v6-mapped-ignore",
+        proc.log_file.to_str().unwrap(),
+        "Connection from <IP>",
+        1,
+        &["::ffff:10.0.0.0/104"],
+    );
+    let in_range = "10.61.0.1";
+    let sentinel = "192.0.2.61";
+
+    // WHEN an address inside it matches, then the sentinel
+    proc.append_log_line(&format!("Connection from {}", in_range));
+    proc.append_log_line(&format!("Connection from {}", sentinel));
+
+    // THEN only the sentinel is banned
+    assert!(proc.wait_for_ban(sentinel, 5000), "IP {sentinel} was not banned");
+    assert!(!proc.banned_ips().iter().any(|ip| ip == in_range), "{in_range} inside ignored /8 was banned");
+}
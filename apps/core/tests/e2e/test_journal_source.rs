@@ -0,0 +1,123 @@
+use crate::utils::{JournalWriter, TestProcess};
+use serde_json::json;
+
+fn sshd(pid: &str) -> [(&'static str, String); 3] {
+    [
+        ("_SYSTEMD_UNIT", "sshd.service".to_string()),
+        ("SYSLOG_IDENTIFIER", "sshd".to_string()),
+        ("_PID", pid.to_string()),
+    ]
+}
+
+fn append(journal: &mut JournalWriter, message: &str, fields: &[(&str, String)]) {
+    let fields: Vec<(&str, &str)> = fields.iter().map(|(k, v)| (*k, v.as_str())).collect();
+    journal.append(message, &fields);
+}
+
+#[test]
+fn test_journal_config_bans_from_matching_unit() {
+    // GIVEN a journal directory whose file already holds an offending entry,
+    // and a journal config scoped to the sshd unit
+    let proc = TestProcess::start();
+    let dir = tempfile::tempdir().unwrap();
+    let machine = dir.path().join("0123456789abcdef0123456789abcdef");
+    let mut journal = JournalWriter::create(&machine.join("system.journal"));
+    for _ in 0..2 {
+        append(&mut journal, "Failed password for root from 10.56.0.9 port 22 ssh2", &sshd("100"));
+    }
+    let resp = proc.post_config_raw(&json!({
+        "id": "cfg-journal",
+        "name": "cfg-journal",
+        "kind": "journal",
+        "param": dir.path().to_str().unwrap(),
+        "regexes": ["Failed password for .* from <IP>"],
+        "ban_time": 60000,
+        "find_time": 60000,
+        "max_matches": 2,
+        "ignore_ips": [],
+        "journal": { "units": ["sshd"] }
+    }));
+    assert_eq!(resp.status(), 200);
+    std::thread::sleep(std::time::Duration::from_millis(500));
+
+    // WHEN the sshd unit and another unit log failures, and a new journal
+    // file appears (as after a rotation)
+    for _ in 0..2 {
+        append(&mut journal, "Failed password for root from 10.56.0.1 port 22 ssh2", &sshd("101"));
+        append(
+            &mut journal,
+            "Failed password for root from 10.56.0.2 port 22 ssh2",
+            &[("_SYSTEMD_UNIT", "cron.service".to_string())],
+        );
+    }
+    let mut rotated = JournalWriter::create(&machine.join("system@0001.journal"));
+    for _ in 0..2 {
+        append(&mut rotated, "Failed password for admin from 10.56.0.3 port 22 ssh2", &sshd("102"));
+    }
+
+    // THEN only the new sshd offenders are banned
+    assert!(proc.wait_for_ban("10.56.0.1", 5000), "sshd offender not banned");
+    assert!(proc.wait_for_ban("10.56.0.3", 5000), "offender in the new file not banned");
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    let banned = proc.banned_ips();
+    assert!(!banned.contains(&"10.56.0.2".to_string()), "other unit was read");
+    assert!(!banned.contains(&"10.56.0.9".to_string()), "old entries were replayed");
+    assert_eq!(proc.match_count("cfg-journal"), 4);
+}
+
+#[test]
+fn test_journal_compact_file_read_past_a_corrupt_entry() {
+    // GIVEN a compact-layout journal file and a journal config reading it
+    let proc = TestProcess::start();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("system.journal");
+    let mut journal = JournalWriter::create_compact(&path);
+    let resp = proc.post_config_raw(&json!({
+        "id": "cfg-journal-compact",
+        "name": "cfg-journal-compact",
+        "kind": "journal",
+        "param": path.to_str().unwrap(),
+        "regexes": ["Failed password for .* from <IP>"],
+        "ban_time": 60000,
+        "find_time": 60000,
+        "max_matches": 2,
+        "ignore_ips": [],
+        "journal": { "units": ["sshd"] }
+    }));
+    assert_eq!(resp.status(), 200);
+    std::thread::sleep(std::time::Duration::from_millis(500));
+
+    // WHEN a corrupt entry is followed by offending entries
+    append(&mut journal, "Failed password for root from 10.56.1.1 port 22 ssh2", &sshd("200"));
+    journal.append_corrupt();
+    append(&mut journal, "Failed password for root from 10.56.1.1 port 22 ssh2", &sshd("200"));
+
+    // THEN the corrupt entry is skipped and the offender is banned
+    assert!(proc.wait_for_ban("10.56.1.1", 5000), "offender past the corrupt entry not banned");
+    assert_eq!(proc.match_count("cfg-journal-compact"), 2);
+}
+
+#[test]
+fn test_journal_filters_validated() {
+    // GIVEN a running process
+    let proc = TestProcess::start();
+    let config = |kind: &str, matches: &[&str]| {
+        json!({
+            "id": "cfg-journal-bad",
+            "name": "cfg-journal-bad",
+            "kind": kind,
+            "param": "/tmp/log",
+            "regexes": ["from <IP>"],
+            "ban_time": 60000,
+            "find_time": 60000,
+            "max_matches": 2,
+            "ignore_ips": [],
+            "journal": { "matches": matches }
+        })
+    };
+
+    // WHEN a journal config has a malformed match, or a file config has filters
+    // THEN both are rejected
+    assert_eq!(proc.post_config_raw(&config("journal", &["sshd"])).status(), 400);
+    assert_eq!(proc.post_config_raw(&config("file", &["_COMM=sshd"])).status(), 400);
+}
@@ -0,0 +1,139 @@
+use crate::utils::{del_drop_rule, drop_rule, TestProcess};
+use serde_json::json;
+
+fn post_ban(proc: &TestProcess, body: serde_json::Value) -> reqwest::blocking::Response {
+    proc.client()
+        .post(proc.api_url("/api/bans"))
+        .json(&body)
+        .send()
+        .unwrap()
+}
+
+#[test]
+fn test_manual_ban_denies_and_is_flagged() {
+    // GIVEN a config that has never matched anything
+    let proc = TestProcess::start();
+    let test_ip = "10.46.0.1";
+    proc.create_config("cfg-manual", proc.log_file.to_str().unwrap(), "Manual hit from <IP>", 3, &[]);
+
+    // WHEN an operator bans an IP by hand with a reason
+    let resp = post_ban(
+        &proc,
+        json!({ "config_id": "cfg-manual", "ip": test_ip, "reason": "port scan seen in IDS" }),
+    );
+    assert_eq!(resp.status(), 200);
+    let ban: serde_json::Value = resp.json().unwrap();
+
+    // THEN the firewall drops it like a detected ban
+    assert!(
+        proc.wait_for_iptables_contains(&drop_rule("cfg-manual", test_ip), 3000),
+        "iptables -A rule not found in log:\n{}",
+        proc.read_iptables_log()
+    );
+
+    // AND the audit log holds it under the returned id, flagged manual
+    assert!(proc.wait_for_ban(test_ip, 3000), "IP {test_ip} not in /api/bans");
+    let bans: Vec<serde_json::Value> = proc
+        .client()
+        .get(proc.api_url("/api/bans"))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    let stored = bans
+        .iter()
+        .find(|b| b["ip"].as_str() == Some(test_ip))
+        .expect("ban event not found");
+    assert_eq!(stored["id"], ban["id"]);
+    assert_eq!(stored["manual"], true);
+    assert_eq!(stored["reason"], "port scan seen in IDS");
+
+    // AND banning it again under the same config is a conflict
+    let resp = post_ban(&proc, json!({ "config_id": "cfg-manual", "ip": test_ip }));
+    assert_eq!(resp.status(), 409);
+}
+
+#[test]
+fn test_manual_cidr_ban_expires() {
+    // GIVEN a config
+    let proc = TestProcess::start();
+    proc.create_config("cfg-manual-cidr", proc.log_file.to_str().unwrap(), "Cidr hit from <IP>", 3, &[]);
+
+    // WHEN a network is banned for one second, host bits included
+    let resp = post_ban(
+        &proc,
+        json!({ "config_id": "cfg-manual-cidr", "ip": "10.46.1.7/24", "duration": 1000 }),
+    );
+    assert_eq!(resp.status(), 200);
+    let ban: serde_json::Value = resp.json().unwrap();
+    assert_eq!(ban["ip"], "10.46.1.0/24");
+    assert_eq!(ban["manual"], true);
+    assert!(ban["reason"].is_null());
+
+    // THEN the whole network is dropped
+    assert!(
+        proc.wait_for_iptables_contains(&drop_rule("cfg-manual-cidr", "10.46.1.0/24"), 3000),
+        "iptables -A rule not found in log:\n{}",
+        proc.read_iptables_log()
+    );
+
+    // AND a host inside it is already banned
+    let resp = post_ban(&proc, json!({ "config_id": "cfg-manual-cidr", "ip": "10.46.1.9" }));
+    assert_eq!(resp.status(), 409);
+
+    // AND the cleaner lifts it once the requested duration is up
+    assert!(proc.wait_for_unban("10.46.1.0/24", 6000), "network was not unbanned");
+    assert!(
+        proc.wait_for_iptables_contains(&del_drop_rule("cfg-manual-cidr", "10.46.1.0/24"), 3000),
+        "iptables -D rule not found in log:\n{}",
+        proc.read_iptables_log()
+    );
+}
+
+#[test]
+fn test_concurrent_manual_bans_of_one_ip_admit_one() {
+    // GIVEN a config
+    let proc = TestProcess::start();
+    proc.create_config("cfg-manual-race", proc.log_file.to_str().unwrap(), "Race hit from <IP>", 3, &[]);
+
+    // WHEN the same IP is banned by several requests at once
+    let statuses: Vec<u16> = std::thread::scope(|scope| {
+        let requests: Vec<_> = (0..8)
+            .map(|_| {
+                scope.spawn(|| {
+                    post_ban(&proc, json!({ "config_id": "cfg-manual-race", "ip": "10.46.3.1" }))
+                        .status()
+                        .as_u16()
+                })
+            })
+            .collect();
+        requests.into_iter().map(|r| r.join().unwrap()).collect()
+    });
+
+    // THEN exactly one gets through
+    assert_eq!(statuses.iter().filter(|&&s| s == 200).count(), 1, "{:?}", statuses);
+    assert!(statuses.iter().all(|&s| s == 200 || s == 409), "{:?}", statuses);
+}
+
+#[test]
+fn test_manual_ban_rejects_bad_input() {
+    // GIVEN a config
+    let proc = TestProcess::start();
+    proc.create_config("cfg-manual-bad", proc.log_file.to_str().unwrap(), "Bad hit from <IP>", 3, &[]);
+
+    // WHEN / THEN an unknown config is not found
+    let resp = post_ban(&proc, json!({ "config_id": "no-such-config", "ip": "10.46.2.1" }));
+    assert_eq!(resp.status(), 404);
+
+    // AND a malformed address or a zero duration is rejected
+    let resp = post_ban(&proc, json!({ "config_id": "cfg-manual-bad", "ip": "10.46.2.300" }));
+    assert_eq!(resp.status(), 400);
+    let resp = post_ban(
+        &proc,
+        json!({ "config_id": "cfg-manual-bad", "ip": "10.46.2.1", "duration": 0 }),
+    );
+    assert_eq!(resp.status(), 400);
+
+    // AND nothing reached the firewall
+    assert!(!proc.read_iptables_log().contains("10.46.2."), "{}", proc.read_iptables_log());
+}
@@ -0,0 +1,94 @@
+use crate::utils::TestProcess;
+
+fn scrape(proc: &TestProcess) -> String {
+    let resp = proc.client().get(proc.api_url("/metrics")).send().unwrap();
+    assert_eq!(resp.status(), 200);
+    let content_type = resp.headers()["content-type"].to_str().unwrap().to_string();
+    assert!(content_type.starts_with("text/plain"), "{}", content_type);
+    resp.text().unwrap()
+}
+
+fn has_line(text: &str, line: &str) -> bool {
+    text.lines().any(|l| l == line)
+}
+
+#[test]
+fn test_metrics_count_lines_matches_and_bans() {
+    // GIVEN a config banning on the second match, with one IP ignored
+    let proc = TestProcess::start();
+    proc.create_config(
+        "cfg-metrics",
+        proc.log_file.to_str().unwrap(),
+        "Failed login from <IP>",
+        2,
+        &["10.66.0.9"],
+    );
+
+    // WHEN an offender and the ignored IP show up, next to unrelated noise
+    proc.append_log_lines(&[
+        "Failed login from 10.66.0.1".to_string(),
+        "Failed login from 10.66.0.9".to_string(),
+        "session opened".to_string(),
+        "Failed login from 10.66.0.1".to_string(),
+    ]);
+    assert!(proc.wait_for_ban("10.66.0.1", 5000), "offender not banned");
+
+    // THEN the scrape reports every line read, the two counted matches, the
+    // ban and its firewall command
+    let mut text = String::new();
+    for _ in 0..50 {
+        text = scrape(&proc);
+        if has_line(&text, "banalize_lines_total{config=\"cfg-metrics\"} 4") {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    for line in [
+        "banalize_lines_total{config=\"cfg-metrics\"} 4",
+        "banalize_matches_total{config=\"cfg-metrics\"} 2",
+        "banalize_bans_total{config=\"cfg-metrics\"} 1",
+        "banalize_active_bans{config=\"cfg-metrics\"} 1",
+        "banalize_line_queue_depth{config=\"cfg-metrics\"} 0",
+        "banalize_broadcast_lagged_total{bus=\"events\"} 0",
+    ] {
+        assert!(has_line(&text, line), "missing {:?} in:\n{}", line, text);
+    }
+    for prefix in [
+        "banalize_queue_depth{queue=\"events\"} ",
+        "banalize_queue_depth{queue=\"notifications\"} ",
+        "banalize_queue_depth{queue=\"firewall\"} ",
+        "banalize_firewall_command_duration_seconds_count{command=\"deny\"} ",
+    ] {
+        assert!(text.lines().any(|l| l.starts_with(prefix)), "missing {:?} in:\n{}", prefix, text);
+    }
+    // AND no GeoIP age without a database
+    assert!(!text.contains("banalize_geoip_database_age_seconds"));
+}
+
+#[test]
+fn test_metrics_drop_deleted_configs() {
+    // GIVEN a config that has read a line
+    let proc = TestProcess::start();
+    proc.create_config(
+        "cfg-metrics-gone",
+        proc.log_file.to_str().unwrap(),
+        "Failed login from <IP>",
+        5,
+        &[],
+    );
+    proc.append_log_line("Failed login from 10.67.0.1");
+    assert!(proc.wait_for_match_count("cfg-metrics-gone", 1, 5000));
+
+    // WHEN it is deleted
+    let resp = proc
+        .client()
+        .delete(proc.api_url("/api/configs/cfg-metrics-gone"))
+        .send()
+        .unwrap();
+    assert!(resp.status().is_success());
+
+    // THEN its series are no longer exported
+    let text = scrape(&proc);
+    assert!(!text.contains("cfg-metrics-gone"), "{}", text);
+    assert!(text.contains("# TYPE banalize_lines_total counter"));
+}
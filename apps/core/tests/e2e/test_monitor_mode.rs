@@ -0,0 +1,149 @@
+use crate::utils::{drop_rule, HttpStub, TestProcess};
+use serde_json::json;
+use std::thread;
+use std::time::Duration;
+
+fn monitor_config(id: &str, log: &str, regex: &str, mode: &str) -> serde_json::Value {
+    json!({
+        "id": id,
+        "name": id,
+        "mode": mode,
+        "param": log,
+        "regexes": [regex],
+        "ban_time": 600000,
+        "find_time": 60000,
+        "max_matches": 1,
+        "ignore_ips": []
+    })
+}
+
+fn get_json(proc: &TestProcess, path: &str) -> Vec<serde_json::Value> {
+    proc.client()
+        .get(proc.api_url(path))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap()
+}
+
+#[test]
+fn test_monitor_mode_reports_without_banning() {
+    // GIVEN a config in monitor mode
+    let proc = TestProcess::start();
+    let log = proc.log_file.to_str().unwrap();
+    let resp = proc.post_config_raw(&monitor_config("cfg-monitor", log, "Probe from <IP>", "monitor"));
+    assert_eq!(resp.status(), 200);
+    let configs = get_json(&proc, "/api/configs");
+    assert_eq!(configs[0]["mode"], "monitor");
+    thread::sleep(Duration::from_millis(200));
+
+    // WHEN an offender crosses the threshold twice
+    proc.append_log_line("Probe from 10.72.0.1");
+    assert!(proc.wait_for_ban("10.72.0.1", 5000), "would-be ban not recorded");
+    proc.append_log_line("Probe from 10.72.0.1");
+    assert!(proc.wait_for_match_count("cfg-monitor", 2, 5000));
+    thread::sleep(Duration::from_millis(300));
+
+    // THEN one simulated ban is audited and listed as active, and the
+    // firewall is never touched
+    let bans = get_json(&proc, "/api/bans/cfg-monitor");
+    assert_eq!(bans.len(), 1, "{:?}", bans);
+    assert_eq!(bans[0]["simulated"], true);
+    let active = get_json(&proc, "/api/bans/active/cfg-monitor");
+    assert_eq!(active.len(), 1);
+    assert_eq!(active[0]["simulated"], true);
+    assert!(
+        !proc.read_iptables_log().contains(&drop_rule("cfg-monitor", "10.72.0.1")),
+        "monitor mode reached the firewall:\n{}",
+        proc.read_iptables_log()
+    );
+
+    // AND there is nothing to disable
+    let resp = proc
+        .client()
+        .post(proc.api_url(&format!("/api/bans/{}/disable", bans[0]["id"].as_str().unwrap())))
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), 409);
+
+    // WHEN the config is switched to enforce mode
+    let resp = proc.put_config_raw("cfg-monitor", &monitor_config("cfg-monitor", log, "Probe from <IP>", "enforce"));
+    assert_eq!(resp.status(), 200);
+    thread::sleep(Duration::from_millis(200));
+    proc.append_log_line("Probe from 10.72.0.1");
+
+    // THEN the next hit is banned for real despite the earlier simulation
+    assert!(
+        proc.wait_for_iptables_contains(&drop_rule("cfg-monitor", "10.72.0.1"), 5000),
+        "enforced ban not applied:\n{}",
+        proc.read_iptables_log()
+    );
+    let bans = get_json(&proc, "/api/bans/cfg-monitor");
+    assert_eq!(bans.len(), 2);
+    assert!(bans.iter().any(|b| b["simulated"] == false));
+}
+
+#[test]
+fn test_simulated_bans_are_not_restored_into_the_firewall() {
+    // GIVEN a monitor-mode config that reported a would-be ban
+    let dir = tempfile::tempdir().unwrap();
+    let log_file = dir.path().join("monitor.log");
+    let mut proc1 = TestProcess::start_at(dir.path(), &log_file, &dir.path().join("iptables_1.log"));
+    let log = log_file.to_str().unwrap();
+    let resp = proc1.post_config_raw(&monitor_config("cfg-monitor-restart", log, "Probe from <IP>", "monitor"));
+    assert_eq!(resp.status(), 200);
+    thread::sleep(Duration::from_millis(200));
+    proc1.append_log_line("Probe from 10.73.0.1");
+    assert!(proc1.wait_for_ban("10.73.0.1", 5000));
+    let db_path = proc1.db_path.clone();
+    proc1.stop();
+    thread::sleep(Duration::from_millis(300));
+
+    // WHEN the process restarts on the same database
+    let proc2 = TestProcess::start_at(&db_path, &log_file, &dir.path().join("iptables_2.log"));
+
+    // THEN the would-be ban is still listed as simulated but never denied
+    let active = get_json(&proc2, "/api/bans/active/cfg-monitor-restart");
+    assert_eq!(active.len(), 1, "{:?}", active);
+    assert_eq!(active[0]["simulated"], true);
+    thread::sleep(Duration::from_millis(300));
+    assert!(
+        !proc2.read_iptables_log().contains(&drop_rule("cfg-monitor-restart", "10.73.0.1")),
+        "simulated ban restored into the firewall:\n{}",
+        proc2.read_iptables_log()
+    );
+}
+
+#[test]
+fn test_monitor_mode_notifies_the_level_behind_its_duration() {
+    // GIVEN a ban webhook and a monitor-mode config
+    let stub = HttpStub::start();
+    let proc = TestProcess::start();
+    let resp = proc
+        .client()
+        .post(proc.api_url("/api/notifiers"))
+        .json(&json!({
+            "id": "monitor-hook",
+            "events": ["ban"],
+            "email_config": null,
+            "signal_config": null,
+            "webhook_config": { "url": stub.url("/monitor") }
+        }))
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let log = proc.log_file.to_str().unwrap();
+    let resp = proc.post_config_raw(&monitor_config("cfg-monitor-level", log, "Probe from <IP>", "monitor"));
+    assert_eq!(resp.status(), 200);
+    thread::sleep(Duration::from_millis(200));
+
+    // WHEN a first offender crosses the threshold
+    proc.append_log_line("Probe from 10.72.1.1");
+
+    // THEN the would-be ban is reported at the level its duration came from
+    let requests = stub.wait_for_requests(1, 5000);
+    assert_eq!(requests.len(), 1, "no notification");
+    let body: serde_json::Value = serde_json::from_str(&requests[0].body).unwrap();
+    assert_eq!(body["ip"], "10.72.1.1");
+    assert_eq!(body["recidive_level"], 1);
+}
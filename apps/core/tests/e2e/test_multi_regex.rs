@@ -0,0 +1,134 @@
+use crate::utils::TestProcess;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn test_each_regex_counts_toward_threshold() {
+    // GIVEN a config with two fail regexes and max_matches=2
+    let proc = TestProcess::start();
+    let test_ip = "10.30.0.1";
+    let resp = proc.post_config_raw(&serde_json::json!({
+        "id": "cfg-multi",
+        "name": "cfg-multi",
+        "param": proc.log_file.to_str().unwrap(),
+        "regexes": ["Invalid user \\S+ from <IP>", "Failed password for \\S+ from <IP>"],
+        "ban_time": 60000,
+        "find_time": 60000,
+        "max_matches": 2,
+        "ignore_ips": [],
+    }));
+    assert!(resp.status().is_success(), "create failed: {}", resp.status());
+    thread::sleep(Duration::from_millis(200));
+
+    // WHEN one line matches each pattern
+    proc.append_log_line(&format!("Invalid user admin from {} port 22", test_ip));
+    thread::sleep(Duration::from_millis(50));
+    proc.append_log_line(&format!("Failed password for root from {} port 22", test_ip));
+
+    // THEN both matches are counted and the IP is banned
+    assert!(proc.wait_for_ban(test_ip, 5000), "IP {test_ip} was not banned");
+}
+
+#[test]
+fn test_ignore_regex_vetoes_line() {
+    // GIVEN a config whose ignore regex skips lines for a trusted user
+    let proc = TestProcess::start();
+    let trusted_ip = "10.30.1.1";
+    let sentinel = "10.30.1.2";
+    let resp = proc.post_config_raw(&serde_json::json!({
+        "id": "cfg-ignore-re",
+        "name": "cfg-ignore-re",
+        "param": proc.log_file.to_str().unwrap(),
+        "regexes": ["Failed password for \\S+ from <IP>"],
+        "ignore_regexes": ["for deploy from"],
+        "ban_time": 60000,
+        "find_time": 60000,
+        "max_matches": 1,
+        "ignore_ips": [],
+    }));
+    assert!(resp.status().is_success(), "create failed: {}", resp.status());
+    thread::sleep(Duration::from_millis(200));
+
+    // WHEN the trusted line is written before a regular one
+    proc.append_log_line(&format!("Failed password for deploy from {} port 22", trusted_ip));
+    proc.append_log_line(&format!("Failed password for root from {} port 22", sentinel));
+
+    // THEN only the regular line leads to a ban
+    assert!(proc.wait_for_ban(sentinel, 5000), "IP {sentinel} was not banned");
+    assert!(
+        !proc.banned_ips().iter().any(|ip| ip == trusted_ip),
+        "IP {trusted_ip} was banned despite the ignore regex"
+    );
+}
+
+#[test]
+fn test_invalid_ignore_regex_rejected() {
+    // GIVEN a running instance
+    let proc = TestProcess::start();
+
+    // WHEN a config is posted with an ignore regex that does not compile
+    let resp = proc.post_config_raw(&serde_json::json!({
+        "id": "cfg-bad-ignore",
+        "name": "cfg-bad-ignore",
+        "param": proc.log_file.to_str().unwrap(),
+        "regexes": ["from <IP>"],
+        "ignore_regexes": ["(unclosed"],
+        "ban_time": 60000,
+        "find_time": 60000,
+        "max_matches": 1,
+        "ignore_ips": [],
+    }));
+
+    // THEN it is rejected
+    assert_eq!(resp.status().as_u16(), 400);
+}
+
+#[test]
+fn test_legacy_single_regex_config_migrates() {
+    // GIVEN a configs.db written before configs held several regexes
+    let db_dir = tempfile::tempdir().unwrap();
+    let log_file = db_dir.path().join("test.log");
+    let iptables_log = db_dir.path().join("iptables.log");
+    {
+        let conn = rusqlite::Connection::open(db_dir.path().join("configs.db")).unwrap();
+        conn.execute(
+            "CREATE TABLE configs (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                param TEXT NOT NULL,
+                regex TEXT NOT NULL,
+                ban_time INTEGER NOT NULL,
+                find_time INTEGER NOT NULL,
+                max_matches INTEGER NOT NULL,
+                ignore_ips TEXT NOT NULL
+            )",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO configs VALUES ('cfg-legacy', 'cfg-legacy', ?1, 'Old format from <IP>', 60000, 60000, 1, '[]')",
+            [log_file.to_str().unwrap()],
+        )
+        .unwrap();
+    }
+
+    // WHEN the process starts on it
+    let proc = TestProcess::start_at(db_dir.path(), &log_file, &iptables_log);
+
+    // THEN the old pattern is served as the config's only regex
+    let configs: Vec<serde_json::Value> = proc
+        .client()
+        .get(proc.api_url("/api/configs"))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    let legacy = configs.iter().find(|c| c["id"] == "cfg-legacy").expect("config not loaded");
+    assert_eq!(legacy["regexes"], serde_json::json!(["Old format from <IP>"]));
+    assert_eq!(legacy["ignore_regexes"], serde_json::json!([]));
+
+    // AND it still drives detection
+    thread::sleep(Duration::from_millis(200));
+    proc.append_log_line("Old format from 10.30.2.1");
+    assert!(proc.wait_for_ban("10.30.2.1", 5000), "legacy config did not ban");
+}
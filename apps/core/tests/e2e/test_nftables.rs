@@ -0,0 +1,152 @@
+use crate::utils::{chain, TestProcess};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const NFT_BACKEND: &[(&str, &str)] = &[("BANALIZE_CORE_FIREWALL_BACKEND", "nftables")];
+
+#[test]
+fn test_nftables_ban_adds_set_elements_with_timeout() {
+    // GIVEN a process running the nftables backend with a one-minute ban time
+    let proc = TestProcess::start_with_env(NFT_BACKEND);
+    let v4_ip = "10.40.0.1";
+    let v6_ip = "2001:db8:40::1";
+    proc.create_config(
+        "cfg-nft",
+        proc.log_file.to_str().unwrap(),
+        "Failed login from <IP> port",
+        1,
+        &[],
+    );
+
+    // WHEN an IPv4 and an IPv6 address reach max_matches
+    proc.append_log_line(&format!("Failed login from {} port 22", v4_ip));
+    proc.append_log_line(&format!("Failed login from {} port 22", v6_ip));
+
+    // THEN each lands in the config's set of its family, with the ban time as
+    // element timeout
+    assert!(proc.wait_for_ban(v4_ip, 5000), "IP {v4_ip} was not banned");
+    assert!(proc.wait_for_ban(v6_ip, 5000), "IP {v6_ip} was not banned");
+    let set = chain("cfg-nft");
+    for (ip, family) in [(v4_ip, "v4"), (v6_ip, "v6")] {
+        let element = format!("add element inet banalize {}-{} {{ {} timeout 60s }}", set, family, ip);
+        assert!(
+            proc.wait_for_nft_contains(&element, 3000),
+            "nft element not found in log:\n{}",
+            proc.read_nft_log()
+        );
+    }
+
+    // AND the sets are referenced by drop rules in the hooked base chain
+    let ruleset = proc.nft_ruleset();
+    assert!(ruleset.contains(&format!("ip saddr @{}-v4 drop", set)), "{ruleset}");
+    assert!(ruleset.contains(&format!("ip6 saddr @{}-v6 drop", set)), "{ruleset}");
+
+    // AND the iptables backend is never touched
+    assert!(
+        proc.read_iptables_log().is_empty(),
+        "iptables was called with the nftables backend:\n{}",
+        proc.read_iptables_log()
+    );
+}
+
+#[test]
+fn test_nftables_unban_and_config_delete() {
+    // GIVEN two configs with a banned IP each under the nftables backend
+    let proc = TestProcess::start_with_env(NFT_BACKEND);
+    let gone_ip = "10.40.1.1";
+    let kept_ip = "10.40.1.2";
+    proc.create_config("cfg-nft-gone", proc.log_file.to_str().unwrap(), "Gone hit from <IP>", 1, &[]);
+    proc.create_config("cfg-nft-kept", proc.log_file.to_str().unwrap(), "Kept hit from <IP>", 1, &[]);
+    proc.append_log_line(&format!("Gone hit from {}", gone_ip));
+    proc.append_log_line(&format!("Kept hit from {}", kept_ip));
+    assert!(proc.wait_for_ban(gone_ip, 5000), "IP {gone_ip} was not banned");
+    assert!(proc.wait_for_ban(kept_ip, 5000), "IP {kept_ip} was not banned");
+    let gone_set = format!("{}-v4", chain("cfg-nft-gone"));
+    let kept_set = format!("{}-v4", chain("cfg-nft-kept"));
+    assert!(proc.wait_for_nft_contains(&format!("{} {{ {}", kept_set, kept_ip), 3000));
+
+    // WHEN the first config is deleted
+    let resp = proc
+        .client()
+        .delete(proc.api_url("/api/configs/cfg-nft-gone"))
+        .send()
+        .unwrap();
+    assert!(resp.status().is_success());
+
+    // THEN its sets are removed while the other config keeps its ban and rule
+    assert!(
+        proc.wait_for_nft_contains(&format!("delete set inet banalize {}", gone_set), 3000),
+        "set was not deleted:\n{}",
+        proc.read_nft_log()
+    );
+    let ruleset = proc.nft_ruleset();
+    assert!(!ruleset.contains(&gone_set), "{ruleset}");
+    assert!(ruleset.contains(&format!("ip saddr @{} drop", kept_set)), "{ruleset}");
+    assert!(ruleset.contains(&format!("{} {}", kept_set, kept_ip)), "{ruleset}");
+}
+
+#[test]
+fn test_nftables_expired_element_unbans_cleanly() {
+    // GIVEN a one-second ban under the nftables backend
+    let proc = TestProcess::start_with_env(NFT_BACKEND);
+    let test_ip = "10.40.2.1";
+    proc.create_config_with_ban_time(
+        "cfg-nft-expiry",
+        proc.log_file.to_str().unwrap(),
+        "Expiry hit from <IP>",
+        1,
+        &[],
+        1000,
+    );
+    proc.append_log_line(&format!("Expiry hit from {}", test_ip));
+    assert!(proc.wait_for_ban(test_ip, 5000), "IP {test_ip} was not banned");
+
+    // WHEN the ban runs out, the set element timing out alongside it
+    thread::sleep(Duration::from_millis(1200));
+    assert!(proc.wait_for_unban(test_ip, 5000), "IP {test_ip} was not unbanned");
+
+    // THEN the element is gone and the cleaner still lifted the ban
+    let set = format!("{}-v4", chain("cfg-nft-expiry"));
+    assert!(!proc.nft_ruleset().contains(test_ip));
+    assert!(
+        proc.wait_for_nft_contains(&format!("delete element inet banalize {} {{ {} }}", set, test_ip), 3000),
+        "cleaner did not try to delete the element:\n{}",
+        proc.read_nft_log()
+    );
+}
+
+#[test]
+fn test_nftables_init_sweeps_stale_table() {
+    // GIVEN a banalize table left behind by a process that was killed
+    let db_dir = tempfile::tempdir().unwrap();
+    let log_file = db_dir.path().join("test.log");
+    let iptables_log = db_dir.path().join("iptables.log");
+    let mut proc1 = TestProcess::start_at_with_env(db_dir.path(), &log_file, &iptables_log, NFT_BACKEND);
+    proc1.create_config("cfg-nft-stale", log_file.to_str().unwrap(), "Stale hit from <IP>", 1, &[]);
+    proc1.append_log_line("Stale hit from 10.40.3.1");
+    assert!(proc1.wait_for_ban("10.40.3.1", 5000), "initial ban failed");
+    proc1.child.kill().unwrap();
+    let _ = proc1.child.wait();
+    assert!(proc1.nft_ruleset().contains("10.40.3.1"));
+
+    // WHEN a new process starts against the same firewall
+    let mut proc2 = TestProcess::start_at_with_env(db_dir.path(), &log_file, &iptables_log, NFT_BACKEND);
+
+    // THEN the stale table is deleted before being recreated, and restore
+    // re-applies the still-active ban
+    let since_restart = |log: &str| {
+        let restart = log.rfind("delete table inet banalize").expect("stale table not swept");
+        log[restart..].to_string()
+    };
+    assert!(since_restart(&proc2.read_nft_log()).contains("add table inet banalize"));
+    let deadline = Instant::now() + Duration::from_secs(3);
+    while !since_restart(&proc2.read_nft_log()).contains("{ 10.40.3.1 timeout") {
+        assert!(Instant::now() < deadline, "ban not restored:\n{}", proc2.read_nft_log());
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    // AND a graceful shutdown deletes the whole table
+    proc2.stop();
+    assert!(proc2.read_nft_log().trim_end().ends_with("delete table inet banalize"));
+    assert!(!proc2.nft_ruleset().contains("table inet banalize"));
+}
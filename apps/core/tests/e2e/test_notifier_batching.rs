@@ -0,0 +1,119 @@
+use crate::utils::{HttpStub, TestProcess};
+use serde_json::json;
+
+fn batching_notifier(proc: &TestProcess, stub: &HttpStub, window: u64, max_size: usize) {
+    let resp = proc
+        .client()
+        .post(proc.api_url("/api/notifiers"))
+        .json(&json!({
+            "id": "digest",
+            "events": ["ban"],
+            "email_config": null,
+            "signal_config": null,
+            "webhook_config": { "url": stub.url("/digest") },
+            "batch": { "window": window, "max_size": max_size }
+        }))
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+}
+
+fn body(stub: &HttpStub, index: usize) -> serde_json::Value {
+    serde_json::from_str(&stub.requests()[index].body).unwrap()
+}
+
+#[test]
+fn test_bans_in_window_sent_as_one_digest() {
+    // GIVEN a webhook notifier batching bans over a 1.5 s window
+    let stub = HttpStub::start();
+    let proc = TestProcess::start();
+    batching_notifier(&proc, &stub, 1500, 10);
+    proc.create_config("cfg-digest", proc.log_file.to_str().unwrap(), "DIGEST from <IP>", 1, &[]);
+
+    // WHEN three IPs are banned within the window
+    for ip in ["10.53.0.1", "10.53.0.2", "10.53.0.3"] {
+        proc.append_log_line(&format!("DIGEST from {ip}"));
+    }
+    assert!(proc.wait_for_ban("10.53.0.3", 3000));
+
+    // THEN one digest listing every IP and config goes out once the window closes
+    let requests = stub.wait_for_requests(1, 5000);
+    assert_eq!(requests.len(), 1, "no digest sent");
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    assert_eq!(stub.requests().len(), 1, "bans were not batched");
+    let digest = body(&stub, 0);
+    assert_eq!(digest["event"], "digest");
+    assert_eq!(digest["count"], 3);
+    assert_eq!(digest["title"], "Banalize digest: 3 bans");
+    let message = digest["message"].as_str().unwrap();
+    for ip in ["10.53.0.1", "10.53.0.2", "10.53.0.3"] {
+        assert!(message.contains(&format!("ban {ip} [cfg-digest]")), "{message}");
+    }
+    assert_eq!(digest["events"].as_array().unwrap().len(), 3);
+}
+
+#[test]
+fn test_full_batch_sent_before_window_closes() {
+    // GIVEN a notifier with a long window but a batch size of 2
+    let stub = HttpStub::start();
+    let proc = TestProcess::start();
+    batching_notifier(&proc, &stub, 600_000, 2);
+    proc.create_config("cfg-full", proc.log_file.to_str().unwrap(), "FULL from <IP>", 1, &[]);
+
+    // WHEN two IPs are banned
+    proc.append_log_line("FULL from 10.54.0.1");
+    proc.append_log_line("FULL from 10.54.0.2");
+
+    // THEN the digest is sent as soon as the batch is full
+    let requests = stub.wait_for_requests(1, 5000);
+    assert_eq!(requests.len(), 1, "full batch was not sent");
+    assert_eq!(body(&stub, 0)["count"], 2);
+}
+
+#[test]
+fn test_batch_of_a_deleted_notifier_is_dropped() {
+    // GIVEN a notifier batching bans over a 1.5 s window, with a ban queued
+    let stub = HttpStub::start();
+    let proc = TestProcess::start();
+    batching_notifier(&proc, &stub, 1500, 10);
+    proc.create_config("cfg-gone", proc.log_file.to_str().unwrap(), "GONE from <IP>", 1, &[]);
+    proc.append_log_line("GONE from 10.54.1.1");
+    assert!(proc.wait_for_ban("10.54.1.1", 3000));
+
+    // WHEN the notifier is deleted before the window closes
+    let resp = proc
+        .client()
+        .delete(proc.api_url("/api/notifiers/digest"))
+        .send()
+        .unwrap();
+    assert!(resp.status().is_success());
+
+    // THEN its batch is never sent
+    std::thread::sleep(std::time::Duration::from_millis(2500));
+    assert!(stub.requests().is_empty(), "batch sent to a deleted notifier");
+}
+
+#[test]
+fn test_invalid_batch_rejected() {
+    // GIVEN a running process
+    let stub = HttpStub::start();
+    let proc = TestProcess::start();
+
+    // WHEN a notifier is created with an empty batch
+    let resp = proc
+        .client()
+        .post(proc.api_url("/api/notifiers"))
+        .json(&json!({
+            "id": "bad",
+            "events": ["ban"],
+            "email_config": null,
+            "signal_config": null,
+            "webhook_config": { "url": stub.url("/x") },
+            "batch": { "window": 1000, "max_size": 0 }
+        }))
+        .send()
+        .unwrap();
+
+    // THEN it is rejected
+    assert_eq!(resp.status(), 400);
+}
@@ -0,0 +1,89 @@
+use crate::utils::{del_drop_rule, HttpStub, TestProcess};
+use serde_json::json;
+
+fn create_notifier(proc: &TestProcess, body: serde_json::Value) -> reqwest::blocking::Response {
+    proc.client()
+        .post(proc.api_url("/api/notifiers"))
+        .json(&body)
+        .send()
+        .unwrap()
+}
+
+#[test]
+fn test_notifier_only_hears_repeat_offenders_of_its_config() {
+    // GIVEN a webhook scoped to repeat offenders of one config, and two
+    // configs with a short ban
+    let stub = HttpStub::start();
+    let proc = TestProcess::start();
+    let resp = create_notifier(
+        &proc,
+        json!({
+            "id": "on-call",
+            "events": ["ban"],
+            "email_config": null,
+            "signal_config": null,
+            "webhook_config": { "url": stub.url("/on-call") },
+            "filters": { "config_ids": ["cfg-prod-ssh"], "min_recidive_level": 2 }
+        }),
+    );
+    assert_eq!(resp.status(), 200);
+    let log = proc.log_file.to_str().unwrap();
+    proc.create_config_with_ban_time("cfg-prod-ssh", log, "SSH fail from <IP>", 1, &[], 1500);
+    proc.create_config_with_ban_time("cfg-staging", log, "STAGING fail from <IP>", 1, &[], 1500);
+
+    // WHEN an IP is banned twice in each config
+    for config in ["cfg-prod-ssh", "cfg-staging"] {
+        let prefix = if config == "cfg-prod-ssh" { "SSH" } else { "STAGING" };
+        proc.append_log_line(&format!("{prefix} fail from 10.55.0.1"));
+        assert!(
+            proc.wait_for_iptables_contains(&del_drop_rule(config, "10.55.0.1"), 10_000),
+            "first ban in {config} did not expire"
+        );
+        proc.append_log_line(&format!("{prefix} fail from 10.55.0.1"));
+        assert!(
+            proc.wait_for_iptables_contains(&del_drop_rule(config, "10.55.0.1"), 10_000),
+            "second ban in {config} did not expire"
+        );
+    }
+
+    // THEN only the second ban in the scoped config was sent
+    let requests = stub.wait_for_requests(1, 5000);
+    assert_eq!(requests.len(), 1, "expected exactly one notification");
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    assert_eq!(stub.requests().len(), 1, "filtered events were sent");
+    let body: serde_json::Value = serde_json::from_str(&requests[0].body).unwrap();
+    assert_eq!(body["config_id"], "cfg-prod-ssh");
+    assert_eq!(body["ip"], "10.55.0.1");
+    assert_eq!(body["recidive_level"], 2);
+}
+
+#[test]
+fn test_invalid_country_filter_rejected() {
+    // GIVEN a running process
+    let stub = HttpStub::start();
+    let proc = TestProcess::start();
+
+    // WHEN a notifier is created with a three-letter country code
+    let resp = create_notifier(
+        &proc,
+        json!({
+            "id": "geo",
+            "events": ["ban"],
+            "email_config": null,
+            "signal_config": null,
+            "webhook_config": { "url": stub.url("/geo") },
+            "filters": { "exclude_countries": ["FRA"] }
+        }),
+    );
+
+    // THEN it is rejected
+    assert_eq!(resp.status(), 400);
+    let list: Vec<serde_json::Value> = proc
+        .client()
+        .get(proc.api_url("/api/notifiers"))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert!(list.is_empty());
+}
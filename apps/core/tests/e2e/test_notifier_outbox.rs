@@ -0,0 +1,137 @@
+use crate::utils::{HttpStub, TestProcess};
+use serde_json::json;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const FAST_RETRY: (&str, &str) = ("BANALIZE_CORE_NOTIFIER_RETRY_BASE", "1");
+
+fn create_webhook(proc: &TestProcess, stub: &HttpStub) {
+    let resp = proc
+        .client()
+        .post(proc.api_url("/api/notifiers"))
+        .json(&json!({
+            "id": "outbox",
+            "events": ["ban"],
+            "email_config": null,
+            "signal_config": null,
+            "webhook_config": { "url": stub.url("/outbox") }
+        }))
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+}
+
+fn deliveries(proc: &TestProcess, query: &str) -> (u64, Vec<serde_json::Value>) {
+    let resp = proc
+        .client()
+        .get(proc.api_url(&format!("/api/notifiers/outbox/deliveries{query}")))
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let total = resp.headers()["x-total-count"].to_str().unwrap().parse().unwrap();
+    (total, resp.json().unwrap())
+}
+
+fn wait_for_status(proc: &TestProcess, status: &str, timeout_ms: u64) -> Option<serde_json::Value> {
+    let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+    loop {
+        let (_, rows) = deliveries(proc, "");
+        if let Some(row) = rows.into_iter().find(|d| d["status"] == status) {
+            return Some(row);
+        }
+        if Instant::now() >= deadline {
+            return None;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+#[test]
+fn test_failed_delivery_is_retried_until_delivered() {
+    // GIVEN a webhook notifier whose endpoint is failing
+    let stub = HttpStub::start();
+    stub.respond_with(500);
+    let proc = TestProcess::start_with_env(&[FAST_RETRY]);
+    create_webhook(&proc, &stub);
+    proc.create_config("cfg-outbox", proc.log_file.to_str().unwrap(), "OUTBOX from <IP>", 1, &[]);
+
+    // WHEN an IP is banned
+    proc.append_log_line("OUTBOX from 10.55.0.1");
+
+    // THEN the failed attempt is recorded and the delivery stays pending
+    assert!(!stub.wait_for_requests(1, 5000).is_empty(), "webhook was not called");
+    let pending = wait_for_status(&proc, "pending", 2000).expect("no pending delivery");
+    assert_eq!(pending["event"], "ban");
+    assert_eq!(pending["ip"], "10.55.0.1");
+    assert_eq!(pending["title"], "Banalize: IP Banned");
+    assert!(pending["last_error"].as_str().unwrap().contains("500"), "{pending}");
+    assert!(pending["next_attempt_at"].is_u64());
+
+    // WHEN the endpoint recovers
+    stub.respond_with(200);
+
+    // THEN a retry delivers it
+    let delivered = wait_for_status(&proc, "delivered", 10_000).expect("never delivered");
+    assert!(delivered["attempts"].as_u64().unwrap() >= 2, "{delivered}");
+    assert!(delivered["last_error"].is_null());
+    assert!(delivered["next_attempt_at"].is_null());
+    let (total, rows) = deliveries(&proc, "?status=failed");
+    assert_eq!((total, rows.len()), (0, 0));
+    let (total, _) = deliveries(&proc, "?limit=0");
+    assert_eq!(total, 1);
+
+    // AND an unknown notifier has no deliveries to list
+    let resp = proc
+        .client()
+        .get(proc.api_url("/api/notifiers/nope/deliveries"))
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+}
+
+#[test]
+fn test_pending_delivery_survives_restart() {
+    // GIVEN a ban notification that could not be delivered before shutdown
+    let stub = HttpStub::start();
+    stub.respond_with(503);
+    let db_dir = tempfile::tempdir().unwrap();
+    let log_file = db_dir.path().join("test.log");
+    let iptables_log = db_dir.path().join("iptables.log");
+    let mut proc1 =
+        TestProcess::start_at_with_env(db_dir.path(), &log_file, &iptables_log, &[FAST_RETRY]);
+    create_webhook(&proc1, &stub);
+    proc1.create_config("cfg-outbox-restart", log_file.to_str().unwrap(), "RESTART from <IP>", 1, &[]);
+    proc1.append_log_line("RESTART from 10.56.0.1");
+    assert!(!stub.wait_for_requests(1, 5000).is_empty(), "webhook was not called");
+    proc1.stop();
+
+    // WHEN the endpoint recovers and the process starts again
+    stub.respond_with(200);
+    let proc2 = TestProcess::start_at_with_env(db_dir.path(), &log_file, &iptables_log, &[FAST_RETRY]);
+
+    // THEN the queued notification is delivered by the new process
+    let delivered = wait_for_status(&proc2, "delivered", 10_000).expect("never delivered");
+    assert_eq!(delivered["ip"], "10.56.0.1");
+    assert!(delivered["attempts"].as_u64().unwrap() >= 2, "{delivered}");
+}
+
+#[test]
+fn test_finished_deliveries_are_pruned_after_retention() {
+    // GIVEN an outbox keeping finished deliveries for one second
+    let stub = HttpStub::start();
+    stub.respond_with(200);
+    let proc = TestProcess::start_with_env(&[FAST_RETRY, ("BANALIZE_CORE_NOTIFIER_RETENTION", "1")]);
+    create_webhook(&proc, &stub);
+    proc.create_config("cfg-outbox-prune", proc.log_file.to_str().unwrap(), "PRUNE from <IP>", 1, &[]);
+
+    // WHEN a notification is delivered
+    proc.append_log_line("PRUNE from 10.57.0.1");
+    wait_for_status(&proc, "delivered", 5000).expect("never delivered");
+
+    // THEN its row is swept once the retention has passed
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while deliveries(&proc, "").0 > 0 {
+        assert!(Instant::now() < deadline, "delivered row was never pruned");
+        thread::sleep(Duration::from_millis(200));
+    }
+}
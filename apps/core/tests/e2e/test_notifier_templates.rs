@@ -0,0 +1,77 @@
+use crate::utils::{HttpStub, TestProcess};
+use serde_json::json;
+
+fn templated_notifier(url: &str) -> serde_json::Value {
+    json!({
+        "id": "tpl",
+        "events": ["ban"],
+        "email_config": null,
+        "signal_config": null,
+        "webhook_config": { "url": url, "body_template": { "title": "{title}", "text": "{message}" } },
+        "title_template": "{country_flag} {ip} banned",
+        "message_template": "{ip} hit {config_name} {match_count} times: {line}"
+    })
+}
+
+#[test]
+fn test_notifier_templates_preview_and_ban() {
+    // GIVEN a webhook notifier with title and message templates
+    let stub = HttpStub::start();
+    let proc = TestProcess::start();
+    let resp = proc
+        .client()
+        .post(proc.api_url("/api/notifiers"))
+        .json(&templated_notifier(&stub.url("/tpl")))
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    // WHEN it is previewed with a dry run
+    let preview: serde_json::Value = proc
+        .client()
+        .post(proc.api_url("/api/notifiers/tpl/test?dry_run=true"))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+
+    // THEN the sample ban is rendered through the templates and nothing is sent
+    assert_eq!(preview["success"], true, "{preview}");
+    assert_eq!(preview["title"], "🇳🇱 192.0.2.1 banned");
+    let body = preview["body"].as_str().unwrap();
+    assert!(body.starts_with("192.0.2.1 hit Sample config 5 times: "), "{preview}");
+    assert!(body.contains("Failed password for root from 192.0.2.1"), "{preview}");
+    assert!(stub.wait_for_requests(1, 500).is_empty());
+
+    // WHEN a real ban happens
+    proc.create_config("cfg-tpl", proc.log_file.to_str().unwrap(), "TPL hit from <IP>", 2, &[]);
+    proc.append_log_line("TPL hit from 10.52.0.1");
+    proc.append_log_line("TPL hit from 10.52.0.1");
+
+    // THEN the webhook receives the templated title and message; a private
+    // address has no country, so its flag renders empty
+    let requests = stub.wait_for_requests(1, 5000);
+    assert_eq!(requests.len(), 1, "webhook was not called");
+    let body: serde_json::Value = serde_json::from_str(&requests[0].body).unwrap();
+    assert_eq!(body["title"], " 10.52.0.1 banned");
+    assert_eq!(body["text"], "10.52.0.1 hit cfg-tpl 2 times: TPL hit from 10.52.0.1");
+}
+
+#[test]
+fn test_notifier_template_unknown_variable_rejected() {
+    // GIVEN a running process
+    let proc = TestProcess::start();
+
+    // WHEN a notifier template references an unknown variable
+    let mut notifier = templated_notifier("http://127.0.0.1:9/x");
+    notifier["message_template"] = json!("{ip} by {jail}");
+    let resp = proc
+        .client()
+        .post(proc.api_url("/api/notifiers"))
+        .json(&notifier)
+        .send()
+        .unwrap();
+
+    // THEN it is rejected
+    assert_eq!(resp.status(), 400);
+}
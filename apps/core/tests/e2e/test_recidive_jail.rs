@@ -0,0 +1,129 @@
+use crate::utils::{drop_rule, TestProcess};
+use serde_json::json;
+use std::thread;
+use std::time::Duration;
+
+fn create_recidive(proc: &TestProcess, id: &str, ignore_regexes: &[&str]) {
+    let resp = proc.post_config_raw(&json!({
+        "id": id,
+        "name": id,
+        "kind": "recidive",
+        "ignore_regexes": ignore_regexes,
+        "ban_time": 600000,
+        "find_time": 60000,
+        "max_matches": 2,
+        "ignore_ips": [],
+    }));
+    assert!(resp.status().is_success(), "recidive config rejected: {}", resp.status());
+}
+
+#[test]
+fn test_recidive_bans_ip_banned_by_several_configs() {
+    // GIVEN two file configs and a recidive jail that bans on the second ban
+    let proc = TestProcess::start();
+    proc.create_config("cfg-rec-ssh", proc.log_file.to_str().unwrap(), "SSH fail from <IP>", 1, &[]);
+    proc.create_config("cfg-rec-web", proc.log_file.to_str().unwrap(), "WEB fail from <IP>", 1, &[]);
+    create_recidive(&proc, "cfg-recidive", &[]);
+
+    // WHEN one IP is banned by both configs and another by only one
+    proc.append_log_line("SSH fail from 10.49.0.1");
+    proc.append_log_line("SSH fail from 10.49.0.2");
+    proc.append_log_line("WEB fail from 10.49.0.1");
+
+    // THEN the repeat offender is banned on the recidive jail's own chain
+    assert!(
+        proc.wait_for_iptables_contains(&drop_rule("cfg-recidive", "10.49.0.1"), 5000),
+        "repeat offender was not banned by the recidive jail:\n{}",
+        proc.read_iptables_log()
+    );
+    // AND each foreign ban was recorded as one of the jail's matches
+    assert!(proc.wait_for_match_count("cfg-recidive", 3, 3000));
+
+    // AND the IP banned only once is left to its own config
+    thread::sleep(Duration::from_millis(300));
+    assert!(!proc.read_iptables_log().contains(&drop_rule("cfg-recidive", "10.49.0.2")));
+}
+
+#[test]
+fn test_recidive_ignore_regexes_exclude_configs() {
+    // GIVEN a recidive jail that ignores bans from one config
+    let proc = TestProcess::start();
+    proc.create_config("cfg-rec-noisy", proc.log_file.to_str().unwrap(), "NOISY fail from <IP>", 1, &[]);
+    proc.create_config("cfg-rec-quiet", proc.log_file.to_str().unwrap(), "QUIET fail from <IP>", 1, &[]);
+    create_recidive(&proc, "cfg-recidive-filtered", &[r"^\[cfg-rec-noisy\]"]);
+
+    // WHEN an IP is banned by both configs
+    proc.append_log_line("NOISY fail from 10.49.1.1");
+    proc.append_log_line("QUIET fail from 10.49.1.1");
+    assert!(proc.wait_for_match_count("cfg-recidive-filtered", 1, 5000));
+
+    // THEN only one ban counted, short of the jail's threshold
+    thread::sleep(Duration::from_millis(300));
+    assert_eq!(proc.match_count("cfg-recidive-filtered"), 1);
+    assert!(!proc
+        .read_iptables_log()
+        .contains(&drop_rule("cfg-recidive-filtered", "10.49.1.1")));
+}
+
+#[test]
+fn test_recidive_config_survives_restart() {
+    // GIVEN a recidive jail and two file configs on a persistent database
+    let dir = tempfile::tempdir().unwrap();
+    let log_file = dir.path().join("recidive.log");
+    let mut proc1 = TestProcess::start_at(dir.path(), &log_file, &dir.path().join("iptables_1.log"));
+    let log = log_file.to_str().unwrap();
+    proc1.create_config("cfg-rec-restart-ssh", log, "SSH fail from <IP>", 1, &[]);
+    proc1.create_config("cfg-rec-restart-web", log, "WEB fail from <IP>", 1, &[]);
+    create_recidive(&proc1, "cfg-recidive-restart", &[]);
+    let db_path = proc1.db_path.clone();
+    proc1.stop();
+    thread::sleep(Duration::from_millis(300));
+
+    // WHEN the process restarts on the same database
+    let proc2 = TestProcess::start_at(&db_path, &log_file, &dir.path().join("iptables_2.log"));
+
+    // THEN the jail is still listed, without fail patterns
+    let configs: Vec<serde_json::Value> = proc2
+        .client()
+        .get(proc2.api_url("/api/configs"))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    let jail = configs
+        .iter()
+        .find(|c| c["id"] == "cfg-recidive-restart")
+        .expect("recidive config lost on restart");
+    assert_eq!(jail["regexes"], json!([]), "{}", jail);
+
+    // AND it still escalates repeat offenders
+    thread::sleep(Duration::from_millis(200));
+    proc2.append_log_line("SSH fail from 10.49.2.1");
+    proc2.append_log_line("WEB fail from 10.49.2.1");
+    assert!(
+        proc2.wait_for_iptables_contains(&drop_rule("cfg-recidive-restart", "10.49.2.1"), 5000),
+        "recidive jail did not escalate after restart:\n{}",
+        proc2.read_iptables_log()
+    );
+}
+
+#[test]
+fn test_recidive_config_rejects_regexes() {
+    // GIVEN a running process
+    let proc = TestProcess::start();
+
+    // WHEN a recidive config is sent with fail patterns
+    let resp = proc.post_config_raw(&json!({
+        "id": "cfg-recidive-bad",
+        "name": "cfg-recidive-bad",
+        "kind": "recidive",
+        "regexes": ["fail from <IP>"],
+        "ban_time": 600000,
+        "find_time": 60000,
+        "max_matches": 2,
+        "ignore_ips": [],
+    }));
+
+    // THEN it is rejected
+    assert_eq!(resp.status(), 400);
+}
@@ -0,0 +1,164 @@
+use crate::utils::TestProcess;
+use serde_json::json;
+use std::io::Write;
+use std::net::{TcpListener, TcpStream, UdpSocket};
+
+/// A port nothing listens on right now.
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+fn syslog_config(id: &str, listen: &str, regex: &str, filter: serde_json::Value) -> serde_json::Value {
+    json!({
+        "id": id,
+        "name": id,
+        "kind": "syslog",
+        "param": listen,
+        "regexes": [regex],
+        "ban_time": 60000,
+        "find_time": 60000,
+        "max_matches": 1,
+        "ignore_ips": [],
+        "syslog": filter
+    })
+}
+
+#[test]
+fn test_udp_syslog_filtered_by_hostname_and_app() {
+    // GIVEN two syslog configs sharing one UDP port, one narrowed to the sshd
+    // messages of a single gateway
+    let proc = TestProcess::start();
+    let port = free_port();
+    let listen = format!("udp://127.0.0.1:{}", port);
+    let resp = proc.post_config_raw(&syslog_config(
+        "cfg-syslog-gw",
+        &listen,
+        "Invalid user .* from <IP>",
+        json!({ "hostnames": ["GW-1"], "app_names": ["sshd"] }),
+    ));
+    assert_eq!(resp.status(), 200);
+    let resp = proc.post_config_raw(&syslog_config(
+        "cfg-syslog-all",
+        &listen,
+        "DROP SRC=<IP>",
+        json!({}),
+    ));
+    assert_eq!(resp.status(), 200);
+
+    // WHEN devices send RFC 3164 and RFC 5424 datagrams
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    for message in [
+        "<38>Oct 18 22:14:15 gw-2 sshd[11]: Invalid user admin from 10.63.0.2",
+        "<38>Oct 18 22:14:15 gw-1 telnetd[12]: Invalid user admin from 10.63.0.3",
+        "<38>Oct 18 22:14:15 gw-1 sshd[13]: Invalid user admin from 10.63.0.1",
+        "<4>1 2026-10-18T22:14:15Z edge kernel - - - DROP SRC=10.63.0.4",
+    ] {
+        socket.send_to(message.as_bytes(), ("127.0.0.1", port)).unwrap();
+    }
+
+    // THEN each config bans what passes its filter and nothing else
+    assert!(proc.wait_for_ban("10.63.0.1", 5000), "gateway sshd offender not banned");
+    assert!(proc.wait_for_ban("10.63.0.4", 5000), "RFC 5424 offender not banned");
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    let banned = proc.banned_ips();
+    assert!(!banned.contains(&"10.63.0.2".to_string()), "other host let through");
+    assert!(!banned.contains(&"10.63.0.3".to_string()), "other app let through");
+    assert_eq!(proc.match_count("cfg-syslog-gw"), 1);
+    assert_eq!(proc.match_count("cfg-syslog-all"), 1);
+}
+
+#[test]
+fn test_tcp_syslog_reads_both_framings() {
+    // GIVEN a syslog config on a TCP port
+    let proc = TestProcess::start();
+    let port = free_port();
+    let resp = proc.post_config_raw(&syslog_config(
+        "cfg-syslog-tcp",
+        &format!("tcp://127.0.0.1:{}", port),
+        "auth failure rhost=<IP>",
+        json!({}),
+    ));
+    assert_eq!(resp.status(), 200);
+
+    // WHEN a sender streams an octet-counted and a newline-framed message,
+    // split across writes
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    let counted = "<86>1 2026-10-18T22:14:15Z nas login - - - auth failure rhost=10.64.0.1";
+    stream
+        .write_all(format!("{} {}", counted.len(), &counted[..20]).as_bytes())
+        .unwrap();
+    stream.flush().unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    stream.write_all(&counted.as_bytes()[20..]).unwrap();
+    stream
+        .write_all(b"<86>Oct 18 22:14:16 nas login: auth failure rhost=10.64.0.2\n")
+        .unwrap();
+
+    // THEN both messages are detected
+    assert!(proc.wait_for_ban("10.64.0.1", 5000), "octet-counted message missed");
+    assert!(proc.wait_for_ban("10.64.0.2", 5000), "newline-framed message missed");
+}
+
+#[test]
+fn test_syslog_config_on_taken_port_is_rejected() {
+    // GIVEN a port already bound by another program
+    let proc = TestProcess::start();
+    let taken = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = taken.local_addr().unwrap().port();
+
+    // WHEN syslog configs are created on it, or with a malformed address
+    let on_taken = proc.post_config_raw(&syslog_config(
+        "cfg-syslog-taken",
+        &format!("udp://127.0.0.1:{}", port),
+        "fail <IP>",
+        json!({}),
+    ));
+    let malformed = proc.post_config_raw(&syslog_config(
+        "cfg-syslog-bad",
+        "127.0.0.1:514",
+        "fail <IP>",
+        json!({}),
+    ));
+
+    // THEN neither is kept
+    assert_eq!(on_taken.status(), 500);
+    assert_eq!(malformed.status(), 400);
+    let configs: Vec<serde_json::Value> = proc
+        .client()
+        .get(proc.api_url("/api/configs"))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert!(configs.is_empty());
+}
+
+#[test]
+fn test_syslog_config_update_rebinds_same_port() {
+    // GIVEN a syslog config listening on a UDP port
+    let proc = TestProcess::start();
+    let port = free_port();
+    let listen = format!("udp://127.0.0.1:{}", port);
+    let resp = proc.post_config_raw(&syslog_config("cfg-syslog-upd", &listen, "old <IP>", json!({})));
+    assert_eq!(resp.status(), 200);
+
+    // WHEN it is updated in place with a new pattern
+    let resp = proc
+        .client()
+        .put(proc.api_url("/api/configs/cfg-syslog-upd"))
+        .json(&syslog_config("cfg-syslog-upd", &listen, "new <IP>", json!({})))
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    // THEN the port is listened on again with the new pattern
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket
+        .send_to(b"<13>Oct 18 22:14:15 host app: new 10.65.0.1", ("127.0.0.1", port))
+        .unwrap();
+    assert!(proc.wait_for_ban("10.65.0.1", 5000), "updated config not listening");
+}
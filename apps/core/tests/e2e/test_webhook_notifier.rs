@@ -0,0 +1,126 @@
+use crate::utils::{HttpStub, TestProcess};
+use hmac::{Hmac, Mac};
+use serde_json::json;
+use sha2::Sha256;
+
+fn create_notifier(proc: &TestProcess, body: serde_json::Value) -> reqwest::blocking::Response {
+    proc.client()
+        .post(proc.api_url("/api/notifiers"))
+        .json(&body)
+        .send()
+        .unwrap()
+}
+
+#[test]
+fn test_webhook_receives_templated_signed_ban() {
+    // GIVEN a webhook notifier with a body template, a header and a secret
+    let stub = HttpStub::start();
+    let proc = TestProcess::start();
+    let resp = create_notifier(
+        &proc,
+        json!({
+            "id": "hook",
+            "events": ["ban"],
+            "email_config": null,
+            "signal_config": null,
+            "webhook_config": {
+                "url": stub.url("/hooks/ban"),
+                "method": "PUT",
+                "headers": { "X-Team": "secops" },
+                "secret": "s3cret",
+                "body_template": {
+                    "text": "{ip} banned by {config_name}",
+                    "count": "{match_count}",
+                    "manual": "{manual}",
+                    "unknown": "{nope}"
+                }
+            }
+        }),
+    );
+    assert_eq!(resp.status(), 200);
+    proc.create_config("cfg-hook", proc.log_file.to_str().unwrap(), "HOOK hit from <IP>", 2, &[]);
+
+    // WHEN an IP is banned
+    proc.append_log_line("HOOK hit from 10.51.0.1");
+    proc.append_log_line("HOOK hit from 10.51.0.1");
+
+    // THEN the stub receives the rendered body with the configured method and header
+    let requests = stub.wait_for_requests(1, 5000);
+    assert_eq!(requests.len(), 1, "webhook was not called");
+    let request = &requests[0];
+    assert_eq!(request.method, "PUT");
+    assert_eq!(request.path, "/hooks/ban");
+    assert_eq!(request.header("x-team"), Some("secops"));
+    assert_eq!(request.header("content-type"), Some("application/json"));
+    let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+    assert_eq!(
+        body,
+        json!({
+            "text": "10.51.0.1 banned by cfg-hook",
+            "count": 2,
+            "manual": false,
+            "unknown": "{nope}"
+        })
+    );
+
+    // AND the signature is the HMAC-SHA256 of the exact body
+    let mut mac = Hmac::<Sha256>::new_from_slice(b"s3cret").unwrap();
+    mac.update(request.body.as_bytes());
+    let expected: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    assert_eq!(request.header("x-banalize-signature"), Some(format!("sha256={expected}").as_str()));
+}
+
+#[test]
+fn test_webhook_default_body_and_validation() {
+    // GIVEN a webhook notifier without a template or secret
+    let stub = HttpStub::start();
+    let proc = TestProcess::start();
+    let resp = create_notifier(
+        &proc,
+        json!({
+            "id": "hook-plain",
+            "events": ["ban"],
+            "email_config": null,
+            "signal_config": null,
+            "webhook_config": { "url": stub.url("/plain") }
+        }),
+    );
+    assert_eq!(resp.status(), 200);
+
+    // WHEN it is tested through the API
+    let result: serde_json::Value = proc
+        .client()
+        .post(proc.api_url("/api/notifiers/hook-plain/test"))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+
+    // THEN every variable of the sample ban is POSTed as one object, unsigned
+    assert_eq!(result["success"], true, "{result}");
+    let requests = stub.wait_for_requests(1, 3000);
+    assert_eq!(requests[0].method, "POST");
+    assert!(requests[0].header("x-banalize-signature").is_none());
+    let body: serde_json::Value = serde_json::from_str(&requests[0].body).unwrap();
+    assert_eq!(body["title"], "Banalize: IP Banned");
+    assert_eq!(body["ip"], "192.0.2.1");
+
+    // AND malformed webhook configs are rejected
+    for webhook in [
+        json!({ "url": "not a url" }),
+        json!({ "url": "ftp://example.com/x" }),
+        json!({ "url": stub.url("/x"), "method": "DELETE" }),
+        json!({ "url": stub.url("/x"), "headers": { "bad header": "x" } }),
+    ] {
+        let resp = create_notifier(
+            &proc,
+            json!({ "id": "bad", "events": ["ban"], "email_config": null, "signal_config": null, "webhook_config": webhook }),
+        );
+        assert_eq!(resp.status(), 400, "accepted {webhook}");
+    }
+}
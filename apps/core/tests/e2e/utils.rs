@@ -1,6 +1,8 @@
 use std::fs::{self, OpenOptions};
-use std::io::Write;
+use std::io::{BufRead, BufReader, Read, Write};
 use std::net::TcpListener;
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::{Arc, Mutex};
 use std::path::{Path, PathBuf};
 use std::process::{Child, Command};
 use std::thread;
@@ -8,6 +10,8 @@ use std::time::{Duration, Instant};
 
 pub const BINARY: &str = env!("CARGO_BIN_EXE_banalize-core");
 pub const FAKE_IPTABLES: &str = env!("CARGO_BIN_EXE_fake-iptables");
+pub const FAKE_NFT: &str = env!("CARGO_BIN_EXE_fake-nft");
+pub const FAKE_IPSET: &str = env!("CARGO_BIN_EXE_fake-ipset");
 
 /// Per-config iptables chain name. Deliberately duplicates
 /// `src/firewall.rs::chain_name` (the crate has no lib target to import from);
@@ -48,12 +52,32 @@ pub fn del_drop_rule(config_id: &str, ip: &str) -> String {
     format!("-D {} -s {} -j DROP", chain(config_id), ip)
 }
 
+/// The fake-ip6tables log that pairs with a given fake-iptables log.
+fn ip6tables_log_for(iptables_log: &Path) -> PathBuf {
+    iptables_log.with_extension("v6.log")
+}
+
+/// The fake-nft log that pairs with a given fake-iptables log.
+fn nft_log_for(iptables_log: &Path) -> PathBuf {
+    iptables_log.with_extension("nft.log")
+}
+
+/// The fake-ipset log that pairs with a given fake-iptables log.
+fn ipset_log_for(iptables_log: &Path) -> PathBuf {
+    iptables_log.with_extension("ipset.log")
+}
+
 pub struct TestProcess {
     pub child: Child,
     pub api_port: u16,
     pub db_path: PathBuf,
     pub log_file: PathBuf,
     pub iptables_log: PathBuf,
+    pub ip6tables_log: PathBuf,
+    /// Only written when the process runs the nftables backend.
+    pub nft_log: PathBuf,
+    /// Only written when the process runs the iptables backend in ipset mode.
+    pub ipset_log: PathBuf,
     // Kept alive so the temp dir is not dropped while the process runs.
     // None when the DB path is externally owned (restart tests).
     _db_dir: Option<tempfile::TempDir>,
@@ -82,6 +106,9 @@ impl TestProcess {
             api_port: port,
             db_path,
             log_file,
+            ip6tables_log: ip6tables_log_for(&iptables_log),
+            nft_log: nft_log_for(&iptables_log),
+            ipset_log: ipset_log_for(&iptables_log),
             iptables_log,
             _db_dir: Some(db_dir),
         };
@@ -92,17 +119,30 @@ impl TestProcess {
     /// Start a process reusing an existing database directory.
     /// Used by restart tests to verify ban restore behaviour.
     pub fn start_at(db_path: &Path, log_file: &Path, iptables_log: &Path) -> Self {
+        Self::start_at_with_env(db_path, log_file, iptables_log, &[])
+    }
+
+    /// `start_at` with extra environment variables (e.g. a firewall backend).
+    pub fn start_at_with_env(
+        db_path: &Path,
+        log_file: &Path,
+        iptables_log: &Path,
+        extra_env: &[(&str, &str)],
+    ) -> Self {
         if !log_file.exists() {
             fs::write(log_file, "").unwrap();
         }
 
-        let (child, port) = Self::spawn_binary(db_path, log_file, iptables_log, &[]);
+        let (child, port) = Self::spawn_binary(db_path, log_file, iptables_log, extra_env);
         let proc = Self {
             child,
             api_port: port,
             db_path: db_path.to_path_buf(),
             log_file: log_file.to_path_buf(),
             iptables_log: iptables_log.to_path_buf(),
+            ip6tables_log: ip6tables_log_for(iptables_log),
+            nft_log: nft_log_for(iptables_log),
+            ipset_log: ipset_log_for(iptables_log),
             _db_dir: None,
         };
         proc.wait_for_api();
@@ -119,6 +159,12 @@ impl TestProcess {
         let port = listener.local_addr().unwrap().port();
         drop(listener);
 
+        // The fake models ip6tables when invoked through a link of that name.
+        let fake_ip6tables = db_path.join("fake-ip6tables");
+        if !fake_ip6tables.exists() {
+            std::os::unix::fs::symlink(FAKE_IPTABLES, &fake_ip6tables).unwrap();
+        }
+
         let mut cmd = Command::new(BINARY);
         cmd.env("BANALIZE_CORE_API_ADDR", format!("127.0.0.1:{}", port))
             .env("BANALIZE_CORE_DATABASE_PATH", db_path.to_str().unwrap())
@@ -127,6 +173,15 @@ impl TestProcess {
             .env("BANALIZE_CORE_CLEANER_INTERVAL", "1")
             .env("BANALIZE_IPTABLE_BIN", FAKE_IPTABLES)
             .env("BANALIZE_FAKE_IPTABLES_LOG", iptables_log.to_str().unwrap())
+            .env("BANALIZE_IP6TABLE_BIN", fake_ip6tables.to_str().unwrap())
+            .env(
+                "BANALIZE_FAKE_IP6TABLES_LOG",
+                ip6tables_log_for(iptables_log).to_str().unwrap(),
+            )
+            .env("BANALIZE_NFT_BIN", FAKE_NFT)
+            .env("BANALIZE_FAKE_NFT_LOG", nft_log_for(iptables_log).to_str().unwrap())
+            .env("BANALIZE_IPSET_BIN", FAKE_IPSET)
+            .env("BANALIZE_FAKE_IPSET_LOG", ipset_log_for(iptables_log).to_str().unwrap())
             // Tests must never reach out to the network for the GeoIP mmdb.
             .env("BANALIZE_CORE_GEOIP_AUTO_DOWNLOAD", "false");
         for (key, value) in extra_env {
@@ -153,7 +208,8 @@ impl TestProcess {
         let deadline = Instant::now() + Duration::from_secs(10);
         loop {
             assert!(Instant::now() < deadline, "API did not start within 10s");
-            if let Ok(r) = self.client().get(self.api_url("/api/configs")).send() {
+            // A public route, so processes started with API tokens come up too.
+            if let Ok(r) = self.client().get(self.api_url("/api/version")).send() {
                 if r.status().is_success() {
                     return;
                 }
@@ -193,13 +249,9 @@ impl TestProcess {
 
     /// Rotate the log: rename it aside and recreate an empty file at the
     /// original path, like logrotate's default (non-copytruncate) mode.
-    /// The pause lets the tailer process the rename and re-register its
-    /// pending watch before the new file's Create event fires; recreating
-    /// instantly can race the watcher and lose the new file.
     pub fn rotate_log(&self) {
         let rotated = self.log_file.with_extension("log.1");
         fs::rename(&self.log_file, rotated).unwrap();
-        thread::sleep(Duration::from_millis(500));
         fs::write(&self.log_file, "").unwrap();
     }
 
@@ -212,6 +264,73 @@ impl TestProcess {
         fs::read_to_string(&self.iptables_log).unwrap_or_default()
     }
 
+    pub fn read_ip6tables_log(&self) -> String {
+        fs::read_to_string(&self.ip6tables_log).unwrap_or_default()
+    }
+
+    pub fn wait_for_ip6tables_contains(&self, pattern: &str, timeout_ms: u64) -> bool {
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+        loop {
+            if Instant::now() > deadline {
+                return false;
+            }
+            if self.read_ip6tables_log().contains(pattern) {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    pub fn read_nft_log(&self) -> String {
+        fs::read_to_string(&self.nft_log).unwrap_or_default()
+    }
+
+    pub fn wait_for_nft_contains(&self, pattern: &str, timeout_ms: u64) -> bool {
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+        loop {
+            if Instant::now() > deadline {
+                return false;
+            }
+            if self.read_nft_log().contains(pattern) {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    /// The fake nft's current ruleset, as `list ruleset` prints it.
+    pub fn nft_ruleset(&self) -> String {
+        let output = Command::new(FAKE_NFT)
+            .args(["list", "ruleset"])
+            .env("BANALIZE_FAKE_NFT_LOG", &self.nft_log)
+            .output()
+            .unwrap();
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    }
+
+    pub fn read_ipset_log(&self) -> String {
+        fs::read_to_string(&self.ipset_log).unwrap_or_default()
+    }
+
+    pub fn wait_for_ipset_contains(&self, pattern: &str, timeout_ms: u64) -> bool {
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+        loop {
+            if Instant::now() > deadline {
+                return false;
+            }
+            if self.read_ipset_log().contains(pattern) {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    /// The fake ipset's current sets, one `set <name> <family>` line per set
+    /// followed by one `member <name> <ip>` line per member.
+    pub fn ipset_state(&self) -> String {
+        fs::read_to_string(format!("{}.state", self.ipset_log.display())).unwrap_or_default()
+    }
+
     pub fn wait_for_ban(&self, ip: &str, timeout_ms: u64) -> bool {
         let deadline = Instant::now() + Duration::from_millis(timeout_ms);
         loop {
@@ -220,7 +339,7 @@ impl TestProcess {
             }
             let result = self
                 .client()
-                .get(self.api_url("/api/bans"))
+                .get(self.api_url("/api/bans?limit=1000"))
                 .send()
                 .and_then(|r| r.json::<Vec<serde_json::Value>>());
             if let Ok(bans) = result {
@@ -303,11 +422,12 @@ impl TestProcess {
 
     /// One-shot count of recorded match events for a config.
     pub fn match_count(&self, config_id: &str) -> usize {
+        // The list is paged; the header counts every match.
         self.client()
-            .get(self.api_url(&format!("/api/matches/{}", config_id)))
+            .get(self.api_url(&format!("/api/matches/{}?limit=1", config_id)))
             .send()
-            .and_then(|r| r.json::<Vec<serde_json::Value>>())
-            .map(|m| m.len())
+            .ok()
+            .and_then(|r| r.headers().get("x-total-count")?.to_str().ok()?.parse().ok())
             .unwrap_or(0)
     }
 
@@ -315,7 +435,7 @@ impl TestProcess {
     /// sentinel ban has proven the pipeline processed earlier lines.
     pub fn banned_ips(&self) -> Vec<String> {
         self.client()
-            .get(self.api_url("/api/bans"))
+            .get(self.api_url("/api/bans?limit=1000"))
             .send()
             .and_then(|r| r.json::<Vec<serde_json::Value>>())
             .map(|bans| {
@@ -412,3 +532,508 @@ impl Drop for TestProcess {
         let _ = self.child.wait();
     }
 }
+
+/// One request received by an `HttpStub`.
+#[derive(Debug, Clone)]
+pub struct StubRequest {
+    pub method: String,
+    pub path: String,
+    /// Header names lowercased.
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl StubRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n == &name.to_ascii_lowercase())
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Minimal HTTP/1.1 server standing in for webhook receivers: it records
+/// every request and answers with an empty body, `200 OK` unless told
+/// otherwise by `respond_with`.
+pub struct HttpStub {
+    port: u16,
+    requests: Arc<Mutex<Vec<StubRequest>>>,
+    status: Arc<AtomicU16>,
+}
+
+impl HttpStub {
+    pub fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        let status = Arc::new(AtomicU16::new(200));
+        let response_status = status.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if let Some(request) = read_request(&stream) {
+                    recorded.lock().unwrap().push(request);
+                }
+                let mut stream = stream;
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {} Stub\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    response_status.load(Ordering::SeqCst)
+                );
+            }
+        });
+        Self {
+            port,
+            requests,
+            status,
+        }
+    }
+
+    /// Answer every later request with `status`.
+    pub fn respond_with(&self, status: u16) {
+        self.status.store(status, Ordering::SeqCst);
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("http://127.0.0.1:{}{}", self.port, path)
+    }
+
+    pub fn requests(&self) -> Vec<StubRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Wait until at least `n` requests arrived; returns them all.
+    pub fn wait_for_requests(&self, n: usize, timeout_ms: u64) -> Vec<StubRequest> {
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+        loop {
+            let requests = self.requests();
+            if requests.len() >= n || Instant::now() >= deadline {
+                return requests;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+}
+
+/// A container known to a `DockerStub`.
+struct StubContainer {
+    id: String,
+    name: String,
+    labels: Vec<String>,
+    running: bool,
+    /// Bumped by a restart, ending the log streams opened before it
+    generation: u64,
+    /// Log lines with their time as (seconds, nanoseconds)
+    lines: Vec<((u64, u32), String)>,
+}
+
+/// Stand-in for the Docker Engine API on a unix socket, serving just what the
+/// docker log source calls: container inspect, the label-filtered container
+/// list, and followed, multiplexed log streams honouring `since`.
+pub struct DockerStub {
+    dir: tempfile::TempDir,
+    containers: Arc<Mutex<Vec<StubContainer>>>,
+}
+
+impl DockerStub {
+    pub fn start() -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let listener = std::os::unix::net::UnixListener::bind(dir.path().join("docker.sock")).unwrap();
+        let containers = Arc::new(Mutex::new(Vec::new()));
+        let served = containers.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let containers = served.clone();
+                thread::spawn(move || serve_docker(stream, containers));
+            }
+        });
+        Self { dir, containers }
+    }
+
+    pub fn socket(&self) -> String {
+        self.dir.path().join("docker.sock").to_str().unwrap().to_string()
+    }
+
+    /// Add a running container with `key=value` labels.
+    pub fn add_container(&self, name: &str, labels: &[&str]) {
+        self.containers.lock().unwrap().push(StubContainer {
+            id: format!("{:0>64}", name.bytes().map(|b| format!("{:x}", b)).collect::<String>()),
+            name: name.to_string(),
+            labels: labels.iter().map(|l| l.to_string()).collect(),
+            running: true,
+            generation: 0,
+            lines: Vec::new(),
+        });
+    }
+
+    /// Write a line to the container's output.
+    pub fn log(&self, name: &str, line: &str) {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap();
+        let mut containers = self.containers.lock().unwrap();
+        let container = containers.iter_mut().find(|c| c.name == name).unwrap();
+        container.lines.push(((now.as_secs(), now.subsec_nanos()), line.to_string()));
+    }
+
+    /// Restart the container: its open log streams end.
+    pub fn restart(&self, name: &str) {
+        let mut containers = self.containers.lock().unwrap();
+        let container = containers.iter_mut().find(|c| c.name == name).unwrap();
+        container.generation += 1;
+    }
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            out.push(u8::from_str_radix(&s[i + 1..i + 3], 16).unwrap());
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).unwrap()
+}
+
+/// RFC 3339 UTC time with nanoseconds, as Docker prints it.
+fn rfc3339((secs, nanos): (u64, u32)) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    // Civil date from days since the epoch (Howard Hinnant's algorithm).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{nanos:09}Z",
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+fn serve_docker(stream: std::os::unix::net::UnixStream, containers: Arc<Mutex<Vec<StubContainer>>>) {
+    let Some(request) = read_request(&stream) else {
+        return;
+    };
+    let mut stream = stream;
+    let (path, query) = request.path.split_once('?').unwrap_or((&request.path, ""));
+    let param = |name: &str| {
+        query
+            .split('&')
+            .find_map(|kv| kv.strip_prefix(name).and_then(|v| v.strip_prefix('=')))
+            .map(percent_decode)
+    };
+    let reply = |stream: &mut std::os::unix::net::UnixStream, status: &str, body: String| {
+        let _ = write!(
+            stream,
+            "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        );
+    };
+
+    if path == "/containers/json" {
+        let filters: serde_json::Value =
+            serde_json::from_str(&param("filters").unwrap_or_else(|| "{}".to_string())).unwrap();
+        let wanted: Vec<String> = serde_json::from_value(filters["label"].clone()).unwrap_or_default();
+        let list: Vec<serde_json::Value> = containers
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|c| c.running && wanted.iter().all(|w| c.labels.contains(w)))
+            .map(|c| serde_json::json!({ "Id": c.id, "Names": [format!("/{}", c.name)] }))
+            .collect();
+        return reply(&mut stream, "200 OK", serde_json::to_string(&list).unwrap());
+    }
+    let Some(rest) = path.strip_prefix("/containers/") else {
+        return reply(&mut stream, "404 Not Found", "{}".to_string());
+    };
+    let (target, action) = rest.split_once('/').unwrap_or((rest, ""));
+    let found = containers
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|c| c.id == target || c.name == target)
+        .map(|c| (c.id.clone(), c.running, c.generation));
+    let Some((id, running, generation)) = found else {
+        return reply(&mut stream, "404 Not Found", r#"{"message":"No such container"}"#.to_string());
+    };
+    if action == "json" {
+        let info = serde_json::json!({ "Id": id, "State": { "Running": running } });
+        return reply(&mut stream, "200 OK", info.to_string());
+    }
+
+    // Logs: stream every line from `since` on until the container restarts.
+    let since = param("since")
+        .and_then(|s| {
+            let (secs, nanos) = s.split_once('.').unwrap_or((&s, "0"));
+            Some((secs.parse().ok()?, nanos.parse().ok()?))
+        })
+        .unwrap_or((0, 0));
+    let _ = write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: application/vnd.docker.multiplexed-stream\r\nTransfer-Encoding: chunked\r\n\r\n"
+    );
+    let mut sent = 0;
+    loop {
+        let (lines, restarted) = {
+            let containers = containers.lock().unwrap();
+            let c = containers.iter().find(|c| c.id == id).unwrap();
+            (c.lines[sent..].to_vec(), c.generation != generation)
+        };
+        sent += lines.len();
+        for (stamp, line) in lines.into_iter().filter(|(stamp, _)| *stamp >= since) {
+            let payload = format!("{} {}\n", rfc3339(stamp), line);
+            let mut frame = vec![1u8, 0, 0, 0];
+            frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+            frame.extend_from_slice(payload.as_bytes());
+            let mut chunk = format!("{:x}\r\n", frame.len()).into_bytes();
+            chunk.extend_from_slice(&frame);
+            chunk.extend_from_slice(b"\r\n");
+            if stream.write_all(&chunk).is_err() {
+                return;
+            }
+        }
+        if restarted {
+            let _ = stream.write_all(b"0\r\n\r\n");
+            return;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+fn read_request(stream: impl Read) -> Option<StubRequest> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).ok()?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let path = parts.next()?.to_string();
+
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).ok()?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = line.split_once(':')?;
+        headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+    }
+    let length = headers
+        .iter()
+        .find(|(n, _)| n == "content-length")
+        .and_then(|(_, v)| v.parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).ok()?;
+
+    Some(StubRequest {
+        method,
+        path,
+        headers,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    })
+}
+
+/// Writes a minimal systemd journal file (regular or compact layout,
+/// uncompressed), so journal configs can be tested without journald. Each
+/// entry gets its own data objects and a one-slot entry array linked onto the
+/// chain; the header counters are written last, the way journald publishes an
+/// entry.
+pub struct JournalWriter {
+    file: fs::File,
+    compact: bool,
+    len: u64,
+    n_entries: u64,
+    n_objects: u64,
+    first_array: u64,
+    last_array: u64,
+    tail_object: u64,
+    head_realtime: u64,
+    tail_realtime: u64,
+}
+
+const JOURNAL_HEADER_SIZE: u64 = 272;
+const JOURNAL_HASH_BUCKETS: u64 = 64;
+const JOURNAL_INCOMPATIBLE_COMPACT: u32 = 1 << 4;
+
+impl JournalWriter {
+    pub fn create(path: &Path) -> Self {
+        Self::open(path, false)
+    }
+
+    /// A journal in the compact layout: 32-bit offsets, no entry item hashes.
+    pub fn create_compact(path: &Path) -> Self {
+        Self::open(path, true)
+    }
+
+    fn open(path: &Path, compact: bool) -> Self {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).unwrap();
+        }
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+            .unwrap();
+        let mut writer = Self {
+            file,
+            compact,
+            len: JOURNAL_HEADER_SIZE,
+            n_entries: 0,
+            n_objects: 0,
+            first_array: 0,
+            last_array: 0,
+            tail_object: 0,
+            head_realtime: 0,
+            tail_realtime: 0,
+        };
+        // Empty hash tables: readers that walk entry arrays never use them.
+        let table = vec![0u8; (JOURNAL_HASH_BUCKETS * 16) as usize];
+        writer.append_object(4, &table);
+        writer.append_object(5, &table);
+        writer.write_header();
+        writer
+    }
+
+    /// Append an entry with `MESSAGE=message` and the given extra fields.
+    pub fn append(&mut self, message: &str, fields: &[(&str, &str)]) {
+        let entry_offset = self.len + self.padded_data_sizes(message, fields);
+        let mut items = Vec::new();
+        let payloads = fields
+            .iter()
+            .map(|(k, v)| format!("{k}={v}"))
+            .chain(std::iter::once(format!("MESSAGE={message}")));
+        for payload in payloads {
+            // Compact data objects carry 8 more bytes of tail array fields.
+            let mut body = vec![0u8; if self.compact { 56 } else { 48 }];
+            body[24..32].copy_from_slice(&entry_offset.to_le_bytes());
+            body[40..48].copy_from_slice(&1u64.to_le_bytes());
+            body.extend_from_slice(payload.as_bytes());
+            items.push(self.append_object(1, &body));
+        }
+        let realtime = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_micros() as u64;
+        let mut body = vec![0u8; 48];
+        body[0..8].copy_from_slice(&(self.n_entries + 1).to_le_bytes());
+        body[8..16].copy_from_slice(&realtime.to_le_bytes());
+        body[16..24].copy_from_slice(&realtime.to_le_bytes());
+        for item in &items {
+            if self.compact {
+                body.extend_from_slice(&(*item as u32).to_le_bytes());
+            } else {
+                body.extend_from_slice(&item.to_le_bytes());
+                body.extend_from_slice(&0u64.to_le_bytes());
+            }
+        }
+        assert_eq!(self.append_object(3, &body), entry_offset);
+        self.link_entry(entry_offset, realtime);
+    }
+
+    /// Link an entry slot pointing at the file header instead of an entry,
+    /// as a corrupt file would.
+    pub fn append_corrupt(&mut self) {
+        let realtime = self.tail_realtime;
+        self.link_entry(8, realtime);
+    }
+
+    /// Add a one-slot entry array for the entry at `entry_offset` to the
+    /// chain, and publish it in the header.
+    fn link_entry(&mut self, entry_offset: u64, realtime: u64) {
+        let mut array = vec![0u8; 8];
+        if self.compact {
+            array.extend_from_slice(&(entry_offset as u32).to_le_bytes());
+        } else {
+            array.extend_from_slice(&entry_offset.to_le_bytes());
+        }
+        let array_offset = self.append_object(6, &array);
+        if self.last_array == 0 {
+            self.first_array = array_offset;
+        } else {
+            self.pwrite(&array_offset.to_le_bytes(), self.last_array + 16);
+        }
+        self.last_array = array_offset;
+        if self.head_realtime == 0 {
+            self.head_realtime = realtime;
+        }
+        self.tail_realtime = realtime;
+        self.n_entries += 1;
+        self.write_header();
+    }
+
+    fn padded_data_sizes(&self, message: &str, fields: &[(&str, &str)]) -> u64 {
+        let sizes = fields
+            .iter()
+            .map(|(k, v)| k.len() + 1 + v.len())
+            .chain(std::iter::once("MESSAGE=".len() + message.len()));
+        let header = if self.compact { 72 } else { 64 };
+        sizes.map(|len| (header + len as u64).div_ceil(8) * 8).sum()
+    }
+
+    /// Append an object of `kind` with `body` after its 16-byte header;
+    /// returns its offset.
+    fn append_object(&mut self, kind: u8, body: &[u8]) -> u64 {
+        let offset = self.len;
+        let size = 16 + body.len() as u64;
+        let mut object = vec![0u8; 16];
+        object[0] = kind;
+        object[8..16].copy_from_slice(&size.to_le_bytes());
+        object.extend_from_slice(body);
+        object.resize(size.div_ceil(8) as usize * 8, 0);
+        self.pwrite(&object, offset);
+        self.len += object.len() as u64;
+        self.n_objects += 1;
+        self.tail_object = offset;
+        offset
+    }
+
+    fn write_header(&mut self) {
+        let mut h = vec![0u8; JOURNAL_HEADER_SIZE as usize];
+        let mut put = |at: usize, value: u64| h[at..at + 8].copy_from_slice(&value.to_le_bytes());
+        let table = JOURNAL_HASH_BUCKETS * 16;
+        put(88, JOURNAL_HEADER_SIZE);
+        put(96, self.len - JOURNAL_HEADER_SIZE);
+        put(104, JOURNAL_HEADER_SIZE + 16);
+        put(112, table);
+        put(120, JOURNAL_HEADER_SIZE + 16 + table + 16);
+        put(128, table);
+        put(136, self.tail_object);
+        put(144, self.n_objects);
+        put(152, self.n_entries);
+        put(160, self.n_entries);
+        put(168, self.n_entries.min(1));
+        put(176, self.first_array);
+        put(184, self.head_realtime);
+        put(192, self.tail_realtime);
+        put(232, self.n_entries);
+        h[..8].copy_from_slice(b"LPKSHHRH");
+        if self.compact {
+            h[12..16].copy_from_slice(&JOURNAL_INCOMPATIBLE_COMPACT.to_le_bytes());
+        }
+        h[24..40].copy_from_slice(&[0x11; 16]); // file_id
+        h[40..56].copy_from_slice(&[0x22; 16]); // machine_id
+        h[72..88].copy_from_slice(&[0x33; 16]); // seqnum_id
+        self.pwrite(&h, 0);
+    }
+
+    fn pwrite(&self, buf: &[u8], offset: u64) {
+        use std::os::unix::fs::FileExt;
+        self.file.write_all_at(buf, offset).unwrap();
+    }
+}
@@ -99,11 +99,11 @@ export class ApiClient {
   }
 
   listBans(): Promise<BanEvent[]> {
-    return this.json<BanEvent[]>("/api/bans");
+    return this.json<BanEvent[]>("/api/bans?limit=1000");
   }
 
   listUnbans(): Promise<UnbanEvent[]> {
-    return this.json<UnbanEvent[]>("/api/unbans");
+    return this.json<UnbanEvent[]>("/api/unbans?limit=1000");
   }
 
   /** Lift a ban by its event id (manual unban); keeps recidive history. */
@@ -112,7 +112,7 @@ export class ApiClient {
   }
 
   listMatches(): Promise<MatchEvent[]> {
-    return this.json<MatchEvent[]>("/api/matches");
+    return this.json<MatchEvent[]>("/api/matches?limit=1000");
   }
 
   listNotifiers(): Promise<NotifierConfig[]> {
@@ -15,6 +15,9 @@ import type {
   UnbanEvent,
 } from "./types";
 
+/** Largest page the event lists return; they default to 100 rows. */
+const PAGE_LIMIT = 1000;
+
 /** HTTP failure carrying the status code so callers can map it to a message. */
 export class ApiError extends Error {
   constructor(
@@ -75,7 +78,7 @@ export class HttpDataSource implements DataSource {
 
   async getMatches(configId?: string) {
     const matches = await fetchJson<MatchEvent[]>(
-      configId ? `/api/matches/${configId}` : "/api/matches",
+      `${configId ? `/api/matches/${configId}` : "/api/matches"}?limit=${PAGE_LIMIT}`,
     );
     // Backends predating the line field omit it; normalize so the UI can
     // rely on the contract.
@@ -84,13 +87,13 @@ export class HttpDataSource implements DataSource {
 
   getBans(configId?: string) {
     return fetchJson<BanEvent[]>(
-      configId ? `/api/bans/${configId}` : "/api/bans",
+      `${configId ? `/api/bans/${configId}` : "/api/bans"}?limit=${PAGE_LIMIT}`,
     );
   }
 
   getUnbans(configId?: string) {
     return fetchJson<UnbanEvent[]>(
-      configId ? `/api/unbans/${configId}` : "/api/unbans",
+      `${configId ? `/api/unbans/${configId}` : "/api/unbans"}?limit=${PAGE_LIMIT}`,
     );
   }
 